@@ -35,9 +35,7 @@ pub async fn handle_configure() -> Result<(), Box<dyn Error>> {
                 // This operation is best-effort and errors are ignored
                 ExtensionManager::set(ExtensionEntry {
                     enabled: true,
-                    config: ExtensionConfig::Builtin {
-                        name: "developer".to_string(),
-                    },
+                    config: ExtensionConfig::builtin("developer"),
                 })?;
             }
             Ok(false) => {
@@ -402,9 +400,7 @@ pub fn configure_extensions_dialog() -> Result<(), Box<dyn Error>> {
 
             ExtensionManager::set(ExtensionEntry {
                 enabled: true,
-                config: ExtensionConfig::Builtin {
-                    name: extension.clone(),
-                },
+                config: ExtensionConfig::builtin(extension.clone()),
             })?;
 
             cliclack::outro(format!("Enabled {} extension", style(extension).green()))?;
@@ -1,20 +1,29 @@
 use rand::{distributions::Alphanumeric, Rng};
 use std::process;
 
+use crate::export::{export_session_file, ExportFormat};
+use crate::logging::{setup_console_logging, setup_logging};
 use crate::prompt::rustyline::RustylinePrompt;
-use crate::session::{ensure_session_dir, get_most_recent_session, Session};
+use crate::search::{query_matcher, search_sessions};
+use crate::session::{branch_session, ensure_session_dir, find_session, Session, LATEST_SESSION};
 use console::style;
 use goose::agents::extension::{Envs, ExtensionError};
 use goose::agents::AgentFactory;
 use goose::config::{Config, ExtensionConfig, ExtensionManager};
 use goose::providers::create;
-use std::path::Path;
+use goose::providers::moderation::ModerationConfig;
+use goose::providers::retry::RetryConfig;
+use goose_mcp::EDIT_HISTORY_DIR_ENV;
+use mcp_core::role::Role;
+use std::path::{Path, PathBuf};
 
 use mcp_client::transport::Error as McpClientError;
 
+/// Build a session from the configuration, saved to the session dir unless `no_session` is set
 pub async fn build_session(
     name: Option<String>,
     resume: bool,
+    no_session: bool,
     extension: Option<String>,
     builtin: Option<String>,
 ) -> Session<'static> {
@@ -24,12 +33,49 @@ pub async fn build_session(
     let provider_name: String = config
         .get("GOOSE_PROVIDER")
         .expect("No provider configured. Run 'goose configure' first");
-    let session_dir = ensure_session_dir().expect("Failed to create session directory");
+    let (session_file, resumed) = if no_session {
+        (None, false)
+    } else {
+        let session_dir = ensure_session_dir().expect("Failed to create session directory");
+        let (session_file, resumed) = resolve_session_file(&session_dir, name, resume);
+        (Some(session_file), resumed)
+    };
+
+    // Set up logging before the session is loaded, so that warnings about a session file that
+    // can't be read are shown. A session that isn't saved only logs to the console.
+    match &session_file {
+        Some(session_file) => setup_logging(session_file.file_stem().and_then(|s| s.to_str())),
+        None => setup_console_logging(),
+    }
+    .expect("Failed to set up logging");
+
+    // Let the developer extension persist its edit history next to the session, so undo
+    // keeps working when the session is resumed after a restart
+    let persist_edit_history: bool = config.get("GOOSE_PERSIST_EDIT_HISTORY").unwrap_or(false);
+    let edit_history_dir = session_file
+        .as_ref()
+        .filter(|_| persist_edit_history)
+        .map(|session_file| session_file.with_extension("history"));
+    let with_edit_history = |config: ExtensionConfig| match (&edit_history_dir, &config) {
+        (Some(dir), ExtensionConfig::Builtin { .. }) => {
+            config.with_env(EDIT_HISTORY_DIR_ENV, dir.to_string_lossy())
+        }
+        _ => config,
+    };
 
     let model: String = config
         .get("GOOSE_MODEL")
         .expect("No model configured. Run 'goose configure' first");
-    let model_config = goose::model::ModelConfig::new(model.clone());
+    let max_request_bytes: Option<usize> = config.get("GOOSE_MAX_REQUEST_BYTES").ok();
+    let model_config = goose::model::ModelConfig::new(model.clone())
+        .with_max_tokens(config.get("GOOSE_MAX_TOKENS").ok())
+        .with_stop(config.get("GOOSE_STOP").ok())
+        .with_seed(config.get("GOOSE_SEED").ok())
+        .with_target_ratio(config.get("GOOSE_CONTEXT_TARGET_RATIO").ok())
+        .with_retry(RetryConfig::from_config(config))
+        .with_cache_control(config.get("GOOSE_PROMPT_CACHE").unwrap_or(false))
+        .with_strict_tools(config.get("GOOSE_STRICT_TOOLS").unwrap_or(false))
+        .with_max_request_bytes(max_request_bytes);
     let provider = create(&provider_name, model_config).expect("Failed to create provider");
 
     // Create the agent
@@ -39,11 +85,15 @@ pub async fn build_session(
         None => AgentFactory::create(AgentFactory::default_version(), provider),
     }
     .expect("Failed to create agent");
+    let moderation = ModerationConfig::from_env()
+        .create()
+        .expect("Failed to create moderation provider");
+    agent.set_moderation(moderation).await;
 
     // Setup extensions for the agent
     for extension in ExtensionManager::get_all().expect("should load extensions") {
         if extension.enabled {
-            let config = extension.config.clone();
+            let config = with_edit_history(extension.config.clone());
             agent
                 .add_extension(config.clone())
                 .await
@@ -54,8 +104,8 @@ pub async fn build_session(
                         }
                         _ => e.to_string(),
                     };
-                    println!("Failed to start extension: {}, {:?}", config.name(), err);
-                    println!(
+                    eprintln!("Failed to start extension: {}, {:?}", config.name(), err);
+                    eprintln!(
                         "Please check extension configuration for {}.",
                         config.name()
                     );
@@ -106,43 +156,52 @@ pub async fn build_session(
 
     // Add builtin extension if provided
     if let Some(name) = builtin {
-        let config = ExtensionConfig::Builtin { name };
+        let config = with_edit_history(ExtensionConfig::builtin(name));
         agent.add_extension(config).await.unwrap_or_else(|e| {
             eprintln!("Failed to start builtin extension: {}", e);
             process::exit(1);
         });
     }
 
-    // If resuming, try to find the session
+    let prompt = Box::new(RustylinePrompt::new());
+    if !resumed {
+        display_session_info(resume, &provider_name, &model, session_file.as_deref());
+    }
+    match session_file {
+        Some(session_file) => Session::new(agent, prompt, session_file),
+        None => Session::new_ephemeral(agent, prompt),
+    }
+}
+
+/// Find the session file to use, returning it with whether it is an existing session
+///
+/// When resuming, `name` may be the start of a session's name, or "latest" for the most
+/// recently used session, which is also what is resumed when no name is given.
+fn resolve_session_file(
+    session_dir: &Path,
+    mut name: Option<String>,
+    resume: bool,
+) -> (PathBuf, bool) {
     if resume {
-        if let Some(ref session_name) = name {
-            // Try to resume specific session
-            let session_file = session_dir.join(format!("{}.jsonl", session_name));
-            if session_file.exists() {
-                let prompt = Box::new(RustylinePrompt::new());
-                return Session::new(agent, prompt, session_file);
-            } else {
-                eprintln!("Session '{}' not found, starting new session", session_name);
-            }
-        } else {
-            // Try to resume most recent session
-            if let Ok(session_file) = get_most_recent_session() {
-                let prompt = Box::new(RustylinePrompt::new());
-                return Session::new(agent, prompt, session_file);
-            } else {
+        let target = name.take().unwrap_or_else(|| LATEST_SESSION.to_string());
+        match find_session(session_dir, &target) {
+            Ok(Some(session_file)) => return (session_file, true),
+            Ok(None) if target == LATEST_SESSION => {
                 eprintln!("No previous sessions found, starting new session");
             }
+            Ok(None) => {
+                eprintln!("Session '{}' not found, starting new session", target);
+                name = Some(target);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
         }
     }
 
     // Generate session name if not provided
-    let name = name.unwrap_or_else(|| {
-        rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(8)
-            .map(char::from)
-            .collect()
-    });
+    let name = name.unwrap_or_else(random_session_name);
 
     let session_file = session_dir.join(format!("{}.jsonl", name));
     if session_file.exists() {
@@ -150,13 +209,114 @@ pub async fn build_session(
         process::exit(1);
     }
 
-    let prompt = Box::new(RustylinePrompt::new());
+    (session_file, false)
+}
 
-    display_session_info(resume, &provider_name, &model, &session_file);
-    Session::new(agent, prompt, session_file)
+fn random_session_name() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(8)
+        .map(char::from)
+        .collect()
 }
 
-fn display_session_info(resume: bool, provider: &str, model: &str, session_file: &Path) {
+/// Create a new session from the messages of `session` up to and including message `index`
+pub fn handle_branch(session: &str, index: usize, name: Option<String>) -> anyhow::Result<()> {
+    let session_dir = ensure_session_dir()?;
+    let source = session_dir.join(format!("{}.jsonl", session));
+    if !source.exists() {
+        return Err(anyhow::anyhow!("Session '{}' not found", session));
+    }
+
+    let name = name.unwrap_or_else(random_session_name);
+    let target = session_dir.join(format!("{}.jsonl", name));
+    let messages = branch_session(&source, index, &target)?;
+
+    println!(
+        "Created session '{}' with the first {} messages of '{}'",
+        name,
+        messages.len(),
+        session
+    );
+    println!(
+        "  {} {}",
+        style("Resume it with").dim(),
+        style(format!("goose session --resume --name {}", name)).cyan(),
+    );
+    Ok(())
+}
+
+/// How many matched lines are shown for each session
+const MATCHES_SHOWN_PER_SESSION: usize = 3;
+
+pub fn handle_search(query: &str, is_regex: bool, limit: usize) -> anyhow::Result<()> {
+    let matcher = query_matcher(query, is_regex)?;
+    let results = search_sessions(&ensure_session_dir()?, &matcher, limit)?;
+    if results.is_empty() {
+        println!("No sessions match '{}'", query);
+        return Ok(());
+    }
+
+    for result in results {
+        println!(
+            "{} {}",
+            style(&result.name).cyan().bold(),
+            style(result.path.display()).dim()
+        );
+        for found in result.matches.iter().take(MATCHES_SHOWN_PER_SESSION) {
+            let role = match found.role {
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+            if let Some(before) = &found.before {
+                println!("      {}", style(before).dim());
+            }
+            println!(
+                "  {} {}",
+                style(format!("[{} {}]", found.message_index, role)).dim(),
+                found.line
+            );
+            if let Some(after) = &found.after {
+                println!("      {}", style(after).dim());
+            }
+        }
+        if result.matches.len() > MATCHES_SHOWN_PER_SESSION {
+            println!(
+                "  {}",
+                style(format!(
+                    "...and {} more matches",
+                    result.matches.len() - MATCHES_SHOWN_PER_SESSION
+                ))
+                .dim()
+            );
+        }
+        println!();
+    }
+    Ok(())
+}
+
+pub fn handle_export(
+    session: &str,
+    format: ExportFormat,
+    output: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let session_file = ensure_session_dir()?.join(format!("{}.jsonl", session));
+    if !session_file.exists() {
+        return Err(anyhow::anyhow!("Session '{}' not found", session));
+    }
+
+    let export = export_session_file(&session_file, format)?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, export)?;
+            println!("Exported session '{}' to {}", session, path.display());
+        }
+        None => print!("{}", export),
+    }
+    Ok(())
+}
+
+fn display_session_info(resume: bool, provider: &str, model: &str, session_file: Option<&Path>) {
     let start_session_msg = if resume {
         "resuming session |"
     } else {
@@ -170,9 +330,12 @@ fn display_session_info(resume: bool, provider: &str, model: &str, session_file:
         style("model:").dim(),
         style(model).cyan().dim(),
     );
-    println!(
-        "    {} {}",
-        style("logging to").dim(),
-        style(session_file.display()).dim().cyan(),
-    );
+    match session_file {
+        Some(session_file) => println!(
+            "    {} {}",
+            style("logging to").dim(),
+            style(session_file.display()).dim().cyan(),
+        ),
+        None => println!("    {}", style("not saving this session").dim()),
+    }
 }
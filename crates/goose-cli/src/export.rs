@@ -0,0 +1,327 @@
+use anyhow::Result;
+use goose::message::{Message, MessageContent, ToolRequest, ToolResponse};
+use mcp_core::content::Content;
+use mcp_core::resource::ResourceContents;
+use mcp_core::role::Role;
+use std::fs::File;
+use std::path::Path;
+
+use crate::session::deserialize_messages;
+
+/// The formats a session can be exported to
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    /// A single HTML page, with images embedded so it has no other files
+    Html,
+}
+
+/// Render the session saved in `session_file`, titled with the session's name
+pub fn export_session_file(session_file: &Path, format: ExportFormat) -> Result<String> {
+    let messages = deserialize_messages(File::open(session_file)?)?;
+    let title = session_file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("session");
+    Ok(match format {
+        ExportFormat::Markdown => session_to_markdown(title, &messages),
+        ExportFormat::Html => session_to_html(title, &messages),
+    })
+}
+
+/// A piece of a conversation, as both exports lay it out
+enum Block {
+    Heading(&'static str),
+    Text(String),
+    Image {
+        data: String,
+        mime_type: String,
+    },
+    /// A labelled block of preformatted text, such as tool arguments or output
+    Code {
+        label: String,
+        language: &'static str,
+        code: String,
+    },
+}
+
+/// Render a session as Markdown, with tool calls and outputs in fenced code blocks
+pub fn session_to_markdown(title: &str, messages: &[Message]) -> String {
+    let mut markdown = format!("# {}\n", title);
+    for block in blocks(messages) {
+        markdown.push('\n');
+        match block {
+            Block::Heading(heading) => markdown.push_str(&format!("## {}\n", heading)),
+            Block::Text(text) => markdown.push_str(&format!("{}\n", text.trim_end())),
+            Block::Image { data, mime_type } => {
+                markdown.push_str(&format!("![image](data:{};base64,{})\n", mime_type, data))
+            }
+            Block::Code {
+                label,
+                language,
+                code,
+            } => {
+                let fence = fence_for(&code);
+                markdown.push_str(&format!(
+                    "{}\n\n{}{}\n{}\n{}\n",
+                    label,
+                    fence,
+                    language,
+                    code.trim_end(),
+                    fence
+                ));
+            }
+        }
+    }
+    markdown
+}
+
+/// Render a session as a self-contained HTML page
+pub fn session_to_html(title: &str, messages: &[Message]) -> String {
+    let mut body = String::new();
+    for block in blocks(messages) {
+        match block {
+            Block::Heading(heading) => body.push_str(&format!("<h2>{}</h2>\n", heading)),
+            Block::Text(text) => body.push_str(&format!(
+                "<div class=\"text\">{}</div>\n",
+                escape_html(&text)
+            )),
+            Block::Image { data, mime_type } => body.push_str(&format!(
+                "<img alt=\"image\" src=\"data:{};base64,{}\">\n",
+                escape_html(&mime_type),
+                escape_html(&data)
+            )),
+            Block::Code { label, code, .. } => body.push_str(&format!(
+                "<p>{}</p>\n<pre><code>{}</code></pre>\n",
+                markdown_label_to_html(&label),
+                escape_html(code.trim_end())
+            )),
+        }
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; max-width: 50rem; margin: 2rem auto; padding: 0 1rem; line-height: 1.5; }}
+.text {{ white-space: pre-wrap; }}
+pre {{ background: #f4f4f4; padding: 0.75rem; overflow-x: auto; }}
+img {{ max-width: 100%; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{body}</body>
+</html>
+"#,
+        title = escape_html(title),
+        body = body
+    )
+}
+
+fn blocks(messages: &[Message]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut last_heading = None;
+    for message in messages {
+        let only_tool_responses = message
+            .content
+            .iter()
+            .all(|content| matches!(content, MessageContent::ToolResponse(_)));
+        // Tool outputs follow the call they answer, rather than starting a user turn
+        if !only_tool_responses {
+            let heading = match message.role {
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+            };
+            if last_heading != Some(heading) {
+                blocks.push(Block::Heading(heading));
+                last_heading = Some(heading);
+            }
+        }
+
+        for content in &message.content {
+            match content {
+                MessageContent::Text(text) => blocks.push(Block::Text(text.text.clone())),
+                MessageContent::Image(image) => blocks.push(Block::Image {
+                    data: image.data.clone(),
+                    mime_type: image.mime_type.clone(),
+                }),
+                MessageContent::ToolRequest(request) => blocks.push(tool_request_block(request)),
+                MessageContent::ToolResponse(response) => {
+                    blocks.extend(tool_response_blocks(response))
+                }
+            }
+        }
+    }
+    blocks
+}
+
+fn tool_request_block(request: &ToolRequest) -> Block {
+    match &request.tool_call {
+        Ok(call) => Block::Code {
+            label: format!("**Tool call** `{}`", call.name),
+            language: "json",
+            code: serde_json::to_string_pretty(&call.arguments).unwrap_or_default(),
+        },
+        Err(e) => Block::Code {
+            label: "**Invalid tool call**".to_string(),
+            language: "",
+            code: e.to_string(),
+        },
+    }
+}
+
+fn tool_response_blocks(response: &ToolResponse) -> Vec<Block> {
+    let contents = match &response.tool_result {
+        Ok(contents) => contents,
+        Err(e) => {
+            return vec![Block::Code {
+                label: "**Tool error**".to_string(),
+                language: "",
+                code: e.to_string(),
+            }]
+        }
+    };
+
+    // Outputs are often sent twice, once for the model and once for the user, so only the
+    // user's copy is kept
+    contents
+        .iter()
+        .filter(|content| match content.audience() {
+            Some(audience) => audience.contains(&Role::User),
+            None => true,
+        })
+        .filter_map(|content| match content {
+            Content::Text(text) => Some(Block::Code {
+                label: "**Tool output**".to_string(),
+                language: "",
+                code: text.text.clone(),
+            }),
+            Content::Image(image) => Some(Block::Image {
+                data: image.data.clone(),
+                mime_type: image.mime_type.clone(),
+            }),
+            Content::Resource(resource) => match &resource.resource {
+                ResourceContents::TextResourceContents { uri, text, .. } => Some(Block::Code {
+                    label: format!("**Resource** `{}`", uri),
+                    language: "",
+                    code: text.clone(),
+                }),
+                ResourceContents::BlobResourceContents { .. } => None,
+            },
+        })
+        .collect()
+}
+
+/// A code fence longer than any run of backticks in `code`, so it can't be closed early
+fn fence_for(code: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in code.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// The labels only use bold and inline code, which is all that is converted
+fn markdown_label_to_html(label: &str) -> String {
+    let mut html = String::new();
+    for (i, part) in escape_html(label).split("**").enumerate() {
+        if i % 2 == 1 {
+            html.push_str(&format!("<strong>{}</strong>", part));
+        } else {
+            for (j, piece) in part.split('`').enumerate() {
+                if j % 2 == 1 {
+                    html.push_str(&format!("<code>{}</code>", piece));
+                } else {
+                    html.push_str(piece);
+                }
+            }
+        }
+    }
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::persist_messages;
+    use mcp_core::tool::ToolCall;
+    use serde_json::json;
+
+    // A 1x1 PNG
+    const PIXEL: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    fn session() -> Vec<Message> {
+        vec![
+            Message::user()
+                .with_text("What is in this directory? Here is a screenshot")
+                .with_image(PIXEL, "image/png"),
+            Message::assistant()
+                .with_text("Let me look.")
+                .with_tool_request(
+                    "1",
+                    Ok(ToolCall::new("developer__shell", json!({"command": "ls"}))),
+                ),
+            Message::user().with_tool_response(
+                "1",
+                Ok(vec![
+                    Content::text("Cargo.toml\nsrc").with_audience(vec![Role::Assistant]),
+                    Content::text("Cargo.toml\nsrc <main>")
+                        .with_audience(vec![Role::User])
+                        .with_priority(0.0),
+                ]),
+            ),
+            Message::assistant().with_text("A Rust crate, with ```code``` in `src`."),
+        ]
+    }
+
+    #[test]
+    fn test_session_to_markdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("demo.jsonl");
+        persist_messages(&path, &session()).unwrap();
+
+        let markdown = export_session_file(&path, ExportFormat::Markdown).unwrap();
+        let expected = format!(
+            "# demo\n\
+             \n## User\n\
+             \nWhat is in this directory? Here is a screenshot\n\
+             \n![image](data:image/png;base64,{PIXEL})\n\
+             \n## Assistant\n\
+             \nLet me look.\n\
+             \n**Tool call** `developer__shell`\n\n```json\n{{\n  \"command\": \"ls\"\n}}\n```\n\
+             \n**Tool output**\n\n```\nCargo.toml\nsrc <main>\n```\n\
+             \nA Rust crate, with ```code``` in `src`.\n"
+        );
+        assert_eq!(markdown, expected);
+    }
+
+    #[test]
+    fn test_session_to_html() {
+        let html = session_to_html("demo <1>", &session());
+        assert!(html.contains("<title>demo &lt;1&gt;</title>"));
+        assert!(html.contains(&format!("src=\"data:image/png;base64,{}\"", PIXEL)));
+        assert!(html.contains("<strong>Tool call</strong> <code>developer__shell</code>"));
+        assert!(html.contains("<pre><code>Cargo.toml\nsrc &lt;main&gt;</code></pre>"));
+        assert!(!html.contains("Cargo.toml\nsrc</code>"));
+    }
+
+    #[test]
+    fn test_fence_outlasts_backticks_in_code() {
+        assert_eq!(fence_for("plain"), "```");
+        assert_eq!(fence_for("a ``` b ```` c"), "`````");
+    }
+}
@@ -16,7 +16,7 @@ pub fn log_usage(session_file: String, usage: Vec<ProviderUsage>) {
     if let Some(home_dir) = dirs::home_dir() {
         let log_dir = home_dir.join(".config").join("goose").join("logs");
         if let Err(e) = std::fs::create_dir_all(&log_dir) {
-            eprintln!("Failed to create log directory: {}", e);
+            tracing::error!(log_dir = %log_dir.display(), error = %e, "Failed to create log directory");
             return;
         }
 
@@ -24,7 +24,7 @@ pub fn log_usage(session_file: String, usage: Vec<ProviderUsage>) {
         let serialized = match serde_json::to_string(&log) {
             Ok(s) => s,
             Err(e) => {
-                eprintln!("Failed to serialize usage log: {}", e);
+                tracing::error!(error = %e, "Failed to serialize usage log");
                 return;
             }
         };
@@ -40,15 +40,18 @@ pub fn log_usage(session_file: String, usage: Vec<ProviderUsage>) {
                 Ok(())
             })
         {
-            eprintln!("Failed to write to usage log file: {}", e);
+            tracing::error!(error = %e, "Failed to write to usage log file");
         }
     } else {
-        eprintln!("Failed to write to usage log file: Failed to determine home directory");
+        tracing::error!("Failed to write to usage log file: Failed to determine home directory");
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
     use goose::providers::base::{ProviderUsage, Usage};
 
     use crate::{
@@ -90,4 +93,43 @@ mod tests {
             std::fs::remove_file(&log_file).ok();
         })
     }
+
+    /// Collects the events written by a tracing subscriber
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_logging_failure_is_traced() {
+        run_with_tmp_dir(|| {
+            // A file where the log directory should be, so it can't be created
+            let logs = dirs::home_dir().unwrap().join(".config/goose/logs");
+            std::fs::create_dir_all(logs.parent().unwrap()).unwrap();
+            std::fs::write(&logs, "").unwrap();
+
+            let captured = Captured::default();
+            let writer = captured.clone();
+            let subscriber = tracing_subscriber::fmt()
+                .with_writer(move || writer.clone())
+                .with_ansi(false)
+                .finish();
+            tracing::subscriber::with_default(subscriber, || {
+                log_usage("path.txt".to_string(), vec![]);
+            });
+
+            let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+            assert!(output.contains("ERROR"));
+            assert!(output.contains("Failed to create log directory"));
+        })
+    }
 }
@@ -1,10 +1,11 @@
 use anyhow::{Context, Result};
 use std::fs;
 use std::path::PathBuf;
+use tracing::Subscriber;
 use tracing_appender::rolling::Rotation;
 use tracing_subscriber::{
-    filter::LevelFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
-    Registry,
+    filter::LevelFilter, fmt, layer::SubscriberExt, registry::LookupSpan, util::SubscriberInitExt,
+    EnvFilter, Layer, Registry,
 };
 
 use goose::tracing::langfuse_layer;
@@ -29,6 +30,32 @@ fn get_log_directory() -> Result<PathBuf> {
     Ok(date_dir)
 }
 
+/// Console logging layer - WARN and above only
+///
+/// Diagnostics go to stderr, so they never mix with the agent's output when it is piped
+fn console_layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_target(true)
+        .with_level(true)
+        .with_ansi(true)
+        .with_file(true)
+        .with_line_number(true)
+        .pretty()
+        .with_filter(LevelFilter::WARN)
+}
+
+/// Sets up logging to the console only, for sessions that leave no files behind
+pub fn setup_console_logging() -> Result<()> {
+    Registry::default()
+        .with(console_layer())
+        .try_init()
+        .context("Failed to set global subscriber")
+}
+
 /// Sets up the logging infrastructure for the application.
 /// This includes:
 /// - File-based logging with JSON formatting (DEBUG level)
@@ -59,15 +86,6 @@ pub fn setup_logging(name: Option<&str>) -> Result<()> {
         .with_file(true)
         .pretty();
 
-    // Create console logging layer for development - INFO and above only
-    let console_layer = fmt::layer()
-        .with_target(true)
-        .with_level(true)
-        .with_ansi(true)
-        .with_file(true)
-        .with_line_number(true)
-        .pretty();
-
     // Base filter
     let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
         // Set default levels for different modules
@@ -87,7 +105,7 @@ pub fn setup_logging(name: Option<&str>) -> Result<()> {
     // Build the subscriber with required layers
     let subscriber = Registry::default()
         .with(file_layer.with_filter(env_filter)) // Gets all logs
-        .with(console_layer.with_filter(LevelFilter::WARN)); // Controls log levels
+        .with(console_layer()); // Controls log levels
 
     // Initialize with Langfuse if available
     if let Some(langfuse) = langfuse_layer::create_langfuse_observer() {
@@ -2,20 +2,23 @@ use anyhow::Result;
 use clap::{CommandFactory, Parser, Subcommand};
 
 mod commands;
+mod export;
 mod log_usage;
 mod logging;
 mod prompt;
+mod search;
 mod session;
 
 use commands::agent_version::AgentCommand;
 use commands::configure::handle_configure;
 use commands::mcp::run_server;
-use commands::session::build_session;
+use commands::session::{build_session, handle_branch, handle_export, handle_search};
 use commands::version::print_version;
 use console::style;
+use export::ExportFormat;
 use goose::config::Config;
-use logging::setup_logging;
 use std::io::{self, Read};
+use std::path::PathBuf;
 
 #[cfg(test)]
 mod test_helpers;
@@ -57,10 +60,21 @@ enum Command {
         #[arg(
             short,
             long,
-            help = "Resume a previous session (last used or specified by --session)",
-            long_help = "Continue from a previous chat session. If --session is provided, resumes that specific session. Otherwise resumes the last used session."
+            value_name = "SESSION",
+            num_args = 0..=1,
+            help = "Resume a previous session (last used, or named by SESSION or --name)",
+            long_help = "Continue from a previous chat session. SESSION, or --name, can be the start of a session's name as long as only one session starts with it, or 'latest' for the last used session. Without either, resumes the last used session."
         )]
-        resume: bool,
+        resume: Option<Option<String>>,
+
+        /// Keep the session in memory only
+        #[arg(
+            long,
+            conflicts_with_all = ["name", "resume"],
+            help = "Don't save the session to a file",
+            long_help = "Keep the conversation in memory only, without writing a session file. The session can't be resumed, searched or exported later."
+        )]
+        no_session: bool,
 
         /// Add a stdio extension with environment variables and command
         #[arg(
@@ -81,6 +95,86 @@ enum Command {
         builtin: Option<String>,
     },
 
+    /// Branch a new session from an earlier point in an existing one
+    #[command(about = "Start a new session from the messages of an existing one")]
+    Branch {
+        /// Name of the session to branch from
+        #[arg(value_name = "SESSION", help = "Name of the session to branch from")]
+        session: String,
+
+        /// Index of the last message to keep
+        #[arg(
+            short,
+            long,
+            value_name = "INDEX",
+            help = "Index of the last message to keep, starting at 0",
+            long_help = "Index of the last message to copy into the new session, starting at 0 for the first message. The original session is left unchanged."
+        )]
+        at: usize,
+
+        /// Name for the new session
+        #[arg(
+            short,
+            long,
+            value_name = "NAME",
+            help = "Name for the new session (e.g., 'project-x-alt')"
+        )]
+        name: Option<String>,
+    },
+
+    /// Search the text of saved sessions
+    #[command(about = "Find the sessions whose messages match a query")]
+    Search {
+        /// Text to search for
+        #[arg(
+            value_name = "QUERY",
+            help = "Text to search for, ignoring case",
+            long_help = "Text to search for in the messages of every saved session, ignoring case. With --regex it is a case sensitive regular expression instead."
+        )]
+        query: String,
+
+        /// Treat the query as a regular expression
+        #[arg(long, help = "Treat the query as a regular expression")]
+        regex: bool,
+
+        /// Most sessions to list
+        #[arg(
+            short,
+            long,
+            value_name = "N",
+            default_value_t = 10,
+            help = "Most sessions to list, most recently used first"
+        )]
+        limit: usize,
+    },
+
+    /// Export a session to Markdown or HTML
+    #[command(about = "Export a session to Markdown or HTML")]
+    Export {
+        /// Name of the session to export
+        #[arg(value_name = "SESSION", help = "Name of the session to export")]
+        session: String,
+
+        /// Format to export to
+        #[arg(
+            short,
+            long,
+            value_enum,
+            default_value_t = ExportFormat::Markdown,
+            help = "Format to export to"
+        )]
+        format: ExportFormat,
+
+        /// File to write the export to
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            help = "File to write the export to, instead of printing it"
+        )]
+        output: Option<PathBuf>,
+    },
+
     /// Execute commands from an instruction file
     #[command(about = "Execute commands from an instruction file or stdin")]
     Run {
@@ -119,11 +213,21 @@ enum Command {
         #[arg(
             short,
             long,
-            action = clap::ArgAction::SetTrue,
-            help = "Resume from a previous run",
-            long_help = "Continue from a previous run, maintaining the execution state and context."
+            value_name = "SESSION",
+            num_args = 0..=1,
+            help = "Resume from a previous run (last used, or named by SESSION or --name)",
+            long_help = "Continue from a previous run, maintaining the execution state and context. SESSION, or --name, can be the start of a run's name or 'latest' for the last one."
+        )]
+        resume: Option<Option<String>>,
+
+        /// Keep the session in memory only
+        #[arg(
+            long,
+            conflicts_with_all = ["name", "resume"],
+            help = "Don't save the session to a file",
+            long_help = "Keep the conversation in memory only, without writing a session file. The session can't be resumed, searched or exported later."
         )]
-        resume: bool,
+        no_session: bool,
 
         /// Add a stdio extension with environment variables and command
         #[arg(
@@ -155,6 +259,15 @@ enum CliProviderVariant {
     Ollama,
 }
 
+/// Combine `--name` with the optional session given to `--resume`, which takes precedence
+fn resume_target(name: Option<String>, resume: Option<Option<String>>) -> (Option<String>, bool) {
+    match resume {
+        Some(Some(session)) => (Some(session), true),
+        Some(None) => (name, true),
+        None => (name, false),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -175,20 +288,50 @@ async fn main() -> Result<()> {
         Some(Command::Session {
             name,
             resume,
+            no_session,
             extension,
             builtin,
         }) => {
-            let mut session = build_session(name, resume, extension, builtin).await;
-            setup_logging(session.session_file().file_stem().and_then(|s| s.to_str()))?;
-
+            let (name, resume) = resume_target(name, resume);
+            let mut session = build_session(name, resume, no_session, extension, builtin).await;
             let _ = session.start().await;
             return Ok(());
         }
+        Some(Command::Branch { session, at, name }) => {
+            if let Err(e) = handle_branch(&session, at, name) {
+                eprintln!("Failed to branch session: {}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Search {
+            query,
+            regex,
+            limit,
+        }) => {
+            if let Err(e) = handle_search(&query, regex, limit) {
+                eprintln!("Failed to search sessions: {}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Export {
+            session,
+            format,
+            output,
+        }) => {
+            if let Err(e) = handle_export(&session, format, output) {
+                eprintln!("Failed to export session: {}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
         Some(Command::Run {
             instructions,
             input_text,
             name,
             resume,
+            no_session,
             extension,
             builtin,
         }) => {
@@ -210,7 +353,8 @@ async fn main() -> Result<()> {
                     .expect("Failed to read from stdin");
                 stdin
             };
-            let mut session = build_session(name, resume, extension, builtin).await;
+            let (name, resume) = resume_target(name, resume);
+            let mut session = build_session(name, resume, no_session, extension, builtin).await;
             let _ = session.headless_start(contents.clone()).await;
             return Ok(());
         }
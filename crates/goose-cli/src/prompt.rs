@@ -1,12 +1,15 @@
 use anyhow::Result;
 use goose::message::Message;
 
+pub mod pager;
 pub mod renderer;
 pub mod rustyline;
 pub mod thinking;
 
 pub trait Prompt {
     fn render(&mut self, message: Box<Message>);
+    /// Show text of the assistant's response as it is streamed, before the full message
+    fn render_text(&mut self, text: &str);
     fn get_input(&mut self) -> Result<Input>;
     fn show_busy(&mut self);
     fn hide_busy(&self);
@@ -30,6 +33,10 @@ pub enum InputType {
     AskAgain, // Ask the user for input again. Control flow command.
     Message,  // User sent a message
     Exit,     // User wants to exit the session
+    Continue, // User wants the model to resume a truncated response
+    Model,    // User wants to see or switch the active model, named in the content
+    Usage,    // User wants to see the tokens used so far and what they cost
+    Strong,   // User sent a message to be answered by the strong model of a routed provider
 }
 
 pub enum Theme {
@@ -0,0 +1,170 @@
+use console::{style, Key, Term};
+
+/// Environment variable setting how many lines of a long message are shown at a time
+pub const PAGE_LINES_ENV: &str = "GOOSE_CLI_PAGE_LINES";
+
+/// How many lines of a message are shown at a time, or None to print messages in full
+///
+/// Paging is off unless `GOOSE_CLI_PAGE_LINES` is set, and never used when stdout is not a
+/// terminal, so piped output is always complete.
+pub fn page_lines_from_env() -> Option<usize> {
+    let lines = std::env::var(PAGE_LINES_ENV).ok()?.parse().ok()?;
+    (lines > 0 && Term::stdout().is_term()).then_some(lines)
+}
+
+/// Split text into pages of at most `page_lines` lines
+pub fn paginate(text: &str, page_lines: usize) -> Vec<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.is_empty() {
+        return vec![String::new()];
+    }
+    lines
+        .chunks(page_lines.max(1))
+        .map(|page| page.join("\n"))
+        .collect()
+}
+
+/// Ask whether to show more of a message, waiting for a key press
+///
+/// Space or enter shows more, while `q` or escape skips the rest of the message.
+pub fn wait_for_more(status: &str) -> bool {
+    let term = Term::stdout();
+    let _ = term.write_line(&format!(
+        "{}",
+        style(format!(
+            "-- {}, press space for more or q to skip the rest --",
+            status
+        ))
+        .dim()
+    ));
+    let key = term.read_key();
+    let _ = term.clear_last_lines(1);
+    !matches!(key, Ok(Key::Char('q')) | Ok(Key::Escape) | Err(_))
+}
+
+/// Note how many lines of a message were skipped in the pager
+pub fn print_skipped(lines: usize) {
+    println!(
+        "{}",
+        style(format!("[{} more lines not shown]", lines)).dim()
+    );
+}
+
+/// Print text one page at a time, waiting for a key press between pages
+pub fn print_paged(text: &str, page_lines: usize, print_page: impl Fn(&str)) {
+    let pages = paginate(text, page_lines);
+    for (index, page) in pages.iter().enumerate() {
+        print_page(page);
+
+        if index + 1 < pages.len()
+            && !wait_for_more(&format!("page {} of {}", index + 1, pages.len()))
+        {
+            print_skipped(pages[index + 1..].iter().map(|p| p.lines().count()).sum());
+            break;
+        }
+    }
+}
+
+/// Pages text streamed in pieces, pausing after every `page_lines` lines
+#[derive(Debug)]
+pub struct StreamPager {
+    page_lines: usize,
+    lines_shown: usize,
+    skipped_lines: Option<usize>,
+}
+
+impl StreamPager {
+    pub fn new(page_lines: usize) -> Self {
+        Self {
+            page_lines: page_lines.max(1),
+            lines_shown: 0,
+            skipped_lines: None,
+        }
+    }
+
+    /// Print the next piece of streamed text, calling `more` whenever a page is full
+    ///
+    /// Once `more` returns false, the rest of the message is only counted until `finish`.
+    pub fn write(
+        &mut self,
+        text: &str,
+        mut print: impl FnMut(&str),
+        mut more: impl FnMut() -> bool,
+    ) {
+        for piece in text.split_inclusive('\n') {
+            let ends_line = piece.ends_with('\n');
+            if let Some(skipped) = &mut self.skipped_lines {
+                *skipped += ends_line as usize;
+                continue;
+            }
+
+            print(piece);
+            if ends_line {
+                self.lines_shown += 1;
+                if self.lines_shown.is_multiple_of(self.page_lines) && !more() {
+                    self.skipped_lines = Some(0);
+                }
+            }
+        }
+    }
+
+    /// End the current message, returning how many of its lines were skipped
+    pub fn finish(&mut self) -> usize {
+        self.lines_shown = 0;
+        self.skipped_lines.take().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paginate_long_message() {
+        let text = (1..=25)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let pages = paginate(&text, 10);
+        assert_eq!(pages.len(), 3);
+        assert!(pages[0].starts_with("line 1\n"));
+        assert!(pages[0].ends_with("line 10"));
+        assert_eq!(pages[2].lines().count(), 5);
+        assert_eq!(pages.join("\n"), text);
+
+        // A message that fits is a single page
+        assert_eq!(paginate("short", 10), vec!["short"]);
+        assert_eq!(paginate(&text, 25).len(), 1);
+    }
+
+    #[test]
+    fn test_stream_pager_pauses_every_page() {
+        let text: String = (1..=25).map(|i| format!("line {}\n", i)).collect();
+        let mut pager = StreamPager::new(10);
+        let mut printed = String::new();
+        let mut pauses = 0;
+
+        // The text arrives in pieces that do not line up with lines
+        for piece in text.as_bytes().chunks(7) {
+            let piece = std::str::from_utf8(piece).unwrap();
+            pager.write(
+                piece,
+                |s| printed.push_str(s),
+                || {
+                    pauses += 1;
+                    true
+                },
+            );
+        }
+        assert_eq!(pauses, 2);
+        assert_eq!(printed, text);
+        assert_eq!(pager.finish(), 0);
+
+        // Skipping at the first pause leaves out the other pages
+        let mut printed = String::new();
+        pager.write(&text, |s| printed.push_str(s), || false);
+        assert_eq!(printed.lines().count(), 10);
+        assert_eq!(pager.finish(), 15);
+    }
+}
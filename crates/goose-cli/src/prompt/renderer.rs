@@ -9,6 +9,7 @@ use mcp_core::role::Role;
 use mcp_core::{content::Content, tool::ToolCall};
 use serde_json::Value;
 
+use super::pager::print_paged;
 use super::Theme;
 
 const MAX_STRING_LENGTH: usize = 40;
@@ -97,6 +98,27 @@ mod tests {
         // Test path with less than 3 components
         assert_eq!(shorten_path("/usr/local"), "/usr/local");
     }
+
+    #[test]
+    fn test_shell_request_shows_explanation() {
+        console::set_colors_enabled(false);
+        let lines = shell_request_lines(&serde_json::json!({
+            "command": "cargo test",
+            "explanation": "Run the tests to check the fix",
+        }))
+        .unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                "explanation: Run the tests to check the fix",
+                "command: cargo test"
+            ]
+        );
+
+        let lines = shell_request_lines(&serde_json::json!({"command": "ls"})).unwrap();
+        assert_eq!(lines, vec!["command: ls"]);
+        assert!(shell_request_lines(&serde_json::json!({"path": "."})).is_none());
+    }
 }
 
 /// Implement the ToolRenderer trait for each tool that you want to render in the prompt.
@@ -210,11 +232,9 @@ impl ToolRenderer for BashDeveloperExtensionRenderer {
             Ok(call) => {
                 default_print_request_header(call);
 
-                match call.arguments.get("command") {
-                    Some(Value::String(s)) => {
-                        println!("{}: {}", style("command").dim(), style(s).green());
-                    }
-                    _ => print_params(&call.arguments, 0),
+                match shell_request_lines(&call.arguments) {
+                    Some(lines) => lines.iter().for_each(|line| println!("{}", line)),
+                    None => print_params(&call.arguments, 0),
                 }
                 print_newline();
             }
@@ -227,7 +247,28 @@ impl ToolRenderer for BashDeveloperExtensionRenderer {
     }
 }
 
-pub fn render(message: &Message, theme: &Theme, renderers: HashMap<String, Box<dyn ToolRenderer>>) {
+/// The lines showing a shell command, after the model's explanation of it if there is one
+fn shell_request_lines(arguments: &Value) -> Option<Vec<String>> {
+    let command = arguments.get("command")?.as_str()?;
+    let mut lines = Vec::new();
+    if let Some(explanation) = arguments.get("explanation").and_then(Value::as_str) {
+        lines.push(format!("{}: {}", style("explanation").dim(), explanation));
+    }
+    lines.push(format!(
+        "{}: {}",
+        style("command").dim(),
+        style(command).green()
+    ));
+    Some(lines)
+}
+
+/// Render a message, showing text longer than `page_lines` lines one page at a time
+pub fn render(
+    message: &Message,
+    theme: &Theme,
+    renderers: HashMap<String, Box<dyn ToolRenderer>>,
+    page_lines: Option<usize>,
+) {
     let theme = match theme {
         Theme::Light => "GitHub",
         Theme::Dark => "zenburn",
@@ -236,7 +277,12 @@ pub fn render(message: &Message, theme: &Theme, renderers: HashMap<String, Box<d
     let mut last_tool_name: &str = "default";
     for message_content in &message.content {
         match message_content {
-            MessageContent::Text(text) => print_markdown(&text.text, theme),
+            MessageContent::Text(text) => match page_lines {
+                Some(page_lines) if text.text.lines().count() > page_lines => {
+                    print_paged(&text.text, page_lines, |page| print_markdown(page, theme))
+                }
+                _ => print_markdown(&text.text, theme),
+            },
             MessageContent::ToolRequest(tool_request) => match &tool_request.tool_call {
                 Ok(call) => {
                     last_tool_name = &call.name;
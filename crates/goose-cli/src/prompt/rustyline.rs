@@ -1,6 +1,8 @@
 use std::collections::HashMap;
+use std::io::Write;
 
 use super::{
+    pager::{page_lines_from_env, print_skipped, wait_for_more, StreamPager},
     renderer::{
         render, BashDeveloperExtensionRenderer, DefaultRenderer, TextEditorRenderer, ToolRenderer,
     },
@@ -21,6 +23,8 @@ pub struct RustylinePrompt {
     theme: Theme,
     renderers: HashMap<String, Box<dyn ToolRenderer>>,
     editor: DefaultEditor,
+    page_lines: Option<usize>,
+    stream_pager: Option<StreamPager>,
 }
 
 impl RustylinePrompt {
@@ -41,6 +45,7 @@ impl RustylinePrompt {
             Box::new(text_editor_renderer),
         );
 
+        let page_lines = page_lines_from_env();
         let mut editor = DefaultEditor::new().expect("Failed to create editor");
         editor.bind_sequence(
             KeyEvent(KeyCode::Char('j'), Modifiers::CTRL),
@@ -61,13 +66,46 @@ impl RustylinePrompt {
                 .unwrap_or(Theme::Dark),
             renderers,
             editor,
+            page_lines,
+            stream_pager: page_lines.map(StreamPager::new),
+        }
+    }
+
+    /// End paging of the streamed message, noting any lines that were skipped
+    fn finish_stream(&mut self) {
+        if let Some(pager) = &mut self.stream_pager {
+            let skipped = pager.finish();
+            if skipped > 0 {
+                print_skipped(skipped);
+            }
         }
     }
 }
 
 impl Prompt for RustylinePrompt {
     fn render(&mut self, message: Box<Message>) {
-        render(&message, &self.theme, self.renderers.clone());
+        self.finish_stream();
+        render(
+            &message,
+            &self.theme,
+            self.renderers.clone(),
+            self.page_lines,
+        );
+    }
+
+    fn render_text(&mut self, text: &str) {
+        match &mut self.stream_pager {
+            Some(pager) => pager.write(
+                text,
+                |piece| {
+                    print!("{}", piece);
+                    let _ = std::io::stdout().flush();
+                },
+                || wait_for_more("more of the response follows"),
+            ),
+            None => print!("{}", text),
+        }
+        let _ = std::io::stdout().flush();
     }
 
     fn show_busy(&mut self) {
@@ -81,6 +119,7 @@ impl Prompt for RustylinePrompt {
     }
 
     fn get_input(&mut self) -> Result<Input> {
+        self.finish_stream();
         let input = self.editor.readline(PROMPT);
         let mut message_text = match input {
             Ok(text) => {
@@ -127,12 +166,41 @@ impl Prompt for RustylinePrompt {
                 input_type: InputType::AskAgain,
                 content: None,
             });
+        } else if message_text.eq_ignore_ascii_case("/continue") {
+            return Ok(Input {
+                input_type: InputType::Continue,
+                content: None,
+            });
+        } else if message_text.eq_ignore_ascii_case("/model") || message_text.starts_with("/model ")
+        {
+            let model = message_text["/model".len()..].trim();
+            return Ok(Input {
+                input_type: InputType::Model,
+                content: (!model.is_empty()).then(|| model.to_string()),
+            });
+        } else if message_text.eq_ignore_ascii_case("/strong")
+            || message_text.starts_with("/strong ")
+        {
+            let message = message_text["/strong".len()..].trim();
+            return Ok(Input {
+                input_type: InputType::Strong,
+                content: (!message.is_empty()).then(|| message.to_string()),
+            });
+        } else if message_text.eq_ignore_ascii_case("/usage") {
+            return Ok(Input {
+                input_type: InputType::Usage,
+                content: None,
+            });
         } else if message_text.eq_ignore_ascii_case("/?")
             || message_text.eq_ignore_ascii_case("/help")
         {
             println!("Commands:");
             println!("/exit - Exit the session");
             println!("/t - Toggle Light/Dark theme");
+            println!("/continue - Resume a response that was cut off by the max output length");
+            println!("/model [name] - Show the active model, or switch to another of the provider's models");
+            println!("/strong <message> - Send a message to the strong model when GOOSE_STRONG_MODEL is set");
+            println!("/usage - Show the tokens used so far in this session and what they cost");
             println!("/? | /help - Display this help message");
             println!("Ctrl+C - Interrupt goose (resets the interaction to before the interrupted user request)");
             println!("Ctrl+j - Adds a newline");
@@ -0,0 +1,253 @@
+use anyhow::Result;
+use goose::message::MessageContent;
+use mcp_core::role::Role;
+use regex::{Regex, RegexBuilder};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::session::deserialize_messages;
+
+/// Longest snippet shown for a matched line, in characters
+const MAX_SNIPPET_CHARS: usize = 160;
+
+/// How much of a long line is kept before the match, in characters
+const SNIPPET_LEAD_CHARS: usize = 40;
+
+/// A session with text that matched the query
+#[derive(Debug)]
+pub struct SessionMatch {
+    /// The session's name, which is its file name without the extension
+    pub name: String,
+    pub path: PathBuf,
+    pub matches: Vec<LineMatch>,
+}
+
+/// A line of a message that matched, with the lines around it
+#[derive(Debug, PartialEq)]
+pub struct LineMatch {
+    /// Index of the message in the session
+    pub message_index: usize,
+    pub role: Role,
+    pub before: Option<String>,
+    pub line: String,
+    pub after: Option<String>,
+}
+
+/// Build the matcher for a query, which is case insensitive text unless `is_regex` is set
+pub fn query_matcher(query: &str, is_regex: bool) -> Result<Regex> {
+    let pattern = if is_regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!is_regex)
+        .build()
+        .map_err(|e| anyhow::anyhow!("Invalid search pattern '{}': {}", query, e))
+}
+
+/// Find the sessions in `session_dir` with text matching `query`, most recently used first
+///
+/// Only the text of messages is searched, not tool calls or their output. Session files that
+/// can't be read are skipped, and at most `limit` sessions are returned.
+pub fn search_sessions(
+    session_dir: &Path,
+    query: &Regex,
+    limit: usize,
+) -> Result<Vec<SessionMatch>> {
+    let mut session_files: Vec<(PathBuf, SystemTime)> = fs::read_dir(session_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+        .map(|path| {
+            let modified = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            (path, modified)
+        })
+        .collect();
+    session_files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut results = Vec::new();
+    for (path, _) in session_files {
+        if results.len() >= limit {
+            break;
+        }
+        let messages = match File::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(deserialize_messages)
+        {
+            Ok(messages) => messages,
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "Skipping unreadable session file");
+                continue;
+            }
+        };
+
+        let mut matches = Vec::new();
+        for (message_index, message) in messages.iter().enumerate() {
+            for content in &message.content {
+                if let MessageContent::Text(text) = content {
+                    matches.extend(match_lines(&text.text, query, message_index, &message.role));
+                }
+            }
+        }
+
+        if !matches.is_empty() {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default()
+                .to_string();
+            results.push(SessionMatch {
+                name,
+                path,
+                matches,
+            });
+        }
+    }
+    Ok(results)
+}
+
+fn match_lines(text: &str, query: &Regex, message_index: usize, role: &Role) -> Vec<LineMatch> {
+    let lines: Vec<&str> = text.lines().collect();
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let found = query.find(line)?;
+            let context = |line: &str| {
+                Some(line.trim())
+                    .filter(|line| !line.is_empty())
+                    .map(|line| snippet(line, 0))
+            };
+            Some(LineMatch {
+                message_index,
+                role: role.clone(),
+                before: i.checked_sub(1).and_then(|i| context(lines[i])),
+                line: snippet(line, found.start()),
+                after: lines.get(i + 1).and_then(|line| context(line)),
+            })
+        })
+        .collect()
+}
+
+/// Shorten a long line to the part around the byte offset `at`, marking what was cut with "..."
+fn snippet(line: &str, at: usize) -> String {
+    let line = line.trim_end();
+    if line.chars().count() <= MAX_SNIPPET_CHARS {
+        return line.to_string();
+    }
+
+    let chars_before = line[..at.min(line.len())].chars().count();
+    let start = chars_before.saturating_sub(SNIPPET_LEAD_CHARS);
+    let kept: String = line.chars().skip(start).take(MAX_SNIPPET_CHARS).collect();
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push_str("...");
+    }
+    snippet.push_str(&kept);
+    if start + MAX_SNIPPET_CHARS < line.chars().count() {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::persist_messages;
+    use goose::message::Message;
+    use mcp_core::tool::ToolCall;
+    use serde_json::json;
+
+    fn write_sessions(dir: &Path) {
+        persist_messages(
+            &dir.join("auth.jsonl"),
+            &[
+                Message::user()
+                    .with_text("The login fails.\nFix the Auth bug in the token check\nthanks"),
+                Message::assistant().with_text("The auth token expires too early."),
+            ],
+        )
+        .unwrap();
+        persist_messages(
+            &dir.join("docs.jsonl"),
+            &[
+                Message::user().with_text("Update the README"),
+                Message::assistant().with_tool_request(
+                    "1",
+                    Ok(ToolCall::new(
+                        "developer__shell",
+                        json!({"command": "grep auth README.md"}),
+                    )),
+                ),
+            ],
+        )
+        .unwrap();
+        fs::write(dir.join("broken.jsonl"), "not json\n").unwrap();
+        fs::write(dir.join("notes.txt"), "auth").unwrap();
+    }
+
+    #[test]
+    fn test_search_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        write_sessions(dir.path());
+
+        let results =
+            search_sessions(dir.path(), &query_matcher("auth", false).unwrap(), 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "auth");
+        assert_eq!(
+            results[0].matches[0],
+            LineMatch {
+                message_index: 0,
+                role: Role::User,
+                before: Some("The login fails.".to_string()),
+                line: "Fix the Auth bug in the token check".to_string(),
+                after: Some("thanks".to_string()),
+            }
+        );
+        assert_eq!(results[0].matches[1].message_index, 1);
+        assert_eq!(results[0].matches[1].before, None);
+
+        let results =
+            search_sessions(dir.path(), &query_matcher("README", false).unwrap(), 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "docs");
+
+        let results =
+            search_sessions(dir.path(), &query_matcher("kubernetes", false).unwrap(), 10).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_search_sessions_with_regex_and_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        write_sessions(dir.path());
+
+        // Regex queries are case sensitive
+        let query = query_matcher(r"^Fix the \w+ bug", true).unwrap();
+        let results = search_sessions(dir.path(), &query, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        let query = query_matcher(r"^fix the \w+ bug", true).unwrap();
+        assert!(search_sessions(dir.path(), &query, 10).unwrap().is_empty());
+
+        let query = query_matcher("the", false).unwrap();
+        assert_eq!(search_sessions(dir.path(), &query, 10).unwrap().len(), 2);
+        assert_eq!(search_sessions(dir.path(), &query, 1).unwrap().len(), 1);
+
+        assert!(query_matcher("(unclosed", true).is_err());
+        assert!(query_matcher("(unclosed", false).is_ok());
+    }
+
+    #[test]
+    fn test_snippet_keeps_the_match() {
+        let line = format!("{}needle{}", "a".repeat(300), "b".repeat(300));
+        let snippet = snippet(&line, 300);
+        assert!(snippet.starts_with("...") && snippet.ends_with("..."));
+        assert!(snippet.contains("needle"));
+        assert_eq!(snippet.chars().count(), MAX_SNIPPET_CHARS + 6);
+    }
+}
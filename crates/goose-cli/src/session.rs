@@ -2,13 +2,16 @@ use anyhow::Result;
 use core::panic;
 use futures::StreamExt;
 use std::fs::{self, File};
-use std::io::{self, BufRead, Write};
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 
 use crate::log_usage::log_usage;
 use crate::prompt::{InputType, Prompt};
-use goose::agents::Agent;
+use goose::agents::{Agent, ReplyEvent, UsageSummary};
+use goose::config::Config;
+use goose::continuation::{can_continue, continuation_request, stitch_continuation};
 use goose::message::{Message, MessageContent};
+use goose::providers::router::Tier;
 use mcp_core::handler::ToolError;
 use mcp_core::role::Role;
 
@@ -24,9 +27,11 @@ pub fn ensure_session_dir() -> Result<PathBuf> {
     Ok(config_dir)
 }
 
-pub fn get_most_recent_session() -> Result<PathBuf> {
-    let session_dir = ensure_session_dir()?;
-    let mut entries = fs::read_dir(&session_dir)?
+/// The name given to `--resume` for the most recently used session
+pub const LATEST_SESSION: &str = "latest";
+
+pub fn get_most_recent_session(session_dir: &Path) -> Result<PathBuf> {
+    let mut entries = fs::read_dir(session_dir)?
         .filter_map(|entry| entry.ok())
         .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "jsonl"))
         .collect::<Vec<_>>();
@@ -50,6 +55,40 @@ pub fn get_most_recent_session() -> Result<PathBuf> {
     Ok(entries[0].path())
 }
 
+/// Find the session file for `name`, which is a session's full name, the start of it, or
+/// [`LATEST_SESSION`] for the most recently used session
+///
+/// A full name is preferred over longer names that start with it. Returns None when no
+/// session matches, and fails when the start of a name matches more than one session.
+pub fn find_session(session_dir: &Path, name: &str) -> Result<Option<PathBuf>> {
+    let exact = session_dir.join(format!("{}.jsonl", name));
+    if exact.exists() {
+        return Ok(Some(exact));
+    }
+    if name == LATEST_SESSION {
+        return Ok(get_most_recent_session(session_dir).ok());
+    }
+
+    let mut matches: Vec<String> = fs::read_dir(session_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+        .filter_map(|path| path.file_stem()?.to_str().map(str::to_string))
+        .filter(|stem| stem.starts_with(name))
+        .collect();
+    matches.sort();
+
+    match matches.as_slice() {
+        [] => Ok(None),
+        [session] => Ok(Some(session_dir.join(format!("{}.jsonl", session)))),
+        _ => Err(anyhow::anyhow!(
+            "'{}' matches more than one session: {}. Give more of the name to choose one",
+            name,
+            matches.join(", ")
+        )),
+    }
+}
+
 pub fn readable_session_file(session_file: &PathBuf) -> Result<File> {
     match fs::OpenOptions::new()
         .read(true)
@@ -63,9 +102,23 @@ pub fn readable_session_file(session_file: &PathBuf) -> Result<File> {
     }
 }
 
+/// Save the messages of a session, replacing what was saved before
+///
+/// The messages are written to a temporary file next to the session and then renamed over it,
+/// so the session file is never left half written if the process is killed mid-write.
 pub fn persist_messages(session_file: &PathBuf, messages: &[Message]) -> Result<()> {
-    let file = fs::File::create(session_file)?; // Create or truncate the file
-    persist_messages_internal(file, messages)
+    let mut temp_name = session_file.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_file = PathBuf::from(temp_name);
+
+    let result = File::create(&temp_file)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| persist_messages_internal(file, messages))
+        .and_then(|_| Ok(fs::rename(&temp_file, session_file)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_file);
+    }
+    result
 }
 
 fn persist_messages_internal(session_file: File, messages: &[Message]) -> Result<()> {
@@ -77,17 +130,72 @@ fn persist_messages_internal(session_file: File, messages: &[Message]) -> Result
     }
 
     writer.flush()?;
+    writer.get_ref().sync_all()?;
     Ok(())
 }
 
-pub fn deserialize_messages(file: File) -> Result<Vec<Message>> {
-    let reader = io::BufReader::new(file);
+/// Read the messages of a session file
+///
+/// A last line that doesn't parse is skipped with a warning, since it is most likely a message
+/// that was cut off while being written. Any other malformed line is an error.
+pub fn deserialize_messages(mut file: File) -> Result<Vec<Message>> {
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+
+    // Lines are parsed as bytes, since a cut off write can end partway through a character
+    let mut lines: Vec<&[u8]> = contents.split(|&byte| byte == b'\n').collect();
+    if lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
     let mut messages = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        match serde_json::from_slice::<Message>(line) {
+            Ok(message) => messages.push(message),
+            Err(e) if i + 1 == lines.len() => {
+                tracing::warn!(
+                    line = i + 1,
+                    error = %e,
+                    "Skipping the partially written last message of the session file"
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
 
-    for line in reader.lines() {
-        messages.push(serde_json::from_str::<Message>(&line?)?);
+    Ok(messages)
+}
+
+/// Create a new session seeded with the messages of an existing one up to and including `index`
+///
+/// The source session is left untouched, so the conversation can continue along either branch.
+pub fn branch_session(source: &PathBuf, index: usize, target: &PathBuf) -> Result<Vec<Message>> {
+    if target.exists() {
+        return Err(anyhow::anyhow!(
+            "Session file {} already exists",
+            target.display()
+        ));
+    }
+    let file = File::open(source)
+        .map_err(|e| anyhow::anyhow!("Failed to open session file {}: {}", source.display(), e))?;
+    let mut messages = deserialize_messages(file)?;
+
+    if index >= messages.len() {
+        return Err(anyhow::anyhow!(
+            "Cannot branch at message {}, the session only has {} messages",
+            index,
+            messages.len()
+        ));
+    }
+    messages.truncate(index + 1);
+    // A tool request must be followed by its response, so it can't end the conversation
+    if messages[index].is_tool_call() {
+        return Err(anyhow::anyhow!(
+            "Cannot branch at message {}, it is a tool request awaiting its response",
+            index
+        ));
     }
 
+    persist_messages(target, &messages)?;
     Ok(messages)
 }
 
@@ -95,7 +203,8 @@ pub fn deserialize_messages(file: File) -> Result<Vec<Message>> {
 pub struct Session<'a> {
     agent: Box<dyn Agent>,
     prompt: Box<dyn Prompt + 'a>,
-    session_file: PathBuf,
+    /// Where the messages are saved, or None when nothing is saved
+    session_file: Option<PathBuf>,
     messages: Vec<Message>,
 }
 
@@ -108,14 +217,19 @@ impl<'a> Session<'a> {
     ) -> Self {
         let messages = match readable_session_file(&session_file) {
             Ok(file) => deserialize_messages(file).unwrap_or_else(|e| {
-                eprintln!(
-                    "Failed to read messages from session file. Starting fresh.\n{}",
-                    e
+                tracing::warn!(
+                    session_file = %session_file.display(),
+                    error = %e,
+                    "Failed to read messages from session file, starting fresh"
                 );
                 Vec::<Message>::new()
             }),
             Err(e) => {
-                eprintln!("Failed to load session file. Starting fresh.\n{}", e);
+                tracing::warn!(
+                    session_file = %session_file.display(),
+                    error = %e,
+                    "Failed to load session file, starting fresh"
+                );
                 Vec::<Message>::new()
             }
         };
@@ -125,11 +239,21 @@ impl<'a> Session<'a> {
         Session {
             agent,
             prompt,
-            session_file,
+            session_file: Some(session_file),
             messages,
         }
     }
 
+    /// A session kept only in memory, which never writes a session file
+    pub fn new_ephemeral(agent: Box<dyn Agent>, prompt: Box<dyn Prompt + 'a>) -> Self {
+        Session {
+            agent,
+            prompt,
+            session_file: None,
+            messages: Vec::new(),
+        }
+    }
+
     pub async fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         self.prompt.goose_ready();
 
@@ -139,11 +263,40 @@ impl<'a> Session<'a> {
                 InputType::Message => {
                     if let Some(content) = &input.content {
                         self.messages.push(Message::user().with_text(content));
-                        persist_messages(&self.session_file, &self.messages)?;
+                        self.persist()?;
                     }
                 }
                 InputType::Exit => break,
                 InputType::AskAgain => continue,
+                InputType::Continue => {
+                    if !can_continue(&self.messages) {
+                        self.prompt
+                            .render(raw_message("There is no truncated response to continue."));
+                        continue;
+                    }
+                    self.prompt.show_busy();
+                    self.agent_continue_message().await;
+                    self.prompt.hide_busy();
+                    continue;
+                }
+                InputType::Model => {
+                    self.handle_model_command(input.content.as_deref()).await;
+                    continue;
+                }
+                InputType::Strong => {
+                    let Some(content) = &input.content else {
+                        self.prompt.render(raw_message("Usage: /strong <message>"));
+                        continue;
+                    };
+                    self.agent.set_route_hint(Some(Tier::Strong)).await;
+                    self.messages.push(Message::user().with_text(content));
+                    self.persist()?;
+                }
+                InputType::Usage => {
+                    let summary = self.agent.usage_summary().await;
+                    self.prompt.render(raw_message(&usage_report(&summary)));
+                    continue;
+                }
             }
 
             self.prompt.show_busy();
@@ -160,7 +313,7 @@ impl<'a> Session<'a> {
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.messages
             .push(Message::user().with_text(initial_message.as_str()));
-        persist_messages(&self.session_file, &self.messages)?;
+        self.persist()?;
 
         self.agent_process_messages().await;
 
@@ -168,27 +321,128 @@ impl<'a> Session<'a> {
         Ok(())
     }
 
+    /// Save the messages to the session file, unless the session is only kept in memory
+    fn persist(&self) -> Result<()> {
+        match &self.session_file {
+            Some(session_file) => persist_messages(session_file, &self.messages),
+            None => Ok(()),
+        }
+    }
+
     async fn agent_process_messages(&mut self) {
-        let mut stream = match self.agent.reply(&self.messages).await {
+        self.process_reply(false).await;
+        self.warn_if_approaching_rate_limit().await;
+    }
+
+    /// Show the active model, or switch the agent to `model` for the rest of the session
+    async fn handle_model_command(&mut self, model: Option<&str>) {
+        let Some(model) = model else {
+            let model_config = self.agent.model_config().await;
+            self.prompt.render(raw_message(&format!(
+                "Using model {} with a context limit of {} tokens.",
+                model_config.model_name,
+                model_config.context_limit()
+            )));
+            return;
+        };
+
+        let provider_name: String = match Config::global().get("GOOSE_PROVIDER") {
+            Ok(name) => name,
+            Err(e) => {
+                self.prompt
+                    .render(raw_message(&format!("Failed to switch model: {}", e)));
+                return;
+            }
+        };
+        let message = match self.agent.switch_model(&provider_name, model).await {
+            Ok(()) => format!("Switched to model {}.", model),
+            Err(e) => format!("Failed to switch model: {}", e),
+        };
+        self.prompt.render(raw_message(&message));
+    }
+
+    /// Ask the agent to resume the truncated assistant response at the end of the conversation
+    async fn agent_continue_message(&mut self) {
+        self.process_reply(true).await;
+        self.warn_if_approaching_rate_limit().await;
+    }
+
+    /// Warn the user when the provider reports that the rate limit quota is running low
+    async fn warn_if_approaching_rate_limit(&mut self) {
+        if let Some(warning) = self
+            .agent
+            .rate_limit()
+            .await
+            .and_then(|rate_limit| rate_limit.warning())
+        {
+            self.prompt.render(raw_message(&format!(
+                "Warning: {}. Goose will slow down to stay under the limit.",
+                warning
+            )));
+        }
+    }
+
+    /// Stream the agent's reply, stitching the first assistant message onto the
+    /// truncated one when continuing
+    async fn process_reply(&mut self, mut continuing: bool) {
+        let request = if continuing {
+            continuation_request(&self.messages)
+        } else {
+            self.messages.clone()
+        };
+        let mut stream = match self.agent.reply_stream(&request).await {
             Ok(stream) => stream,
             Err(e) => {
-                eprintln!("Error starting reply stream: {}", e);
+                tracing::error!(error = %e, "Error starting reply stream");
                 return;
             }
         };
+        // Whether text of the current response has been shown as it was streamed
+        let mut streamed_text = false;
         loop {
             tokio::select! {
                 response = stream.next() => {
                     match response {
-                        Some(Ok(message)) => {
-                            self.messages.push(message.clone());
-                            persist_messages(&self.session_file, &self.messages).unwrap_or_else(|e| eprintln!("Failed to persist messages: {}", e));
-                            self.prompt.hide_busy();
-                            self.prompt.render(Box::new(message.clone()));
+                        Some(Ok(ReplyEvent::Text(text))) => {
+                            if !streamed_text {
+                                streamed_text = true;
+                                self.prompt.hide_busy();
+                            }
+                            self.prompt.render_text(&text);
+                        }
+                        Some(Ok(ReplyEvent::Message(message))) => {
+                            if continuing && message.role == Role::Assistant {
+                                continuing = false;
+                                if let Err(e) = stitch_continuation(&mut self.messages, message.clone()) {
+                                    tracing::error!(error = %e, "Failed to continue response");
+                                    break;
+                                }
+                            } else {
+                                self.messages.push(message.clone());
+                            }
+                            self.persist().unwrap_or_else(|e| tracing::error!(error = %e, "Failed to persist messages"));
+                            if streamed_text {
+                                // Only render what wasn't already shown as it was streamed
+                                streamed_text = false;
+                                self.prompt.render_text("\n");
+                                let mut rest = message.clone();
+                                rest.content.retain(|content| content.as_text().is_none());
+                                if !rest.content.is_empty() {
+                                    self.prompt.render(Box::new(rest));
+                                }
+                            } else {
+                                self.prompt.hide_busy();
+                                self.prompt.render(Box::new(message.clone()));
+                            }
+                            if message.is_truncated() {
+                                self.prompt.render(raw_message(
+                                    "Note: the response was cut off because it reached the maximum output length. Use /continue to pick up where it left off.",
+                                ));
+                            }
                             self.prompt.show_busy();
                         }
                         Some(Err(e)) => {
-                            eprintln!("Error: {}", e);
+                            tracing::error!(error = %e, "Error in reply stream");
                             drop(stream);
                             self.rewind_messages();
                             self.prompt.render(raw_message(r#"
@@ -215,27 +469,7 @@ We've removed the conversation up to the most recent user message
 
     /// Rewind the messages to before the last user message (they have cancelled it).
     fn rewind_messages(&mut self) {
-        if self.messages.is_empty() {
-            return;
-        }
-
-        // Remove messages until we find the last user 'Text' message (not a tool response).
-        while let Some(message) = self.messages.last() {
-            if message.role == Role::User
-                && message
-                    .content
-                    .iter()
-                    .any(|c| matches!(c, MessageContent::Text(_)))
-            {
-                break;
-            }
-            self.messages.pop();
-        }
-
-        // Remove the last user text message we found.
-        if !self.messages.is_empty() {
-            self.messages.pop();
-        }
+        rewind_to_last_user_message(&mut self.messages);
     }
 
     fn handle_interrupted_messages(&mut self) {
@@ -287,21 +521,19 @@ We've removed the conversation up to the most recent user message
             // An interruption occurred outside of a tool request-response.
             if let Some(last_msg) = self.messages.last() {
                 if last_msg.role == Role::User {
-                    match last_msg.content.first() {
-                        Some(MessageContent::ToolResponse(_)) => {
-                            // Interruption occurred after a tool had completed but not assistant reply
-                            let prompt_response = "We interrupted the existing calls to tools. How would you like to proceed?";
-                            self.messages
-                                .push(Message::assistant().with_text(prompt_response));
-                            self.prompt.render(raw_message(prompt_response));
-                        }
-                        Some(_) => {
-                            // A real users message
-                            self.messages.pop();
-                            let prompt_response = "We interrupted before the model replied and removed the last message.";
-                            self.prompt.render(raw_message(prompt_response));
-                        }
-                        None => panic!("No content in last message"),
+                    // Check every content item, tool responses are not always first
+                    if last_msg.is_tool_response() {
+                        // Interruption occurred after a tool had completed but not assistant reply
+                        let prompt_response = "We interrupted the existing calls to tools. How would you like to proceed?";
+                        self.messages
+                            .push(Message::assistant().with_text(prompt_response));
+                        self.prompt.render(raw_message(prompt_response));
+                    } else {
+                        // A real users message
+                        self.messages.pop();
+                        let prompt_response =
+                            "We interrupted before the model replied and removed the last message.";
+                        self.prompt.render(raw_message(prompt_response));
                     }
                 }
             }
@@ -309,23 +541,416 @@ We've removed the conversation up to the most recent user message
     }
 
     async fn close_session(&mut self) {
-        self.prompt.render(raw_message(
-            format!(
-                "Closing session. Recorded to {}\n",
-                self.session_file.display()
-            )
-            .as_str(),
-        ));
+        let summary = self.agent.usage_summary().await;
+        if summary.total_tokens > 0 {
+            self.prompt.render(raw_message(&usage_report(&summary)));
+        }
+        let closing = match &self.session_file {
+            Some(session_file) => {
+                format!("Closing session. Recorded to {}\n", session_file.display())
+            }
+            None => "Closing session. Nothing was saved.\n".to_string(),
+        };
+        self.prompt.render(raw_message(&closing));
         self.prompt.close();
-        let usage = self.agent.usage().await;
-        log_usage(self.session_file.to_string_lossy().to_string(), usage);
+        if let Some(session_file) = &self.session_file {
+            log_usage(session_file.to_string_lossy().to_string(), summary.models);
+        }
     }
 
-    pub fn session_file(&self) -> PathBuf {
+    pub fn session_file(&self) -> Option<PathBuf> {
         self.session_file.clone()
     }
 }
 
+/// Whether a message was typed by the user, rather than carrying tool responses back to the model
+fn is_user_input(message: &Message) -> bool {
+    message.role == Role::User
+        && !message.is_tool_response()
+        && message
+            .content
+            .iter()
+            .any(|c| matches!(c, MessageContent::Text(_)))
+}
+
+/// Remove the last message typed by the user and everything after it
+///
+/// Tool responses are never mistaken for user input, even when they carry text as well, so a
+/// tool request is never left behind without its response.
+fn rewind_to_last_user_message(messages: &mut Vec<Message>) {
+    let start = messages.iter().rposition(is_user_input).unwrap_or_default();
+    messages.truncate(start);
+}
+
+/// A report of the tokens used, in total and by each model, with their cost where it is known
+fn usage_report(summary: &UsageSummary) -> String {
+    let cost = |cost: Option<f64>| match cost {
+        Some(cost) => format!("${:.4}", cost),
+        None => "unknown cost".to_string(),
+    };
+    let total_cost = if summary.cost_is_partial {
+        format!("at least {}", cost(summary.cost))
+    } else {
+        cost(summary.cost)
+    };
+    let mut report = format!(
+        "Usage: {} input and {} output tokens, {}",
+        summary.input_tokens, summary.output_tokens, total_cost
+    );
+    if summary.models.len() > 1 {
+        for model in &summary.models {
+            report.push_str(&format!(
+                "\n  {}: {} input and {} output tokens, {}",
+                model.model,
+                model.usage.input_tokens.unwrap_or(0),
+                model.usage.output_tokens.unwrap_or(0),
+                cost(model.cost)
+            ));
+        }
+    }
+    report
+}
+
 fn raw_message(content: &str) -> Box<Message> {
     Box::new(Message::assistant().with_text(content))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("original.jsonl");
+        let messages = vec![
+            Message::user().with_text("Write a haiku"),
+            Message::assistant().with_text("Leaves fall silently"),
+            Message::user().with_text("Make it about spring"),
+        ];
+        persist_messages(&source, &messages).unwrap();
+
+        let target = dir.path().join("branch.jsonl");
+        let branched = branch_session(&source, 1, &target).unwrap();
+        assert_eq!(branched, messages[..2]);
+
+        let stored = deserialize_messages(File::open(&target).unwrap()).unwrap();
+        assert_eq!(stored, messages[..2]);
+        // The original session is unchanged
+        let original = deserialize_messages(File::open(&source).unwrap()).unwrap();
+        assert_eq!(original, messages);
+
+        // The new session is never overwritten
+        assert!(branch_session(&source, 0, &target).is_err());
+        let out_of_range = branch_session(&source, 3, &dir.path().join("other.jsonl"));
+        assert!(out_of_range
+            .unwrap_err()
+            .to_string()
+            .contains("only has 3 messages"));
+    }
+
+    #[test]
+    fn test_find_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = std::time::SystemTime::now();
+        for (i, name) in ["abc123", "abd456", "xyz789", "auth", "auth-fix"]
+            .iter()
+            .enumerate()
+        {
+            let file = File::create(dir.path().join(format!("{}.jsonl", name))).unwrap();
+            // xyz789 is the most recently used
+            let age = if *name == "xyz789" { 0 } else { 60 + i as u64 };
+            file.set_modified(now - std::time::Duration::from_secs(age))
+                .unwrap();
+        }
+        fs::write(dir.path().join("zzz.txt"), "").unwrap();
+        let path = |name: &str| dir.path().join(format!("{}.jsonl", name));
+
+        let find = |name: &str| find_session(dir.path(), name).unwrap();
+        assert_eq!(find("latest"), Some(path("xyz789")));
+        assert_eq!(find("abd"), Some(path("abd456")));
+        assert_eq!(find("x"), Some(path("xyz789")));
+        // A full name wins over longer names starting with it
+        assert_eq!(find("auth"), Some(path("auth")));
+        assert_eq!(find("auth-"), Some(path("auth-fix")));
+        assert_eq!(find("zzz"), None);
+
+        let ambiguous = find_session(dir.path(), "ab").unwrap_err().to_string();
+        assert!(ambiguous.contains("more than one session: abc123, abd456"));
+
+        let empty = tempfile::tempdir().unwrap();
+        assert_eq!(find_session(empty.path(), "latest").unwrap(), None);
+    }
+
+    #[test]
+    fn test_truncated_last_message_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let messages = vec![
+            Message::user().with_text("Explain lifetimes"),
+            Message::assistant().with_text("A lifetime is how long a reference is valid"),
+            Message::user().with_text("Show an example with ünïcödé"),
+        ];
+        persist_messages(&path, &messages).unwrap();
+        assert!(!dir.path().join("session.jsonl.tmp").exists());
+
+        // Cut the last message off partway through a multi-byte character, as a killed write would
+        let contents = fs::read_to_string(&path).unwrap();
+        let cut = contents.rfind('ö').unwrap() + 1;
+        fs::write(&path, &contents.as_bytes()[..cut]).unwrap();
+
+        let loaded = deserialize_messages(File::open(&path).unwrap()).unwrap();
+        assert_eq!(loaded, messages[..2]);
+
+        // Anything malformed before the last line is still an error
+        let mut contents = b"{not json}\n".to_vec();
+        contents.extend(fs::read(&path).unwrap());
+        fs::write(&path, contents).unwrap();
+        assert!(deserialize_messages(File::open(&path).unwrap()).is_err());
+
+        // So is a message that is not valid UTF-8
+        persist_messages(&path, &messages).unwrap();
+        let mut contents = fs::read(&path).unwrap();
+        let start = contents.iter().position(|&byte| byte == b'E').unwrap();
+        contents[start] = 0xff;
+        fs::write(&path, contents).unwrap();
+        assert!(deserialize_messages(File::open(&path).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_usage_report() {
+        use goose::providers::base::{ProviderUsage, Usage};
+
+        let usage = |model: &str, input, output| {
+            ProviderUsage::new(
+                model.to_string(),
+                Usage::new(Some(input), Some(output), Some(input + output)),
+            )
+            .with_cost()
+        };
+        let summary = UsageSummary::new(vec![usage("gpt-4o", 4000, 1000)]);
+        assert_eq!(
+            usage_report(&summary),
+            "Usage: 4000 input and 1000 output tokens, $0.0200"
+        );
+
+        let summary = UsageSummary::new(vec![
+            usage("gpt-4o", 4000, 1000),
+            usage("llama3.3", 100, 50),
+        ]);
+        assert_eq!(
+            usage_report(&summary),
+            "Usage: 4100 input and 1050 output tokens, at least $0.0200\n  \
+             gpt-4o: 4000 input and 1000 output tokens, $0.0200\n  \
+             llama3.3: 100 input and 50 output tokens, unknown cost"
+        );
+    }
+
+    /// A prompt that shows nothing and ends the session when asked for input
+    struct SilentPrompt;
+
+    impl Prompt for SilentPrompt {
+        fn render(&mut self, _message: Box<Message>) {}
+        fn render_text(&mut self, _text: &str) {}
+        fn get_input(&mut self) -> Result<crate::prompt::Input> {
+            Ok(crate::prompt::Input {
+                input_type: InputType::Exit,
+                content: None,
+            })
+        }
+        fn show_busy(&mut self) {}
+        fn hide_busy(&self) {}
+        fn close(&self) {}
+    }
+
+    fn replying_agent(reply: &str) -> Box<dyn Agent> {
+        use goose::agents::replay::{RecordedCompletion, SessionRecording};
+        use goose::providers::base::{ProviderUsage, Usage};
+
+        let recording = SessionRecording {
+            completions: vec![RecordedCompletion {
+                message: Message::assistant().with_text(reply),
+                usage: ProviderUsage::new("replay".to_string(), Usage::default()),
+            }],
+            ..Default::default()
+        };
+        goose::agents::AgentFactory::create(
+            goose::agents::AgentFactory::default_version(),
+            recording.replay_provider(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_session_writes_no_file() {
+        crate::test_helpers::run_with_tmp_dir_async(|| async {
+            let home = dirs::home_dir().unwrap();
+            let mut session =
+                Session::new_ephemeral(replying_agent("Paris"), Box::new(SilentPrompt));
+            session
+                .headless_start("What is the capital of France?".to_string())
+                .await
+                .unwrap();
+
+            assert_eq!(session.messages.len(), 2);
+            assert_eq!(session.messages[1].as_concat_text(), "Paris");
+            assert_eq!(session.session_file(), None);
+            let sessions = home.join(".config").join("goose").join("sessions");
+            assert!(!sessions.exists() || fs::read_dir(&sessions).unwrap().next().is_none());
+            // Nor is its usage logged
+            assert!(!home.join(".config").join("goose").join("logs").exists());
+
+            // The same turn in a saved session writes its file
+            let session_file = home.join("saved.jsonl");
+            let mut session = Session::new(
+                replying_agent("Paris"),
+                Box::new(SilentPrompt),
+                session_file.clone(),
+            );
+            session
+                .headless_start("What is the capital of France?".to_string())
+                .await
+                .unwrap();
+            let saved = deserialize_messages(File::open(&session_file).unwrap()).unwrap();
+            assert_eq!(saved, session.messages);
+        })
+        .await
+    }
+
+    /// Replies in a child process, as the test harness captures stdout before it can be read
+    #[test]
+    fn test_reply_writes_nothing_to_stdout() {
+        const CHILD_ENV: &str = "GOOSE_TEST_STDOUT_CHILD";
+        if std::env::var_os(CHILD_ENV).is_some() {
+            println!("BEGIN");
+            tokio::runtime::Runtime::new().unwrap().block_on(async {
+                let mut session =
+                    Session::new_ephemeral(replying_agent("Paris"), Box::new(SilentPrompt));
+                session
+                    .headless_start("What is the capital of France?".to_string())
+                    .await
+                    .unwrap();
+            });
+            println!("END");
+            return;
+        }
+
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "session::tests::test_reply_writes_nothing_to_stdout",
+                "--nocapture",
+            ])
+            .env(CHILD_ENV, "1")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (_, reply) = stdout.split_once("BEGIN\n").unwrap();
+        let (written, _) = reply.split_once("END\n").unwrap();
+        assert_eq!(written, "");
+    }
+
+    /// Fails to start a reply in a child process, so its stderr can be read
+    #[test]
+    fn test_ephemeral_session_reports_errors_on_stderr() {
+        use goose::providers::moderation::OpenAiModeration;
+
+        const CHILD_ENV: &str = "GOOSE_TEST_STDERR_CHILD";
+        if std::env::var_os(CHILD_ENV).is_some() {
+            crate::logging::setup_console_logging().unwrap();
+            tokio::runtime::Runtime::new().unwrap().block_on(async {
+                // Nothing listens on port 1, so moderating the input fails
+                let mut agent = replying_agent("Paris");
+                agent
+                    .set_moderation(Some(Box::new(OpenAiModeration::new(
+                        "http://127.0.0.1:1".to_string(),
+                        "test-key".to_string(),
+                        "omni-moderation-latest".to_string(),
+                    ))))
+                    .await;
+                let mut session = Session::new_ephemeral(agent, Box::new(SilentPrompt));
+                session
+                    .headless_start("What is the capital of France?".to_string())
+                    .await
+                    .unwrap();
+            });
+            return;
+        }
+
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "session::tests::test_ephemeral_session_reports_errors_on_stderr",
+                "--nocapture",
+            ])
+            .env(CHILD_ENV, "1")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Error starting reply stream"), "{}", stderr);
+    }
+
+    fn tool_call(id: &str) -> Message {
+        Message::assistant()
+            .with_text("Checking")
+            .with_tool_request(
+                id,
+                Ok(mcp_core::tool::ToolCall::new(
+                    "developer__shell",
+                    serde_json::json!({"command": "ls"}),
+                )),
+            )
+    }
+
+    #[test]
+    fn test_rewind_keeps_tool_pairs_intact() {
+        let mut messages = vec![
+            Message::user().with_text("List the files"),
+            tool_call("1"),
+            Message::user().with_tool_response("1", Ok(vec![])),
+            Message::assistant().with_text("There are two files"),
+            Message::user().with_text("Now show the hidden ones"),
+            tool_call("2"),
+            // A tool response that also carries text, and does not start with the response
+            Message::user()
+                .with_text("Tool output follows")
+                .with_tool_response("2", Ok(vec![])),
+            tool_call("3"),
+        ];
+
+        rewind_to_last_user_message(&mut messages);
+
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[3].as_concat_text(), "There are two files");
+        let requests: Vec<&str> = messages
+            .iter()
+            .flat_map(|m| m.get_tool_request_ids())
+            .collect();
+        let responses: Vec<&str> = messages
+            .iter()
+            .flat_map(|m| m.get_tool_response_ids())
+            .collect();
+        assert_eq!(requests, responses);
+
+        // Without any user input left everything is removed
+        let mut messages = vec![
+            tool_call("4"),
+            Message::user().with_tool_response("4", Ok(vec![])),
+        ];
+        rewind_to_last_user_message(&mut messages);
+        assert!(messages.is_empty());
+    }
+
+    #[test]
+    fn test_tool_responses_are_not_user_input() {
+        assert!(is_user_input(&Message::user().with_text("hi")));
+        assert!(!is_user_input(&Message::assistant().with_text("hi")));
+        assert!(!is_user_input(
+            &Message::user()
+                .with_text("note")
+                .with_tool_response("1", Ok(vec![]))
+        ));
+    }
+}
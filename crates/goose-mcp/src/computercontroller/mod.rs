@@ -4,7 +4,7 @@ use reqwest::{Client, Url};
 use serde_json::{json, Value};
 use std::{
     collections::HashMap, fs, future::Future, os::unix::fs::PermissionsExt, path::PathBuf,
-    pin::Pin, sync::Arc, sync::Mutex,
+    pin::Pin, process::Stdio, sync::Arc, sync::Mutex,
 };
 use tokio::process::Command;
 
@@ -189,10 +189,11 @@ impl ComputerControllerRouter {
             .unwrap_or_else(|| PathBuf::from("/tmp"))
             .join("goose")
             .join("computer_controller");
-        fs::create_dir_all(&cache_dir).unwrap_or_else(|_| {
-            println!(
-                "Warning: Failed to create cache directory at {:?}",
-                cache_dir
+        fs::create_dir_all(&cache_dir).unwrap_or_else(|e| {
+            tracing::warn!(
+                cache_dir = %cache_dir.display(),
+                error = %e,
+                "Failed to create cache directory"
             )
         });
 
@@ -468,6 +469,7 @@ impl ComputerControllerRouter {
         let output = Command::new("bash")
             .arg("-c")
             .arg(&command)
+            .stdin(Stdio::null())
             .output()
             .await
             .map_err(|e| ToolError::ExecutionError(format!("Failed to run script: {}", e)))?;
@@ -531,6 +533,7 @@ impl ComputerControllerRouter {
         let output = Command::new("bash")
             .arg("-c")
             .arg(&command)
+            .stdin(Stdio::null())
             .output()
             .await
             .map_err(|e| ToolError::ExecutionError(format!("Failed to run AppleScript: {}", e)))?;
@@ -0,0 +1,111 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// Write `contents` to `path` so that it is either fully replaced or left untouched
+///
+/// See [`write_atomic_with`].
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    write_atomic_with(path, |file| file.write_all(contents.as_bytes()))
+}
+
+/// Replace `path` with a file produced by `write`, atomically
+///
+/// The new content is written to a temporary file in the same directory, flushed to disk and
+/// then renamed over the target. A crash or error part way through leaves the original file
+/// intact instead of truncated. The permissions of an existing file are preserved, and a
+/// symlink is resolved so its target is replaced rather than the link itself.
+pub fn write_atomic_with<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    let target = match fs::canonicalize(path) {
+        Ok(resolved) => resolved,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(e),
+    };
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    // Dropping the temp file on any error below removes it
+    let mut temp = NamedTempFile::new_in(dir)?;
+    write(temp.as_file_mut())?;
+    temp.as_file().sync_all()?;
+
+    if let Ok(metadata) = fs::metadata(&target) {
+        fs::set_permissions(temp.path(), metadata.permissions())?;
+    }
+
+    temp.persist(&target).map_err(|e| e.error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    #[test]
+    fn test_write_atomic_creates_and_replaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+
+        write_atomic(&path, "first").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+
+        write_atomic(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_write_atomic_preserves_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("script.sh");
+        fs::write(&path, "#!/bin/sh\necho hi\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+
+        write_atomic(&path, "#!/bin/sh\necho bye\n").unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "#!/bin/sh\necho bye\n");
+    }
+
+    #[test]
+    fn test_interrupted_write_leaves_original_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, "original content").unwrap();
+
+        let result = write_atomic_with(&path, |file| {
+            file.write_all(b"partial")?;
+            Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original content");
+        // The temporary file is cleaned up
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_write_atomic_through_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.txt");
+        let link = dir.path().join("link.txt");
+        fs::write(&target, "old").unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_atomic(&link, "new").unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+    }
+}
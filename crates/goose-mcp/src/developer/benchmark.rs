@@ -0,0 +1,150 @@
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use tokio::process::Command;
+
+/// How many times a command is run when the number of runs is not given
+pub const DEFAULT_RUNS: u64 = 5;
+
+/// The most runs of a command a single benchmark will do
+pub const MAX_RUNS: u64 = 100;
+
+/// How many seconds each run of a benchmarked command may take when not given
+pub const DEFAULT_RUN_TIMEOUT_SECS: u64 = 60;
+
+/// How many seconds all the runs of a single benchmark may take together
+pub const MAX_TOTAL_SECS: u64 = 300;
+
+/// A single timed run of a command
+#[derive(Debug, Clone, Copy)]
+pub struct Run {
+    pub duration: Duration,
+    /// How the command exited, or None if it was killed for taking longer than the timeout
+    pub status: Option<ExitStatus>,
+}
+
+/// Run `command` with bash in `dir` and time it, killing it after `timeout`
+///
+/// The output is discarded so that printing it does not count towards the duration.
+pub async fn time_command(command: &str, dir: &Path, timeout: Duration) -> Result<Run, String> {
+    let start = Instant::now();
+    let mut child = Command::new("bash")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .current_dir(dir)
+        .spawn()
+        .map_err(|e| format!("Failed to start bash to run '{}': {}", command, e))?;
+
+    match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(status) => {
+            let status = status.map_err(|e| format!("Failed to wait for '{}': {}", command, e))?;
+            Ok(Run {
+                duration: start.elapsed(),
+                status: Some(status),
+            })
+        }
+        Err(_) => {
+            let _ = child.kill().await;
+            Ok(Run {
+                duration: timeout,
+                status: None,
+            })
+        }
+    }
+}
+
+/// Summarize the runs of a benchmark with duration statistics and every exit status
+///
+/// Runs that timed out are left out of the statistics, since their duration is unknown.
+pub fn summarize(command: &str, runs: &[Run]) -> String {
+    let mut durations: Vec<Duration> = runs
+        .iter()
+        .filter(|run| run.status.is_some())
+        .map(|run| run.duration)
+        .collect();
+    durations.sort();
+
+    let times = if runs.len() == 1 { "time" } else { "times" };
+    let mut summary = format!("Ran `{}` {} {}\n", command, runs.len(), times);
+    if durations.is_empty() {
+        summary.push_str("No run finished within the timeout\n");
+    } else {
+        let mean = durations.iter().sum::<Duration>() / durations.len() as u32;
+        summary.push_str(&format!(
+            "min: {:.3?}\nmedian: {:.3?}\nmean: {:.3?}\nmax: {:.3?}\n",
+            durations[0],
+            median(&durations),
+            mean,
+            durations[durations.len() - 1]
+        ));
+    }
+
+    let statuses: Vec<String> = runs
+        .iter()
+        .map(|run| match run.status {
+            Some(status) => match status.code() {
+                Some(code) => code.to_string(),
+                None => "terminated".to_string(),
+            },
+            None => "timed out".to_string(),
+        })
+        .collect();
+    summary.push_str(&format!("exit statuses: {}", statuses.join(", ")));
+
+    let failed = runs
+        .iter()
+        .filter(|run| !run.status.is_some_and(|status| status.success()))
+        .count();
+    if failed > 0 {
+        summary.push_str(&format!(
+            "\n{} of {} runs failed, so their durations may not be meaningful",
+            failed,
+            runs.len()
+        ));
+    }
+    summary
+}
+
+/// The median of sorted durations
+fn median(sorted: &[Duration]) -> Duration {
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2
+    } else {
+        sorted[middle]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median() {
+        let ms = Duration::from_millis;
+        assert_eq!(median(&[ms(1), ms(5), ms(9)]), ms(5));
+        assert_eq!(median(&[ms(1), ms(3), ms(5), ms(100)]), ms(4));
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_runs_are_left_out_of_stats() {
+        let dir = std::env::temp_dir();
+        let fast = time_command("true", &dir, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let slow = time_command("sleep 10", &dir, Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert!(slow.status.is_none());
+
+        let summary = summarize("cmd", &[fast, slow]);
+        assert!(summary.contains(&format!("max: {:.3?}", fast.duration)));
+        assert!(summary.contains("exit statuses: 0, timed out"));
+        assert!(summary.contains("1 of 2 runs failed"));
+    }
+}
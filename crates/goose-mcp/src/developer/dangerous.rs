@@ -0,0 +1,143 @@
+use regex::Regex;
+
+/// Environment variable with newline-separated regexes of shell commands to refuse
+///
+/// The regexes replace the default rules, an empty value turns the guard off.
+pub const SHELL_DENYLIST_ENV: &str = "GOOSE_SHELL_DENYLIST";
+
+/// Destructive commands refused by default, named by what they do
+const DEFAULT_DENYLIST: &[(&str, &str)] = &[
+    (
+        "recursive rm of / or the home directory",
+        r"\brm\s+(-\S+\s+)*-[a-zA-Z]*[rR][a-zA-Z]*\s+(-\S+\s+)*(/\*?|~/?|\$HOME/?)(\s|$|[;&|])",
+    ),
+    ("rm --no-preserve-root", r"\brm\b.*--no-preserve-root"),
+    ("mkfs", r"\bmkfs(\.\w+)?\b"),
+    ("dd to a device", r"\bdd\b.*\bof=/dev/"),
+    (
+        "redirect to a disk device",
+        r">\s*/dev/(sd|hd|vd|xvd|nvme|mmcblk|disk)",
+    ),
+    ("fork bomb", r":\(\)\s*\{\s*:\s*\|\s*:\s*&\s*\}\s*;\s*:"),
+];
+
+/// A rule of the denylist
+#[derive(Debug, Clone)]
+struct Rule {
+    name: String,
+    pattern: Regex,
+}
+
+/// The shell commands refused before they are run because they are destructive
+#[derive(Debug, Clone)]
+pub struct DangerousCommands {
+    rules: Vec<Rule>,
+}
+
+impl Default for DangerousCommands {
+    fn default() -> Self {
+        Self::build(
+            DEFAULT_DENYLIST
+                .iter()
+                .map(|(name, pattern)| (name.to_string(), *pattern)),
+        )
+    }
+}
+
+impl DangerousCommands {
+    /// Read the rules from `GOOSE_SHELL_DENYLIST`, or use the defaults if it is unset
+    pub fn from_env() -> Self {
+        match std::env::var(SHELL_DENYLIST_ENV) {
+            Ok(patterns) => Self::from_patterns(&patterns),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Build the guard from newline-separated regexes, skipping any that are invalid
+    ///
+    /// Each rule is named by its regex.
+    pub fn from_patterns(patterns: &str) -> Self {
+        Self::build(
+            patterns
+                .lines()
+                .map(str::trim)
+                .filter(|pattern| !pattern.is_empty())
+                .map(|pattern| (pattern.to_string(), pattern)),
+        )
+    }
+
+    fn build<'a>(patterns: impl Iterator<Item = (String, &'a str)>) -> Self {
+        let rules = patterns
+            .filter_map(|(name, pattern)| match Regex::new(pattern) {
+                Ok(pattern) => Some(Rule { name, pattern }),
+                Err(e) => {
+                    tracing::warn!("Ignoring shell denylist pattern '{}': {}", pattern, e);
+                    None
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// The error to return instead of running `command`, if it matches a rule
+    pub fn check(&self, command: &str) -> Option<String> {
+        let command = command.trim();
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| rule.pattern.is_match(command))?;
+        Some(format!(
+            "The command was refused because it matches the dangerous command rule '{}'. \
+             If it is really needed, ask the user to run it themselves.",
+            rule.name
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_rules_block_destructive_commands() {
+        let guard = DangerousCommands::default();
+        for command in [
+            "rm -rf /",
+            "  sudo rm -fr /* ",
+            "rm -r -f ~",
+            "cd /tmp && rm -Rf $HOME/",
+            "rm -rf --no-preserve-root /",
+            "mkfs.ext4 /dev/sda1",
+            "dd if=/dev/zero of=/dev/sda bs=1M",
+            "echo oops > /dev/nvme0n1",
+            ":(){ :|:& };:",
+        ] {
+            assert!(guard.check(command).is_some(), "{} was allowed", command);
+        }
+
+        let error = guard.check("mkfs /dev/sdb").unwrap();
+        assert!(error.contains("'mkfs'"));
+
+        for command in [
+            "rm -rf ./build",
+            "rm -rf /tmp/build",
+            "rm -rf ~/project/target",
+            "ls -la /",
+            "dd if=disk.img of=copy.img",
+            "echo done > /dev/null",
+        ] {
+            assert!(guard.check(command).is_none(), "{} was refused", command);
+        }
+    }
+
+    #[test]
+    fn test_configured_rules_replace_defaults() {
+        let strict = DangerousCommands::from_patterns("\\brm\\s+-rf\\b\n(\n");
+        let error = strict.check("rm -rf ./build").unwrap();
+        assert!(error.contains(r"'\brm\s+-rf\b'"));
+        assert!(strict.check("mkfs /dev/sdb").is_none());
+
+        let off = DangerousCommands::from_patterns("");
+        assert!(off.check("rm -rf /").is_none());
+    }
+}
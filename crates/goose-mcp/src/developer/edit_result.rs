@@ -0,0 +1,78 @@
+use std::ops::Range;
+use std::path::Path;
+
+use mcp_core::content::Content;
+use mcp_core::resource::ResourceContents;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// MIME type of the structured edit results returned alongside text_editor edits
+pub const EDIT_RESULT_MIME_TYPE: &str = "application/vnd.goose.edit+json";
+
+/// A range of a file before it was edited
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditRange {
+    pub start_byte: usize,
+    /// The byte after the range, so an insertion has `end_byte == start_byte`
+    pub end_byte: usize,
+    /// The 1-based line that `start_byte` is on
+    pub start_line: usize,
+    /// The 1-based line that `end_byte` is on
+    pub end_line: usize,
+}
+
+/// A single replacement of a range of the file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub range: EditRange,
+    pub new_text: String,
+}
+
+/// What an edit changed, so an editor can apply the same change to its buffer
+///
+/// The ranges all refer to the file before the edit and do not overlap.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditResult {
+    pub path: String,
+    pub edits: Vec<TextEdit>,
+}
+
+impl EditResult {
+    /// Describe the replacement of each byte range of `before` with its new text
+    pub fn new(
+        path: &Path,
+        before: &str,
+        replacements: impl IntoIterator<Item = (Range<usize>, String)>,
+    ) -> Self {
+        let line_at = |byte: usize| before[..byte].matches('\n').count() + 1;
+        let edits = replacements
+            .into_iter()
+            .map(|(range, new_text)| TextEdit {
+                range: EditRange {
+                    start_byte: range.start,
+                    end_byte: range.end,
+                    start_line: line_at(range.start),
+                    end_line: line_at(range.end),
+                },
+                new_text,
+            })
+            .collect();
+        Self {
+            path: path.display().to_string(),
+            edits,
+        }
+    }
+
+    /// The result as content meant for clients, which is neither shown to the user nor the model
+    pub fn into_content(self) -> Content {
+        let uri = Url::from_file_path(&self.path)
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| format!("file://{}", self.path));
+        Content::resource(ResourceContents::TextResourceContents {
+            uri,
+            mime_type: Some(EDIT_RESULT_MIME_TYPE.to_string()),
+            text: serde_json::to_string(&self).unwrap_or_default(),
+        })
+        .with_audience(vec![])
+    }
+}
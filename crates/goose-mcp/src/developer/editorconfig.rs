@@ -0,0 +1,139 @@
+use std::path::Path;
+
+use ec4rs::property::{FinalNewline, IndentSize, IndentStyle, TabWidth};
+
+/// Indent width used when an `.editorconfig` sets a style without a size
+const DEFAULT_INDENT_SIZE: usize = 4;
+
+/// How the leading whitespace of each line is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    /// Tabs, each standing for `width` columns, with spaces for any remainder
+    Tabs { width: usize },
+    /// Spaces, with tabs expanded to `width` columns
+    Spaces { width: usize },
+}
+
+/// Formatting rules for written files, from the `.editorconfig` files that apply to them
+///
+/// Rules that are not set leave the content as it was written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WriteStyle {
+    pub indent: Option<Indent>,
+    pub final_newline: Option<bool>,
+}
+
+impl WriteStyle {
+    /// Find the rules for `path` in the `.editorconfig` files of its directory and parents
+    ///
+    /// A missing or unreadable `.editorconfig` gives the default, which changes nothing.
+    pub fn for_path(path: &Path) -> Self {
+        let mut properties = match ec4rs::properties_of(path) {
+            Ok(properties) => properties,
+            Err(e) => {
+                tracing::debug!("Ignoring .editorconfig for {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+        properties.use_fallbacks();
+
+        let width = match properties.get::<IndentSize>() {
+            Ok(IndentSize::Value(size)) if size > 0 => size,
+            _ => match properties.get::<TabWidth>() {
+                Ok(TabWidth::Value(width)) if width > 0 => width,
+                _ => DEFAULT_INDENT_SIZE,
+            },
+        };
+        let indent = match properties.get::<IndentStyle>() {
+            Ok(IndentStyle::Tabs) => Some(Indent::Tabs { width }),
+            Ok(IndentStyle::Spaces) => Some(Indent::Spaces { width }),
+            Err(_) => None,
+        };
+        let final_newline = match properties.get::<FinalNewline>() {
+            Ok(FinalNewline::Value(insert)) => Some(insert),
+            Err(_) => None,
+        };
+
+        Self {
+            indent,
+            final_newline,
+        }
+    }
+
+    /// Rewrite `text` to follow the rules
+    pub fn apply(&self, text: &str) -> String {
+        let mut result = match self.indent {
+            Some(indent) => text
+                .split_inclusive('\n')
+                .map(|line| reindent(line, indent))
+                .collect(),
+            None => text.to_string(),
+        };
+
+        match self.final_newline {
+            Some(true) if !result.is_empty() && !result.ends_with('\n') => result.push('\n'),
+            Some(false) => {
+                let trimmed = result.trim_end_matches(['\n', '\r']).len();
+                result.truncate(trimmed);
+            }
+            _ => {}
+        }
+        result
+    }
+}
+
+/// Rewrite the leading whitespace of a line in the given indent style
+fn reindent(line: &str, indent: Indent) -> String {
+    let body = line.trim_start_matches([' ', '\t']);
+    let leading = &line[..line.len() - body.len()];
+    if leading.is_empty() || body.trim().is_empty() {
+        return line.to_string();
+    }
+
+    let (Indent::Tabs { width } | Indent::Spaces { width }) = indent;
+    let columns = leading.chars().fold(0, |column, c| match c {
+        '\t' => (column / width + 1) * width,
+        _ => column + 1,
+    });
+    let leading = match indent {
+        Indent::Tabs { width } => {
+            format!(
+                "{}{}",
+                "\t".repeat(columns / width),
+                " ".repeat(columns % width)
+            )
+        }
+        Indent::Spaces { .. } => " ".repeat(columns),
+    };
+    format!("{}{}", leading, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_indent_styles() {
+        let text = "fn main() {\n    if true {\n\t    run();\n    }\n}";
+
+        let tabs = WriteStyle {
+            indent: Some(Indent::Tabs { width: 4 }),
+            final_newline: Some(true),
+        };
+        assert_eq!(
+            tabs.apply(text),
+            "fn main() {\n\tif true {\n\t\trun();\n\t}\n}\n"
+        );
+
+        let spaces = WriteStyle {
+            indent: Some(Indent::Spaces { width: 2 }),
+            final_newline: Some(false),
+        };
+        assert_eq!(
+            spaces.apply("a:\n\tb: 1\n\t\tc: 2\n\n"),
+            "a:\n  b: 1\n    c: 2"
+        );
+
+        assert_eq!(WriteStyle::default().apply(text), text);
+    }
+}
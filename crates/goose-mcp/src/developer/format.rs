@@ -0,0 +1,241 @@
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+
+/// Structured data formats supported by the validate_format tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl DataFormat {
+    /// Parse an explicit format name as passed to the tool
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    /// Infer the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::from_name)
+    }
+}
+
+impl fmt::Display for DataFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json => write!(f, "JSON"),
+            Self::Yaml => write!(f, "YAML"),
+            Self::Toml => write!(f, "TOML"),
+        }
+    }
+}
+
+/// A parse error with a 1-based location in the source text, when the parser reports one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "line {}, column {}: {}", line, column, self.message)
+            }
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Parse `text` as `format`, reporting where it is invalid
+pub fn validate(text: &str, format: DataFormat) -> Result<(), ParseError> {
+    match format {
+        DataFormat::Json => {
+            serde_json::from_str::<serde::de::IgnoredAny>(text).map_err(|e| ParseError {
+                line: Some(e.line()),
+                column: Some(e.column()),
+                message: strip_location(&e.to_string()),
+            })?;
+        }
+        DataFormat::Yaml => {
+            serde_yaml::from_str::<serde_yaml::Value>(text).map_err(|e| ParseError {
+                line: e.location().map(|l| l.line()),
+                column: e.location().map(|l| l.column()),
+                message: strip_location(&e.to_string()),
+            })?;
+        }
+        DataFormat::Toml => {
+            toml::from_str::<toml::Table>(text).map_err(|e| {
+                let location = e.span().map(|span| line_column(text, span.start));
+                ParseError {
+                    line: location.map(|(line, _)| line),
+                    column: location.map(|(_, column)| column),
+                    message: e.message().trim().to_string(),
+                }
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Validate `text` as JSON and re-indent it the way `serde_json::to_string_pretty` would
+///
+/// The text is re-indented rather than re-serialized, so keys keep their order and numbers
+/// keep their spelling.
+pub fn pretty_print_json(text: &str) -> Result<String, ParseError> {
+    validate(text, DataFormat::Json)?;
+
+    let mut pretty = String::with_capacity(text.len());
+    let mut depth = 0;
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    let mut escaped = false;
+    let newline = |pretty: &mut String, depth: usize| {
+        pretty.push('\n');
+        pretty.push_str(&"  ".repeat(depth));
+    };
+    while let Some(c) = chars.next() {
+        if in_string {
+            pretty.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                pretty.push(c);
+            }
+            '{' | '[' => {
+                pretty.push(c);
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if matches!(chars.peek(), Some('}' | ']')) {
+                    pretty.extend(chars.next());
+                } else {
+                    depth += 1;
+                    newline(&mut pretty, depth);
+                }
+            }
+            '}' | ']' => {
+                depth -= 1;
+                newline(&mut pretty, depth);
+                pretty.push(c);
+            }
+            ',' => {
+                pretty.push(c);
+                newline(&mut pretty, depth);
+            }
+            ':' => pretty.push_str(": "),
+            c if c.is_whitespace() => {}
+            c => pretty.push(c),
+        }
+    }
+    pretty.push('\n');
+    Ok(pretty)
+}
+
+/// Convert a byte offset into a 1-based line and column
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+/// Remove the " at line X column Y" that serde_json and serde_yaml add for the error itself,
+/// keeping any later location that points at the enclosing context
+fn strip_location(message: &str) -> String {
+    let location = Regex::new(r" at line \d+ column \d+").expect("valid regex");
+    location.replace(message, "").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_detection() {
+        assert_eq!(
+            DataFormat::from_path(Path::new("/a/b.json")),
+            Some(DataFormat::Json)
+        );
+        assert_eq!(
+            DataFormat::from_path(Path::new("/a/b.yml")),
+            Some(DataFormat::Yaml)
+        );
+        assert_eq!(
+            DataFormat::from_path(Path::new("/a/Cargo.toml")),
+            Some(DataFormat::Toml)
+        );
+        assert_eq!(DataFormat::from_path(Path::new("/a/b.txt")), None);
+        assert_eq!(DataFormat::from_name("YAML"), Some(DataFormat::Yaml));
+    }
+
+    #[test]
+    fn test_pretty_print_json() {
+        let pretty = pretty_print_json(r#"{"a":[1,2],"b":{"c":true}}"#).unwrap();
+        assert_eq!(
+            pretty,
+            "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": {\n    \"c\": true\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_pretty_print_json_keeps_order_and_spelling() {
+        let text = r#" { "z" : 1.50, "a" : { }, "m" : [ "x, \"y\": [z]" ] } "#;
+        let pretty = pretty_print_json(text).unwrap();
+        assert_eq!(
+            pretty,
+            "{\n  \"z\": 1.50,\n  \"a\": {},\n  \"m\": [\n    \"x, \\\"y\\\": [z]\"\n  ]\n}\n"
+        );
+        // Only the layout changed
+        let value: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+        assert_eq!(
+            value,
+            serde_json::from_str::<serde_json::Value>(text).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json_error_location() {
+        let err = validate("{\n  \"a\": 1,\n  \"b\": ,\n}", DataFormat::Json).unwrap_err();
+        assert_eq!(err.line, Some(3));
+        assert_eq!(err.column, Some(8));
+        assert_eq!(err.message, "expected value");
+    }
+
+    #[test]
+    fn test_yaml_error_location() {
+        let err = validate("a: 1\nb: [1, 2\nc: 3\n", DataFormat::Yaml).unwrap_err();
+        assert_eq!(err.line, Some(3));
+        assert_eq!(err.column, Some(2));
+        assert_eq!(
+            err.message,
+            "did not find expected ',' or ']', while parsing a flow sequence at line 2 column 4"
+        );
+    }
+
+    #[test]
+    fn test_toml_error_location() {
+        let err = validate("[package]\nname = \"x\"\nversion = \n", DataFormat::Toml).unwrap_err();
+        assert_eq!(err.line, Some(3));
+        assert!(err.column.is_some());
+
+        assert!(validate("# comment\nb = 1\na = \"x\"", DataFormat::Toml).is_ok());
+    }
+}
@@ -0,0 +1,133 @@
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Environment variable with comma-separated globs of files that should not be edited by hand
+///
+/// The globs replace the default list and are matched against file names, an empty value
+/// turns the guard off.
+pub const GENERATED_FILES_ENV: &str = "GOOSE_GENERATED_FILES";
+
+/// Files that are generated by tools, with the command that regenerates them
+const DEFAULT_GENERATED_FILES: &[(&str, &str)] = &[
+    ("Cargo.lock", "`cargo update` or `cargo build`"),
+    ("package-lock.json", "`npm install`"),
+    ("npm-shrinkwrap.json", "`npm install`"),
+    ("yarn.lock", "`yarn install`"),
+    ("pnpm-lock.yaml", "`pnpm install`"),
+    ("bun.lockb", "`bun install`"),
+    ("poetry.lock", "`poetry lock`"),
+    ("Pipfile.lock", "`pipenv lock`"),
+    ("uv.lock", "`uv lock`"),
+    ("Gemfile.lock", "`bundle install`"),
+    ("composer.lock", "`composer update`"),
+    ("go.sum", "`go mod tidy`"),
+    ("flake.lock", "`nix flake update`"),
+    ("*.pb.go", "`protoc`"),
+    ("*_pb2.py", "`protoc`"),
+];
+
+/// The files that edits are refused for unless forced, because they are generated
+#[derive(Debug, Clone)]
+pub struct GeneratedFiles {
+    globs: GlobSet,
+    /// The regeneration hint for each glob, by index in the set
+    hints: Vec<Option<&'static str>>,
+}
+
+impl Default for GeneratedFiles {
+    fn default() -> Self {
+        Self::build(
+            DEFAULT_GENERATED_FILES
+                .iter()
+                .map(|(pattern, hint)| (pattern.to_string(), Some(*hint))),
+        )
+    }
+}
+
+impl GeneratedFiles {
+    /// Read the globs from `GOOSE_GENERATED_FILES`, or use the defaults if it is unset
+    pub fn from_env() -> Self {
+        match std::env::var(GENERATED_FILES_ENV) {
+            Ok(patterns) => Self::from_patterns(&patterns),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Build the guard from comma-separated globs, skipping any that are invalid
+    pub fn from_patterns(patterns: &str) -> Self {
+        Self::build(
+            patterns
+                .split(',')
+                .map(str::trim)
+                .filter(|pattern| !pattern.is_empty())
+                .map(|pattern| {
+                    // Known files keep their regeneration hint
+                    let hint = DEFAULT_GENERATED_FILES
+                        .iter()
+                        .find(|(known, _)| *known == pattern)
+                        .map(|(_, hint)| *hint);
+                    (pattern.to_string(), hint)
+                }),
+        )
+    }
+
+    fn build(patterns: impl Iterator<Item = (String, Option<&'static str>)>) -> Self {
+        let mut builder = GlobSetBuilder::new();
+        let mut hints = Vec::new();
+        for (pattern, hint) in patterns {
+            match Glob::new(&pattern) {
+                Ok(glob) => {
+                    builder.add(glob);
+                    hints.push(hint);
+                }
+                Err(e) => tracing::warn!("Ignoring generated file glob '{}': {}", pattern, e),
+            }
+        }
+        let globs = builder.build().unwrap_or_else(|e| {
+            tracing::warn!("Ignoring generated file globs: {}", e);
+            GlobSet::empty()
+        });
+        Self { globs, hints }
+    }
+
+    /// A warning to return instead of editing `path` by hand, if it is a generated file
+    pub fn check(&self, path: &Path) -> Option<String> {
+        let name = path.file_name()?;
+        let index = *self.globs.matches(name).first()?;
+        let regenerate = match self.hints[index] {
+            Some(command) => format!("regenerate it with {}", command),
+            None => "regenerate it with the tool that produced it".to_string(),
+        };
+        Some(format!(
+            "'{}' is a generated file, editing it by hand is almost always wrong. Instead {}, \
+             or change the files it is generated from. If you really need to edit it, retry with `force` set to true.",
+            path.display(),
+            regenerate
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_patterns_replace_defaults() {
+        let defaults = GeneratedFiles::default();
+        let warning = defaults.check(Path::new("/repo/web/yarn.lock")).unwrap();
+        assert!(warning.contains("`yarn install`"));
+        assert!(defaults.check(Path::new("/repo/api/user.pb.go")).is_some());
+        assert!(defaults.check(Path::new("/repo/src/main.rs")).is_none());
+
+        let configured = GeneratedFiles::from_patterns("*.gen.ts, Cargo.lock,[");
+        let warning = configured.check(Path::new("/repo/api.gen.ts")).unwrap();
+        assert!(warning.contains("the tool that produced it"));
+        let warning = configured.check(Path::new("/repo/Cargo.lock")).unwrap();
+        assert!(warning.contains("`cargo update`"));
+        assert!(configured.check(Path::new("/repo/yarn.lock")).is_none());
+
+        let off = GeneratedFiles::from_patterns("");
+        assert!(off.check(Path::new("/repo/Cargo.lock")).is_none());
+    }
+}
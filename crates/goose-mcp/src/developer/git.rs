@@ -0,0 +1,217 @@
+use std::path::Path;
+
+use git2::{Branch, ErrorCode, Repository, Status, StatusOptions};
+use serde::Serialize;
+
+/// The state of a git working tree, with paths relative to the repository root
+///
+/// A path can be in more than one list, e.g. a file with staged changes that was modified again.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GitStatus {
+    /// The checked out branch, or None when HEAD is detached
+    pub branch: Option<String>,
+    /// The branch's upstream, such as `origin/main`
+    pub upstream: Option<String>,
+    /// Commits on the branch that are not on its upstream
+    pub ahead: usize,
+    /// Commits on the upstream that are not on the branch
+    pub behind: usize,
+    /// Paths with changes in the index, other than deletions
+    pub staged: Vec<String>,
+    /// Tracked paths changed in the working tree but not staged
+    pub modified: Vec<String>,
+    pub untracked: Vec<String>,
+    /// Paths deleted from the working tree or the index
+    pub deleted: Vec<String>,
+    /// Paths with unresolved merge conflicts
+    pub conflicted: Vec<String>,
+}
+
+impl GitStatus {
+    /// Whether there are no changes to the working tree or index
+    pub fn is_clean(&self) -> bool {
+        self.staged.is_empty()
+            && self.modified.is_empty()
+            && self.untracked.is_empty()
+            && self.deleted.is_empty()
+            && self.conflicted.is_empty()
+    }
+}
+
+/// The status of the git repository that contains `dir`
+pub fn git_status(dir: &Path) -> Result<GitStatus, String> {
+    let repo = Repository::discover(dir)
+        .map_err(|e| format!("'{}' is not in a git repository: {}", dir.display(), e))?;
+    let mut status = GitStatus::default();
+    read_branch(&repo, &mut status)?;
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+    let entries = repo
+        .statuses(Some(&mut options))
+        .map_err(|e| format!("Failed to read the git status: {}", e))?;
+
+    for entry in entries.iter() {
+        let Some(path) = entry.path() else {
+            continue;
+        };
+        let path = path.to_string();
+        let flags = entry.status();
+
+        if flags.is_conflicted() {
+            status.conflicted.push(path);
+            continue;
+        }
+        if flags.intersects(
+            Status::INDEX_NEW
+                | Status::INDEX_MODIFIED
+                | Status::INDEX_RENAMED
+                | Status::INDEX_TYPECHANGE,
+        ) {
+            status.staged.push(path.clone());
+        }
+        if flags.intersects(Status::WT_MODIFIED | Status::WT_RENAMED | Status::WT_TYPECHANGE) {
+            status.modified.push(path.clone());
+        }
+        if flags.intersects(Status::INDEX_DELETED | Status::WT_DELETED) {
+            status.deleted.push(path.clone());
+        }
+        if flags.contains(Status::WT_NEW) {
+            status.untracked.push(path);
+        }
+    }
+    Ok(status)
+}
+
+/// Fill in the current branch and how far it is from its upstream
+fn read_branch(repo: &Repository, status: &mut GitStatus) -> Result<(), String> {
+    let head = match repo.head() {
+        Ok(head) => head,
+        // A new repository has no commits, but HEAD still names the branch to create
+        Err(e) if e.code() == ErrorCode::UnbornBranch => {
+            status.branch = repo
+                .find_reference("HEAD")
+                .ok()
+                .and_then(|head| head.symbolic_target().map(str::to_string))
+                .map(|target| {
+                    target
+                        .strip_prefix("refs/heads/")
+                        .unwrap_or(&target)
+                        .to_string()
+                });
+            return Ok(());
+        }
+        Err(e) => return Err(format!("Failed to read HEAD: {}", e)),
+    };
+    if !head.is_branch() {
+        return Ok(());
+    }
+    status.branch = head.shorthand().map(str::to_string);
+
+    let Ok(upstream) = Branch::wrap(head).upstream() else {
+        return Ok(());
+    };
+    status.upstream = upstream.name().ok().flatten().map(str::to_string);
+
+    let local = repo.head().ok().and_then(|head| head.target());
+    if let (Some(local), Some(remote)) = (local, upstream.get().target()) {
+        let (ahead, behind) = repo
+            .graph_ahead_behind(local, remote)
+            .map_err(|e| format!("Failed to compare the branch with its upstream: {}", e))?;
+        status.ahead = ahead;
+        status.behind = behind;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{RepositoryInitOptions, Signature};
+    use std::fs;
+
+    fn commit_all(repo: &Repository, message: &str) -> git2::Oid {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.update_all(["*"], None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parents: Vec<_> = repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok())
+            .into_iter()
+            .collect();
+        let parents: Vec<_> = parents.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_git_status_categorizes_paths() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let mut init = RepositoryInitOptions::new();
+        init.initial_head("main");
+        let repo = Repository::init_opts(root, &init).unwrap();
+
+        let status = git_status(root).unwrap();
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert!(status.is_clean());
+
+        fs::write(root.join("modified.txt"), "one\n").unwrap();
+        fs::write(root.join("deleted.txt"), "two\n").unwrap();
+        fs::write(root.join("partly_staged.txt"), "three\n").unwrap();
+        let first = commit_all(&repo, "first");
+
+        // The upstream is one commit behind the branch
+        repo.reference("refs/remotes/origin/main", first, true, "fake fetch")
+            .unwrap();
+        repo.remote("origin", "https://example.com/repo.git")
+            .unwrap();
+        fs::write(root.join("second.txt"), "four\n").unwrap();
+        commit_all(&repo, "second");
+        repo.find_branch("main", git2::BranchType::Local)
+            .unwrap()
+            .set_upstream(Some("origin/main"))
+            .unwrap();
+
+        fs::write(root.join("modified.txt"), "changed\n").unwrap();
+        fs::remove_file(root.join("deleted.txt")).unwrap();
+        fs::write(root.join("staged.txt"), "new\n").unwrap();
+        fs::write(root.join("partly_staged.txt"), "staged\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("staged.txt")).unwrap();
+        index.add_path(Path::new("partly_staged.txt")).unwrap();
+        index.write().unwrap();
+        fs::write(root.join("partly_staged.txt"), "staged and changed\n").unwrap();
+        fs::create_dir(root.join("new_dir")).unwrap();
+        fs::write(root.join("new_dir/untracked.txt"), "untracked\n").unwrap();
+
+        let status = git_status(&root.join("new_dir")).unwrap();
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert_eq!(status.upstream.as_deref(), Some("origin/main"));
+        assert_eq!((status.ahead, status.behind), (1, 0));
+        assert_eq!(status.staged, vec!["partly_staged.txt", "staged.txt"]);
+        assert_eq!(status.modified, vec!["modified.txt", "partly_staged.txt"]);
+        assert_eq!(status.deleted, vec!["deleted.txt"]);
+        assert_eq!(status.untracked, vec!["new_dir/untracked.txt"]);
+        assert!(status.conflicted.is_empty());
+
+        let outside = tempfile::tempdir().unwrap();
+        assert!(git_status(outside.path()).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Number of undo snapshots kept per file, in memory and on disk, older ones are dropped first
+pub const MAX_SNAPSHOTS_PER_FILE: usize = 20;
+
+#[derive(Serialize, Deserialize)]
+struct HistoryEntry {
+    path: PathBuf,
+    snapshots: Vec<String>,
+}
+
+/// Stores the undo history of edited files in a directory, one JSON file per edited file
+///
+/// Each file is named after a hash of the edited path, so arbitrary paths map to safe
+/// file names. The original path is stored inside to rebuild the history on load.
+#[derive(Debug, Clone)]
+pub struct HistoryStore {
+    dir: PathBuf,
+}
+
+impl HistoryStore {
+    /// Use `dir` to store history, creating it if needed
+    pub fn new(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Load the stored history for every file, skipping entries that can't be read
+    pub fn load(&self) -> HashMap<PathBuf, Vec<String>> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return HashMap::new();
+        };
+
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|contents| serde_json::from_str::<HistoryEntry>(&contents).ok())
+            .map(|entry| (entry.path, entry.snapshots))
+            .collect()
+    }
+
+    /// Store the history of `path`, keeping only the most recent snapshots
+    pub fn save(&self, path: &Path, snapshots: &[String]) -> io::Result<()> {
+        let entry_path = self.entry_path(path);
+        if snapshots.is_empty() {
+            return match fs::remove_file(entry_path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+
+        let start = snapshots.len().saturating_sub(MAX_SNAPSHOTS_PER_FILE);
+        let entry = HistoryEntry {
+            path: path.to_path_buf(),
+            snapshots: snapshots[start..].to_vec(),
+        };
+        fs::write(entry_path, serde_json::to_string(&entry)?)
+    }
+
+    fn entry_path(&self, path: &Path) -> PathBuf {
+        let hash = sha2::Sha256::digest(path.to_string_lossy().as_bytes());
+        self.dir.join(format!("{:x}.json", hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HistoryStore::new(dir.path().join("history")).unwrap();
+
+        let a = PathBuf::from("/project/a.txt");
+        let b = PathBuf::from("/project/nested/b.txt");
+        store.save(&a, &["one".to_string()]).unwrap();
+        store
+            .save(&b, &["first".to_string(), "second".to_string()])
+            .unwrap();
+
+        let loaded = HistoryStore::new(dir.path().join("history"))
+            .unwrap()
+            .load();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[&a], vec!["one"]);
+        assert_eq!(loaded[&b], vec!["first", "second"]);
+
+        // Saving an empty history removes the entry
+        store.save(&a, &[]).unwrap();
+        assert!(!store.load().contains_key(&a));
+    }
+
+    #[test]
+    fn test_snapshots_are_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HistoryStore::new(dir.path().to_path_buf()).unwrap();
+
+        let path = PathBuf::from("/project/a.txt");
+        let snapshots: Vec<String> = (0..MAX_SNAPSHOTS_PER_FILE + 5)
+            .map(|i| i.to_string())
+            .collect();
+        store.save(&path, &snapshots).unwrap();
+
+        let loaded = &store.load()[&path];
+        assert_eq!(loaded.len(), MAX_SNAPSHOTS_PER_FILE);
+        assert_eq!(loaded.first().unwrap(), "5");
+        assert_eq!(loaded.last().unwrap(), &snapshots.last().unwrap().clone());
+    }
+}
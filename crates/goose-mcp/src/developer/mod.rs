@@ -1,42 +1,560 @@
+mod atomic;
+mod benchmark;
+mod dangerous;
+mod edit_result;
+mod editorconfig;
+mod format;
+mod generated;
+mod git;
+mod history;
 mod lang;
+mod process_store;
+mod prompt_library;
+mod rename;
+mod report;
+mod screenshot;
+mod stack_trace;
+mod walk;
+mod watcher;
 
 use anyhow::Result;
 use base64::Engine;
 use indoc::formatdoc;
 use serde_json::{json, Value};
 use std::{
+    borrow::Cow,
     collections::HashMap,
     future::Future,
-    io::Cursor,
+    io::{self, BufRead, BufReader, Read, Seek},
     path::{Path, PathBuf},
     pin::Pin,
 };
 use tokio::process::Command;
 use url::Url;
 
+use atomic::write_atomic;
+use benchmark::{summarize, time_command};
+use dangerous::DangerousCommands;
+use edit_result::EditResult;
+use editorconfig::WriteStyle;
+use format::{pretty_print_json, validate, DataFormat};
+use generated::GeneratedFiles;
+use git::git_status;
+use history::{HistoryStore, MAX_SNAPSHOTS_PER_FILE};
 use mcp_core::{
-    handler::{ResourceError, ToolError},
+    handler::{PromptError, ResourceError, ToolError},
+    prompt::Prompt,
     protocol::ServerCapabilities,
     resource::Resource,
     tool::Tool,
 };
 use mcp_server::router::CapabilitiesBuilder;
 use mcp_server::Router;
+use process_store::{ProcessStore, Stopped};
+use prompt_library::PromptLibrary;
+use regex::Regex;
+use report::{parse_junit, parse_lcov, ReportFormat};
+use screenshot::{crop_region, encode_screenshot, EncodeOptions, Region, ScreenshotFormat};
+use similar::TextDiff;
+use stack_trace::find_trace;
+use walk::{directory_tree, walk_files, WalkOptions};
+use watcher::{watch_resources_enabled, ResourceWatcher};
 
 use mcp_core::content::Content;
 use mcp_core::role::Role;
+use mcp_core::text::{truncate_str, truncate_str_start};
 
 use indoc::indoc;
-use std::process::Stdio;
+use std::process::{ExitStatus, Stdio};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use xcap::{Monitor, Window};
 
+/// Default number of seconds the shell tool waits for a command before killing it
+const DEFAULT_SHELL_TIMEOUT_SECS: u64 = 300;
+
+/// Default number of bytes of shell output returned to the assistant
+const DEFAULT_SHELL_MAX_OUTPUT_BYTES: usize = 100_000;
+
+/// Number of bytes of shell output shown to the user, which costs no context
+const USER_SHELL_MAX_OUTPUT_BYTES: usize = 400_000;
+
+/// How long kill_process waits after SIGTERM before sending SIGKILL
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Environment variable naming a directory to persist edit history in, so undo survives restarts
+pub const EDIT_HISTORY_DIR_ENV: &str = "GOOSE_EDIT_HISTORY_DIR";
+
+/// Environment variable that makes the shell tool require an explanation of each command
+pub const EXPLAIN_COMMANDS_ENV: &str = "GOOSE_EXPLAIN_COMMANDS";
+
+/// Whether `GOOSE_EXPLAIN_COMMANDS` asks for every shell command to be explained
+fn explain_commands_enabled() -> bool {
+    matches!(
+        std::env::var(EXPLAIN_COMMANDS_ENV).as_deref(),
+        Ok("1") | Ok("true")
+    )
+}
+
+/// URI of the in-memory scratchpad resource
+const SCRATCHPAD_URI: &str = "str:///scratchpad";
+
+/// Environment variable setting how the working directory resource is offered, see [`CwdResource`]
+pub const CWD_RESOURCE_ENV: &str = "GOOSE_CWD_RESOURCE";
+
+/// How the resource holding the working directory is offered to the agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CwdResource {
+    /// Listed and pinned into the context, so the agent always sees the current directory
+    Pinned,
+    /// Listed, but only read when the agent asks for it
+    #[default]
+    Listed,
+    /// Not offered at all
+    Off,
+}
+
+impl CwdResource {
+    /// Read the policy from `GOOSE_CWD_RESOURCE`, one of `pinned`, `listed` or `off`
+    pub fn from_env() -> Self {
+        match std::env::var(CWD_RESOURCE_ENV).as_deref() {
+            Ok("pinned") => Self::Pinned,
+            Ok("off") => Self::Off,
+            _ => Self::Listed,
+        }
+    }
+}
+
+/// The `str://` URI of the working directory resource, with the path encoded as in a file URL
+///
+/// The URI changes along with the working directory, so a stale one can be told apart. None if
+/// `dir` is not an absolute path.
+fn cwd_uri(dir: &Path) -> Option<String> {
+    let url = Url::from_file_path(dir).ok()?;
+    Some(format!("str://{}", url.path()))
+}
+
+/// Lines of a written file shown to the user from each end, the rest are left in the file
+const WRITE_ECHO_LINES: usize = 50;
+
+/// Default number of matching lines returned by the text_search tool
+const DEFAULT_SEARCH_MAX_RESULTS: usize = 100;
+
+/// Matching lines longer than this many bytes are shortened in text_search results
+const SEARCH_MAX_LINE_BYTES: usize = 300;
+
+/// How much of a file text_editor `view` returns at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewLimits {
+    /// Most bytes returned, whether of the whole file or the lines in `view_range`
+    pub max_bytes: u64,
+    /// Most characters returned, whether of the whole file or the lines in `view_range`
+    pub max_chars: usize,
+}
+
+impl Default for ViewLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 400 * 1024,
+            max_chars: 400_000,
+        }
+    }
+}
+
+impl ViewLimits {
+    /// The limits for a single view that asked for up to `max_bytes`
+    ///
+    /// A character is at least a byte, so the byte limit bounds the characters as well.
+    fn for_call(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            max_chars: usize::try_from(max_bytes).unwrap_or(usize::MAX),
+        }
+    }
+}
+
+/// Default number of levels listed when viewing a directory with text_editor
+const DEFAULT_TREE_MAX_DEPTH: usize = 3;
+
+/// Most entries listed when viewing a directory with text_editor
+const TREE_MAX_ENTRIES: usize = 500;
+
+/// Bytes at the start of a file searched for a NUL when telling whether it is binary, as git does
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+/// Bytes at the start of a binary file shown as a hexdump when it is viewed
+const BINARY_PREVIEW_BYTES: usize = 256;
+
+/// Whether `bytes` look like the contents of a binary file rather than text
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0)
+}
+
+/// A hexdump of `bytes` in the style of `xxd`, sixteen bytes to a line
+fn hexdump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:08x}: {:<47}  {}", i * 16, hex.join(" "), ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether most lines of `content` end with CRLF rather than LF
+fn uses_crlf(content: &str) -> bool {
+    content.matches("\r\n").count() * 2 > content.matches('\n').count()
+}
+
+/// `text` with every line ending changed to CRLF if `crlf` is set, or to LF otherwise
+fn with_line_endings(text: &str, crlf: bool) -> Cow<'_, str> {
+    let lf = if text.contains("\r\n") {
+        Cow::Owned(text.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(text)
+    };
+    if crlf && lf.contains('\n') {
+        Cow::Owned(lf.replace('\n', "\r\n"))
+    } else {
+        lf
+    }
+}
+
+/// Keep the head and tail of `output` within `max_bytes`, replacing the middle with a marker
+///
+/// Returns the (possibly) shortened output and the number of bytes that were removed.
+fn truncate_output(output: &str, max_bytes: usize) -> (String, usize) {
+    if output.len() <= max_bytes {
+        return (output.to_string(), 0);
+    }
+
+    let head = truncate_str(output, max_bytes / 2);
+    let tail = truncate_str_start(output, max_bytes - max_bytes / 2);
+    let truncated = output.len() - head.len() - tail.len();
+    (
+        format!(
+            "{}\n... [{} bytes truncated] ...\n{}",
+            head, truncated, tail
+        ),
+        truncated,
+    )
+}
+
+/// Lines requested with the text_editor `view_range` parameter, starting at 1 and inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ViewRange {
+    start: usize,
+    /// None views to the end of the file
+    end: Option<usize>,
+}
+
+impl ViewRange {
+    /// The first and last line to view in a file of `total_lines` lines
+    fn resolve(self, total_lines: usize) -> Result<(usize, usize), ToolError> {
+        let end = self.end.unwrap_or(total_lines).min(total_lines);
+        if self.start > total_lines.max(1) || self.start > end.max(1) {
+            return Err(ToolError::InvalidParameters(format!(
+                "Invalid view_range: line {} is outside the file, which has {} lines",
+                self.start, total_lines
+            )));
+        }
+        Ok((self.start, end))
+    }
+}
+
+fn parse_view_range(value: &Value) -> Result<ViewRange, ToolError> {
+    let invalid = || {
+        ToolError::InvalidParameters(
+            "view_range must be two line numbers `[start, end]`, with -1 as end for the end of the file"
+                .into(),
+        )
+    };
+    let bounds = value
+        .as_array()
+        .filter(|a| a.len() == 2)
+        .ok_or_else(invalid)?;
+    let start = bounds[0].as_u64().filter(|&n| n >= 1).ok_or_else(invalid)? as usize;
+    let end = match bounds[1].as_i64().ok_or_else(invalid)? {
+        -1 => None,
+        end if end >= start as i64 => Some(end as usize),
+        _ => return Err(invalid()),
+    };
+    Ok(ViewRange { start, end })
+}
+
+/// Read one line from `reader` and return its length in bytes, which is 0 at the end
+///
+/// If `keep` is set the line is appended to `kept`, but only until `kept` holds more than
+/// `limit` bytes, so a single very long line is never held whole.
+fn read_line(
+    reader: &mut impl BufRead,
+    kept: &mut Vec<u8>,
+    keep: bool,
+    limit: usize,
+) -> io::Result<usize> {
+    let mut len = 0;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(len);
+        }
+        let (chunk, ends_line) = match buf.iter().position(|&b| b == b'\n') {
+            Some(i) => (&buf[..=i], true),
+            None => (buf, false),
+        };
+        if keep && kept.len() <= limit {
+            kept.extend_from_slice(&chunk[..chunk.len().min(limit + 1 - kept.len())]);
+        }
+        let read = chunk.len();
+        reader.consume(read);
+        len += read;
+        if ends_line {
+            return Ok(len);
+        }
+    }
+}
+
+/// The lines of a viewed file and how many lines the file has
+struct ViewedLines {
+    /// Empty if the lines come to more than the limit they were read with
+    content: String,
+    /// Size of the lines in bytes
+    bytes: u64,
+    total_lines: usize,
+}
+
+/// Read the lines in `range` from a file that is too large to read whole
+///
+/// The file is read a line at a time and lines outside the range are dropped as they go by,
+/// as are the lines in the range once they come to more than `max_bytes`.
+fn read_line_range(
+    path: &Path,
+    range: ViewRange,
+    max_bytes: u64,
+) -> Result<ViewedLines, ToolError> {
+    let read_error =
+        |e: io::Error| ToolError::ExecutionError(format!("Failed to read file: {}", e));
+    let mut file = std::fs::File::open(path).map_err(read_error)?;
+
+    let mut head = Vec::new();
+    file.by_ref()
+        .take(BINARY_SNIFF_BYTES as u64)
+        .read_to_end(&mut head)
+        .map_err(read_error)?;
+    if is_binary(&head) {
+        let size = file.metadata().map_err(read_error)?.len();
+        return Err(binary_file_error(path, size, &head));
+    }
+    file.rewind().map_err(read_error)?;
+
+    let limit = usize::try_from(max_bytes).unwrap_or(usize::MAX - 1);
+    let mut reader = BufReader::new(file);
+    let mut kept = Vec::new();
+    let mut bytes = 0;
+    let mut total_lines = 0;
+    loop {
+        let line = total_lines + 1;
+        let in_range = line >= range.start && range.end.is_none_or(|end| line <= end);
+        let read = read_line(&mut reader, &mut kept, in_range, limit).map_err(read_error)?;
+        if read == 0 {
+            break;
+        }
+        total_lines = line;
+        if in_range {
+            bytes += read as u64;
+        }
+    }
+
+    let content = if bytes <= max_bytes {
+        String::from_utf8(kept).map_err(|_| not_utf8_error(path))?
+    } else {
+        String::new()
+    };
+    Ok(ViewedLines {
+        content,
+        bytes,
+        total_lines,
+    })
+}
+
+/// The error for viewing a binary file of `size` bytes, with a hexdump of its first `bytes`
+fn binary_file_error(path: &Path, size: u64, bytes: &[u8]) -> ToolError {
+    let preview = &bytes[..bytes.len().min(BINARY_PREVIEW_BYTES)];
+    ToolError::ExecutionError(format!(
+        "File '{}' is a binary file ({} bytes), so it can't be viewed as text. Inspect it with a tool made for its format in the shell instead, such as `file` or `xxd`. {}:\n{}",
+        path.display(),
+        size,
+        if (preview.len() as u64) < size {
+            format!("Its first {} bytes are", preview.len())
+        } else {
+            "Its bytes are".to_string()
+        },
+        hexdump(preview)
+    ))
+}
+
+fn not_utf8_error(path: &Path) -> ToolError {
+    ToolError::ExecutionError(format!(
+        "File '{}' is not valid UTF-8 text, so it can't be viewed. Convert it with a shell command such as `iconv` first.",
+        path.display()
+    ))
+}
+
+/// Which matches of `old_str` a str_replace edit replaces
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Occurrence {
+    /// The only match, which is an error if there are several
+    Unique,
+    /// The 1-indexed match
+    Nth(usize),
+    All,
+}
+
+fn parse_occurrence(value: &Value) -> Result<Occurrence, ToolError> {
+    match value {
+        Value::String(s) if s == "all" => Ok(Occurrence::All),
+        Value::Number(n) => match n.as_u64() {
+            Some(n) if n >= 1 => Ok(Occurrence::Nth(n as usize)),
+            _ => Err(ToolError::InvalidParameters(
+                "occurrence must be 1 or more, counting matches from the start of the file".into(),
+            )),
+        },
+        _ => Err(ToolError::InvalidParameters(
+            "occurrence must be a match number starting at 1, or \"all\"".into(),
+        )),
+    }
+}
+
+/// Keep the first and last `keep` lines of `text`, noting how many lines in between were left out
+fn cap_lines(text: &str, keep: usize, omitted_note: impl Fn(usize) -> String) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.len() <= keep * 2 {
+        return text.to_string();
+    }
+    format!(
+        "{}\n... [{}] ...\n{}",
+        lines[..keep].join("\n"),
+        omitted_note(lines.len() - keep * 2),
+        lines[lines.len() - keep..].join("\n")
+    )
+}
+
+/// A unified diff of an edit to `path` in a `diff` code block, for showing the edit to the user
+fn edit_diff(path: &Path, before: &str, after: &str) -> String {
+    let diff = TextDiff::from_lines(before, after)
+        .unified_diff()
+        .context_radius(3)
+        .header(
+            &format!("a/{}", path.display()),
+            &format!("b/{}", path.display()),
+        )
+        .to_string();
+    let diff = cap_lines(diff.trim_end(), WRITE_ECHO_LINES, |omitted| {
+        format!("{} diff lines not shown", omitted)
+    });
+    format!("```diff\n{}\n```\n", diff)
+}
+
+/// Append `line` to shell output, after a blank line if there is any output
+fn append_line(output: &mut String, line: &str) {
+    if !output.is_empty() {
+        output.push_str(if output.ends_with('\n') { "\n" } else { "\n\n" });
+    }
+    output.push_str(line);
+}
+
+/// Describe how a shell command ended, saying plainly when it failed
+fn describe_exit_status(status: ExitStatus) -> String {
+    match status.code() {
+        Some(0) => "The command finished with status code 0".to_string(),
+        Some(code) => format!("The command failed with status code {}", code),
+        None => format!("The command failed, it was terminated ({})", status),
+    }
+}
+
+/// The directory of a shell command that is only a `cd`, like `cd src` or `cd ~/project`
+fn lone_cd_target(command: &str) -> Option<&str> {
+    let dir = command.trim().strip_prefix("cd")?;
+    if !dir.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let dir = dir.trim().trim_matches(['"', '\'']);
+    let is_plain = !dir.is_empty() && !dir.contains([';', '&', '|', '$', '`', '<', '>', '(']);
+    is_plain.then_some(dir)
+}
+
+/// Whether a shell command backgrounds something with `&`, like `server > log 2>&1 &`
+///
+/// `&&`, `|&`, redirections like `2>&1` or `&>`, and a quoted or escaped `&` don't count.
+fn runs_in_background(command: &str) -> bool {
+    let mut quote = None;
+    let mut prev = ' ';
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') if prev != '\\' => quote = Some(c),
+            (None, '&') if chars.peek() == Some(&'&') => {
+                chars.next();
+            }
+            (None, '&')
+                if !matches!(prev, '>' | '<' | '|' | '\\') && chars.peek() != Some(&'>') =>
+            {
+                return true;
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    false
+}
+
 pub struct DeveloperRouter {
     tools: Vec<Tool>,
     file_history: Arc<Mutex<HashMap<PathBuf, Vec<String>>>>,
+    redo_history: Arc<Mutex<HashMap<PathBuf, Vec<String>>>>,
+    /// The content of each viewed file when it was last viewed or edited with text_editor
+    viewed_files: Arc<Mutex<HashMap<PathBuf, String>>>,
+    /// The files viewed with text_editor, listed as resources by their `file://` URI
+    active_resources: Arc<Mutex<HashMap<String, Resource>>>,
+    /// Keeps the timestamps of the listed files current, if `GOOSE_WATCH_RESOURCES` is set
+    resource_watcher: Option<Arc<ResourceWatcher>>,
+    scratchpad: Arc<Mutex<String>>,
+    cwd_resource: CwdResource,
+    file_locks: Arc<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>>,
+    /// Directory relative paths and shell commands are resolved in, the process cwd if unset
+    working_dir: Arc<Mutex<Option<PathBuf>>>,
+    /// Jobs that shell commands backgrounded with `&`, which may still be running
+    process_store: ProcessStore,
+    /// Files like lock files that are refused for `write` and `str_replace` unless forced
+    generated_files: GeneratedFiles,
+    dangerous_commands: DangerousCommands,
+    /// Whether every shell command must come with an explanation for the user
+    explain_commands: bool,
+    view_limits: ViewLimits,
+    prompts: Arc<PromptLibrary>,
+    history_store: Option<HistoryStore>,
     instructions: String,
 }
 
+fn edit_history_dir_from_env() -> Option<PathBuf> {
+    std::env::var(EDIT_HISTORY_DIR_ENV).ok().map(PathBuf::from)
+}
+
 impl Default for DeveloperRouter {
     fn default() -> Self {
         Self::new()
@@ -44,13 +562,31 @@ impl Default for DeveloperRouter {
 }
 
 impl DeveloperRouter {
+    /// Create a router working in the process's current directory, persisting edit history
+    /// if `GOOSE_EDIT_HISTORY_DIR` is set
     pub fn new() -> Self {
-        // TODO consider rust native search tools, we could use
-        // https://docs.rs/ignore/latest/ignore/
+        Self::build(None, edit_history_dir_from_env())
+    }
 
-        let bash_tool = Tool::new(
-            "shell".to_string(),
-            indoc! {r#"
+    /// Create a router working in `cwd` rather than the process's current directory
+    ///
+    /// Relative paths, shell commands, the working directory resource and the instructions,
+    /// including the `.goosehints` they are read from, all use `cwd` until the working
+    /// directory is changed.
+    pub fn with_cwd(cwd: PathBuf) -> Self {
+        Self::build(Some(cwd), edit_history_dir_from_env())
+    }
+
+    /// Create a router that persists edit history to `history_dir`, loading any history
+    /// stored there by a previous instance
+    pub fn with_history_dir(history_dir: Option<PathBuf>) -> Self {
+        Self::build(None, history_dir)
+    }
+
+    /// Create a router working in `cwd`, or following the process's current directory if None
+    fn build(working_dir: Option<PathBuf>, history_dir: Option<PathBuf>) -> Self {
+        let explain_commands = explain_commands_enabled();
+        let mut bash_description = indoc! {r#"
                 Execute a command in the shell.
 
                 This will return the output and error concatenated into a single string, as
@@ -58,19 +594,68 @@ impl DeveloperRouter {
                 of if the command succeeded or failed.
 
                 Avoid commands that produce a large amount of ouput, and consider piping those outputs to files.
-                If you need to run a long lived command, background it - e.g. `uvicorn main:app &` so that
-                this tool does not run indefinitely.
-
-                **Important**: Use ripgrep - `rg` - when you need to locate a file or a code reference, other solutions
-                may show ignored or hidden files. For example *do not* use `find` or `ls -r`
-                  - To locate a file by name: `rg --files | rg example.py`
-                  - To locate consent inside files: `rg 'class Example'`
-            "#}.to_string(),
+                If you need to run a long lived command, background it with its output redirected - e.g.
+                `uvicorn main:app > server.log 2>&1 &` so that this tool does not run indefinitely. Use
+                list_processes and kill_process to see and stop the commands you backgrounded.
+
+                Commands that do not finish within `timeout_seconds` (default 300) are killed and reported
+                as timed out. Output longer than `max_output_bytes` (default 100000) is truncated, keeping
+                the beginning and end.
+
+                Commands run in the working directory, or in `working_dir` if it is given. Running `cd <dir>`
+                on its own changes the working directory for later commands and for relative paths in the
+                other tools, while a `cd` combined with other commands only lasts for that command. Prefer
+                `working_dir` over `cd <dir> && ...` to run a single command in another directory.
+
+                Commands cannot read from a terminal, so interactive prompts see the end of their input.
+                Pass `stdin` to give a command its input, such as the answers to its prompts.
+                Destructive commands such as `rm -rf /` or `mkfs` are refused.
+
+                **Important**: Use the text_search tool when you need to locate a code reference, other solutions
+                may show ignored or hidden files. For example *do not* use `grep -r` or `find`.
+            "#}.to_string();
+        let mut bash_required = vec!["command"];
+        if explain_commands {
+            bash_description.push_str(
+                "\nEvery command needs an `explanation`, a single line saying what it does and why, which is shown to the user with the command.\n",
+            );
+            bash_required.push("explanation");
+        }
+        let bash_tool = Tool::new(
+            "shell".to_string(),
+            bash_description,
             json!({
                 "type": "object",
-                "required": ["command"],
+                "required": bash_required,
                 "properties": {
-                    "command": {"type": "string"}
+                    "command": {"type": "string"},
+                    "explanation": {
+                        "type": "string",
+                        "description": "Optional: a single line explaining what the command does, shown to the user with the command"
+                    },
+                    "timeout_seconds": {
+                        "type": "integer",
+                        "default": DEFAULT_SHELL_TIMEOUT_SECS,
+                        "description": "Optional: maximum number of seconds to wait for the command to finish"
+                    },
+                    "max_output_bytes": {
+                        "type": "integer",
+                        "default": DEFAULT_SHELL_MAX_OUTPUT_BYTES,
+                        "description": "Optional: maximum number of bytes of output to return"
+                    },
+                    "working_dir": {
+                        "type": "string",
+                        "description": "Optional: absolute path of an existing directory to run this command in"
+                    },
+                    "stdin": {
+                        "type": "string",
+                        "description": "Optional: text to send to the standard input of the command, which is otherwise empty"
+                    },
+                    "summarize_traces": {
+                        "type": "boolean",
+                        "default": false,
+                        "description": "Optional: when the output has a Rust panic, Python traceback or Node.js error, return only the error message and the closest frames in project files, saving the full output to a file"
+                    }
                 }
             }),
         );
@@ -81,17 +666,35 @@ impl DeveloperRouter {
                 Perform text editing operations on files.
 
                 The `command` parameter specifies the operation to perform. Allowed options are:
-                - `view`: View the content of a file.
+                - `view`: View the content of a file, or only the lines in `view_range`. Viewing a directory lists
+                  its files and subdirectories up to `max_depth` levels deep, skipping gitignored entries.
                 - `write`: Create or overwrite a file with the given content
                 - `str_replace`: Replace a string in a file with a new string.
                 - `undo_edit`: Undo the last edit made to a file.
+                - `redo`: Reapply the last edit undone with `undo_edit`.
+                - `move`: Move or rename a file to `new_path`, keeping its undo history.
 
                 To use the write command, you must specify `file_text` which will become the new content of the file. Be careful with
                 existing files! This is a full overwrite, so you must include everything - not just sections you are modifying.
 
                 To use the str_replace command, you must specify both `old_str` and `new_str` - the `old_str` needs to exactly match one
                 unique section of the original file, including any whitespace. Make sure to include enough context that the match is not
-                ambiguous. The entire original string will be replaced with `new_str`.
+                ambiguous. The entire original string will be replaced with `new_str`. When `old_str` appears several times,
+                set `occurrence` to the number of the match to replace, counting from 1 at the start of the file, or to
+                "all" to replace every match. Line endings are matched to the file's own, so `old_str` and `new_str`
+                can use `\n` even in files with CRLF line endings.
+
+                Set `dry_run` to true with `write`, `str_replace` or `move` to preview the result without changing anything.
+
+                Generated files such as `Cargo.lock` or `package-lock.json` cannot be edited with `write` or `str_replace`
+                unless `force` is true. Regenerate them with the command that produces them instead.
+
+                To use the move command, you must specify `new_path`, the absolute path to move the file to. The destination
+                must not already exist. Prefer this over running `mv` in the shell so the file can still be undone.
+
+                The view command reports `truncated: true` when only part of the file was returned, and
+                `truncated: false` when the output is the complete file. Files too large to view whole can be
+                viewed a part at a time with `view_range`, or with a higher limit set in `max_bytes`.
             "#}.to_string(),
             json!({
                 "type": "object",
@@ -103,118 +706,618 @@ impl DeveloperRouter {
                     },
                     "command": {
                         "type": "string",
-                        "enum": ["view", "write", "str_replace", "undo_edit"],
-                        "description": "Allowed options are: `view`, `write`, `str_replace`, undo_edit`."
+                        "enum": ["view", "write", "str_replace", "undo_edit", "redo", "move"],
+                        "description": "Allowed options are: `view`, `write`, `str_replace`, `undo_edit`, `redo`, `move`."
                     },
                     "old_str": {"type": "string"},
                     "new_str": {"type": "string"},
-                    "file_text": {"type": "string"}
+                    "occurrence": {
+                        "description": "Optional: which match of `old_str` to replace with `str_replace`, a number starting at 1 or \"all\". By default `old_str` must match exactly once.",
+                        "oneOf": [
+                            {"type": "integer", "minimum": 1},
+                            {"type": "string", "enum": ["all"]}
+                        ]
+                    },
+                    "file_text": {"type": "string"},
+                    "dry_run": {
+                        "description": "Optional: preview the result of `write`, `str_replace` or `move` without changing any files.",
+                        "type": "boolean",
+                        "default": false
+                    },
+                    "force": {
+                        "description": "Optional: edit a generated file such as a lock file with `write` or `str_replace` anyway.",
+                        "type": "boolean",
+                        "default": false
+                    },
+                    "new_path": {
+                        "description": "Absolute path to move the file to, only used by `move`.",
+                        "type": "string"
+                    },
+                    "max_depth": {
+                        "description": "Optional: how many levels of a directory to list with `view`.",
+                        "type": "integer",
+                        "default": DEFAULT_TREE_MAX_DEPTH
+                    },
+                    "view_range": {
+                        "description": "Optional: `[start, end]` line numbers to view, starting at 1 and inclusive. Use -1 as `end` to view to the end of the file.",
+                        "type": "array",
+                        "items": {"type": "integer"},
+                        "minItems": 2,
+                        "maxItems": 2
+                    },
+                    "max_bytes": {
+                        "description": "Optional: the most bytes `view` may return, for files larger than the default limit.",
+                        "type": "integer",
+                        "minimum": 1
+                    }
                 }
             }),
         );
 
-        let list_windows_tool = Tool::new(
-            "list_windows",
+        let text_search_tool = Tool::new(
+            "text_search".to_string(),
             indoc! {r#"
-                List all available window titles that can be used with screen_capture.
-                Returns a list of window titles that can be used with the window_title parameter
-                of the screen_capture tool.
-            "#},
+                Search file contents for a regular expression.
+
+                Walks `path` (defaults to the current directory) respecting .gitignore files and
+                returns each matching line as `path:line_number:line`. Use `glob` to restrict the
+                search to matching files, e.g. `*.rs`. At most `max_results` matches are returned.
+                Symlinks are only followed when `follow_symlinks` is true, and never outside `path`.
+            "#}
+            .to_string(),
             json!({
                 "type": "object",
-                "required": [],
-                "properties": {}
+                "required": ["pattern"],
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Regular expression to search for, e.g. `fn \\w+_test`"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Optional: absolute path to the directory or file to search"
+                    },
+                    "glob": {
+                        "type": "string",
+                        "description": "Optional: only search files matching this glob, e.g. `*.py`"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "default": DEFAULT_SEARCH_MAX_RESULTS,
+                        "description": "Optional: maximum number of matching lines to return"
+                    },
+                    "follow_symlinks": {
+                        "type": "boolean",
+                        "default": false,
+                        "description": "Optional: follow symlinks that stay inside `path`"
+                    }
+                }
             }),
         );
 
-        let screen_capture_tool = Tool::new(
-            "screen_capture",
+        let validate_format_tool = Tool::new(
+            "validate_format".to_string(),
             indoc! {r#"
-                Capture a screenshot of a specified display or window.
-                You can capture either:
-                1. A full display (monitor) using the display parameter
-                2. A specific window by its title using the window_title parameter
+                Validate a JSON, YAML or TOML file and optionally rewrite it pretty-printed.
 
-                Only one of display or window_title should be specified.
-            "#},
+                The format is inferred from the file extension unless `format` is given. Parse errors
+                are reported with their line and column. When `rewrite` is true, a valid JSON file
+                is re-indented, keeping its key order, which can be reverted with the text_editor
+                `undo_edit` command. YAML and TOML files are only validated, so their comments
+                and layout are never lost.
+            "#}.to_string(),
             json!({
                 "type": "object",
-                "required": [],
+                "required": ["path"],
                 "properties": {
-                    "display": {
-                        "type": "integer",
-                        "default": 0,
-                        "description": "The display number to capture (0 is main display)"
+                    "path": {
+                        "type": "string",
+                        "description": "Absolute path to the file to validate"
                     },
-                    "window_title": {
+                    "format": {
                         "type": "string",
-                        "default": null,
-                        "description": "Optional: the exact title of the window to capture. use the list_windows tool to find the available windows."
+                        "enum": ["json", "yaml", "toml"],
+                        "description": "Optional: the format to parse the file as"
+                    },
+                    "rewrite": {
+                        "type": "boolean",
+                        "default": false,
+                        "description": "Optional: rewrite a valid JSON file pretty-printed"
                     }
                 }
             }),
         );
 
-        // Get base instructions and working directory
-        let cwd = std::env::current_dir().expect("should have a current working dir");
-        let base_instructions = formatdoc! {r#"
-            The developer extension gives you the capabilities to edit code files and run shell commands,
-            and can be used to solve a wide range of problems.
+        let read_test_report_tool = Tool::new(
+            "read_test_report".to_string(),
+            indoc! {r#"
+                Summarize a JUnit XML test report or an LCOV coverage file.
+
+                For JUnit reports this returns the pass, fail, error and skip counts and the failed
+                tests with their messages. For LCOV files it returns the overall line coverage and
+                the uncovered lines of each file, least covered first. The format is inferred from
+                the file name (`*.xml`, `lcov.info`, `*.lcov`) unless `format` is given.
+            "#}
+            .to_string(),
+            json!({
+                "type": "object",
+                "required": ["path"],
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Absolute path to the report file"
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["junit", "lcov"],
+                        "description": "Optional: the format to parse the report as"
+                    }
+                }
+            }),
+        );
 
-            You can use the shell tool to run any command that would work on the relevant operating system.
-            Use the shell tool as needed to locate files or interact with the project.
+        let changes_since_view_tool = Tool::new(
+            "changes_since_view".to_string(),
+            indoc! {r#"
+                Show how a file on disk differs from when you last viewed it with text_editor.
 
-            Your windows/screen tools can be used for visual debugging. You should not use these tools unless
-            prompted to, but you can mention they are available if they are relevant.
+                Returns a unified diff from the content you last saw to the current content, such as
+                changes made by a shell command, a formatter or the user. Edits made with text_editor
+                are already known to you and are not reported.
+            "#}
+            .to_string(),
+            json!({
+                "type": "object",
+                "required": ["path"],
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Absolute path to a file viewed earlier"
+                    }
+                }
+            }),
+        );
 
-            operating system: {os}
-            current directory: {cwd}
+        let rename_symbol_tool = Tool::new(
+            "rename_symbol".to_string(),
+            indoc! {r#"
+                Rename a variable, function, type or other symbol within a Rust or Python file.
+
+                Unlike a text replacement, only identifiers named `old_name` are renamed. Strings,
+                comments and longer names that contain `old_name` are left as they are. The rename
+                is refused if the file has syntax errors or already uses `new_name`. It can be
+                reverted with the text_editor `undo_edit` command.
+            "#}
+            .to_string(),
+            json!({
+                "type": "object",
+                "required": ["path", "old_name", "new_name"],
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Absolute path to the file to edit"
+                    },
+                    "old_name": {
+                        "type": "string",
+                        "description": "The current name of the symbol"
+                    },
+                    "new_name": {
+                        "type": "string",
+                        "description": "The name to rename the symbol to"
+                    }
+                }
+            }),
+        );
 
-            "#,
-            os=std::env::consts::OS,
-            cwd=cwd.to_string_lossy(),
-        };
+        let benchmark_tool = Tool::new(
+            "benchmark".to_string(),
+            indoc! {r#"
+                Time a shell command by running it several times, reporting the min, median, mean and
+                max wall-clock durations and the exit status of every run.
+
+                Use this for quick performance checks instead of timing commands in the shell. The output
+                of the command is discarded. Runs that take longer than `timeout_seconds` are killed and
+                left out of the durations, and no more runs are started once the benchmark has taken
+                five minutes in total. Commands are refused by the same safety checks as the shell tool.
+            "#}
+            .to_string(),
+            json!({
+                "type": "object",
+                "required": ["command"],
+                "properties": {
+                    "command": {"type": "string"},
+                    "runs": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": benchmark::MAX_RUNS,
+                        "default": benchmark::DEFAULT_RUNS,
+                        "description": "Optional: how many times to run the command"
+                    },
+                    "timeout_seconds": {
+                        "type": "integer",
+                        "default": benchmark::DEFAULT_RUN_TIMEOUT_SECS,
+                        "description": "Optional: maximum number of seconds each run may take"
+                    },
+                    "working_dir": {
+                        "type": "string",
+                        "description": "Optional: absolute path of an existing directory to run the command in"
+                    }
+                }
+            }),
+        );
 
-        // Check for and read .goosehints file if it exists
-        let hints_path = cwd.join(".goosehints");
-        let instructions = if hints_path.is_file() {
-            if let Ok(hints) = std::fs::read_to_string(&hints_path) {
-                format!("{base_instructions}\n### Project Hints\nThe developer extension includes some hints for working on the project in this directory.\n{hints}")
-            } else {
-                base_instructions
-            }
-        } else {
+        let list_processes_tool = Tool::new(
+            "list_processes".to_string(),
+            indoc! {r#"
+                List the processes that shell commands left running in the background with `&`, such
+                as servers, with their PID, command and start time.
+            "#}
+            .to_string(),
+            json!({
+                "type": "object",
+                "required": [],
+                "properties": {}
+            }),
+        );
+
+        let kill_process_tool = Tool::new(
+            "kill_process".to_string(),
+            indoc! {r#"
+                Stop a process listed by list_processes, along with any processes it started.
+                The processes are sent SIGTERM, then SIGKILL if they are still running after a few seconds.
+            "#}
+            .to_string(),
+            json!({
+                "type": "object",
+                "required": ["pid"],
+                "properties": {
+                    "pid": {
+                        "type": "integer",
+                        "description": "The PID of the process, as shown by list_processes"
+                    }
+                }
+            }),
+        );
+
+        let git_status_tool = Tool::new(
+            "git_status".to_string(),
+            indoc! {r#"
+                Show the status of a git repository as JSON: the current branch, its upstream, how many
+                commits it is ahead and behind, and the staged, modified, untracked, deleted and
+                conflicted paths relative to the repository root.
+
+                Use this instead of parsing `git status` output in the shell.
+            "#}
+            .to_string(),
+            json!({
+                "type": "object",
+                "required": [],
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Optional: absolute path of a directory in the repository, defaults to the working directory"
+                    }
+                }
+            }),
+        );
+
+        let read_scratchpad_tool = Tool::new(
+            "read_scratchpad".to_string(),
+            indoc! {r#"
+                Read your scratchpad, a private notes area that persists across turns of this session.
+                The scratchpad is also included in your context whenever it is not empty.
+            "#}.to_string(),
+            json!({
+                "type": "object",
+                "required": [],
+                "properties": {}
+            }),
+        );
+
+        let write_scratchpad_tool = Tool::new(
+            "write_scratchpad".to_string(),
+            indoc! {r#"
+                Replace the contents of your scratchpad with `content`.
+                Use it to keep running notes such as plans, findings and todo lists without creating
+                a file in the project. Write an empty string to clear it.
+            "#}
+            .to_string(),
+            json!({
+                "type": "object",
+                "required": ["content"],
+                "properties": {
+                    "content": {"type": "string"}
+                }
+            }),
+        );
+
+        let append_scratchpad_tool = Tool::new(
+            "append_scratchpad".to_string(),
+            indoc! {r#"
+                Append `content` to the end of your scratchpad on a new line.
+            "#}
+            .to_string(),
+            json!({
+                "type": "object",
+                "required": ["content"],
+                "properties": {
+                    "content": {"type": "string"}
+                }
+            }),
+        );
+
+        let list_prompts_tool = Tool::new(
+            "list_prompts",
+            indoc! {r#"
+                List the prompt templates of this extension, with their arguments.
+                Use get_prompt to fill in a template with its arguments and read the result.
+            "#},
+            json!({
+                "type": "object",
+                "required": [],
+                "properties": {}
+            }),
+        );
+
+        let get_prompt_tool = Tool::new(
+            "get_prompt",
+            indoc! {r#"
+                Read the prompt template called `name`, with its arguments filled in.
+                The available prompts and their arguments are listed by list_prompts.
+            "#},
+            json!({
+                "type": "object",
+                "required": ["name"],
+                "properties": {
+                    "name": {"type": "string"},
+                    "arguments": {
+                        "type": "object",
+                        "additionalProperties": {"type": "string"},
+                        "description": "Optional: the values of the prompt's arguments, by name"
+                    }
+                }
+            }),
+        );
+
+        let list_windows_tool = Tool::new(
+            "list_windows",
+            indoc! {r#"
+                List all available window titles that can be used with screen_capture.
+                Returns a list of window titles that can be used with the window_title parameter
+                of the screen_capture tool.
+            "#},
+            json!({
+                "type": "object",
+                "required": [],
+                "properties": {}
+            }),
+        );
+
+        let screen_capture_tool = Tool::new(
+            "screen_capture",
+            indoc! {r#"
+                Capture a screenshot of a specified display or window.
+                You can capture either:
+                1. A full display (monitor) using the display parameter
+                2. A specific window by its title using the window_title parameter
+
+                Only one of display or window_title should be specified.
+
+                Use `region` to capture only part of the display or window, in pixels of the captured image.
+                Screenshots are PNG scaled to 768px wide by default. Use `format` "jpeg" with a lower
+                `quality`, or a smaller `max_width`, to reduce the size of photographic screenshots.
+            "#},
+            json!({
+                "type": "object",
+                "required": [],
+                "properties": {
+                    "display": {
+                        "type": "integer",
+                        "default": 0,
+                        "description": "The display number to capture (0 is main display)"
+                    },
+                    "window_title": {
+                        "type": "string",
+                        "default": null,
+                        "description": "Optional: the exact title of the window to capture. use the list_windows tool to find the available windows."
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["png", "jpeg", "webp"],
+                        "default": "png",
+                        "description": "Optional: the image format to encode the screenshot as"
+                    },
+                    "max_width": {
+                        "type": "integer",
+                        "default": screenshot::DEFAULT_MAX_WIDTH,
+                        "description": "Optional: scale the screenshot down to at most this many pixels wide"
+                    },
+                    "quality": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 100,
+                        "default": screenshot::DEFAULT_JPEG_QUALITY,
+                        "description": "Optional: JPEG quality from 1 to 100, ignored for other formats"
+                    },
+                    "region": {
+                        "type": "object",
+                        "required": ["x", "y", "width", "height"],
+                        "properties": {
+                            "x": {"type": "integer", "minimum": 0},
+                            "y": {"type": "integer", "minimum": 0},
+                            "width": {"type": "integer", "minimum": 1},
+                            "height": {"type": "integer", "minimum": 1}
+                        },
+                        "description": "Optional: crop the capture to this rectangle before scaling"
+                    }
+                }
+            }),
+        );
+
+        // Get base instructions and working directory, which is unknown if the process
+        // directory has been removed
+        let cwd = working_dir.clone().or_else(|| std::env::current_dir().ok());
+        let base_instructions = formatdoc! {r#"
+            The developer extension gives you the capabilities to edit code files and run shell commands,
+            and can be used to solve a wide range of problems.
+
+            You can use the shell tool to run any command that would work on the relevant operating system.
+            Use the shell tool as needed to locate files or interact with the project, and the text_search
+            tool to find code references.
+
+            Your windows/screen tools can be used for visual debugging. You should not use these tools unless
+            prompted to, but you can mention they are available if they are relevant.
+
+            operating system: {os}
+            current directory: {cwd}
+
+            "#,
+            os=std::env::consts::OS,
+            cwd=cwd.as_deref().map_or("unknown".into(), Path::to_string_lossy),
+        };
+
+        // Check for and read .goosehints file if it exists
+        let hints_path = cwd.map(|cwd| cwd.join(".goosehints"));
+        let instructions = if let Some(hints_path) = hints_path.filter(|p| p.is_file()) {
+            if let Ok(hints) = std::fs::read_to_string(&hints_path) {
+                format!("{base_instructions}\n### Project Hints\nThe developer extension includes some hints for working on the project in this directory.\n{hints}")
+            } else {
+                base_instructions
+            }
+        } else {
             base_instructions
         };
 
+        let history_store = history_dir.and_then(|dir| {
+            HistoryStore::new(dir.clone())
+                .map_err(|e| {
+                    tracing::warn!(
+                        "Edit history will not be persisted, failed to use {}: {}",
+                        dir.display(),
+                        e
+                    )
+                })
+                .ok()
+        });
+        let file_history = history_store
+            .as_ref()
+            .map(|store| store.load())
+            .unwrap_or_default();
+
+        let active_resources = Arc::new(Mutex::new(HashMap::new()));
+        let resource_watcher = if watch_resources_enabled() {
+            ResourceWatcher::new(Arc::clone(&active_resources))
+                .map_err(|e| tracing::warn!("{}", e))
+                .ok()
+                .map(Arc::new)
+        } else {
+            None
+        };
+
         Self {
             tools: vec![
                 bash_tool,
                 text_editor_tool,
+                text_search_tool,
+                validate_format_tool,
+                read_test_report_tool,
+                changes_since_view_tool,
+                rename_symbol_tool,
+                benchmark_tool,
+                list_processes_tool,
+                kill_process_tool,
+                git_status_tool,
+                read_scratchpad_tool,
+                write_scratchpad_tool,
+                append_scratchpad_tool,
+                list_prompts_tool,
+                get_prompt_tool,
                 list_windows_tool,
                 screen_capture_tool,
             ],
-            file_history: Arc::new(Mutex::new(HashMap::new())),
+            file_history: Arc::new(Mutex::new(file_history)),
+            redo_history: Arc::new(Mutex::new(HashMap::new())),
+            viewed_files: Arc::new(Mutex::new(HashMap::new())),
+            active_resources,
+            resource_watcher,
+            scratchpad: Arc::new(Mutex::new(String::new())),
+            cwd_resource: CwdResource::from_env(),
+            file_locks: Arc::new(Mutex::new(HashMap::new())),
+            working_dir: Arc::new(Mutex::new(working_dir)),
+            process_store: ProcessStore::default(),
+            generated_files: GeneratedFiles::from_env(),
+            dangerous_commands: DangerousCommands::from_env(),
+            explain_commands,
+            view_limits: ViewLimits::default(),
+            prompts: Arc::new(PromptLibrary::load()),
+            history_store,
             instructions,
         }
     }
 
-    // Helper method to resolve a path relative to cwd
+    /// Set how much of a file text_editor `view` returns, unless a call asks for more
+    pub fn with_view_limits(mut self, view_limits: ViewLimits) -> Self {
+        self.view_limits = view_limits;
+        self
+    }
+
+    /// The directory relative paths and shell commands are resolved in
+    ///
+    /// Fails when following the process's current directory and that has been removed.
+    pub fn working_directory(&self) -> Result<PathBuf, ToolError> {
+        if let Some(dir) = self.working_dir.lock().unwrap().clone() {
+            return Ok(dir);
+        }
+        std::env::current_dir().map_err(|e| {
+            ToolError::ExecutionError(format!(
+                "Failed to get the current working directory: {}",
+                e
+            ))
+        })
+    }
+
+    /// Change the directory relative paths and shell commands are resolved in
+    ///
+    /// A relative `dir` is resolved against the current working directory. Fails if it is not
+    /// an existing directory.
+    pub fn set_working_directory(&self, dir: impl AsRef<Path>) -> Result<PathBuf, ToolError> {
+        let dir = self.resolve_path(&dir.as_ref().to_string_lossy())?;
+        if !dir.is_dir() {
+            return Err(ToolError::InvalidParameters(format!(
+                "Cannot change the working directory to '{}', it is not a directory",
+                dir.display()
+            )));
+        }
+        *self.working_dir.lock().unwrap() = Some(dir.clone());
+        Ok(dir)
+    }
+
+    /// Resolve a path against the working directory, expanding a leading `~`
+    ///
+    /// A relative path that only exists relative to the process directory, and not the working
+    /// directory, is ambiguous and fails with a suggestion of the absolute path meant instead.
     fn resolve_path(&self, path_str: &str) -> Result<PathBuf, ToolError> {
-        let cwd = std::env::current_dir().expect("should have a current working dir");
         let expanded = shellexpand::tilde(path_str);
         let path = Path::new(expanded.as_ref());
+        if path.is_absolute() {
+            return Ok(path.to_path_buf());
+        }
 
-        let suggestion = cwd.join(path);
-
-        match path.is_absolute() {
-            true => Ok(path.to_path_buf()),
-            false => Err(ToolError::InvalidParameters(format!(
-                "The path {} is not an absolute path, did you possibly mean {}?",
-                path_str,
-                suggestion.to_string_lossy(),
-            ))),
+        let working_dir = self.working_directory()?;
+        let resolved = working_dir.join(path);
+        if !resolved.exists() {
+            if let Ok(suggestion) = std::env::current_dir().map(|cwd| cwd.join(path)) {
+                if suggestion.exists() && suggestion != resolved {
+                    return Err(ToolError::InvalidParameters(format!(
+                        "The path {} does not exist in the working directory {}, did you possibly mean {}?",
+                        path_str,
+                        working_dir.display(),
+                        suggestion.display(),
+                    )));
+                }
+            }
         }
+        Ok(resolved)
     }
 
     // Implement bash tool functionality
@@ -227,119 +1330,554 @@ impl DeveloperRouter {
                     "The command string is required".to_string(),
                 ))?;
 
-        // TODO consider command suggestions and safety rails
+        if let Some(error) = self.dangerous_commands.check(command) {
+            return Err(ToolError::InvalidParameters(error));
+        }
+
+        let explanation = params
+            .get("explanation")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|explanation| !explanation.is_empty());
+        if self.explain_commands && explanation.is_none() {
+            return Err(ToolError::InvalidParameters(
+                "An `explanation` is required: add a single line saying what the command does and why"
+                    .to_string(),
+            ));
+        }
+
+        // A lone `cd` changes the working directory for the following commands, which would
+        // otherwise be lost with the shell it ran in
+        if let Some(dir) = lone_cd_target(command) {
+            let dir = self.set_working_directory(dir)?;
+            let message = format!("Changed the working directory to {}", dir.display());
+            return Ok(vec![
+                Content::text(message.clone()).with_audience(vec![Role::Assistant]),
+                Content::text(message)
+                    .with_audience(vec![Role::User])
+                    .with_priority(0.0),
+            ]);
+        }
+
+        let working_dir = self.command_directory(&params)?;
 
         // TODO be more careful about backgrounding, revisit interleave
         // Redirect stderr to stdout to interleave outputs
         let cmd_with_redirect = format!("{} 2>&1", command);
 
+        let timeout_secs = params
+            .get("timeout_seconds")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_SHELL_TIMEOUT_SECS);
+
+        let max_output_bytes = params
+            .get("max_output_bytes")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(DEFAULT_SHELL_MAX_OUTPUT_BYTES);
+
+        // There is no terminal to read from, so commands get the given input or none at all
+        let input = params.get("stdin").and_then(|v| v.as_str());
+        let stdin = if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        };
+
+        // The jobs a command backgrounds are written to a file, so they can be listed and
+        // stopped later
+        let background = runs_in_background(command);
+        let mut script = cmd_with_redirect;
+        let jobs_file = if background {
+            let file = tempfile::NamedTempFile::new().map_err(|e| {
+                ToolError::ExecutionError(format!(
+                    "Failed to create a file for the background jobs: {}",
+                    e
+                ))
+            })?;
+            script.push_str(&format!("\njobs -p > '{}'", file.path().display()));
+            Some(file)
+        } else {
+            None
+        };
+
         // Execute the command
-        let child = Command::new("bash")
+        let mut shell = Command::new("bash");
+        shell
             .stdout(Stdio::piped()) // These two pipes required to capture output later.
             .stderr(Stdio::piped())
-            .stdin(Stdio::null())
+            .stdin(stdin)
             .kill_on_drop(true) // Critical so that the command is killed when the agent.reply stream is interrupted.
+            .current_dir(&working_dir)
             .arg("-c")
-            .arg(cmd_with_redirect)
-            .spawn()
-            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
-
-        // Wait for the command to complete and get output
-        let output = child
-            .wait_with_output()
-            .await
-            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+            .arg(script);
+        if background {
+            // A process group of its own, so interrupting the agent doesn't stop the servers
+            // the command leaves running
+            shell.process_group(0);
+        }
+        let mut child = shell.spawn().map_err(|e| {
+            ToolError::ExecutionError(format!(
+                "Failed to start bash to run '{}' in {}: {}",
+                command,
+                working_dir.display(),
+                e
+            ))
+        })?;
+
+        let mut stdout = child
+            .stdout
+            .take()
+            .expect("stdout should be piped for the spawned command");
+
+        let stdin = child.stdin.take();
+
+        // Read the output as it arrives so that whatever was captured is still available on timeout
+        let mut output = Vec::new();
+        let completed = tokio::time::timeout(Duration::from_secs(timeout_secs), async {
+            // Feed the input while reading, so neither pipe fills up waiting on the other
+            let feed = async {
+                if let (Some(mut stdin), Some(input)) = (stdin, input) {
+                    // A command may exit without reading all of its input, which is not an error
+                    let _ = stdin.write_all(input.as_bytes()).await;
+                    // Dropping stdin closes it so the command sees the end of its input
+                }
+            };
+            let (read, ()) = tokio::join!(stdout.read_to_end(&mut output), feed);
+            read?;
+            child.wait().await
+        })
+        .await;
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
+        if completed.is_err() {
+            let _ = child.kill().await;
+        }
+        if let Some(file) = &jobs_file {
+            let jobs = std::fs::read_to_string(file.path()).unwrap_or_default();
+            for pid in jobs.lines().filter_map(|line| line.trim().parse().ok()) {
+                self.process_store.track(pid, command);
+            }
+        }
 
-        // Check the character count of the output
-        const MAX_CHAR_COUNT: usize = 400_000; // 409600 chars = 400KB
-        let char_count = output_str.chars().count();
-        if char_count > MAX_CHAR_COUNT {
-            return Err(ToolError::ExecutionError(format!(
-                    "Shell output from command '{}' has too many characters ({}). Maximum character count is {}.",
+        let status = match completed {
+            Ok(result) => result.map_err(|e| {
+                ToolError::ExecutionError(format!("Failed to wait for '{}': {}", command, e))
+            })?,
+            Err(_) => {
+                return Err(ToolError::ExecutionError(format!(
+                    "Command '{}' timed out after {} seconds. Partial output:\n{}",
                     command,
-                    char_count,
-                    MAX_CHAR_COUNT
+                    timeout_secs,
+                    truncate_output(&String::from_utf8_lossy(&output), max_output_bytes).0
                 )));
+            }
+        };
+        let status_line = describe_exit_status(status);
+
+        let output_str = String::from_utf8_lossy(&output);
+
+        // Cap what is sent to the model, the user can see more since it costs no context
+        let trace = params
+            .get("summarize_traces")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+            .then(|| find_trace(&output_str, &working_dir))
+            .flatten();
+        let (mut assistant_output, truncated) = match &trace {
+            Some(trace) => (self.summarize_trace_output(trace, &output_str)?, 0),
+            None => truncate_output(&output_str, max_output_bytes),
+        };
+        if truncated > 0 {
+            assistant_output.push_str(&format!(
+                "\n\nThe output was {} bytes and has been truncated to {} bytes. \
+                 Consider redirecting the output to a file and inspecting it with `head`, `tail` or `rg`.",
+                output_str.len(),
+                max_output_bytes
+            ));
+        }
+        let (mut user_output, _) = truncate_output(
+            &output_str,
+            max_output_bytes.max(USER_SHELL_MAX_OUTPUT_BYTES),
+        );
+
+        // The model always learns how the command ended, the user only when it failed
+        append_line(&mut assistant_output, &status_line);
+        if !status.success() {
+            append_line(&mut user_output, &status_line);
         }
 
         Ok(vec![
-            Content::text(output_str.clone()).with_audience(vec![Role::Assistant]),
-            Content::text(output_str)
+            Content::text(assistant_output).with_audience(vec![Role::Assistant]),
+            Content::text(user_output)
                 .with_audience(vec![Role::User])
                 .with_priority(0.0),
         ])
     }
 
-    async fn text_editor(&self, params: Value) -> Result<Vec<Content>, ToolError> {
-        let command = params
-            .get("command")
+    async fn text_search(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let pattern = params
+            .get("pattern")
             .and_then(|v| v.as_str())
             .ok_or_else(|| {
-                ToolError::InvalidParameters("Missing 'command' parameter".to_string())
+                ToolError::InvalidParameters("Missing 'pattern' parameter".to_string())
             })?;
+        let regex = Regex::new(pattern).map_err(|e| {
+            ToolError::InvalidParameters(format!("Invalid regex '{}': {}", pattern, e))
+        })?;
 
-        let path_str = params
-            .get("path")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| ToolError::InvalidParameters("Missing 'path' parameter".into()))?;
-
-        let path = self.resolve_path(path_str)?;
+        let root = match params.get("path").and_then(|v| v.as_str()) {
+            Some(path_str) => self.resolve_path(path_str)?,
+            None => self.working_directory()?,
+        };
+        if !root.exists() {
+            return Err(ToolError::InvalidParameters(format!(
+                "The path '{}' does not exist",
+                root.display()
+            )));
+        }
 
-        match command {
-            "view" => self.text_editor_view(&path).await,
-            "write" => {
-                let file_text = params
-                    .get("file_text")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(|| {
-                        ToolError::InvalidParameters("Missing 'file_text' parameter".into())
-                    })?;
+        let max_results = params
+            .get("max_results")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(DEFAULT_SEARCH_MAX_RESULTS);
+        let options = WalkOptions {
+            follow_symlinks: params
+                .get("follow_symlinks")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            glob: params
+                .get("glob")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+        };
 
-                self.text_editor_write(&path, file_text).await
-            }
-            "str_replace" => {
-                let old_str = params
-                    .get("old_str")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(|| {
-                        ToolError::InvalidParameters("Missing 'old_str' parameter".into())
-                    })?;
-                let new_str = params
-                    .get("new_str")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(|| {
-                        ToolError::InvalidParameters("Missing 'new_str' parameter".into())
-                    })?;
+        let files = walk_files(&root, &options)
+            .map_err(|e| ToolError::InvalidParameters(format!("Invalid glob: {}", e)))?;
 
-                self.text_editor_replace(&path, old_str, new_str).await
+        let mut matches = Vec::new();
+        let mut limited = false;
+        'files: for file in files {
+            // Skip binary and non utf-8 files
+            let Ok(content) = std::fs::read_to_string(&file) else {
+                continue;
+            };
+            for (index, line) in content.lines().enumerate() {
+                if !regex.is_match(line) {
+                    continue;
+                }
+                if matches.len() >= max_results {
+                    limited = true;
+                    break 'files;
+                }
+                let snippet = truncate_str(line, SEARCH_MAX_LINE_BYTES);
+                let line = if snippet.len() < line.len() {
+                    format!("{}...", snippet)
+                } else {
+                    line.to_string()
+                };
+                matches.push(format!("{}:{}:{}", file.display(), index + 1, line));
             }
-            "undo_edit" => self.text_editor_undo(&path).await,
-            _ => Err(ToolError::InvalidParameters(format!(
-                "Unknown command '{}'",
-                command
-            ))),
         }
-    }
-
-    async fn text_editor_view(&self, path: &PathBuf) -> Result<Vec<Content>, ToolError> {
-        if path.is_file() {
-            // Check file size first (400KB limit)
-            const MAX_FILE_SIZE: u64 = 400 * 1024; // 400KB in bytes
-            const MAX_CHAR_COUNT: usize = 400_000; // 409600 chars = 400KB
 
+        let mut output = if matches.is_empty() {
+            format!("No matches found for '{}'", pattern)
+        } else {
+            matches.join("\n")
+        };
+        if limited {
+            output.push_str(&format!(
+                "\n\nShowing the first {} matches. Narrow the search with a more specific pattern, path or glob.",
+                max_results
+            ));
+        }
+
+        Ok(vec![
+            Content::text(output.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(output)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
+    }
+
+    /// Lock `paths` for the duration of a file operation
+    ///
+    /// Tool calls in one turn can run in parallel, so two of them may edit the same file. Holding
+    /// the per-path lock makes them apply one after the other, in the order they asked for it,
+    /// with a consistent undo history. Paths are locked in sorted order so operations touching
+    /// several files can't deadlock.
+    async fn lock_paths(&self, paths: &[&Path]) -> Vec<tokio::sync::OwnedMutexGuard<()>> {
+        let mut paths = paths.to_vec();
+        paths.sort();
+        paths.dedup();
+
+        let locks: Vec<_> = {
+            let mut file_locks = self.file_locks.lock().unwrap();
+            paths
+                .iter()
+                .map(|path| Arc::clone(file_locks.entry(path.to_path_buf()).or_default()))
+                .collect()
+        };
+
+        let mut guards = Vec::with_capacity(locks.len());
+        for lock in locks {
+            guards.push(lock.lock_owned().await);
+        }
+        guards
+    }
+
+    async fn validate_format(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let path_str = params
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'path' parameter".into()))?;
+        let path = self.resolve_path(path_str)?;
+        let _guards = self.lock_paths(&[&path]).await;
+
+        let format = match params.get("format").and_then(|v| v.as_str()) {
+            Some(name) => DataFormat::from_name(name).ok_or_else(|| {
+                ToolError::InvalidParameters(format!(
+                    "Unsupported format '{}', expected one of json, yaml or toml",
+                    name
+                ))
+            })?,
+            None => DataFormat::from_path(&path).ok_or_else(|| {
+                ToolError::InvalidParameters(format!(
+                    "Could not infer the format of '{}' from its extension, pass `format` explicitly",
+                    path.display()
+                ))
+            })?,
+        };
+        let rewrite = params
+            .get("rewrite")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if rewrite && format != DataFormat::Json {
+            return Err(ToolError::InvalidParameters(format!(
+                "Only JSON files can be rewritten, rewriting {} would lose its comments and key order",
+                format
+            )));
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to read file: {}", e)))?;
+
+        let invalid = |e: format::ParseError| {
+            ToolError::ExecutionError(format!("Invalid {} in {} at {}", format, path.display(), e))
+        };
+        let message = if !rewrite {
+            validate(&content, format).map_err(invalid)?;
+            format!("{} is valid {}", path.display(), format)
+        } else {
+            let pretty = pretty_print_json(&content).map_err(invalid)?;
+            if pretty == content {
+                format!(
+                    "{} is valid JSON and already pretty-printed",
+                    path.display()
+                )
+            } else {
+                self.save_file_history(&path)?;
+                self.write_file(&path, &pretty)?;
+                format!(
+                    "{} is valid JSON and has been rewritten pretty-printed",
+                    path.display()
+                )
+            }
+        };
+
+        Ok(vec![
+            Content::text(message.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(message)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
+    }
+
+    async fn read_test_report(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let path_str = params
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'path' parameter".into()))?;
+        let path = self.resolve_path(path_str)?;
+
+        let format = match params.get("format").and_then(|v| v.as_str()) {
+            Some(name) => ReportFormat::from_name(name).ok_or_else(|| {
+                ToolError::InvalidParameters(format!(
+                    "Unsupported report format '{}', expected junit or lcov",
+                    name
+                ))
+            })?,
+            None => ReportFormat::from_path(&path).ok_or_else(|| {
+                ToolError::InvalidParameters(format!(
+                    "Could not infer the report format of '{}' from its name, pass `format` explicitly",
+                    path.display()
+                ))
+            })?,
+        };
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to read file: {}", e)))?;
+        let summary = match format {
+            ReportFormat::Junit => parse_junit(&content).map(|summary| summary.to_string()),
+            ReportFormat::Lcov => parse_lcov(&content).map(|summary| summary.to_string()),
+        }
+        .map_err(|e| {
+            ToolError::ExecutionError(format!("Failed to parse {}: {}", path.display(), e))
+        })?;
+
+        Ok(vec![
+            Content::text(summary.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(summary)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
+    }
+
+    async fn text_editor(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let command = params
+            .get("command")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| {
+                ToolError::InvalidParameters("Missing 'command' parameter".to_string())
+            })?;
+
+        let path_str = params
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'path' parameter".into()))?;
+
+        let path = self.resolve_path(path_str)?;
+
+        // Generated files should be regenerated instead, so editing them has to be forced
+        let force = params
+            .get("force")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if matches!(command, "write" | "str_replace") && !force {
+            if let Some(warning) = self.generated_files.check(&path) {
+                return Err(ToolError::InvalidParameters(warning));
+            }
+        }
+
+        let new_path = match command {
+            "move" => {
+                let new_path_str =
+                    params
+                        .get("new_path")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| {
+                            ToolError::InvalidParameters("Missing 'new_path' parameter".into())
+                        })?;
+                Some(self.resolve_path(new_path_str)?)
+            }
+            _ => None,
+        };
+
+        let mut locked = vec![path.as_path()];
+        locked.extend(new_path.as_deref());
+        let _guards = self.lock_paths(&locked).await;
+
+        let dry_run = params
+            .get("dry_run")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        match command {
+            "view" => {
+                if path.is_dir() {
+                    let max_depth = params
+                        .get("max_depth")
+                        .and_then(|v| v.as_u64())
+                        .map_or(DEFAULT_TREE_MAX_DEPTH, |depth| depth as usize);
+                    return self.text_editor_view_directory(&path, max_depth).await;
+                }
+                let view_range = params.get("view_range").map(parse_view_range).transpose()?;
+                let limits = params
+                    .get("max_bytes")
+                    .and_then(|v| v.as_u64())
+                    .map_or(self.view_limits, ViewLimits::for_call);
+                self.text_editor_view(&path, view_range, limits).await
+            }
+            "write" => {
+                let file_text = params
+                    .get("file_text")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| {
+                        ToolError::InvalidParameters("Missing 'file_text' parameter".into())
+                    })?;
+
+                self.text_editor_write(&path, file_text, dry_run).await
+            }
+            "str_replace" => {
+                let old_str = params
+                    .get("old_str")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| {
+                        ToolError::InvalidParameters("Missing 'old_str' parameter".into())
+                    })?;
+                let new_str = params
+                    .get("new_str")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| {
+                        ToolError::InvalidParameters("Missing 'new_str' parameter".into())
+                    })?;
+
+                let occurrence = params
+                    .get("occurrence")
+                    .map(parse_occurrence)
+                    .transpose()?
+                    .unwrap_or(Occurrence::Unique);
+
+                self.text_editor_replace(&path, old_str, new_str, occurrence, dry_run)
+                    .await
+            }
+            "undo_edit" => self.text_editor_undo(&path).await,
+            "redo" => self.text_editor_redo(&path).await,
+            "move" => {
+                let new_path = new_path.expect("new_path is resolved for move");
+                self.text_editor_move(&path, &new_path, dry_run).await
+            }
+            _ => Err(ToolError::InvalidParameters(format!(
+                "Unknown command '{}'",
+                command
+            ))),
+        }
+    }
+
+    async fn text_editor_view_directory(
+        &self,
+        path: &Path,
+        max_depth: usize,
+    ) -> Result<Vec<Content>, ToolError> {
+        let tree = directory_tree(path, max_depth.max(1), TREE_MAX_ENTRIES).to_string();
+        Ok(vec![
+            Content::text(tree.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(tree)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
+    }
+
+    async fn text_editor_view(
+        &self,
+        path: &PathBuf,
+        view_range: Option<ViewRange>,
+        limits: ViewLimits,
+    ) -> Result<Vec<Content>, ToolError> {
+        if path.is_file() {
+            // Check the file size before reading it, when it would all be returned
             let file_size = std::fs::metadata(path)
                 .map_err(|e| {
                     ToolError::ExecutionError(format!("Failed to get file metadata: {}", e))
                 })?
                 .len();
 
-            if file_size > MAX_FILE_SIZE {
+            if view_range.is_none() && file_size > limits.max_bytes {
                 return Err(ToolError::ExecutionError(format!(
-                    "File '{}' is too large ({:.2}KB). Maximum size is 400KB to prevent memory issues.",
+                    "File '{}' is too large ({:.2}KB) to view whole, the limit is {:.2}KB. View part of it with `view_range`, or raise the limit with `max_bytes`.",
                     path.display(),
-                    file_size as f64 / 1024.0
+                    file_size as f64 / 1024.0,
+                    limits.max_bytes as f64 / 1024.0
                 )));
             }
 
@@ -347,19 +1885,106 @@ impl DeveloperRouter {
                 .map_err(|_| ToolError::ExecutionError("Invalid file path".into()))?
                 .to_string();
 
-            let content = std::fs::read_to_string(path)
-                .map_err(|e| ToolError::ExecutionError(format!("Failed to read file: {}", e)))?;
-
+            // Only the requested lines of a file too large to view whole are read into memory,
+            // and such a file is not kept for `changes_since_view`
+            let (viewed, full_content, start, end) = match view_range {
+                Some(range) if file_size > limits.max_bytes => {
+                    let viewed = read_line_range(path, range, limits.max_bytes)?;
+                    let (start, end) = range.resolve(viewed.total_lines)?;
+                    (viewed, None, start, end)
+                }
+                _ => {
+                    let bytes = std::fs::read(path).map_err(|e| {
+                        ToolError::ExecutionError(format!("Failed to read file: {}", e))
+                    })?;
+                    if is_binary(&bytes) {
+                        return Err(binary_file_error(path, bytes.len() as u64, &bytes));
+                    }
+                    let full_content =
+                        String::from_utf8(bytes).map_err(|_| not_utf8_error(path))?;
+
+                    let total_lines = full_content.lines().count();
+                    let (start, end) = match view_range {
+                        Some(range) => range.resolve(total_lines)?,
+                        None => (1, total_lines),
+                    };
+                    let content: String = if start > 1 || end < total_lines {
+                        full_content
+                            .split_inclusive('\n')
+                            .skip(start - 1)
+                            .take(end + 1 - start)
+                            .collect()
+                    } else {
+                        full_content.clone()
+                    };
+                    let viewed = ViewedLines {
+                        bytes: content.len() as u64,
+                        content,
+                        total_lines,
+                    };
+                    (viewed, Some(full_content), start, end)
+                }
+            };
+            let ViewedLines {
+                content,
+                bytes,
+                total_lines,
+            } = viewed;
+            let truncated = start > 1 || end < total_lines;
+
+            let (shown, is, has, smaller) = if truncated {
+                (
+                    format!("Lines {}-{} of '{}'", start, end, path.display()),
+                    "are",
+                    "have",
+                    "View fewer lines with `view_range`",
+                )
+            } else {
+                (
+                    format!("File '{}'", path.display()),
+                    "is",
+                    "has",
+                    "View part of it with `view_range`",
+                )
+            };
+            if bytes > limits.max_bytes {
+                return Err(ToolError::ExecutionError(format!(
+                    "{} {} too large ({:.2}KB) to view, the limit is {:.2}KB. {}, or raise the limit with `max_bytes`.",
+                    shown,
+                    is,
+                    bytes as f64 / 1024.0,
+                    limits.max_bytes as f64 / 1024.0,
+                    smaller
+                )));
+            }
             let char_count = content.chars().count();
-            if char_count > MAX_CHAR_COUNT {
+            if char_count > limits.max_chars {
                 return Err(ToolError::ExecutionError(format!(
-                    "File '{}' has too many characters ({}). Maximum character count is {}.",
-                    path.display(),
-                    char_count,
-                    MAX_CHAR_COUNT
+                    "{} {} too many characters ({}). Maximum character count is {}. {}, or raise the limit with `max_bytes`.",
+                    shown, has, char_count, limits.max_chars, smaller
                 )));
             }
 
+            if let Some(full_content) = full_content {
+                self.viewed_files
+                    .lock()
+                    .unwrap()
+                    .insert(path.clone(), full_content);
+            }
+            self.register_as_resource(&uri, path);
+
+            let status = if truncated {
+                format!(
+                    "truncated: true, showing lines {}-{} of {}. The rest of the file was not returned.",
+                    start, end, total_lines
+                )
+            } else {
+                format!(
+                    "truncated: false, showing the complete file ({} lines).",
+                    total_lines
+                )
+            };
+
             let language = lang::get_language_identifier(path);
             let formatted = formatdoc! {"
                 ### {path}
@@ -376,6 +2001,7 @@ impl DeveloperRouter {
             // but we send a low priority message for the human
             Ok(vec![
                 Content::embedded_text(uri, content).with_audience(vec![Role::Assistant]),
+                Content::text(status).with_audience(vec![Role::Assistant]),
                 Content::text(formatted)
                     .with_audience(vec![Role::User])
                     .with_priority(0.0),
@@ -392,32 +2018,82 @@ impl DeveloperRouter {
         &self,
         path: &PathBuf,
         file_text: &str,
+        dry_run: bool,
     ) -> Result<Vec<Content>, ToolError> {
-        // Write to the file
-        std::fs::write(path, file_text)
-            .map_err(|e| ToolError::ExecutionError(format!("Failed to write file: {}", e)))?;
+        let previous = path
+            .exists()
+            .then(|| Self::read_for_history(path))
+            .transpose()?;
+
+        // Follow the indentation and final newline rules of any .editorconfig for the file
+        let original_text = file_text;
+        let formatted = WriteStyle::for_path(path).apply(file_text);
+        let file_text = formatted.as_str();
+
+        if !dry_run {
+            // Save history for undo
+            self.save_file_history(path)?;
+
+            // Write to the file
+            self.write_file(path, file_text)?;
+        }
 
         // Try to detect the language from the file extension
         let language = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
 
         // The assistant output does not show the file again because the content is already in the tool request
-        // but we do show it to the user here
-        Ok(vec![
-            Content::text(format!("Successfully wrote to {}", path.display()))
-                .with_audience(vec![Role::Assistant]),
-            Content::text(formatdoc! {r#"
-                ### {path}
-                ```{language}
-                {content}
-                ```
-                "#,
-                path=path.display(),
-                language=language,
-                content=file_text,
-            })
-            .with_audience(vec![Role::User])
-            .with_priority(0.2),
-        ])
+        // but we do show it to the user here, as a diff when an existing file was overwritten,
+        // capped so a huge file is not repeated in full
+        let shown = match &previous {
+            Some(previous) => edit_diff(path, previous, file_text),
+            None => {
+                let shown = cap_lines(file_text, WRITE_ECHO_LINES, |omitted| {
+                    format!("{} lines not shown, see {}", omitted, path.display())
+                });
+                formatdoc! {r#"
+                    ```{language}
+                    {content}
+                    ```
+                    "#,
+                    language=language,
+                    content=shown,
+                }
+            }
+        };
+        let reformatted = file_text != original_text;
+        let message = if dry_run {
+            // The preview is the only way to see the result, so the assistant gets it too
+            let mut message = format!(
+                "Dry run, no changes were applied. Writing {} would produce:\n{}",
+                path.display(),
+                shown
+            );
+            if reformatted {
+                message.push_str("The content would be reformatted to follow its .editorconfig.\n");
+            }
+            message
+        } else {
+            let mut message = format!("Successfully wrote to {}", path.display());
+            if reformatted {
+                message
+                    .push_str(", reformatted to follow its .editorconfig, view the file to see it");
+            }
+            message
+        };
+        let mut contents = vec![
+            Content::text(message).with_audience(vec![Role::Assistant]),
+            Content::text(format!("### {}\n{}", path.display(), shown))
+                .with_audience(vec![Role::User])
+                .with_priority(0.2),
+        ];
+        if !dry_run {
+            let before = previous.unwrap_or_default();
+            let replaced = 0..before.len();
+            contents.push(
+                EditResult::new(path, &before, [(replaced, file_text.to_string())]).into_content(),
+            );
+        }
+        Ok(contents)
     }
 
     async fn text_editor_replace(
@@ -425,7 +2101,11 @@ impl DeveloperRouter {
         path: &PathBuf,
         old_str: &str,
         new_str: &str,
+        occurrence: Occurrence,
+        dry_run: bool,
     ) -> Result<Vec<Content>, ToolError> {
+        self.ensure_still_on_disk(path)?;
+
         // Check if file exists and is active
         if !path.exists() {
             return Err(ToolError::InvalidParameters(format!(
@@ -438,26 +2118,55 @@ impl DeveloperRouter {
         let content = std::fs::read_to_string(path)
             .map_err(|e| ToolError::ExecutionError(format!("Failed to read file: {}", e)))?;
 
-        // Ensure 'old_str' appears exactly once
-        if content.matches(old_str).count() > 1 {
-            return Err(ToolError::InvalidParameters(
-                "'old_str' must appear exactly once in the file, but it appears multiple times"
-                    .into(),
-            ));
-        }
-        if content.matches(old_str).count() == 0 {
+        // Match and write the file's own line endings, whichever the model used, falling back
+        // to the text as given for files that mix them
+        let crlf = uses_crlf(&content);
+        let normalized = with_line_endings(old_str, crlf);
+        let (old_str, new_str) = if content.contains(normalized.as_ref()) {
+            (normalized.as_ref(), with_line_endings(new_str, crlf))
+        } else {
+            (old_str, Cow::Borrowed(new_str))
+        };
+
+        // Find the matches to replace, by default 'old_str' must appear exactly once
+        let matches: Vec<usize> = content.match_indices(old_str).map(|(i, _)| i).collect();
+        if matches.is_empty() {
             return Err(ToolError::InvalidParameters(
-                "'old_str' must appear exactly once in the file, but it does not appear in the file. Make sure the string exactly matches existing file content, including whitespace!".into(),
+                "'old_str' must appear in the file, but it does not appear in the file. Make sure the string exactly matches existing file content, including whitespace!".into(),
             ));
         }
-
-        // Save history for undo
-        self.save_file_history(path)?;
+        let selected = match occurrence {
+            Occurrence::Unique if matches.len() > 1 => {
+                return Err(ToolError::InvalidParameters(format!(
+                    "'old_str' must appear exactly once in the file, but it appears {} times. Include more context, or set `occurrence` to choose a match",
+                    matches.len()
+                )));
+            }
+            Occurrence::Nth(n) if n > matches.len() => {
+                return Err(ToolError::InvalidParameters(format!(
+                    "occurrence {} is out of range, 'old_str' appears {} times in the file",
+                    n,
+                    matches.len()
+                )));
+            }
+            Occurrence::Nth(n) => vec![matches[n - 1]],
+            Occurrence::Unique | Occurrence::All => matches,
+        };
 
         // Replace and write back
-        let new_content = content.replace(old_str, new_str);
-        std::fs::write(path, &new_content)
-            .map_err(|e| ToolError::ExecutionError(format!("Failed to write file: {}", e)))?;
+        let mut new_content = String::with_capacity(content.len());
+        let mut last = 0;
+        for &start in &selected {
+            new_content.push_str(&content[last..start]);
+            new_content.push_str(&new_str);
+            last = start + old_str.len();
+        }
+        new_content.push_str(&content[last..]);
+        if !dry_run {
+            // Save history for undo
+            self.save_file_history(path)?;
+            self.write_file(path, &new_content)?;
+        }
 
         // Try to detect the language from the file extension
         let language = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
@@ -465,13 +2174,8 @@ impl DeveloperRouter {
         // Show a snippet of the changed content with context
         const SNIPPET_LINES: usize = 4;
 
-        // Count newlines before the replacement to find the line number
-        let replacement_line = content
-            .split(old_str)
-            .next()
-            .expect("should split on already matched content")
-            .matches('\n')
-            .count();
+        // Count newlines before the first replacement to find the line number
+        let replacement_line = content[..selected[0]].matches('\n').count();
 
         // Calculate start and end lines for the snippet
         let start_line = replacement_line.saturating_sub(SNIPPET_LINES);
@@ -496,327 +2200,3027 @@ impl DeveloperRouter {
             snippet=snippet
         };
 
-        let success_message = formatdoc! {r#"
-            The file {} has been edited, and the section now reads:
-            {}
-            Review the changes above for errors. Undo and edit the file again if necessary!
-            "#,
-            path.display(),
-            output
+        let success_message = if dry_run {
+            let replaced = match selected.len() {
+                1 => String::new(),
+                n => format!(" {} occurrences would be replaced,", n),
+            };
+            formatdoc! {r#"
+                Dry run, no changes were applied. The file {} would be edited,{} and the section would read:
+                {}
+                Repeat the edit without `dry_run` to apply it.
+                "#,
+                path.display(),
+                replaced,
+                output
+            }
+        } else {
+            let replaced = match selected.len() {
+                1 => String::new(),
+                n => format!(" {} occurrences were replaced,", n),
+            };
+            formatdoc! {r#"
+                The file {} has been edited,{} and the section now reads:
+                {}
+                Review the changes above for errors. Undo and edit the file again if necessary!
+                "#,
+                path.display(),
+                replaced,
+                output
+            }
         };
 
-        Ok(vec![
+        let mut contents = vec![
             Content::text(success_message).with_audience(vec![Role::Assistant]),
-            Content::text(output)
+            Content::text(edit_diff(path, &content, &new_content))
                 .with_audience(vec![Role::User])
                 .with_priority(0.2),
-        ])
+        ];
+        if !dry_run {
+            let replacements = selected
+                .iter()
+                .map(|&start| (start..start + old_str.len(), new_str.to_string()));
+            contents.push(EditResult::new(path, &content, replacements).into_content());
+        }
+        Ok(contents)
     }
 
     async fn text_editor_undo(&self, path: &PathBuf) -> Result<Vec<Content>, ToolError> {
-        let mut history = self.file_history.lock().unwrap();
-        if let Some(contents) = history.get_mut(path) {
-            if let Some(previous_content) = contents.pop() {
-                // Write previous content back to file
-                std::fs::write(path, previous_content).map_err(|e| {
-                    ToolError::ExecutionError(format!("Failed to write file: {}", e))
-                })?;
-                Ok(vec![Content::text("Undid the last edit")])
-            } else {
-                Err(ToolError::InvalidParameters(
-                    "No edit history available to undo".into(),
-                ))
-            }
-        } else {
-            Err(ToolError::InvalidParameters(
-                "No edit history available to undo".into(),
-            ))
-        }
-    }
+        self.ensure_still_on_disk(path)?;
 
-    fn save_file_history(&self, path: &PathBuf) -> Result<(), ToolError> {
         let mut history = self.file_history.lock().unwrap();
-        let content = if path.exists() {
-            std::fs::read_to_string(path)
-                .map_err(|e| ToolError::ExecutionError(format!("Failed to read file: {}", e)))?
-        } else {
-            String::new()
-        };
-        history.entry(path.clone()).or_default().push(content);
-        Ok(())
+        let previous_content = history
+            .get_mut(path)
+            .and_then(|contents| contents.pop())
+            .ok_or_else(|| {
+                ToolError::InvalidParameters("No edit history available to undo".into())
+            })?;
+        self.persist_history(path, &history);
+
+        // Keep the current content so the undo can be redone
+        let current_content = Self::read_for_history(path)?;
+        self.redo_history
+            .lock()
+            .unwrap()
+            .entry(path.clone())
+            .or_default()
+            .push(current_content);
+
+        // Write previous content back to file
+        self.write_file(path, &previous_content)?;
+        Ok(vec![Content::text("Undid the last edit")])
     }
 
-    async fn list_windows(&self, _params: Value) -> Result<Vec<Content>, ToolError> {
-        let windows = Window::all()
-            .map_err(|_| ToolError::ExecutionError("Failed to list windows".into()))?;
+    async fn text_editor_redo(&self, path: &PathBuf) -> Result<Vec<Content>, ToolError> {
+        self.ensure_still_on_disk(path)?;
 
-        let window_titles: Vec<String> =
-            windows.into_iter().map(|w| w.title().to_string()).collect();
+        let mut history = self.file_history.lock().unwrap();
+        let next_content = self
+            .redo_history
+            .lock()
+            .unwrap()
+            .get_mut(path)
+            .and_then(|contents| contents.pop())
+            .ok_or_else(|| {
+                ToolError::InvalidParameters("No undone edits available to redo".into())
+            })?;
 
-        Ok(vec![
-            Content::text(format!("Available windows:\n{}", window_titles.join("\n")))
-                .with_audience(vec![Role::Assistant]),
-            Content::text(format!("Available windows:\n{}", window_titles.join("\n")))
-                .with_audience(vec![Role::User])
-                .with_priority(0.0),
-        ])
-    }
+        // The redone edit can itself be undone again
+        let current_content = Self::read_for_history(path)?;
+        history
+            .entry(path.clone())
+            .or_default()
+            .push(current_content);
+        self.persist_history(path, &history);
 
-    async fn screen_capture(&self, params: Value) -> Result<Vec<Content>, ToolError> {
-        let mut image = if let Some(window_title) =
-            params.get("window_title").and_then(|v| v.as_str())
-        {
-            // Try to find and capture the specified window
-            let windows = Window::all()
-                .map_err(|_| ToolError::ExecutionError("Failed to list windows".into()))?;
+        self.write_file(path, &next_content)?;
+        Ok(vec![Content::text("Redid the last undone edit")])
+    }
 
-            let window = windows
-                .into_iter()
-                .find(|w| w.title() == window_title)
-                .ok_or_else(|| {
-                    ToolError::ExecutionError(format!(
-                        "No window found with title '{}'",
-                        window_title
-                    ))
-                })?;
+    async fn text_editor_move(
+        &self,
+        path: &PathBuf,
+        new_path: &PathBuf,
+        dry_run: bool,
+    ) -> Result<Vec<Content>, ToolError> {
+        if !path.exists() {
+            return Err(ToolError::InvalidParameters(format!(
+                "The file '{}' does not exist",
+                path.display()
+            )));
+        }
+        if new_path.exists() {
+            return Err(ToolError::InvalidParameters(format!(
+                "The destination '{}' already exists, choose a different path or remove it first",
+                new_path.display()
+            )));
+        }
+        if dry_run {
+            return Ok(vec![Content::text(format!(
+                "Dry run, no changes were applied. {} would be moved to {}",
+                path.display(),
+                new_path.display()
+            ))]);
+        }
 
-            window.capture_image().map_err(|e| {
-                ToolError::ExecutionError(format!(
-                    "Failed to capture window '{}': {}",
-                    window_title, e
-                ))
-            })?
-        } else {
-            // Default to display capture if no window title is specified
-            let display = params.get("display").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        std::fs::rename(path, new_path)
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to move file: {}", e)))?;
 
-            let monitors = Monitor::all()
-                .map_err(|_| ToolError::ExecutionError("Failed to access monitors".into()))?;
-            let monitor = monitors.get(display).ok_or_else(|| {
-                ToolError::ExecutionError(format!(
-                    "{} was not an available monitor, {} found.",
-                    display,
-                    monitors.len()
+        // The history belongs to the file rather than the path, so it follows the file
+        let mut history = self.file_history.lock().unwrap();
+        if let Some(contents) = history.remove(path) {
+            history.insert(new_path.clone(), contents);
+            self.persist_history(path, &history);
+            self.persist_history(new_path, &history);
+        }
+        drop(history);
+        let mut redo_history = self.redo_history.lock().unwrap();
+        if let Some(contents) = redo_history.remove(path) {
+            redo_history.insert(new_path.clone(), contents);
+        }
+        drop(redo_history);
+        let mut viewed_files = self.viewed_files.lock().unwrap();
+        if let Some(content) = viewed_files.remove(path) {
+            viewed_files.insert(new_path.clone(), content);
+        }
+        drop(viewed_files);
+        if self.unregister_resource(path) {
+            if let Ok(uri) = Url::from_file_path(new_path) {
+                self.register_as_resource(uri.as_str(), new_path);
+            }
+        }
+
+        Ok(vec![Content::text(format!(
+            "Successfully moved {} to {}",
+            path.display(),
+            new_path.display()
+        ))])
+    }
+
+    /// Fail clearly if a file that was viewed or edited has since been removed from disk
+    ///
+    /// The stale view and edit history for the file are forgotten, and it is no longer listed
+    /// as a resource, since they no longer describe anything that exists.
+    fn ensure_still_on_disk(&self, path: &PathBuf) -> Result<(), ToolError> {
+        if path.exists() {
+            return Ok(());
+        }
+
+        self.unregister_resource(path);
+        let was_viewed = self.viewed_files.lock().unwrap().remove(path).is_some();
+        let mut history = self.file_history.lock().unwrap();
+        let had_history = history.remove(path).is_some();
+        self.persist_history(path, &history);
+        drop(history);
+        self.redo_history.lock().unwrap().remove(path);
+
+        if was_viewed || had_history {
+            return Err(ToolError::InvalidParameters(format!(
+                "The file '{}' no longer exists on disk. It was deleted or moved after it was last viewed or edited, \
+                 list its directory to find it or use the `write` command to recreate it.",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Write a file for a text_editor edit
+    ///
+    /// The file's last viewed content follows the edit, so `changes_since_view` only reports
+    /// changes made outside of the text editor.
+    fn write_file(&self, path: &Path, content: &str) -> Result<(), ToolError> {
+        write_atomic(path, content)
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to write file: {}", e)))?;
+        if let Some(viewed) = self.viewed_files.lock().unwrap().get_mut(path) {
+            *viewed = content.to_string();
+        }
+        Ok(())
+    }
+
+    async fn changes_since_view(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let path_str = params
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'path' parameter".into()))?;
+        let path = self.resolve_path(path_str)?;
+        let _guards = self.lock_paths(&[&path]).await;
+
+        let viewed = self
+            .viewed_files
+            .lock()
+            .unwrap()
+            .get(&path)
+            .cloned()
+            .ok_or_else(|| {
+                ToolError::InvalidParameters(format!(
+                    "'{}' has not been viewed yet, use the text_editor `view` command first",
+                    path.display()
                 ))
             })?;
+        let current = std::fs::read_to_string(&path).map_err(|e| {
+            ToolError::ExecutionError(format!(
+                "Failed to read '{}', it may have been deleted since it was viewed: {}",
+                path.display(),
+                e
+            ))
+        })?;
 
-            monitor.capture_image().map_err(|e| {
-                ToolError::ExecutionError(format!("Failed to capture display {}: {}", display, e))
-            })?
+        let output = if current == viewed {
+            format!(
+                "{} has not changed since it was last viewed",
+                path.display()
+            )
+        } else {
+            TextDiff::from_lines(&viewed, &current)
+                .unified_diff()
+                .header(
+                    &format!("{} (last viewed)", path.display()),
+                    &format!("{} (on disk)", path.display()),
+                )
+                .to_string()
         };
 
-        // Resize the image to a reasonable width while maintaining aspect ratio
-        let max_width = 768;
-        if image.width() > max_width {
-            let scale = max_width as f32 / image.width() as f32;
-            let new_height = (image.height() as f32 * scale) as u32;
-            image = xcap::image::imageops::resize(
-                &image,
-                max_width,
-                new_height,
-                xcap::image::imageops::FilterType::Lanczos3,
-            )
+        Ok(vec![
+            Content::text(output.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(output)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
+    }
+
+    async fn rename_symbol(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let path_str = params
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'path' parameter".into()))?;
+        let old_name = params
+            .get("old_name")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'old_name' parameter".into()))?;
+        let new_name = params
+            .get("new_name")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'new_name' parameter".into()))?;
+        let path = self.resolve_path(path_str)?;
+        let _guards = self.lock_paths(&[&path]).await;
+        self.ensure_still_on_disk(&path)?;
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to read file: {}", e)))?;
+        let renamed = rename::rename_symbol(&path, &content, old_name, new_name)
+            .map_err(ToolError::InvalidParameters)?;
+
+        self.save_file_history(&path)?;
+        self.write_file(&path, &renamed.content)?;
+
+        let lines = renamed
+            .lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let message = format!(
+            "Renamed {} occurrence{} of `{}` to `{}` in {} on line{} {}",
+            renamed.occurrences,
+            if renamed.occurrences == 1 { "" } else { "s" },
+            old_name,
+            new_name,
+            path.display(),
+            if renamed.lines.len() == 1 { "" } else { "s" },
+            lines
+        );
+
+        Ok(vec![
+            Content::text(message.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(message)
+                .with_audience(vec![Role::User])
+                .with_priority(0.2),
+        ])
+    }
+
+    /// List a viewed file as a resource, or mark it as accessed again if it already is
+    fn register_as_resource(&self, uri: &str, path: &Path) {
+        let mut active_resources = self.active_resources.lock().unwrap();
+        if let Some(resource) = active_resources.get_mut(uri) {
+            resource.update_timestamp();
+            return;
+        }
+        match Resource::new(
+            uri,
+            Some("text".to_string()),
+            Some(path.display().to_string()),
+        ) {
+            Ok(resource) => {
+                active_resources.insert(uri.to_string(), resource);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to list {} as a resource: {}", uri, e);
+                return;
+            }
+        }
+        drop(active_resources);
+
+        if let Some(watcher) = &self.resource_watcher {
+            if let Err(e) = watcher.watch(path) {
+                tracing::warn!("{}", e);
+            }
+        }
+    }
+
+    /// Stop listing the file at `path` as a resource, returning whether it was listed
+    fn unregister_resource(&self, path: &Path) -> bool {
+        let Ok(uri) = Url::from_file_path(path) else {
+            return false;
         };
+        let was_listed = self
+            .active_resources
+            .lock()
+            .unwrap()
+            .remove(uri.as_str())
+            .is_some();
+        if was_listed {
+            if let Some(watcher) = &self.resource_watcher {
+                if let Err(e) = watcher.unwatch(path) {
+                    tracing::warn!("{}", e);
+                }
+            }
+        }
+        was_listed
+    }
 
-        let mut bytes: Vec<u8> = Vec::new();
-        image
-            .write_to(&mut Cursor::new(&mut bytes), xcap::image::ImageFormat::Png)
-            .map_err(|e| {
-                ToolError::ExecutionError(format!("Failed to write image buffer {}", e))
+    /// Read a viewed file resource from disk, given its `file://` URI
+    fn read_file_resource(&self, uri: &str) -> Result<String, ResourceError> {
+        if !self.active_resources.lock().unwrap().contains_key(uri) {
+            return Err(ResourceError::NotFound(format!(
+                "Resource not found: {}",
+                uri
+            )));
+        }
+        let path = Url::parse(uri)
+            .ok()
+            .and_then(|url| url.to_file_path().ok())
+            .ok_or_else(|| ResourceError::NotFound(format!("Invalid file URI: {}", uri)))?;
+        std::fs::read_to_string(&path).map_err(|e| {
+            ResourceError::ExecutionError(format!("Failed to read {}: {}", path.display(), e))
+        })
+    }
+
+    /// Read the working directory resource, given the encoded path from its URI
+    fn read_cwd_resource(&self, encoded: &str) -> Result<String, ResourceError> {
+        let path = Url::parse(&format!("file://{}", encoded))
+            .ok()
+            .and_then(|url| url.to_file_path().ok())
+            .ok_or_else(|| {
+                ResourceError::NotFound(format!("Invalid working directory URI: str://{}", encoded))
             })?;
+        let cwd = self
+            .working_directory()
+            .map_err(|e| ResourceError::ExecutionError(e.to_string()))?;
+        if self.cwd_resource == CwdResource::Off || path != cwd {
+            return Err(ResourceError::NotFound(format!(
+                "{} is not the working directory, which is now {}",
+                path.display(),
+                cwd.display()
+            )));
+        }
+        Ok(cwd.display().to_string())
+    }
 
-        // Convert to base64
-        let data = base64::prelude::BASE64_STANDARD.encode(bytes);
+    /// Read the content of a file to store in the edit history, empty if it doesn't exist yet
+    fn read_for_history(path: &PathBuf) -> Result<String, ToolError> {
+        if path.exists() {
+            std::fs::read_to_string(path)
+                .map_err(|e| ToolError::ExecutionError(format!("Failed to read file: {}", e)))
+        } else {
+            Ok(String::new())
+        }
+    }
+
+    fn save_file_history(&self, path: &PathBuf) -> Result<(), ToolError> {
+        let mut history = self.file_history.lock().unwrap();
+        let content = Self::read_for_history(path)?;
+        let snapshots = history.entry(path.clone()).or_default();
+        snapshots.push(content);
+        let excess = snapshots.len().saturating_sub(MAX_SNAPSHOTS_PER_FILE);
+        snapshots.drain(..excess);
+        self.persist_history(path, &history);
+
+        // A fresh edit starts a new branch of history, so undone edits can no longer be redone
+        self.redo_history.lock().unwrap().remove(path);
+        Ok(())
+    }
+
+    /// Write the undo history of `path` to disk when persistence is enabled
+    fn persist_history(&self, path: &Path, history: &HashMap<PathBuf, Vec<String>>) {
+        let Some(store) = &self.history_store else {
+            return;
+        };
+        let snapshots = history.get(path).map(Vec::as_slice).unwrap_or_default();
+        if let Err(e) = store.save(path, snapshots) {
+            tracing::warn!(
+                "Failed to persist edit history for {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+
+    /// The summary of a stack trace in shell output, with the full output saved to a file
+    fn summarize_trace_output(
+        &self,
+        trace: &stack_trace::TraceSummary,
+        output: &str,
+    ) -> Result<String, ToolError> {
+        let mut file = tempfile::Builder::new()
+            .prefix("goose-shell-output-")
+            .suffix(".log")
+            .tempfile()
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to save the output: {}", e)))?;
+        std::io::Write::write_all(&mut file, output.as_bytes())
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to save the output: {}", e)))?;
+        let (_, path) = file
+            .keep()
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to save the output: {}", e)))?;
+        Ok(format!(
+            "{}\n\nThe full output ({} bytes) is saved to {}",
+            trace.render(),
+            output.len(),
+            path.display()
+        ))
+    }
+
+    /// The directory to run a command in, the `working_dir` parameter if given
+    fn command_directory(&self, params: &Value) -> Result<PathBuf, ToolError> {
+        match params.get("working_dir").and_then(|v| v.as_str()) {
+            Some(dir) => {
+                let dir = self.resolve_path(dir)?;
+                if !dir.is_dir() {
+                    return Err(ToolError::InvalidParameters(format!(
+                        "The working_dir '{}' is not an existing directory",
+                        dir.display()
+                    )));
+                }
+                Ok(dir)
+            }
+            None => self.working_directory(),
+        }
+    }
+
+    async fn benchmark(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let command = params
+            .get("command")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'command' parameter".into()))?;
+        if let Some(error) = self.dangerous_commands.check(command) {
+            return Err(ToolError::InvalidParameters(error));
+        }
+        let runs = params
+            .get("runs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(benchmark::DEFAULT_RUNS);
+        if !(1..=benchmark::MAX_RUNS).contains(&runs) {
+            return Err(ToolError::InvalidParameters(format!(
+                "'runs' must be between 1 and {}",
+                benchmark::MAX_RUNS
+            )));
+        }
+        let timeout = Duration::from_secs(
+            params
+                .get("timeout_seconds")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(benchmark::DEFAULT_RUN_TIMEOUT_SECS),
+        );
+        let working_dir = self.command_directory(&params)?;
+
+        let deadline = Instant::now() + Duration::from_secs(benchmark::MAX_TOTAL_SECS);
+        let mut results = Vec::new();
+        for _ in 0..runs {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            let run = time_command(command, &working_dir, timeout.min(remaining))
+                .await
+                .map_err(ToolError::ExecutionError)?;
+            results.push(run);
+        }
 
+        let mut summary = summarize(command, &results);
+        if (results.len() as u64) < runs {
+            summary.push_str(&format!(
+                "\nStopped after {} of {} runs, a benchmark may take at most {} seconds",
+                results.len(),
+                runs,
+                benchmark::MAX_TOTAL_SECS
+            ));
+        }
         Ok(vec![
-            Content::text("Screenshot captured").with_audience(vec![Role::Assistant]),
-            Content::image(data, "image/png").with_priority(0.0),
+            Content::text(summary.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(summary)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
         ])
     }
-}
 
-impl Router for DeveloperRouter {
-    fn name(&self) -> String {
-        "developer".to_string()
+    async fn git_status(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let dir = match params.get("path").and_then(|v| v.as_str()) {
+            Some(path) => self.resolve_path(path)?,
+            None => self.working_directory()?,
+        };
+        let status = git_status(&dir).map_err(ToolError::ExecutionError)?;
+        let json = serde_json::to_string_pretty(&status).map_err(|e| {
+            ToolError::ExecutionError(format!("Failed to encode the status: {}", e))
+        })?;
+
+        let changes = if status.is_clean() {
+            "nothing to commit, working tree clean".to_string()
+        } else {
+            format!(
+                "{} staged, {} modified, {} untracked, {} deleted, {} conflicted",
+                status.staged.len(),
+                status.modified.len(),
+                status.untracked.len(),
+                status.deleted.len(),
+                status.conflicted.len()
+            )
+        };
+        let summary = format!(
+            "On {}{}: {}",
+            status.branch.as_deref().unwrap_or("a detached HEAD"),
+            match &status.upstream {
+                Some(upstream) => format!(
+                    " ({} ahead, {} behind {})",
+                    status.ahead, status.behind, upstream
+                ),
+                None => String::new(),
+            },
+            changes
+        );
+        Ok(vec![
+            Content::text(json).with_audience(vec![Role::Assistant]),
+            Content::text(summary)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
     }
 
-    fn instructions(&self) -> String {
-        self.instructions.clone()
+    async fn list_processes(&self) -> Result<Vec<Content>, ToolError> {
+        let processes = self.process_store.running();
+        let output = if processes.is_empty() {
+            "No background processes are running".to_string()
+        } else {
+            processes
+                .iter()
+                .map(|p| {
+                    format!(
+                        "PID {}: `{}` started at {}",
+                        p.pid,
+                        p.command,
+                        p.started_at.format("%Y-%m-%d %H:%M:%S UTC")
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        Ok(vec![
+            Content::text(output.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(output)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
     }
 
-    fn capabilities(&self) -> ServerCapabilities {
-        CapabilitiesBuilder::new().with_tools(false).build()
+    async fn kill_process(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let pid = params
+            .get("pid")
+            .and_then(|v| v.as_u64())
+            .and_then(|pid| u32::try_from(pid).ok())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'pid' parameter".into()))?;
+
+        let (process, stopped) = self
+            .process_store
+            .kill(pid, KILL_GRACE_PERIOD)
+            .await
+            .map_err(ToolError::ExecutionError)?;
+        let how = match stopped {
+            Stopped::Terminated => "stopped",
+            Stopped::Killed => "killed after it ignored SIGTERM",
+        };
+        let message = format!("PID {} (`{}`) was {}", process.pid, process.command, how);
+        Ok(vec![
+            Content::text(message.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(message)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
     }
 
-    fn list_tools(&self) -> Vec<Tool> {
-        self.tools.clone()
+    async fn list_prompts_tool(&self) -> Result<Vec<Content>, ToolError> {
+        let listing = self.prompts.describe();
+        Ok(vec![
+            Content::text(listing.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(listing)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
     }
 
-    fn call_tool(
-        &self,
-        tool_name: &str,
-        arguments: Value,
-    ) -> Pin<Box<dyn Future<Output = Result<Vec<Content>, ToolError>> + Send + 'static>> {
-        let this = self.clone();
-        let tool_name = tool_name.to_string();
-        Box::pin(async move {
-            match tool_name.as_str() {
-                "shell" => this.bash(arguments).await,
-                "text_editor" => this.text_editor(arguments).await,
-                "list_windows" => this.list_windows(arguments).await,
-                "screen_capture" => this.screen_capture(arguments).await,
-                _ => Err(ToolError::NotFound(format!("Tool {} not found", tool_name))),
+    async fn get_prompt_tool(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let name = params
+            .get("name")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'name' parameter".into()))?;
+        let arguments = match params.get("arguments") {
+            None | Some(Value::Null) => serde_json::Map::new(),
+            Some(Value::Object(arguments)) => arguments.clone(),
+            Some(_) => {
+                return Err(ToolError::InvalidParameters(
+                    "'arguments' must be an object".into(),
+                ))
             }
-        })
+        };
+        let prompt = self
+            .prompts
+            .render(name, &arguments)
+            .map_err(ToolError::InvalidParameters)?;
+        Ok(vec![
+            Content::text(prompt.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(prompt)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
+    }
+
+    async fn read_scratchpad(&self) -> Result<Vec<Content>, ToolError> {
+        let scratchpad = self.scratchpad.lock().unwrap().clone();
+        let output = if scratchpad.is_empty() {
+            "The scratchpad is empty".to_string()
+        } else {
+            scratchpad
+        };
+        Ok(vec![
+            Content::text(output.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(output)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
+    }
+
+    async fn write_scratchpad(
+        &self,
+        params: Value,
+        append: bool,
+    ) -> Result<Vec<Content>, ToolError> {
+        let content = params
+            .get("content")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'content' parameter".into()))?;
+
+        let mut scratchpad = self.scratchpad.lock().unwrap();
+        if append && !scratchpad.is_empty() {
+            scratchpad.push('\n');
+        } else if !append {
+            scratchpad.clear();
+        }
+        scratchpad.push_str(content);
+
+        let message = format!(
+            "The scratchpad now has {} characters",
+            scratchpad.chars().count()
+        );
+        Ok(vec![
+            Content::text(message.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(message)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
     }
 
-    // TODO see if we can make it easy to skip implementing these
-    fn list_resources(&self) -> Vec<Resource> {
-        Vec::new()
-    }
+    async fn list_windows(&self, _params: Value) -> Result<Vec<Content>, ToolError> {
+        let windows = Window::all()
+            .map_err(|_| ToolError::ExecutionError("Failed to list windows".into()))?;
+
+        let window_titles: Vec<String> =
+            windows.into_iter().map(|w| w.title().to_string()).collect();
+
+        Ok(vec![
+            Content::text(format!("Available windows:\n{}", window_titles.join("\n")))
+                .with_audience(vec![Role::Assistant]),
+            Content::text(format!("Available windows:\n{}", window_titles.join("\n")))
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ])
+    }
+
+    async fn screen_capture(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let mut options = EncodeOptions::default();
+        if let Some(name) = params.get("format").and_then(|v| v.as_str()) {
+            options.format = ScreenshotFormat::from_name(name).ok_or_else(|| {
+                ToolError::InvalidParameters(format!(
+                    "Unsupported format '{}', expected one of png, jpeg or webp",
+                    name
+                ))
+            })?;
+        }
+        if let Some(max_width) = params.get("max_width").and_then(|v| v.as_u64()) {
+            if max_width == 0 {
+                return Err(ToolError::InvalidParameters(
+                    "max_width must be greater than 0".into(),
+                ));
+            }
+            options.max_width = max_width.min(u32::MAX as u64) as u32;
+        }
+        if let Some(quality) = params.get("quality").and_then(|v| v.as_u64()) {
+            if !(1..=100).contains(&quality) {
+                return Err(ToolError::InvalidParameters(
+                    "quality must be between 1 and 100".into(),
+                ));
+            }
+            options.quality = quality as u8;
+        }
+        let region = params
+            .get("region")
+            .filter(|v| !v.is_null())
+            .map(|v| serde_json::from_value::<Region>(v.clone()))
+            .transpose()
+            .map_err(|e| {
+                ToolError::InvalidParameters(format!(
+                    "region must be an object with integer x, y, width and height: {}",
+                    e
+                ))
+            })?;
+
+        let image = if let Some(window_title) = params.get("window_title").and_then(|v| v.as_str())
+        {
+            // Try to find and capture the specified window
+            let windows = Window::all()
+                .map_err(|_| ToolError::ExecutionError("Failed to list windows".into()))?;
+
+            let window = windows
+                .into_iter()
+                .find(|w| w.title() == window_title)
+                .ok_or_else(|| {
+                    ToolError::ExecutionError(format!(
+                        "No window found with title '{}'",
+                        window_title
+                    ))
+                })?;
+
+            window.capture_image().map_err(|e| {
+                ToolError::ExecutionError(format!(
+                    "Failed to capture window '{}': {}",
+                    window_title, e
+                ))
+            })?
+        } else {
+            // Default to display capture if no window title is specified
+            let display = params.get("display").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+
+            let monitors = Monitor::all()
+                .map_err(|_| ToolError::ExecutionError("Failed to access monitors".into()))?;
+            let monitor = monitors.get(display).ok_or_else(|| {
+                ToolError::ExecutionError(format!(
+                    "{} was not an available monitor, {} found.",
+                    display,
+                    monitors.len()
+                ))
+            })?;
+
+            monitor.capture_image().map_err(|e| {
+                ToolError::ExecutionError(format!("Failed to capture display {}: {}", display, e))
+            })?
+        };
+
+        let image = match region {
+            Some(region) => crop_region(image, &region)?,
+            None => image,
+        };
+        let bytes = encode_screenshot(image, &options)?;
+
+        // Convert to base64
+        let data = base64::prelude::BASE64_STANDARD.encode(bytes);
+
+        Ok(vec![
+            Content::text("Screenshot captured").with_audience(vec![Role::Assistant]),
+            Content::image(data, options.format.mime_type()).with_priority(0.0),
+        ])
+    }
+}
+
+impl Router for DeveloperRouter {
+    fn name(&self) -> String {
+        "developer".to_string()
+    }
+
+    fn instructions(&self) -> String {
+        self.instructions.clone()
+    }
+
+    fn capabilities(&self) -> ServerCapabilities {
+        // A capability is advertised by being present. Its flags promise notifications, which
+        // this server never sends, so they stay false even though the listed resources change
+        // as files are viewed and the working directory moves
+        let mut builder = CapabilitiesBuilder::new().with_resources(false, false);
+        if !self.tools.is_empty() {
+            builder = builder.with_tools(false);
+        }
+        if self
+            .list_prompts()
+            .is_some_and(|prompts| !prompts.is_empty())
+        {
+            builder = builder.with_prompts(false);
+        }
+        builder.build()
+    }
+
+    fn list_tools(&self) -> Vec<Tool> {
+        self.tools.clone()
+    }
+
+    fn call_tool(
+        &self,
+        tool_name: &str,
+        arguments: Value,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<Content>, ToolError>> + Send + 'static>> {
+        let this = self.clone();
+        let tool_name = tool_name.to_string();
+        Box::pin(async move {
+            match tool_name.as_str() {
+                "shell" => this.bash(arguments).await,
+                "text_editor" => this.text_editor(arguments).await,
+                "text_search" => this.text_search(arguments).await,
+                "validate_format" => this.validate_format(arguments).await,
+                "read_test_report" => this.read_test_report(arguments).await,
+                "changes_since_view" => this.changes_since_view(arguments).await,
+                "rename_symbol" => this.rename_symbol(arguments).await,
+                "benchmark" => this.benchmark(arguments).await,
+                "list_processes" => this.list_processes().await,
+                "kill_process" => this.kill_process(arguments).await,
+                "git_status" => this.git_status(arguments).await,
+                "read_scratchpad" => this.read_scratchpad().await,
+                "write_scratchpad" => this.write_scratchpad(arguments, false).await,
+                "append_scratchpad" => this.write_scratchpad(arguments, true).await,
+                "list_prompts" => this.list_prompts_tool().await,
+                "get_prompt" => this.get_prompt_tool(arguments).await,
+                "list_windows" => this.list_windows(arguments).await,
+                "screen_capture" => this.screen_capture(arguments).await,
+                _ => Err(ToolError::NotFound(format!("Tool {} not found", tool_name))),
+            }
+        })
+    }
+
+    fn list_prompts(&self) -> Option<Vec<Prompt>> {
+        Some(self.prompts.prompts())
+    }
+
+    fn get_prompt(
+        &self,
+        prompt_name: &str,
+        arguments: &serde_json::Map<String, Value>,
+    ) -> Option<Pin<Box<dyn Future<Output = Result<String, PromptError>> + Send + 'static>>> {
+        let prompt = if self.prompts.has(prompt_name) {
+            self.prompts
+                .render(prompt_name, arguments)
+                .map_err(PromptError::InvalidParameters)
+        } else {
+            Err(PromptError::NotFound(format!("Prompt '{}'", prompt_name)))
+        };
+        Some(Box::pin(async move { prompt }))
+    }
+
+    fn list_resources(&self) -> Vec<Resource> {
+        let mut resources = Vec::new();
+
+        // The working directory is listed under its current URI, so it never goes stale
+        let priority = match self.cwd_resource {
+            CwdResource::Pinned => Some(1.0),
+            CwdResource::Listed => Some(0.0),
+            CwdResource::Off => None,
+        };
+        let uri = self.working_directory().ok().and_then(|cwd| cwd_uri(&cwd));
+        if let (Some(priority), Some(uri)) = (priority, uri) {
+            if let Ok(resource) = Resource::with_uri(uri, "cwd".to_string(), priority, None) {
+                resources.push(
+                    resource
+                        .with_description(
+                            "The working directory for shell commands and relative paths",
+                        )
+                        .with_pinned(self.cwd_resource == CwdResource::Pinned),
+                );
+            }
+        }
+
+        // The scratchpad is only pinned into the context once there is something in it
+        if !self.scratchpad.lock().unwrap().is_empty() {
+            if let Ok(resource) =
+                Resource::with_uri(SCRATCHPAD_URI, "scratchpad", 1.0, Some("text".to_string()))
+            {
+                resources.push(resource.with_description("Your running notes for this session"));
+            }
+        }
+
+        // Viewed files are listed but not active, so they are only read when asked for
+        let mut files: Vec<Resource> = self
+            .active_resources
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        files.sort_by(|a, b| a.uri.cmp(&b.uri));
+        resources.extend(files);
+        resources
+    }
+
+    fn read_resource(
+        &self,
+        uri: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<String, ResourceError>> + Send + 'static>> {
+        let content = if uri == SCRATCHPAD_URI {
+            Ok(self.scratchpad.lock().unwrap().clone())
+        } else if let Some(encoded) = uri.strip_prefix("str://") {
+            self.read_cwd_resource(encoded)
+        } else if uri.starts_with("file://") {
+            self.read_file_resource(uri)
+        } else {
+            Err(ResourceError::NotFound(format!(
+                "Resource not found: {}",
+                uri
+            )))
+        };
+        Box::pin(async move { content })
+    }
+}
+
+impl Clone for DeveloperRouter {
+    fn clone(&self) -> Self {
+        Self {
+            tools: self.tools.clone(),
+            file_history: Arc::clone(&self.file_history),
+            redo_history: Arc::clone(&self.redo_history),
+            viewed_files: Arc::clone(&self.viewed_files),
+            active_resources: Arc::clone(&self.active_resources),
+            resource_watcher: self.resource_watcher.clone(),
+            scratchpad: Arc::clone(&self.scratchpad),
+            cwd_resource: self.cwd_resource,
+            file_locks: Arc::clone(&self.file_locks),
+            working_dir: Arc::clone(&self.working_dir),
+            process_store: self.process_store.clone(),
+            generated_files: self.generated_files.clone(),
+            dangerous_commands: self.dangerous_commands.clone(),
+            explain_commands: self.explain_commands,
+            view_limits: self.view_limits,
+            prompts: Arc::clone(&self.prompts),
+            history_store: self.history_store.clone(),
+            instructions: self.instructions.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::resource::ResourceContents;
+    use serde_json::json;
+    use serial_test::serial;
+    use std::fs;
+    use tempfile::TempDir;
+    use tokio::sync::OnceCell;
+
+    #[test]
+    #[serial]
+    fn test_goosehints_when_present() {
+        let dir = TempDir::new().unwrap();
+        std::env::set_current_dir(dir.path()).unwrap();
+
+        fs::write(".goosehints", "Test hint content").unwrap();
+        let router = DeveloperRouter::new();
+        let instructions = router.instructions();
+
+        assert!(instructions.contains("Test hint content"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_goosehints_when_missing() {
+        let dir = TempDir::new().unwrap();
+        std::env::set_current_dir(dir.path()).unwrap();
+
+        let router = DeveloperRouter::new();
+        let instructions = router.instructions();
+
+        assert!(!instructions.contains("Project Hints"));
+    }
+
+    static DEV_ROUTER: OnceCell<DeveloperRouter> = OnceCell::const_new();
+
+    async fn get_router() -> &'static DeveloperRouter {
+        DEV_ROUTER
+            .get_or_init(|| async { DeveloperRouter::new() })
+            .await
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_missing_parameters() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        let router = get_router().await;
+        let result = router.call_tool("shell", json!({})).await;
+
+        assert!(result.is_err());
+        let err = result.err().unwrap();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_timeout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        let router = get_router().await;
+        let result = router
+            .call_tool(
+                "shell",
+                json!({
+                    "command": "echo started && sleep 10",
+                    "timeout_seconds": 1
+                }),
+            )
+            .await;
+
+        let err = result.err().unwrap();
+        assert!(matches!(err, ToolError::ExecutionError(_)));
+        assert!(err.to_string().contains("timed out after 1 seconds"));
+        assert!(err.to_string().contains("started"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_write_caps_echoed_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("big.txt");
+        let file_text: String = (1..=1000).map(|i| format!("line {}\n", i)).collect();
+
+        let router = get_router().await;
+        let result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "write",
+                    "path": file_path.to_str().unwrap(),
+                    "file_text": file_text
+                }),
+            )
+            .await
+            .unwrap();
+
+        // The file gets everything while the user sees the ends of it
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), file_text);
+        let user_text = result[1].as_text().unwrap();
+        assert!(user_text.contains("line 1\n"));
+        assert!(user_text.contains("line 50\n... [900 lines not shown, see "));
+        assert!(user_text.contains("] ...\nline 951\n"));
+        assert!(user_text.contains("line 1000\n"));
+        assert!(!user_text.contains("line 500\n"));
+        assert!(user_text.len() < file_text.len() / 5);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_write_follows_editorconfig() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        fs::write(
+            temp_dir.path().join(".editorconfig"),
+            "root = true\n\n[*.go]\nindent_style = tab\nindent_size = 4\ninsert_final_newline = true\n",
+        )
+        .unwrap();
+
+        let router = get_router().await;
+        let go_path = temp_dir.path().join("main.go");
+        let result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "write",
+                    "path": go_path.to_str().unwrap(),
+                    "file_text": "func main() {\n    if ok {\n        run()\n    }\n}"
+                }),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&go_path).unwrap(),
+            "func main() {\n\tif ok {\n\t\trun()\n\t}\n}\n"
+        );
+        assert!(result[0].as_text().unwrap().contains(".editorconfig"));
+
+        // Files the config does not match are written as given
+        let txt_path = temp_dir.path().join("notes.txt");
+        router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "write",
+                    "path": txt_path.to_str().unwrap(),
+                    "file_text": "notes\n    indented"
+                }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&txt_path).unwrap(),
+            "notes\n    indented"
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_working_dir() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let temp_path = temp_dir.path().canonicalize().unwrap();
+        let sub_dir = temp_path.join("nested");
+        fs::create_dir(&sub_dir).unwrap();
+
+        let router = DeveloperRouter::new();
+        let pwd = |params: Value| async {
+            let result = router.call_tool("shell", params).await?;
+            Ok::<_, ToolError>(PathBuf::from(
+                result[0].as_text().unwrap().lines().next().unwrap(),
+            ))
+        };
+
+        assert_eq!(pwd(json!({"command": "pwd"})).await.unwrap(), temp_path);
+        assert_eq!(
+            pwd(json!({"command": "pwd", "working_dir": sub_dir}))
+                .await
+                .unwrap(),
+            sub_dir
+        );
+
+        // The directory only applies to that command
+        assert_eq!(pwd(json!({"command": "pwd"})).await.unwrap(), temp_path);
+
+        let missing = temp_path.join("missing");
+        let err = pwd(json!({"command": "pwd", "working_dir": missing}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_benchmark_reports_stats() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let router = get_router().await;
+
+        let result = router
+            .call_tool("benchmark", json!({"command": "true", "runs": 3}))
+            .await
+            .unwrap();
+        let summary = result[0].as_text().unwrap();
+        assert!(summary.starts_with("Ran `true` 3 times"));
+        for stat in ["min: ", "median: ", "mean: ", "max: "] {
+            assert!(summary.contains(stat), "missing {} in {}", stat, summary);
+        }
+        assert!(summary.contains("exit statuses: 0, 0, 0"));
+        assert!(!summary.contains("failed"));
+
+        let err = router
+            .call_tool("benchmark", json!({"command": "true", "runs": 0}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+
+        let err = router
+            .call_tool("benchmark", json!({"command": "rm -rf /", "runs": 1}))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ToolError::InvalidParameters(msg) if msg.contains("recursive rm")),
+            "{:?}",
+            err
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_runs_in_background() {
+        assert!(runs_in_background("uvicorn main:app > server.log 2>&1 &"));
+        assert!(runs_in_background("npm start & sleep 1"));
+        assert!(!runs_in_background("cargo build && cargo test"));
+        assert!(!runs_in_background("make 2>&1 | tail"));
+        assert!(!runs_in_background("make &> build.log"));
+        assert!(!runs_in_background("echo 'a & b' \\& done"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_list_and_kill_background_process() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let router = DeveloperRouter::new();
+        let list = || async {
+            let result = router.call_tool("list_processes", json!({})).await.unwrap();
+            result[0].as_text().unwrap().to_string()
+        };
+
+        // Commands that don't background anything are not listed
+        router
+            .call_tool("shell", json!({"command": "echo done && echo 2>&1"}))
+            .await
+            .unwrap();
+        assert_eq!(list().await, "No background processes are running");
+
+        let command = "sleep 30 > /dev/null 2>&1 &";
+        router
+            .call_tool("shell", json!({"command": command}))
+            .await
+            .unwrap();
+        let listed = list().await;
+        assert!(listed.contains(&format!("`{}` started at", command)));
+        let pid: u64 = listed
+            .trim_start_matches("PID ")
+            .split(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let result = router
+            .call_tool("kill_process", json!({"pid": pid}))
+            .await
+            .unwrap();
+        assert!(result[0].as_text().unwrap().contains("was stopped"));
+        assert_eq!(list().await, "No background processes are running");
+
+        let err = router
+            .call_tool("kill_process", json!({"pid": pid}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::ExecutionError(_)));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_refuses_dangerous_commands() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let router = DeveloperRouter::new();
+
+        let err = router
+            .call_tool("shell", json!({"command": " rm -rf / "}))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ToolError::InvalidParameters(msg) if msg.contains("recursive rm")),
+            "{:?}",
+            err
+        );
+
+        fs::create_dir(temp_dir.path().join("build")).unwrap();
+        router
+            .call_tool("shell", json!({"command": "rm -rf ./build"}))
+            .await
+            .unwrap();
+        assert!(!temp_dir.path().join("build").exists());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_requires_explanation_when_enabled() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        std::env::set_var(EXPLAIN_COMMANDS_ENV, "true");
+        let router = DeveloperRouter::new();
+        std::env::remove_var(EXPLAIN_COMMANDS_ENV);
+
+        let shell = router
+            .list_tools()
+            .into_iter()
+            .find(|tool| tool.name == "shell")
+            .unwrap();
+        assert_eq!(
+            shell.input_schema["required"],
+            json!(["command", "explanation"])
+        );
+
+        for params in [
+            json!({"command": "ls"}),
+            json!({"command": "ls", "explanation": "  "}),
+        ] {
+            let err = router.call_tool("shell", params).await.unwrap_err();
+            assert!(
+                matches!(&err, ToolError::InvalidParameters(msg) if msg.contains("explanation")),
+                "{:?}",
+                err
+            );
+        }
+        router
+            .call_tool(
+                "shell",
+                json!({"command": "ls", "explanation": "List the files in the project"}),
+            )
+            .await
+            .unwrap();
+
+        // Off by default, where the explanation is optional
+        let router = DeveloperRouter::new();
+        router
+            .call_tool("shell", json!({"command": "ls"}))
+            .await
+            .unwrap();
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_summarizes_python_traceback() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let router = DeveloperRouter::new();
+        let app = temp_dir.path().join("app.py");
+        fs::write(
+            temp_dir.path().join("run.log"),
+            format!(
+                "starting\nTraceback (most recent call last):\n  File \"{0}\", line 5, in <module>\n    load({{}})\n  File \"{0}\", line 2, in load\n    return config['port']\nKeyError: 'port'\n",
+                app.display()
+            ),
+        )
+        .unwrap();
+
+        let result = router
+            .call_tool(
+                "shell",
+                json!({"command": "cat run.log; exit 1", "summarize_traces": true}),
+            )
+            .await
+            .unwrap();
+        let output = result[0].as_text().unwrap();
+        assert!(
+            output.starts_with("Python traceback: KeyError: 'port'\n  at load ("),
+            "{}",
+            output
+        );
+        assert!(output.contains("app.py:2)"));
+        assert!(!output.contains("starting"));
+
+        // The full output is kept in a file
+        let saved = output
+            .lines()
+            .find_map(|line| line.strip_prefix("The full output ("))
+            .and_then(|line| line.split_once(" is saved to "))
+            .map(|(_, path)| PathBuf::from(path))
+            .unwrap();
+        let full = fs::read_to_string(&saved).unwrap();
+        assert!(full.starts_with("starting\nTraceback"));
+        fs::remove_file(saved).unwrap();
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_stdin() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let router = get_router().await;
+
+        let result = router
+            .call_tool(
+                "shell",
+                json!({
+                    "command": "read -p 'Continue? ' answer && echo \"answer=$answer\" && head -n 1",
+                    "stdin": "yes\nfirst\nsecond\n"
+                }),
+            )
+            .await
+            .unwrap();
+        let output = result[0].as_text().unwrap();
+        assert!(output.contains("answer=yes\nfirst\n"), "{}", output);
+        assert!(!output.contains("second"));
+
+        // Without input, commands that read it see it end instead of waiting
+        let result = router
+            .call_tool(
+                "shell",
+                json!({"command": "head -n 1; echo end", "timeout_seconds": 5}),
+            )
+            .await
+            .unwrap();
+        assert!(result[0].as_text().unwrap().starts_with("end\n"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_reports_exit_status() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let router = get_router().await;
+        let run = |command: &'static str| async move {
+            router
+                .call_tool("shell", json!({"command": command}))
+                .await
+                .unwrap()
+        };
+
+        let result = run("echo hello").await;
+        assert_eq!(
+            result[0].as_text().unwrap(),
+            "hello\n\nThe command finished with status code 0"
+        );
+        assert_eq!(result[1].as_text().unwrap(), "hello\n");
+
+        let result = run("false").await;
+        assert!(result[0]
+            .as_text()
+            .unwrap()
+            .ends_with("The command failed with status code 1"));
+        assert!(result[1]
+            .as_text()
+            .unwrap()
+            .contains("failed with status code 1"));
+
+        let result = run("echo partial; exit 3").await;
+        let assistant_text = result[0].as_text().unwrap();
+        assert!(assistant_text.starts_with("partial\n"));
+        assert!(assistant_text.ends_with("The command failed with status code 3"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_truncate_output_boundaries() {
+        let output = "a".repeat(100);
+        assert_eq!(truncate_output(&output, 100), (output.clone(), 0));
+
+        let output = format!("{}{}", "a".repeat(60), "b".repeat(60));
+        let (truncated, removed) = truncate_output(&output, 100);
+        assert_eq!(removed, 20);
+        assert_eq!(
+            truncated,
+            format!(
+                "{}\n... [20 bytes truncated] ...\n{}",
+                "a".repeat(50),
+                "b".repeat(50)
+            )
+        );
+    }
+
+    #[test]
+    fn test_truncate_output_multibyte() {
+        // Each char is 3 bytes so the cut points fall inside characters
+        let output = "€".repeat(50);
+        let (truncated, removed) = truncate_output(&output, 100);
+        let (head, rest) = truncated.split_once("\n... [").unwrap();
+        let (_, tail) = rest.split_once("] ...\n").unwrap();
+        assert_eq!(head, "€".repeat(16));
+        assert_eq!(tail, "€".repeat(16));
+        assert_eq!(removed, 150 - 96);
+        assert!(truncated.contains(&format!("[{} bytes truncated]", removed)));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_output_truncation() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        let router = get_router().await;
+        let result = router
+            .call_tool(
+                "shell",
+                json!({
+                    "command": "seq 1 10000",
+                    "max_output_bytes": 1000
+                }),
+            )
+            .await
+            .unwrap();
+
+        let assistant_text = result[0].as_text().unwrap();
+        assert!(assistant_text.starts_with("1\n2\n3\n"));
+        assert!(assistant_text.contains("bytes truncated] ..."));
+        assert!(assistant_text.contains("10000\n"));
+        assert!(assistant_text.contains("has been truncated to 1000 bytes"));
+        assert!(assistant_text.contains("redirecting the output to a file"));
+
+        // The user facing output keeps the full output since it is under the user cap
+        let user_text = result[1].as_text().unwrap();
+        assert!(!user_text.contains("bytes truncated"));
+        assert!(user_text.ends_with("9999\n10000\n"));
+
+        temp_dir.close().unwrap();
+    }
+
+    fn set_up_search_tree(root: &Path) {
+        fs::create_dir_all(root.join("src/generated")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(root.join("src/.gitignore"), "generated/\n").unwrap();
+        fs::write(
+            root.join("src/lib.rs"),
+            "fn helper() {}\nfn search_me() {}\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/generated/out.rs"), "fn search_me() {}\n").unwrap();
+        fs::write(root.join("docs/notes.md"), "call search_me() first\n").unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("target/build.rs"), "fn search_me() {}\n").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_search_respects_gitignore() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        set_up_search_tree(&root);
+
+        let router = get_router().await;
+        let result = router
+            .call_tool(
+                "text_search",
+                json!({
+                    "pattern": r"search_me\(\)",
+                    "path": root.to_str().unwrap()
+                }),
+            )
+            .await
+            .unwrap();
+
+        let text = result[0].as_text().unwrap();
+        let mut lines: Vec<&str> = text.lines().collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                format!(
+                    "{}:1:call search_me() first",
+                    root.join("docs/notes.md").display()
+                ),
+                format!(
+                    "{}:2:fn search_me() {{}}",
+                    root.join("src/lib.rs").display()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_search_glob_and_max_results() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        set_up_search_tree(&root);
+
+        let router = get_router().await;
+        let result = router
+            .call_tool(
+                "text_search",
+                json!({
+                    "pattern": "fn ",
+                    "path": root.to_str().unwrap(),
+                    "glob": "*.rs",
+                    "max_results": 1
+                }),
+            )
+            .await
+            .unwrap();
+
+        let text = result[0].as_text().unwrap();
+        assert!(text.starts_with(&format!(
+            "{}:1:fn helper() {{}}",
+            root.join("src/lib.rs").display()
+        )));
+        assert!(text.contains("Showing the first 1 matches"));
+        assert!(!text.contains("notes.md"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_router_with_cwd() {
+        let process_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&process_dir).unwrap();
+        let project = tempfile::tempdir().unwrap();
+        fs::write(
+            project.path().join(".goosehints"),
+            "Run make before committing",
+        )
+        .unwrap();
+        let router = DeveloperRouter::with_cwd(project.path().to_path_buf());
+
+        assert_eq!(router.working_directory().unwrap(), project.path());
+        assert_eq!(
+            router.resolve_path("src/main.rs").unwrap(),
+            project.path().join("src/main.rs")
+        );
+        let instructions = router.instructions();
+        assert!(instructions.contains(&format!("current directory: {}", project.path().display())));
+        assert!(instructions.contains("Run make before committing"));
+        assert_eq!(
+            router.list_resources()[0].uri,
+            cwd_uri(project.path()).unwrap()
+        );
+
+        // Changing the process directory doesn't move the router
+        let elsewhere = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&elsewhere).unwrap();
+        assert_eq!(router.working_directory().unwrap(), project.path());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_resolve_path_against_working_directory() {
+        let process_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&process_dir).unwrap();
+        fs::write(process_dir.path().join("only_here.txt"), "").unwrap();
+        let router = DeveloperRouter::new();
+
+        // Relative paths follow the process directory until the working directory is set
+        let process_dir = process_dir.path().canonicalize().unwrap();
+        assert_eq!(
+            router.resolve_path("src/main.rs").unwrap(),
+            process_dir.join("src/main.rs")
+        );
+
+        let project = tempfile::tempdir().unwrap();
+        fs::create_dir(project.path().join("src")).unwrap();
+        router.set_working_directory(project.path()).unwrap();
+        assert_eq!(
+            router.resolve_path("src/main.rs").unwrap(),
+            project.path().join("src/main.rs")
+        );
+        assert_eq!(
+            router.resolve_path("/etc/hosts").unwrap(),
+            PathBuf::from("/etc/hosts")
+        );
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(
+            router.resolve_path("~/notes.md").unwrap(),
+            home.join("notes.md")
+        );
+
+        // A path that only exists relative to the process directory is flagged
+        let err = router.resolve_path("only_here.txt").unwrap_err();
+        assert!(err.to_string().contains(&format!(
+            "did you possibly mean {}",
+            process_dir.join("only_here.txt").display()
+        )));
+
+        let err = router.set_working_directory("missing").unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_removed_working_directory_is_an_error() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let router = DeveloperRouter::new();
+        temp_dir.close().unwrap();
+
+        let err = router
+            .call_tool("shell", json!({"command": "ls"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::ExecutionError(_)));
+        assert!(router.resolve_path("file.txt").is_err());
+
+        // A router can still be created, it just can't tell the model where it is
+        let router = DeveloperRouter::new();
+        assert!(router.instructions().contains("current directory: unknown"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_cd_changes_working_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(temp_dir.path().join("sub dir")).unwrap();
+        let router = DeveloperRouter::new();
+
+        let pwd = || async {
+            let result = router
+                .call_tool("shell", json!({"command": "pwd"}))
+                .await
+                .unwrap();
+            PathBuf::from(result[0].as_text().unwrap().lines().next().unwrap())
+        };
+        let temp_path = temp_dir.path().canonicalize().unwrap();
+        assert_eq!(pwd().await, temp_path);
+
+        // A cd combined with other commands does not last
+        router
+            .call_tool("shell", json!({"command": "cd 'sub dir' && ls"}))
+            .await
+            .unwrap();
+        assert_eq!(pwd().await, temp_path);
+
+        let result = router
+            .call_tool("shell", json!({"command": "cd 'sub dir'"}))
+            .await
+            .unwrap();
+        assert!(result[0]
+            .as_text()
+            .unwrap()
+            .starts_with("Changed the working directory"));
+        assert_eq!(pwd().await, temp_path.join("sub dir"));
+        assert_eq!(
+            router.resolve_path("file.txt").unwrap(),
+            temp_path.join("sub dir/file.txt")
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_search_invalid_parameters() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        let router = get_router().await;
+
+        let err = router
+            .call_tool("text_search", json!({"pattern": "("}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+
+        let err = router
+            .call_tool(
+                "text_search",
+                json!({"pattern": "fn", "path": "relative/path"}),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_validate_format_json() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        let good = temp_dir.path().join("good.json");
+        fs::write(&good, r#"{"tags":["a","b"],"name":"goose"}"#).unwrap();
+        let bad = temp_dir.path().join("bad.json");
+        fs::write(
+            &bad,
+            "{\n  \"name\": \"goose\",\n  \"tags\": [\"a\" \"b\"]\n}\n",
+        )
+        .unwrap();
+
+        let router = get_router().await;
+        let result = router
+            .call_tool("validate_format", json!({"path": good.to_str().unwrap()}))
+            .await
+            .unwrap();
+        assert!(result[0].as_text().unwrap().ends_with("is valid JSON"));
+
+        let err = router
+            .call_tool("validate_format", json!({"path": bad.to_str().unwrap()}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::ExecutionError(_)));
+        assert!(err.to_string().contains("Invalid JSON"));
+        assert!(err.to_string().contains("line 3, column 16"));
+
+        // Rewriting pretty prints the file, keeping its key order, and can be undone
+        router
+            .call_tool(
+                "validate_format",
+                json!({"path": good.to_str().unwrap(), "rewrite": true}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&good).unwrap(),
+            "{\n  \"tags\": [\n    \"a\",\n    \"b\"\n  ],\n  \"name\": \"goose\"\n}\n"
+        );
+        router
+            .call_tool(
+                "text_editor",
+                json!({"command": "undo_edit", "path": good.to_str().unwrap()}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&good).unwrap(),
+            r#"{"tags":["a","b"],"name":"goose"}"#
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_validate_format_requires_known_format() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        let config = temp_dir.path().join("config.conf");
+        fs::write(&config, "# settings\nkey: value\n").unwrap();
+
+        let router = get_router().await;
+        let err = router
+            .call_tool("validate_format", json!({"path": config.to_str().unwrap()}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+
+        let result = router
+            .call_tool(
+                "validate_format",
+                json!({"path": config.to_str().unwrap(), "format": "yaml"}),
+            )
+            .await
+            .unwrap();
+        assert!(result[0].as_text().unwrap().ends_with("is valid YAML"));
+
+        // Rewriting would drop the comment, so only JSON files are rewritten
+        let err = router
+            .call_tool(
+                "validate_format",
+                json!({"path": config.to_str().unwrap(), "format": "yaml", "rewrite": true}),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+        assert_eq!(
+            fs::read_to_string(&config).unwrap(),
+            "# settings\nkey: value\n"
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_read_test_report() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        let junit = temp_dir.path().join("junit.xml");
+        fs::write(
+            &junit,
+            r#"<testsuite name="suite">
+                <testcase name="passes"/>
+                <testcase name="fails"><failure message="boom"/></testcase>
+            </testsuite>"#,
+        )
+        .unwrap();
+        let lcov = temp_dir.path().join("lcov.info");
+        fs::write(&lcov, "SF:src/lib.rs\nDA:1,1\nDA:2,0\nend_of_record\n").unwrap();
+
+        let router = get_router().await;
+        let result = router
+            .call_tool("read_test_report", json!({"path": junit.to_str().unwrap()}))
+            .await
+            .unwrap();
+        let text = result[0].as_text().unwrap();
+        assert!(text.starts_with("2 tests: 1 passed, 1 failed"));
+        assert!(text.contains("FAILED suite::fails\n  boom"));
+
+        let result = router
+            .call_tool("read_test_report", json!({"path": lcov.to_str().unwrap()}))
+            .await
+            .unwrap();
+        assert!(result[0]
+            .as_text()
+            .unwrap()
+            .contains("src/lib.rs 50.0% uncovered lines: 2"));
+
+        // An LCOV file is not valid XML
+        let err = router
+            .call_tool(
+                "read_test_report",
+                json!({"path": lcov.to_str().unwrap(), "format": "junit"}),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::ExecutionError(_)));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_scratchpad_write_append_read() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        let router = DeveloperRouter::new();
+        let scratchpad = |router: &DeveloperRouter| {
+            router
+                .list_resources()
+                .into_iter()
+                .find(|r| r.uri == SCRATCHPAD_URI)
+        };
+        assert!(scratchpad(&router).is_none());
+
+        let result = router
+            .call_tool("read_scratchpad", json!({}))
+            .await
+            .unwrap();
+        assert_eq!(result[0].as_text().unwrap(), "The scratchpad is empty");
+
+        router
+            .call_tool(
+                "write_scratchpad",
+                json!({"content": "- plan the refactor"}),
+            )
+            .await
+            .unwrap();
+        router
+            .call_tool("append_scratchpad", json!({"content": "- run the tests"}))
+            .await
+            .unwrap();
+
+        let result = router
+            .call_tool("read_scratchpad", json!({}))
+            .await
+            .unwrap();
+        assert_eq!(
+            result[0].as_text().unwrap(),
+            "- plan the refactor\n- run the tests"
+        );
+
+        // The scratchpad is exposed as an active resource so it is included in the context
+        let resource = scratchpad(&router).unwrap();
+        assert!(resource.is_active());
+        assert_eq!(
+            router.read_resource(SCRATCHPAD_URI).await.unwrap(),
+            "- plan the refactor\n- run the tests"
+        );
+
+        // Writing replaces the content
+        router
+            .call_tool("write_scratchpad", json!({"content": "done"}))
+            .await
+            .unwrap();
+        assert_eq!(router.read_resource(SCRATCHPAD_URI).await.unwrap(), "done");
+
+        let err = router
+            .call_tool("append_scratchpad", json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    #[serial]
+    async fn test_concurrent_edits_to_same_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("shared.txt");
+        let original: String = (0..200).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&file_path, &original).unwrap();
+
+        let router = DeveloperRouter::new();
+        let replace = |old: &str, new: &str| {
+            tokio::spawn(router.call_tool(
+                "text_editor",
+                json!({
+                    "command": "str_replace",
+                    "path": file_path.to_str().unwrap(),
+                    "old_str": old,
+                    "new_str": new,
+                }),
+            ))
+        };
+
+        let (first, second) = tokio::join!(
+            replace("line 10\n", "line ten\n"),
+            replace("line 150\n", "line one hundred fifty\n")
+        );
+        first.unwrap().unwrap();
+        second.unwrap().unwrap();
+
+        // Both edits are applied, neither overwrote the other
+        let expected = original
+            .replace("line 10\n", "line ten\n")
+            .replace("line 150\n", "line one hundred fifty\n");
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), expected);
+
+        // The history records each edit on top of the previous one
+        let undo = || {
+            router.call_tool(
+                "text_editor",
+                json!({"command": "undo_edit", "path": file_path.to_str().unwrap()}),
+            )
+        };
+        undo().await.unwrap();
+        let after_one_undo = fs::read_to_string(&file_path).unwrap();
+        assert!(
+            after_one_undo.contains("line ten\n")
+                ^ after_one_undo.contains("line one hundred fifty\n")
+        );
+        undo().await.unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_redo() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("redo.txt");
+        let path = file_path.to_str().unwrap();
+
+        let router = get_router().await;
+        let edit = |command: &str, extra: Value| {
+            let mut params = json!({"command": command, "path": path});
+            params
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            router.call_tool("text_editor", params)
+        };
+
+        edit("write", json!({"file_text": "one"})).await.unwrap();
+        edit("write", json!({"file_text": "two"})).await.unwrap();
+
+        // Nothing has been undone yet
+        let err = edit("redo", json!({})).await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("No undone edits available to redo"));
+
+        edit("undo_edit", json!({})).await.unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "one");
+
+        let result = edit("redo", json!({})).await.unwrap();
+        assert!(result[0]
+            .as_text()
+            .unwrap()
+            .contains("Redid the last undone edit"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "two");
+
+        // The redone edit can be undone again
+        edit("undo_edit", json!({})).await.unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "one");
+
+        // A fresh edit clears the redo stack
+        edit("str_replace", json!({"old_str": "one", "new_str": "three"}))
+            .await
+            .unwrap();
+        let err = edit("redo", json!({})).await.unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "three");
+
+        edit("undo_edit", json!({})).await.unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "one");
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_changes_since_view_reports_external_edits() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("config.py");
+        let path = file_path.to_str().unwrap();
+        fs::write(
+            &file_path,
+            "DEBUG = False\nPORT = 8000\nHOST = 'localhost'\n",
+        )
+        .unwrap();
+
+        let router = get_router().await;
+        let changes = || async {
+            let result = router
+                .call_tool("changes_since_view", json!({"path": path}))
+                .await?;
+            Ok::<_, ToolError>(result[0].as_text().unwrap().to_string())
+        };
+
+        let err = changes().await.unwrap_err();
+        assert!(err.to_string().contains("has not been viewed yet"));
+
+        router
+            .call_tool("text_editor", json!({"command": "view", "path": path}))
+            .await
+            .unwrap();
+        assert!(changes().await.unwrap().contains("has not changed"));
+
+        // Edits through the text editor are already known to the model
+        router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "str_replace",
+                    "path": path,
+                    "old_str": "DEBUG = False",
+                    "new_str": "DEBUG = True"
+                }),
+            )
+            .await
+            .unwrap();
+        assert!(changes().await.unwrap().contains("has not changed"));
+
+        // An edit made outside of the text editor, like a shell command
+        fs::write(
+            &file_path,
+            "DEBUG = True\nPORT = 9000\nHOST = 'localhost'\n",
+        )
+        .unwrap();
+        let diff = changes().await.unwrap();
+        assert!(diff.contains("(last viewed)"));
+        assert!(diff.contains("\n-PORT = 8000\n+PORT = 9000\n"));
+        assert!(diff.contains("\n DEBUG = True\n"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_file_deleted_after_view() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("deleted.txt");
+        let path = file_path.to_str().unwrap();
+        fs::write(&file_path, "Hello, world!").unwrap();
+
+        let router = get_router().await;
+        router
+            .call_tool("text_editor", json!({"command": "view", "path": path}))
+            .await
+            .unwrap();
+
+        let uri = Url::from_file_path(&file_path).unwrap().to_string();
+        let is_listed = || router.list_resources().iter().any(|r| r.uri == uri);
+        assert!(is_listed());
+
+        fs::remove_file(&file_path).unwrap();
+
+        let err = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "str_replace",
+                    "path": path,
+                    "old_str": "world",
+                    "new_str": "goose"
+                }),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+        assert!(err.to_string().contains("no longer exists on disk"));
+        // Nor is it listed as a resource any more
+        assert!(!is_listed());
+        assert!(matches!(
+            router.read_resource(&uri).await,
+            Err(ResourceError::NotFound(_))
+        ));
+
+        // The stale entry is evicted, later attempts fall back to the regular missing file error
+        let err = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "str_replace",
+                    "path": path,
+                    "old_str": "world",
+                    "new_str": "goose"
+                }),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+        assert!(!err.to_string().contains("no longer exists on disk"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_undo_after_file_deleted() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("deleted.txt");
+        let path = file_path.to_str().unwrap();
+
+        let router = get_router().await;
+        router
+            .call_tool(
+                "text_editor",
+                json!({"command": "write", "path": path, "file_text": "one"}),
+            )
+            .await
+            .unwrap();
+
+        fs::remove_file(&file_path).unwrap();
+
+        let err = router
+            .call_tool("text_editor", json!({"command": "undo_edit", "path": path}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no longer exists on disk"));
+        assert!(!file_path.exists());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_size_limits() {
+        // Create temp directory first so it stays in scope for the whole test
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        // Get router after setting current directory
+        let router = get_router().await;
+
+        // Test file size limit
+        {
+            let large_file_path = temp_dir.path().join("large.txt");
+            let large_file_str = large_file_path.to_str().unwrap();
+
+            // Create a file larger than 2MB
+            let content = "x".repeat(3 * 1024 * 1024); // 3MB
+            std::fs::write(&large_file_path, content).unwrap();
+
+            let result = router
+                .call_tool(
+                    "text_editor",
+                    json!({
+                        "command": "view",
+                        "path": large_file_str
+                    }),
+                )
+                .await;
+
+            assert!(result.is_err());
+            let err = result.err().unwrap();
+            assert!(matches!(err, ToolError::ExecutionError(_)));
+            assert!(err.to_string().contains("too large"));
+        }
+
+        // Test character count limit
+        {
+            let many_chars_path = temp_dir.path().join("many_chars.txt");
+            let many_chars_str = many_chars_path.to_str().unwrap();
+
+            // Create a file with more than 400K characters but less than 400KB
+            let content = "x".repeat(405_000);
+            std::fs::write(&many_chars_path, content).unwrap();
+
+            let result = router
+                .call_tool(
+                    "text_editor",
+                    json!({
+                        "command": "view",
+                        "path": many_chars_str
+                    }),
+                )
+                .await;
+
+            assert!(result.is_err());
+            let err = result.err().unwrap();
+            assert!(matches!(err, ToolError::ExecutionError(_)));
+            assert!(err.to_string().contains("too many characters"));
+        }
+
+        // Let temp_dir drop naturally at end of scope
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_view_binary_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let router = get_router().await;
+        let view = |path: PathBuf| async move {
+            router
+                .call_tool(
+                    "text_editor",
+                    json!({"command": "view", "path": path.to_str().unwrap()}),
+                )
+                .await
+        };
+
+        let path = temp_dir.path().join("tiny.bin");
+        fs::write(&path, b"GOOSE\0\x01\x02").unwrap();
+        let err = view(path).await.unwrap_err().to_string();
+        assert!(err.contains("is a binary file (8 bytes)"));
+        assert!(err.contains("00000000: 47 4f 4f 53 45 00 01 02"));
+        assert!(err.contains("GOOSE..."));
+        assert!(!err.contains("UTF-8"));
+
+        // Only the start of a larger binary is shown
+        let path = temp_dir.path().join("large.bin");
+        let mut bytes = b"text before a NUL".to_vec();
+        bytes.push(0);
+        bytes.extend([b'x'; 1000]);
+        fs::write(&path, bytes).unwrap();
+        let err = view(path).await.unwrap_err().to_string();
+        assert!(err.contains("Its first 256 bytes are"));
+        assert!(err.contains("000000f0: "));
+        assert!(!err.contains("00000100: "));
+
+        let path = temp_dir.path().join("latin1.txt");
+        fs::write(&path, b"caf\xe9").unwrap();
+        let err = view(path).await.unwrap_err().to_string();
+        assert!(err.contains("is not valid UTF-8 text"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_view_limits_can_be_raised() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let path = temp_dir.path().join("data.csv");
+        let row = format!("{}\n", "x".repeat(1023));
+        std::fs::write(&path, row.repeat(500)).unwrap();
+        let params = |extra: Value| {
+            let mut params = json!({"command": "view", "path": path.to_str().unwrap()});
+            params
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            params
+        };
+
+        // 500KB is over the default limit, and the error says how to see it anyway
+        let err = DeveloperRouter::new()
+            .call_tool("text_editor", params(json!({})))
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("too large (500.00KB) to view whole"));
+        assert!(err.to_string().contains("`view_range`"));
+        let err = DeveloperRouter::new()
+            .call_tool("text_editor", params(json!({"view_range": [1, 450]})))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Lines 1-450 of"));
+        assert!(err.to_string().contains("View fewer lines"));
+
+        // A range within the limit can be viewed without raising it
+        let result = DeveloperRouter::new()
+            .call_tool("text_editor", params(json!({"view_range": [1, 100]})))
+            .await
+            .unwrap();
+        assert!(result[1]
+            .as_text()
+            .unwrap()
+            .contains("showing lines 1-100 of 500"));
+
+        let result = DeveloperRouter::new()
+            .call_tool("text_editor", params(json!({"max_bytes": 600 * 1024})))
+            .await
+            .unwrap();
+        assert!(result[1].as_text().unwrap().starts_with("truncated: false"));
+
+        let router = DeveloperRouter::new().with_view_limits(ViewLimits {
+            max_bytes: 1024 * 1024,
+            max_chars: 1024 * 1024,
+        });
+        let result = router
+            .call_tool("text_editor", params(json!({})))
+            .await
+            .unwrap();
+        assert!(result[1].as_text().unwrap().starts_with("truncated: false"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_view_range_of_large_file_reads_only_its_lines() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let path = temp_dir.path().join("dump.log");
+        // Two short lines, then a 600KB line that isn't valid UTF-8
+        let mut contents = b"first\nsecond\n".to_vec();
+        contents.extend(std::iter::repeat_n(0xff, 600 * 1024));
+        contents.extend(b"\nlast\n");
+        std::fs::write(&path, contents).unwrap();
+        let view = |view_range: Value| json!({"command": "view", "path": path.to_str().unwrap(), "view_range": view_range});
+
+        let router = DeveloperRouter::new();
+        let result = router
+            .call_tool("text_editor", view(json!([1, 2])))
+            .await
+            .unwrap();
+        assert!(result[2].as_text().unwrap().contains("first\nsecond\n"));
+        assert!(result[1]
+            .as_text()
+            .unwrap()
+            .contains("showing lines 1-2 of 4"));
+        let result = router
+            .call_tool("text_editor", view(json!([4, -1])))
+            .await
+            .unwrap();
+        assert!(result[2].as_text().unwrap().contains("last\n"));
+
+        // The long line is measured without being kept whole
+        let err = router
+            .call_tool("text_editor", view(json!([3, 3])))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Lines 3-3 of"));
+        assert!(err.to_string().contains("too large (600.00KB)"));
+
+        // Only part of the file was read, so there is no view to compare against
+        let err = router
+            .call_tool(
+                "changes_since_view",
+                json!({"path": path.to_str().unwrap()}),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("has not been viewed yet"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_write_and_view_file() {
+        let router = get_router().await;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        let file_path_str = file_path.to_str().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        // Create a new file
+        router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "write",
+                    "path": file_path_str,
+                    "file_text": "Hello, world!"
+                }),
+            )
+            .await
+            .unwrap();
+
+        // View the file
+        let view_result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "view",
+                    "path": file_path_str
+                }),
+            )
+            .await
+            .unwrap();
+
+        assert!(!view_result.is_empty());
+        let text = view_result
+            .iter()
+            .find(|c| {
+                c.audience()
+                    .is_some_and(|roles| roles.contains(&Role::User))
+            })
+            .unwrap()
+            .as_text()
+            .unwrap();
+        assert!(text.contains("Hello, world!"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_view_reports_truncation() {
+        let router = get_router().await;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("lines.txt");
+        let path = file_path.to_str().unwrap();
+        fs::write(&file_path, "one\ntwo\nthree\nfour\n").unwrap();
+
+        // Returns the text the assistant sees of the file, and the view status
+        let view = |view_range: Option<Value>| {
+            let router = &router;
+            async move {
+                let mut params = json!({"command": "view", "path": path});
+                if let Some(view_range) = view_range {
+                    params["view_range"] = view_range;
+                }
+                let result = router.call_tool("text_editor", params).await?;
+                let content = match &result[0] {
+                    Content::Resource(resource) => resource.get_text(),
+                    other => panic!("expected an embedded resource, got {:?}", other),
+                };
+                Ok::<_, ToolError>((content, result[1].as_text().unwrap().to_string()))
+            }
+        };
+
+        let (content, status) = view(None).await.unwrap();
+        assert_eq!(content, "one\ntwo\nthree\nfour\n");
+        assert!(status.starts_with("truncated: false"));
+
+        let (content, status) = view(Some(json!([2, 3]))).await.unwrap();
+        assert_eq!(content, "two\nthree\n");
+        assert!(status.starts_with("truncated: true, showing lines 2-3 of 4"));
+
+        // A range covering the whole file is complete
+        let (content, status) = view(Some(json!([1, -1]))).await.unwrap();
+        assert_eq!(content, "one\ntwo\nthree\nfour\n");
+        assert!(status.starts_with("truncated: false"));
+
+        let err = view(Some(json!([7, -1]))).await.unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_view_directory() {
+        let router = get_router().await;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/nested")).unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), "pub mod nested;").unwrap();
+        fs::write(temp_dir.path().join("src/nested/mod.rs"), "").unwrap();
+
+        let list = |max_depth: Option<u64>| {
+            let router = &router;
+            let mut params = json!({"command": "view", "path": temp_dir.path()});
+            if let Some(max_depth) = max_depth {
+                params["max_depth"] = json!(max_depth);
+            }
+            async move {
+                let result = router.call_tool("text_editor", params).await.unwrap();
+                result[0].as_text().unwrap().to_string()
+            }
+        };
+
+        let tree = list(None).await;
+        assert!(tree.contains("\n  src/\n    lib.rs (15 B)\n    nested/\n      mod.rs (0 B)"));
+
+        let tree = list(Some(1)).await;
+        assert!(tree.ends_with("\n  src/"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_str_replace() {
+        let router = get_router().await;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        let file_path_str = file_path.to_str().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        // Create a new file
+        router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "write",
+                    "path": file_path_str,
+                    "file_text": "Hello, world!"
+                }),
+            )
+            .await
+            .unwrap();
+
+        // Replace string
+        let replace_result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "str_replace",
+                    "path": file_path_str,
+                    "old_str": "world",
+                    "new_str": "Rust"
+                }),
+            )
+            .await
+            .unwrap();
+
+        let text = replace_result
+            .iter()
+            .find(|c| {
+                c.audience()
+                    .is_some_and(|roles| roles.contains(&Role::Assistant))
+            })
+            .unwrap()
+            .as_text()
+            .unwrap();
+
+        assert!(text.contains("has been edited, and the section now reads"));
+
+        // View the file to verify the change
+        let view_result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "view",
+                    "path": file_path_str
+                }),
+            )
+            .await
+            .unwrap();
+
+        let text = view_result
+            .iter()
+            .find(|c| {
+                c.audience()
+                    .is_some_and(|roles| roles.contains(&Role::User))
+            })
+            .unwrap()
+            .as_text()
+            .unwrap();
+        assert!(text.contains("Hello, Rust!"));
 
-    fn read_resource(
-        &self,
-        _uri: &str,
-    ) -> Pin<Box<dyn Future<Output = Result<String, ResourceError>> + Send + 'static>> {
-        Box::pin(async move { Ok("".to_string()) })
+        temp_dir.close().unwrap();
     }
-}
 
-impl Clone for DeveloperRouter {
-    fn clone(&self) -> Self {
-        Self {
-            tools: self.tools.clone(),
-            file_history: Arc::clone(&self.file_history),
-            instructions: self.instructions.clone(),
-        }
-    }
-}
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_edits_show_user_a_diff() {
+        let router = get_router().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("lib.py");
+        let file_path_str = file_path.to_str().unwrap();
+        fs::write(&file_path, "def add(a, b):\n    return a - b\n").unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
-    use serial_test::serial;
-    use std::fs;
-    use tempfile::TempDir;
-    use tokio::sync::OnceCell;
+        let user_text = |result: Vec<Content>| result[1].as_text().unwrap().to_string();
 
-    #[test]
-    #[serial]
-    fn test_goosehints_when_present() {
-        let dir = TempDir::new().unwrap();
-        std::env::set_current_dir(dir.path()).unwrap();
+        let result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "str_replace",
+                    "path": file_path_str,
+                    "old_str": "a - b",
+                    "new_str": "a + b"
+                }),
+            )
+            .await
+            .unwrap();
+        let diff = user_text(result);
+        assert!(diff.starts_with("```diff\n"));
+        assert!(diff.contains(&format!("+++ b/{}", file_path.display())));
+        assert!(diff.contains("@@ -1,2 +1,2 @@"));
+        assert!(diff.contains("\n def add(a, b):\n-    return a - b\n+    return a + b\n"));
+
+        // Overwriting a file shows what changed rather than the whole file
+        let result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "write",
+                    "path": file_path_str,
+                    "file_text": "def add(a, b):\n    return a + b\n\n\ndef sub(a, b):\n    return a - b\n"
+                }),
+            )
+            .await
+            .unwrap();
+        let diff = user_text(result);
+        assert!(diff.contains("\n+def sub(a, b):\n+    return a - b\n"));
+        assert!(!diff
+            .lines()
+            .any(|line| line.starts_with('-') && !line.starts_with("---")));
 
-        fs::write(".goosehints", "Test hint content").unwrap();
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_edits_return_edit_results() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
         let router = DeveloperRouter::new();
-        let instructions = router.instructions();
+        let file_path = temp_dir.path().join("main.py");
+        let path = file_path.to_str().unwrap();
+        let before = "import os\n\ndef main():\n    print('hi')\n    return 0\n";
+        fs::write(&file_path, before).unwrap();
+
+        let edit_result = |contents: &[Content]| -> EditResult {
+            let resource = contents
+                .iter()
+                .find_map(|content| match content {
+                    Content::Resource(resource) => Some(resource),
+                    _ => None,
+                })
+                .unwrap();
+            let ResourceContents::TextResourceContents {
+                mime_type, text, ..
+            } = &resource.resource
+            else {
+                panic!("the edit result is not text");
+            };
+            assert_eq!(
+                mime_type.as_deref(),
+                Some(edit_result::EDIT_RESULT_MIME_TYPE)
+            );
+            // Edit results are only for clients
+            assert_eq!(
+                resource.annotations.as_ref().unwrap().audience,
+                Some(vec![])
+            );
+            serde_json::from_str(text).unwrap()
+        };
 
-        assert!(instructions.contains("Test hint content"));
+        let result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "str_replace",
+                    "path": path,
+                    "old_str": "print('hi')\n    return 0",
+                    "new_str": "return 1"
+                }),
+            )
+            .await
+            .unwrap();
+        let edits = edit_result(&result);
+        assert_eq!(edits.path, path);
+        assert_eq!(edits.edits.len(), 1);
+        let edit = &edits.edits[0];
+        assert_eq!(edit.range.start_byte, before.find("print").unwrap());
+        assert_eq!(edit.range.end_byte, before.rfind('\n').unwrap());
+        assert_eq!((edit.range.start_line, edit.range.end_line), (4, 5));
+        assert_eq!(edit.new_text, "return 1");
+
+        // Applying the edit to the old text gives the file as it is now
+        let mut applied = before.to_string();
+        applied.replace_range(edit.range.start_byte..edit.range.end_byte, &edit.new_text);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), applied);
+
+        // A write replaces the whole file
+        let result = router
+            .call_tool(
+                "text_editor",
+                json!({"command": "write", "path": path, "file_text": "pass\n"}),
+            )
+            .await
+            .unwrap();
+        let edit = &edit_result(&result).edits[0];
+        assert_eq!(
+            (edit.range.start_byte, edit.range.end_byte),
+            (0, applied.len())
+        );
+        assert_eq!((edit.range.start_line, edit.range.end_line), (1, 5));
+        assert_eq!(edit.new_text, "pass\n");
+
+        temp_dir.close().unwrap();
     }
 
-    #[test]
+    #[tokio::test]
     #[serial]
-    fn test_goosehints_when_missing() {
-        let dir = TempDir::new().unwrap();
-        std::env::set_current_dir(dir.path()).unwrap();
-
+    async fn test_text_editor_dry_run() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
         let router = DeveloperRouter::new();
-        let instructions = router.instructions();
+        let file_path = temp_dir.path().join("config.toml");
+        let file_path_str = file_path.to_str().unwrap();
+        let original = "[server]\nport = 8080\n";
+        fs::write(&file_path, original).unwrap();
 
-        assert!(!instructions.contains("Project Hints"));
-    }
+        let edit = |dry_run: bool| {
+            router.call_tool(
+                "text_editor",
+                json!({
+                    "command": "str_replace",
+                    "path": file_path_str,
+                    "old_str": "8080",
+                    "new_str": "9090",
+                    "dry_run": dry_run
+                }),
+            )
+        };
 
-    static DEV_ROUTER: OnceCell<DeveloperRouter> = OnceCell::const_new();
+        let preview = edit(true).await.unwrap();
+        assert!(preview[0]
+            .as_text()
+            .unwrap()
+            .starts_with("Dry run, no changes were applied."));
+        assert!(preview[0].as_text().unwrap().contains("port = 9090"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
+
+        // The preview did not add to the history, so there is nothing to undo
+        let undo = json!({"command": "undo_edit", "path": file_path_str});
+        assert!(router.call_tool("text_editor", undo.clone()).await.is_err());
+
+        // The preview shows the user the same diff as the real edit
+        let applied = edit(false).await.unwrap();
+        assert_eq!(preview[1].as_text(), applied[1].as_text());
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "[server]\nport = 9090\n"
+        );
+        router.call_tool("text_editor", undo).await.unwrap();
 
-    async fn get_router() -> &'static DeveloperRouter {
-        DEV_ROUTER
-            .get_or_init(|| async { DeveloperRouter::new() })
+        let result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "write",
+                    "path": file_path_str,
+                    "file_text": "[server]\nport = 1\n",
+                    "dry_run": true
+                }),
+            )
+            .await
+            .unwrap();
+        assert!(result[0].as_text().unwrap().contains("+port = 1"));
+
+        let new_path = temp_dir.path().join("moved.toml");
+        router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "move",
+                    "path": file_path_str,
+                    "new_path": new_path.to_str().unwrap(),
+                    "dry_run": true
+                }),
+            )
             .await
+            .unwrap();
+        assert!(!new_path.exists());
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
+
+        temp_dir.close().unwrap();
     }
 
     #[tokio::test]
     #[serial]
-    async fn test_shell_missing_parameters() {
+    async fn test_text_editor_str_replace_occurrence() {
+        let router = get_router().await;
         let temp_dir = tempfile::tempdir().unwrap();
         std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("repeat.txt");
+        let original = "x = 1\nx = 1\nx = 1\n";
+
+        let replace = |occurrence: Value| {
+            let file_path = file_path.clone();
+            async move {
+                fs::write(&file_path, original).unwrap();
+                let mut params = json!({
+                    "command": "str_replace",
+                    "path": file_path.to_str().unwrap(),
+                    "old_str": "x = 1",
+                    "new_str": "x = 2"
+                });
+                if !occurrence.is_null() {
+                    params["occurrence"] = occurrence;
+                }
+                router.call_tool("text_editor", params).await?;
+                Ok::<_, ToolError>(fs::read_to_string(&file_path).unwrap())
+            }
+        };
 
-        let router = get_router().await;
-        let result = router.call_tool("shell", json!({})).await;
+        assert_eq!(replace(json!(2)).await.unwrap(), "x = 1\nx = 2\nx = 1\n");
+        assert_eq!(
+            replace(json!("all")).await.unwrap(),
+            "x = 2\nx = 2\nx = 2\n"
+        );
 
-        assert!(result.is_err());
-        let err = result.err().unwrap();
-        assert!(matches!(err, ToolError::InvalidParameters(_)));
+        let err = replace(json!(4)).await.unwrap_err();
+        assert!(
+            matches!(&err, ToolError::InvalidParameters(msg) if msg.contains("occurrence 4 is out of range, 'old_str' appears 3 times")),
+            "{:?}",
+            err
+        );
+        // Without an occurrence the match must still be unique
+        let err = replace(Value::Null).await.unwrap_err();
+        assert!(
+            matches!(&err, ToolError::InvalidParameters(msg) if msg.contains("appears 3 times")),
+            "{:?}",
+            err
+        );
+        assert!(replace(json!(0)).await.is_err());
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
 
         temp_dir.close().unwrap();
     }
 
     #[tokio::test]
     #[serial]
-    async fn test_text_editor_size_limits() {
-        // Create temp directory first so it stays in scope for the whole test
+    async fn test_text_editor_str_replace_keeps_crlf_line_endings() {
+        let router = get_router().await;
         let temp_dir = tempfile::tempdir().unwrap();
         std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("Program.cs");
+        let replace = |old_str: &str, new_str: &str| {
+            router.call_tool(
+                "text_editor",
+                json!({
+                    "command": "str_replace",
+                    "path": file_path.to_str().unwrap(),
+                    "old_str": old_str,
+                    "new_str": new_str
+                }),
+            )
+        };
 
-        // Get router after setting current directory
-        let router = get_router().await;
-
-        // Test file size limit
-        {
-            let large_file_path = temp_dir.path().join("large.txt");
-            let large_file_str = large_file_path.to_str().unwrap();
-
-            // Create a file larger than 2MB
-            let content = "x".repeat(3 * 1024 * 1024); // 3MB
-            std::fs::write(&large_file_path, content).unwrap();
+        fs::write(&file_path, "class A\r\n{\r\n    int x;\r\n}\r\n").unwrap();
+        replace("{\n    int x;\n}", "{\n    int x;\n    int y;\n}")
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "class A\r\n{\r\n    int x;\r\n    int y;\r\n}\r\n"
+        );
 
-            let result = router
-                .call_tool(
-                    "text_editor",
-                    json!({
-                        "command": "view",
-                        "path": large_file_str
-                    }),
-                )
-                .await;
+        // CRLF given for a file with LF endings is matched and written as LF
+        fs::write(&file_path, "a\nb\nc\n").unwrap();
+        replace("a\r\nb", "a\r\nB").await.unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "a\nB\nc\n");
+
+        // In a file mixing both, text given exactly as it is still matches
+        fs::write(&file_path, "one\r\ntwo\nthree\n").unwrap();
+        replace("one\r\ntwo", "1\n2").await.unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "1\n2\nthree\n");
+
+        // and its replacement is written as given too, rather than with the most common ending
+        fs::write(&file_path, "one\r\ntwo\r\nthree\nfour\r\n").unwrap();
+        replace("three\nfour", "3\n4").await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "one\r\ntwo\r\n3\n4\r\n"
+        );
 
-            assert!(result.is_err());
-            let err = result.err().unwrap();
-            assert!(matches!(err, ToolError::ExecutionError(_)));
-            assert!(err.to_string().contains("too large"));
-        }
+        temp_dir.close().unwrap();
+    }
 
-        // Test character count limit
-        {
-            let many_chars_path = temp_dir.path().join("many_chars.txt");
-            let many_chars_str = many_chars_path.to_str().unwrap();
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_guards_generated_files() {
+        let router = get_router().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let lock_path = temp_dir.path().join("Cargo.lock");
+        let lock_text = "version = 4\n";
+        fs::write(&lock_path, lock_text).unwrap();
 
-            // Create a file with more than 400K characters but less than 400KB
-            let content = "x".repeat(405_000);
-            std::fs::write(&many_chars_path, content).unwrap();
+        let edit = |force: bool| {
+            router.call_tool(
+                "text_editor",
+                json!({
+                    "command": "str_replace",
+                    "path": lock_path.to_str().unwrap(),
+                    "old_str": "4",
+                    "new_str": "3",
+                    "force": force
+                }),
+            )
+        };
 
-            let result = router
-                .call_tool(
-                    "text_editor",
-                    json!({
-                        "command": "view",
-                        "path": many_chars_str
-                    }),
-                )
-                .await;
+        let err = edit(false).await.unwrap_err();
+        assert!(
+            matches!(&err, ToolError::InvalidParameters(msg) if msg.contains("generated file") && msg.contains("cargo update")),
+            "{:?}",
+            err
+        );
+        assert_eq!(fs::read_to_string(&lock_path).unwrap(), lock_text);
 
-            assert!(result.is_err());
-            let err = result.err().unwrap();
-            assert!(matches!(err, ToolError::ExecutionError(_)));
-            assert!(err.to_string().contains("too many characters"));
-        }
+        edit(true).await.unwrap();
+        assert_eq!(fs::read_to_string(&lock_path).unwrap(), "version = 3\n");
 
-        // Let temp_dir drop naturally at end of scope
+        temp_dir.close().unwrap();
     }
 
     #[tokio::test]
     #[serial]
-    async fn test_text_editor_write_and_view_file() {
+    async fn test_text_editor_undo_edit() {
         let router = get_router().await;
 
         let temp_dir = tempfile::tempdir().unwrap();
@@ -824,20 +5228,49 @@ mod tests {
         let file_path_str = file_path.to_str().unwrap();
         std::env::set_current_dir(&temp_dir).unwrap();
 
-        // Create a new file
-        router
+        // Create a new file
+        router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "write",
+                    "path": file_path_str,
+                    "file_text": "First line"
+                }),
+            )
+            .await
+            .unwrap();
+
+        // Replace string
+        router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "str_replace",
+                    "path": file_path_str,
+                    "old_str": "First line",
+                    "new_str": "Second line"
+                }),
+            )
+            .await
+            .unwrap();
+
+        // Undo the edit
+        let undo_result = router
             .call_tool(
                 "text_editor",
                 json!({
-                    "command": "write",
-                    "path": file_path_str,
-                    "file_text": "Hello, world!"
+                    "command": "undo_edit",
+                    "path": file_path_str
                 }),
             )
             .await
             .unwrap();
 
-        // View the file
+        let text = undo_result.first().unwrap().as_text().unwrap();
+        assert!(text.contains("Undid the last edit"));
+
+        // View the file to verify the undo
         let view_result = router
             .call_tool(
                 "text_editor",
@@ -849,7 +5282,6 @@ mod tests {
             .await
             .unwrap();
 
-        assert!(!view_result.is_empty());
         let text = view_result
             .iter()
             .find(|c| {
@@ -859,97 +5291,161 @@ mod tests {
             .unwrap()
             .as_text()
             .unwrap();
-        assert!(text.contains("Hello, world!"));
+        assert!(text.contains("First line"));
 
         temp_dir.close().unwrap();
     }
 
     #[tokio::test]
     #[serial]
-    async fn test_text_editor_str_replace() {
+    async fn test_text_editor_move() {
         let router = get_router().await;
 
         let temp_dir = tempfile::tempdir().unwrap();
-        let file_path = temp_dir.path().join("test.txt");
-        let file_path_str = file_path.to_str().unwrap();
+        let file_path = temp_dir.path().join("old.txt");
+        let new_path = temp_dir.path().join("new.txt");
         std::env::set_current_dir(&temp_dir).unwrap();
-
-        // Create a new file
+        fs::write(&file_path, "content").unwrap();
         router
+            .call_tool(
+                "text_editor",
+                json!({"command": "view", "path": file_path.to_str().unwrap()}),
+            )
+            .await
+            .unwrap();
+
+        let result = router
             .call_tool(
                 "text_editor",
                 json!({
-                    "command": "write",
-                    "path": file_path_str,
-                    "file_text": "Hello, world!"
+                    "command": "move",
+                    "path": file_path.to_str().unwrap(),
+                    "new_path": new_path.to_str().unwrap()
                 }),
             )
             .await
             .unwrap();
 
-        // Replace string
-        let replace_result = router
+        let text = result.first().unwrap().as_text().unwrap();
+        assert!(text.contains(file_path.to_str().unwrap()));
+        assert!(text.contains(new_path.to_str().unwrap()));
+        assert!(!file_path.exists());
+        assert_eq!(fs::read_to_string(&new_path).unwrap(), "content");
+
+        // The viewed file is listed as a resource under its new path only
+        let uris: Vec<String> = router.list_resources().into_iter().map(|r| r.uri).collect();
+        assert!(!uris.contains(&Url::from_file_path(&file_path).unwrap().to_string()));
+        assert!(uris.contains(&Url::from_file_path(&new_path).unwrap().to_string()));
+
+        // The source no longer exists, so moving it again fails
+        let result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "move",
+                    "path": file_path.to_str().unwrap(),
+                    "new_path": temp_dir.path().join("other.txt").to_str().unwrap()
+                }),
+            )
+            .await;
+        assert!(matches!(result, Err(ToolError::InvalidParameters(_))));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_move_onto_existing_file() {
+        let router = get_router().await;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("a.txt");
+        let existing = temp_dir.path().join("b.txt");
+        std::env::set_current_dir(&temp_dir).unwrap();
+        fs::write(&file_path, "a").unwrap();
+        fs::write(&existing, "b").unwrap();
+
+        let result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "move",
+                    "path": file_path.to_str().unwrap(),
+                    "new_path": existing.to_str().unwrap()
+                }),
+            )
+            .await;
+
+        let err = result.unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+        assert!(err.to_string().contains("already exists"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "a");
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "b");
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_move_keeps_undo_history() {
+        let router = get_router().await;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("old.txt");
+        let new_path = temp_dir.path().join("new.txt");
+        std::env::set_current_dir(&temp_dir).unwrap();
+        fs::write(&file_path, "First line").unwrap();
+
+        router
             .call_tool(
                 "text_editor",
                 json!({
                     "command": "str_replace",
-                    "path": file_path_str,
-                    "old_str": "world",
-                    "new_str": "Rust"
+                    "path": file_path.to_str().unwrap(),
+                    "old_str": "First line",
+                    "new_str": "Second line"
                 }),
             )
             .await
             .unwrap();
-
-        let text = replace_result
-            .iter()
-            .find(|c| {
-                c.audience()
-                    .is_some_and(|roles| roles.contains(&Role::Assistant))
-            })
-            .unwrap()
-            .as_text()
+        router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "move",
+                    "path": file_path.to_str().unwrap(),
+                    "new_path": new_path.to_str().unwrap()
+                }),
+            )
+            .await
             .unwrap();
-
-        assert!(text.contains("has been edited, and the section now reads"));
-
-        // View the file to verify the change
-        let view_result = router
+        router
             .call_tool(
                 "text_editor",
                 json!({
-                    "command": "view",
-                    "path": file_path_str
+                    "command": "undo_edit",
+                    "path": new_path.to_str().unwrap()
                 }),
             )
             .await
             .unwrap();
 
-        let text = view_result
-            .iter()
-            .find(|c| {
-                c.audience()
-                    .is_some_and(|roles| roles.contains(&Role::User))
-            })
-            .unwrap()
-            .as_text()
-            .unwrap();
-        assert!(text.contains("Hello, Rust!"));
+        assert_eq!(fs::read_to_string(&new_path).unwrap(), "First line");
+        assert!(!file_path.exists());
 
         temp_dir.close().unwrap();
     }
 
     #[tokio::test]
     #[serial]
-    async fn test_text_editor_undo_edit() {
-        let router = get_router().await;
-
+    async fn test_text_editor_undo_survives_restart() {
         let temp_dir = tempfile::tempdir().unwrap();
+        let history_dir = temp_dir.path().join("history");
         let file_path = temp_dir.path().join("test.txt");
         let file_path_str = file_path.to_str().unwrap();
         std::env::set_current_dir(&temp_dir).unwrap();
 
-        // Create a new file
+        let router = DeveloperRouter::with_history_dir(Some(history_dir.clone()));
         router
             .call_tool(
                 "text_editor",
@@ -961,8 +5457,6 @@ mod tests {
             )
             .await
             .unwrap();
-
-        // Replace string
         router
             .call_tool(
                 "text_editor",
@@ -975,9 +5469,11 @@ mod tests {
             )
             .await
             .unwrap();
+        drop(router);
 
-        // Undo the edit
-        let undo_result = router
+        // A new router pointing at the same directory can undo both edits
+        let router = DeveloperRouter::with_history_dir(Some(history_dir.clone()));
+        router
             .call_tool(
                 "text_editor",
                 json!({
@@ -987,32 +5483,284 @@ mod tests {
             )
             .await
             .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "First line");
+        drop(router);
 
-        let text = undo_result.first().unwrap().as_text().unwrap();
-        assert!(text.contains("Undid the last edit"));
-
-        // View the file to verify the undo
-        let view_result = router
+        // The undo itself is persisted too, leaving only the write to undo
+        let router = DeveloperRouter::with_history_dir(Some(history_dir));
+        router
             .call_tool(
                 "text_editor",
                 json!({
-                    "command": "view",
+                    "command": "undo_edit",
+                    "path": file_path_str
+                }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "");
+        let result = router
+            .call_tool(
+                "text_editor",
+                json!({
+                    "command": "undo_edit",
                     "path": file_path_str
                 }),
             )
+            .await;
+        assert!(matches!(result, Err(ToolError::InvalidParameters(_))));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_text_editor_undo_history_is_capped() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        let file_path_str = file_path.to_str().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        let router = DeveloperRouter::with_history_dir(None);
+        for i in 0..MAX_SNAPSHOTS_PER_FILE + 5 {
+            router
+                .call_tool(
+                    "text_editor",
+                    json!({
+                        "command": "write",
+                        "path": file_path_str,
+                        "file_text": format!("Version {}", i)
+                    }),
+                )
+                .await
+                .unwrap();
+        }
+
+        let undo = || {
+            router.call_tool(
+                "text_editor",
+                json!({"command": "undo_edit", "path": file_path_str}),
+            )
+        };
+        for _ in 0..MAX_SNAPSHOTS_PER_FILE {
+            undo().await.unwrap();
+        }
+        assert!(matches!(undo().await, Err(ToolError::InvalidParameters(_))));
+        // The oldest snapshots were dropped, so the earliest versions can't be restored
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "Version 4");
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_rename_symbol_only_renames_identifiers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("lib.rs");
+        let path = file_path.to_str().unwrap();
+        let original = indoc! {r#"
+            // Sum the values, keeping the total in total_len
+            fn sum(values: &[u32]) -> u32 {
+                let len = values.len();
+                let total_len = len + 1;
+                println!("len is {}", len);
+                total_len as u32
+            }
+        "#};
+        fs::write(&file_path, original).unwrap();
+
+        let router = get_router().await;
+        let result = router
+            .call_tool(
+                "rename_symbol",
+                json!({"path": path, "old_name": "len", "new_name": "count"}),
+            )
             .await
             .unwrap();
+        assert!(result[0]
+            .as_text()
+            .unwrap()
+            .contains("Renamed 3 occurrences of `len` to `count`"));
 
-        let text = view_result
-            .iter()
-            .find(|c| {
-                c.audience()
-                    .is_some_and(|roles| roles.contains(&Role::User))
-            })
+        // The method call, the longer name and the string and comment mentions are untouched
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            indoc! {r#"
+                // Sum the values, keeping the total in total_len
+                fn sum(values: &[u32]) -> u32 {
+                    let count = values.len();
+                    let total_len = count + 1;
+                    println!("len is {}", count);
+                    total_len as u32
+                }
+            "#}
+        );
+
+        router
+            .call_tool("text_editor", json!({"command": "undo_edit", "path": path}))
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_capabilities_match_registered_features() {
+        let router = get_router().await;
+        let capabilities = router.capabilities();
+
+        assert!(!router.list_tools().is_empty());
+        assert_eq!(capabilities.tools.unwrap().list_changed, Some(false));
+        assert!(capabilities.resources.is_some());
+        assert!(!router.list_prompts().unwrap_or_default().is_empty());
+        assert_eq!(capabilities.prompts.unwrap().list_changed, Some(false));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_prompts_can_be_discovered_and_requested() {
+        let router = get_router().await;
+
+        let result = router.call_tool("list_prompts", json!({})).await.unwrap();
+        let listing = result[0].as_text().unwrap();
+        assert!(listing.contains("unit_test: "));
+        assert!(listing.contains("- source_file (required): "));
+        assert!(listing.contains("- test_framework (optional): "));
+
+        let result = router
+            .call_tool(
+                "get_prompt",
+                json!({"name": "unit_test", "arguments": {"source_file": "src/main.rs"}}),
+            )
+            .await
+            .unwrap();
+        assert!(result[0].as_text().unwrap().contains("src/main.rs"));
+
+        let err = router
+            .call_tool("get_prompt", json!({"name": "unit_test"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+
+        // Over MCP the prompt is rendered with its arguments too
+        let arguments = json!({"source_file": "src/main.rs"});
+        let prompt = router
+            .get_prompt("unit_test", arguments.as_object().unwrap())
             .unwrap()
-            .as_text()
+            .await
             .unwrap();
-        assert!(text.contains("First line"));
+        assert!(prompt.contains("unit tests for src/main.rs"));
+        assert!(!prompt.contains('{'));
+        let missing = router
+            .get_prompt("missing", &serde_json::Map::new())
+            .unwrap();
+        assert!(matches!(missing.await, Err(PromptError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_viewed_files_are_listed_as_resources() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let file_path = temp_dir.path().join("notes.txt");
+        fs::write(&file_path, "first draft\n").unwrap();
+
+        let router = DeveloperRouter::new();
+        let capabilities = router.capabilities();
+        assert!(capabilities.tools.is_some());
+        assert!(capabilities.resources.is_some());
+
+        let file_resources = |router: &DeveloperRouter| -> Vec<Resource> {
+            router
+                .list_resources()
+                .into_iter()
+                .filter(|r| r.uri.starts_with("file://"))
+                .collect()
+        };
+        assert!(file_resources(&router).is_empty());
+
+        router
+            .call_tool(
+                "text_editor",
+                json!({"command": "view", "path": file_path.to_str().unwrap()}),
+            )
+            .await
+            .unwrap();
+        let resources = file_resources(&router);
+        assert_eq!(resources.len(), 1);
+        let resource = &resources[0];
+        assert_eq!(resource.name, file_path.display().to_string());
+        assert!(!resource.is_active());
+        assert_eq!(
+            router.read_resource(&resource.uri).await.unwrap(),
+            "first draft\n"
+        );
+
+        // Reading the resource gives the file as it is now
+        fs::write(&file_path, "second draft\n").unwrap();
+        assert_eq!(
+            router.read_resource(&resource.uri).await.unwrap(),
+            "second draft\n"
+        );
+
+        // Files that were never viewed are not resources
+        let other = temp_dir.path().join("other.txt");
+        fs::write(&other, "private").unwrap();
+        let other_uri = Url::from_file_path(&other).unwrap().to_string();
+        assert!(router.read_resource(&other_uri).await.is_err());
+        assert!(router.read_resource("unknown://thing").await.is_err());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_cwd_resource_follows_working_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+        let project = temp_dir.path().join("my project #1");
+        fs::create_dir(&project).unwrap();
+
+        let router = DeveloperRouter::new();
+        let cwd_resource = |router: &DeveloperRouter| {
+            router
+                .list_resources()
+                .into_iter()
+                .find(|r| r.name == "cwd")
+                .unwrap()
+        };
+
+        // Only listed by default, the model reads it when it needs to
+        let before = cwd_resource(&router);
+        assert!(!before.is_active());
+        assert!(before.uri.starts_with("str:///") && !before.uri.starts_with("str:////"));
+        assert_eq!(
+            router.read_resource(&before.uri).await.unwrap(),
+            router.working_directory().unwrap().display().to_string()
+        );
+
+        // A lone `cd` moves the working directory, and the resource with it
+        router
+            .call_tool("shell", json!({"command": "cd 'my project #1'"}))
+            .await
+            .unwrap();
+        let after = cwd_resource(&router);
+        assert!(after.uri.ends_with("/my%20project%20%231"));
+        assert!(router
+            .working_directory()
+            .unwrap()
+            .ends_with("my project #1"));
+        assert_eq!(
+            router.read_resource(&after.uri).await.unwrap(),
+            router.working_directory().unwrap().display().to_string()
+        );
+
+        // The old URI is stale and says where the working directory is now
+        let err = router.read_resource(&before.uri).await.unwrap_err();
+        assert!(err.to_string().contains("not the working directory"));
 
         temp_dir.close().unwrap();
     }
@@ -0,0 +1,172 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use kill_tree::blocking::kill_tree_with_config;
+use kill_tree::Config;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessStatus, ProcessesToUpdate, System};
+
+/// How often a process being stopped is checked for having exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A process that a shell command left running in the background
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedProcess {
+    /// The pid of the background job, whose child processes are stopped along with it
+    pub pid: u32,
+    pub command: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// How a process was stopped by [`ProcessStore::kill`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stopped {
+    /// The process exited after SIGTERM
+    Terminated,
+    /// The process was still running after the grace period and was sent SIGKILL
+    Killed,
+}
+
+/// The background jobs started by shell commands that are still running
+#[derive(Debug, Clone, Default)]
+pub struct ProcessStore {
+    processes: Arc<Mutex<Vec<TrackedProcess>>>,
+}
+
+impl ProcessStore {
+    pub fn track(&self, pid: u32, command: &str) {
+        self.processes.lock().unwrap().push(TrackedProcess {
+            pid,
+            command: command.to_string(),
+            started_at: Utc::now(),
+        });
+    }
+
+    /// The tracked processes that are still running, forgetting the others
+    pub fn running(&self) -> Vec<TrackedProcess> {
+        let mut processes = self.processes.lock().unwrap();
+        processes.retain(|p| is_running(p.pid));
+        processes.clone()
+    }
+
+    /// Stop the tracked process `pid` along with every process it started
+    ///
+    /// The processes are sent SIGTERM, then SIGKILL if any are still running after `grace`.
+    /// Only tracked processes can be stopped, so unrelated processes are never signalled.
+    pub async fn kill(
+        &self,
+        pid: u32,
+        grace: Duration,
+    ) -> Result<(TrackedProcess, Stopped), String> {
+        let process = self
+            .processes
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|p| p.pid == pid)
+            .cloned()
+            .ok_or_else(|| {
+                format!(
+                    "No process with PID {} was started by the shell tool, use list_processes to see them",
+                    pid
+                )
+            })?;
+
+        let result = stop_tree(pid, grace).await;
+        self.processes.lock().unwrap().retain(|p| p.pid != pid);
+        result.map(|stopped| (process, stopped))
+    }
+}
+
+async fn stop_tree(pid: u32, grace: Duration) -> Result<Stopped, String> {
+    if !is_running(pid) {
+        return Err(format!("The process with PID {} has already exited", pid));
+    }
+
+    signal_tree(pid, "SIGTERM").await?;
+    if wait_for_exit(pid, grace).await {
+        return Ok(Stopped::Terminated);
+    }
+
+    signal_tree(pid, "SIGKILL").await?;
+    if wait_for_exit(pid, grace).await {
+        Ok(Stopped::Killed)
+    } else {
+        Err(format!(
+            "The process with PID {} is still running after SIGKILL",
+            pid
+        ))
+    }
+}
+
+/// Send `signal` to `pid` and all of its descendants
+async fn signal_tree(pid: u32, signal: &str) -> Result<(), String> {
+    let config = Config {
+        signal: signal.to_string(),
+        ..Default::default()
+    };
+    tokio::task::spawn_blocking(move || kill_tree_with_config(pid, &config))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result.map_err(|e| e.to_string()))
+        .map(|_| ())
+        .map_err(|e| format!("Failed to send {} to PID {}: {}", signal, pid, e))
+}
+
+/// Whether `pid` is running, not counting an exited process that has not been reaped yet
+fn is_running(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::new(),
+    );
+    system
+        .process(pid)
+        .is_some_and(|p| p.status() != ProcessStatus::Zombie)
+}
+
+async fn wait_for_exit(pid: u32, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if !is_running(pid) {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::process::Command;
+
+    #[tokio::test]
+    async fn test_kill_escalates_to_sigkill() {
+        // The shell ignores SIGTERM, so only SIGKILL stops it
+        let mut child = Command::new("bash")
+            .args(["-c", "trap '' TERM; while true; do sleep 0.1; done"])
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        // Reap the shell once it is killed, as the shell tool's jobs are reaped by init
+        tokio::spawn(async move { child.wait().await });
+
+        let store = ProcessStore::default();
+        store.track(pid, "stubborn");
+        assert_eq!(store.running().len(), 1);
+
+        let (process, stopped) = store.kill(pid, Duration::from_millis(500)).await.unwrap();
+        assert_eq!(process.command, "stubborn");
+        assert_eq!(stopped, Stopped::Killed);
+        assert!(store.running().is_empty());
+
+        // Only tracked processes can be killed
+        let err = store.kill(pid, Duration::ZERO).await.unwrap_err();
+        assert!(err.contains("list_processes"));
+    }
+}
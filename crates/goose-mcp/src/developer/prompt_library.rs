@@ -0,0 +1,168 @@
+use include_dir::{include_dir, Dir};
+use mcp_core::prompt::{Prompt, PromptArgument, PromptTemplate};
+use serde_json::{Map, Value};
+
+static PROMPTS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/developer/prompts");
+
+/// What a missing optional argument is rendered as
+const NOT_GIVEN: &str = "(not given)";
+
+/// The prompt templates of the developer extension, one JSON [`PromptTemplate`] per file
+#[derive(Debug, Default)]
+pub struct PromptLibrary {
+    /// Sorted by id, so listings are stable
+    templates: Vec<PromptTemplate>,
+}
+
+impl PromptLibrary {
+    /// Load the templates bundled with the extension
+    pub fn load() -> Self {
+        Self::from_dir(&PROMPTS_DIR).expect("The bundled prompt templates are valid")
+    }
+
+    /// Load the templates in `dir`, failing on the first that is malformed
+    pub fn from_dir(dir: &Dir) -> Result<Self, String> {
+        let mut templates = dir
+            .files()
+            .filter(|file| file.path().extension().is_some_and(|ext| ext == "json"))
+            .map(|file| {
+                serde_json::from_slice::<PromptTemplate>(file.contents()).map_err(|e| {
+                    format!("Malformed prompt template {}: {}", file.path().display(), e)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        templates.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(Self { templates })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    /// The templates as MCP prompts, described by the first line of their template
+    pub fn prompts(&self) -> Vec<Prompt> {
+        self.templates
+            .iter()
+            .map(|template| {
+                let arguments = template
+                    .arguments
+                    .iter()
+                    .map(|arg| PromptArgument {
+                        name: arg.name.clone(),
+                        description: arg.description.clone(),
+                        required: arg.required,
+                    })
+                    .collect();
+                let description = template.template.lines().next().unwrap_or_default();
+                Prompt::new(&template.id, description, arguments)
+            })
+            .collect()
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.find(name).is_some()
+    }
+
+    /// A listing of the prompts and their arguments, for a model to choose from
+    pub fn describe(&self) -> String {
+        if self.is_empty() {
+            return "No prompts are available".to_string();
+        }
+        self.prompts()
+            .iter()
+            .map(|prompt| {
+                let mut entry = format!("{}: {}", prompt.name, prompt.description);
+                for arg in &prompt.arguments {
+                    entry.push_str(&format!(
+                        "\n  - {} ({}): {}",
+                        arg.name,
+                        if arg.required { "required" } else { "optional" },
+                        arg.description
+                    ));
+                }
+                entry
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Fill in the arguments of the prompt called `name`
+    ///
+    /// Each `{argument}` in the template is replaced by its value. Fails when the prompt does
+    /// not exist or a required argument is missing.
+    pub fn render(&self, name: &str, arguments: &Map<String, Value>) -> Result<String, String> {
+        let template = self
+            .find(name)
+            .ok_or_else(|| format!("No prompt named '{}'", name))?;
+
+        let mut rendered = template.template.clone();
+        for arg in &template.arguments {
+            let value = match arguments.get(&arg.name) {
+                Some(Value::String(value)) if !value.is_empty() => value.clone(),
+                Some(value) if !value.is_string() && !value.is_null() => value.to_string(),
+                _ if arg.required => {
+                    return Err(format!(
+                        "Missing required argument '{}' for prompt '{}'",
+                        arg.name, name
+                    ))
+                }
+                _ => NOT_GIVEN.to_string(),
+            };
+            rendered = rendered.replace(&format!("{{{}}}", arg.name), &value);
+        }
+        Ok(rendered)
+    }
+
+    fn find(&self, name: &str) -> Option<&PromptTemplate> {
+        self.templates.iter().find(|template| template.id == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_bundled_prompts_load_and_render() {
+        let library = PromptLibrary::load();
+        let prompt = library
+            .prompts()
+            .into_iter()
+            .find(|prompt| prompt.name == "unit_test")
+            .unwrap();
+        assert!(prompt
+            .arguments
+            .iter()
+            .any(|arg| arg.name == "source_file" && arg.required));
+
+        let arguments = json!({"source_file": "src/lib.rs"});
+        let rendered = library
+            .render("unit_test", arguments.as_object().unwrap())
+            .unwrap();
+        assert!(rendered.contains("unit tests for src/lib.rs"));
+        assert!(rendered.contains(NOT_GIVEN));
+        assert!(!rendered.contains('{'));
+
+        assert!(library.render("unit_test", &Map::new()).is_err());
+        assert!(library.render("missing", &Map::new()).is_err());
+    }
+
+    #[test]
+    fn test_malformed_template_is_an_error() {
+        use include_dir::{DirEntry, File};
+
+        static DIR: Dir = Dir::new(
+            "prompts",
+            &[
+                DirEntry::File(File::new(
+                    "prompts/greet.json",
+                    br#"{"id": "greet", "template": "Say hi to {name}", "arguments": []}"#,
+                )),
+                DirEntry::File(File::new("prompts/broken.json", b"{\"id\": ")),
+            ],
+        );
+        let err = PromptLibrary::from_dir(&DIR).unwrap_err();
+        assert!(err.contains("prompts/broken.json"));
+    }
+}
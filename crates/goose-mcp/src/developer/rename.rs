@@ -0,0 +1,242 @@
+use std::ops::Range;
+use std::path::Path;
+
+use tree_sitter::{Language, Parser, TreeCursor};
+
+use super::lang::get_language_identifier;
+
+/// A language the rename_symbol tool can parse
+#[derive(Clone, Copy)]
+enum Grammar {
+    Rust,
+    Python,
+}
+
+impl Grammar {
+    fn for_path(path: &Path) -> Option<Self> {
+        match get_language_identifier(path) {
+            "rust" => Some(Self::Rust),
+            "python" => Some(Self::Python),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::Python => "python",
+        }
+    }
+
+    fn language(self) -> Language {
+        match self {
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+        }
+    }
+
+    /// The text to replace the node at the cursor with, if it is an occurrence of the symbol
+    ///
+    /// Member names after a `.` and Python keyword argument names belong to another scope than
+    /// the variables and functions being renamed, so they are skipped. A Rust shorthand field,
+    /// as in `Point { x }`, keeps its field name and only renames the variable.
+    fn replacement(self, cursor: &TreeCursor, old_name: &str, new_name: &str) -> Option<String> {
+        let node = cursor.node();
+        match (self, node.kind()) {
+            (Self::Rust, "identifier")
+                if node.parent().map(|parent| parent.kind())
+                    == Some("shorthand_field_initializer") =>
+            {
+                Some(format!("{}: {}", old_name, new_name))
+            }
+            (Self::Rust, "identifier" | "type_identifier") => Some(new_name.to_string()),
+            (Self::Rust, "shorthand_field_identifier") => {
+                Some(format!("{}: {}", old_name, new_name))
+            }
+            (Self::Python, "identifier") => {
+                let parent = node.parent().map(|parent| parent.kind());
+                match (parent, cursor.field_name()) {
+                    (Some("attribute"), Some("attribute")) => None,
+                    (Some("keyword_argument"), Some("name")) => None,
+                    _ => Some(new_name.to_string()),
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The result of renaming a symbol in a source file
+#[derive(Debug, PartialEq)]
+pub struct Renamed {
+    pub content: String,
+    /// The 1-indexed lines that had an occurrence renamed
+    pub lines: Vec<usize>,
+    pub occurrences: usize,
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Rename every identifier named `old_name` in the source of the file at `path`
+///
+/// The file is parsed with tree-sitter, so only identifier tokens are renamed, while strings,
+/// comments, member names and longer names that contain `old_name` are left untouched. The
+/// rename is refused if the file does not parse cleanly or already uses `new_name`, since either
+/// could make the result wrong.
+pub fn rename_symbol(
+    path: &Path,
+    source: &str,
+    old_name: &str,
+    new_name: &str,
+) -> Result<Renamed, String> {
+    let grammar = Grammar::for_path(path).ok_or_else(|| {
+        format!(
+            "Renaming symbols is not supported for '{}', only Rust and Python files are",
+            path.display()
+        )
+    })?;
+    for name in [old_name, new_name] {
+        if !is_identifier(name) {
+            return Err(format!("'{}' is not a valid identifier", name));
+        }
+    }
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&grammar.language())
+        .map_err(|e| format!("Failed to load the {} grammar: {}", grammar.name(), e))?;
+    let tree = parser
+        .parse(source, None)
+        .ok_or_else(|| format!("Failed to parse '{}'", path.display()))?;
+    if tree.root_node().has_error() {
+        return Err(format!(
+            "'{}' has syntax errors as {}, fix them before renaming",
+            path.display(),
+            grammar.name()
+        ));
+    }
+
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    let mut cursor = tree.walk();
+    'walk: loop {
+        let node = cursor.node();
+        let text = &source[node.byte_range()];
+        if node.child_count() == 0 && (text == old_name || text == new_name) {
+            match grammar.replacement(&cursor, old_name, new_name) {
+                Some(_) if text == new_name => {
+                    return Err(format!(
+                        "'{}' is already used in '{}', renaming to it could change what names refer to",
+                        new_name,
+                        path.display()
+                    ));
+                }
+                Some(replacement) => edits.push((node.byte_range(), replacement)),
+                None => {}
+            }
+        }
+
+        if cursor.goto_first_child() || cursor.goto_next_sibling() {
+            continue;
+        }
+        while cursor.goto_parent() {
+            if cursor.goto_next_sibling() {
+                continue 'walk;
+            }
+        }
+        break;
+    }
+
+    if edits.is_empty() {
+        return Err(format!(
+            "No identifier named '{}' was found in '{}'",
+            old_name,
+            path.display()
+        ));
+    }
+
+    let mut content = String::with_capacity(source.len());
+    let mut lines = Vec::new();
+    let mut last = 0;
+    for (range, replacement) in &edits {
+        content.push_str(&source[last..range.start]);
+        content.push_str(replacement);
+        last = range.end;
+
+        let line = source[..range.start].matches('\n').count() + 1;
+        if lines.last() != Some(&line) {
+            lines.push(line);
+        }
+    }
+    content.push_str(&source[last..]);
+
+    Ok(Renamed {
+        content,
+        lines,
+        occurrences: edits.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_rename_python_skips_strings_and_comments() {
+        let source = indoc! {r#"
+            count = 0
+            counter = "count"
+            # count the items
+            for item in items:
+                count += 1
+            print(f"count={count}", counter, items.count(count))
+            report(count=count)
+        "#};
+
+        let renamed = rename_symbol(Path::new("script.py"), source, "count", "total").unwrap();
+        assert_eq!(
+            renamed.content,
+            indoc! {r#"
+                total = 0
+                counter = "count"
+                # count the items
+                for item in items:
+                    total += 1
+                print(f"count={total}", counter, items.count(total))
+                report(count=total)
+            "#}
+        );
+        assert_eq!(renamed.lines, vec![1, 5, 6, 7]);
+        assert_eq!(renamed.occurrences, 5);
+    }
+
+    #[test]
+    fn test_rename_rust_keeps_shorthand_field_names() {
+        let source = "fn mirror() -> Point {\n    let Point { x, .. } = origin();\n    Point { x, y: -x }\n}\n";
+        let renamed = rename_symbol(Path::new("point.rs"), source, "x", "dx").unwrap();
+        assert_eq!(
+            renamed.content,
+            "fn mirror() -> Point {\n    let Point { x: dx, .. } = origin();\n    Point { x: dx, y: -dx }\n}\n"
+        );
+        assert_eq!(renamed.lines, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_rename_refuses_unsafe_renames() {
+        let source = "fn main() {\n    let a = 1;\n    let b = a;\n}\n";
+        let path = Path::new("main.rs");
+
+        let err = rename_symbol(path, source, "a", "b").unwrap_err();
+        assert!(err.contains("already used"));
+        let err = rename_symbol(path, source, "a", "not valid").unwrap_err();
+        assert!(err.contains("not a valid identifier"));
+        let err = rename_symbol(path, "fn main( {", "main", "start").unwrap_err();
+        assert!(err.contains("syntax errors"));
+        let err = rename_symbol(Path::new("main.go"), source, "a", "c").unwrap_err();
+        assert!(err.contains("not supported"));
+    }
+}
@@ -0,0 +1,365 @@
+use std::fmt::{self, Write};
+use std::path::Path;
+
+/// Test and coverage report formats supported by the read_test_report tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Junit,
+    Lcov,
+}
+
+impl ReportFormat {
+    /// Parse an explicit format name as passed to the tool
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "junit" | "xml" => Some(Self::Junit),
+            "lcov" | "info" => Some(Self::Lcov),
+            _ => None,
+        }
+    }
+
+    /// Infer the format from a file name, such as `junit.xml` or `lcov.info`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if file_name == "lcov" || file_name.starts_with("lcov.") {
+            return Some(Self::Lcov);
+        }
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::from_name)
+    }
+}
+
+/// A test case that failed or errored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedTest {
+    pub suite: String,
+    pub name: String,
+    pub message: String,
+}
+
+/// Pass and fail counts from a JUnit XML report
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TestSummary {
+    pub tests: usize,
+    pub failures: usize,
+    pub errors: usize,
+    pub skipped: usize,
+    pub failed_tests: Vec<FailedTest>,
+}
+
+impl TestSummary {
+    pub fn passed(&self) -> usize {
+        self.tests - self.failures - self.errors - self.skipped
+    }
+}
+
+impl fmt::Display for TestSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} tests: {} passed, {} failed, {} errors, {} skipped",
+            self.tests,
+            self.passed(),
+            self.failures,
+            self.errors,
+            self.skipped
+        )?;
+        for test in &self.failed_tests {
+            write!(f, "\nFAILED {}::{}", test.suite, test.name)?;
+            if !test.message.is_empty() {
+                write!(f, "\n  {}", test.message.replace('\n', "\n  "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a JUnit XML report, counting test cases rather than trusting the summary attributes
+///
+/// Accepts both a `<testsuites>` root and a single `<testsuite>`.
+pub fn parse_junit(xml: &str) -> Result<TestSummary, String> {
+    let document = roxmltree::Document::parse(xml).map_err(|e| e.to_string())?;
+
+    let mut summary = TestSummary::default();
+    for case in document
+        .descendants()
+        .filter(|node| node.has_tag_name("testcase"))
+    {
+        summary.tests += 1;
+
+        let outcome = case.children().find(|child| {
+            child.has_tag_name("failure")
+                || child.has_tag_name("error")
+                || child.has_tag_name("skipped")
+        });
+        let Some(outcome) = outcome else {
+            continue;
+        };
+        if outcome.has_tag_name("skipped") {
+            summary.skipped += 1;
+            continue;
+        }
+        if outcome.has_tag_name("failure") {
+            summary.failures += 1;
+        } else {
+            summary.errors += 1;
+        }
+
+        let suite = case
+            .attribute("classname")
+            .or_else(|| {
+                case.ancestors()
+                    .find(|node| node.has_tag_name("testsuite"))
+                    .and_then(|suite| suite.attribute("name"))
+            })
+            .unwrap_or_default();
+        let message = outcome
+            .attribute("message")
+            .or_else(|| outcome.text())
+            .unwrap_or_default()
+            .trim();
+        summary.failed_tests.push(FailedTest {
+            suite: suite.to_string(),
+            name: case.attribute("name").unwrap_or_default().to_string(),
+            message: message.to_string(),
+        });
+    }
+    Ok(summary)
+}
+
+/// Line coverage of one source file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileCoverage {
+    pub path: String,
+    pub lines_found: usize,
+    pub lines_hit: usize,
+    pub uncovered_lines: Vec<u32>,
+}
+
+impl FileCoverage {
+    pub fn percent(&self) -> f64 {
+        percent(self.lines_hit, self.lines_found)
+    }
+}
+
+/// Line coverage from an LCOV tracefile
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoverageSummary {
+    pub files: Vec<FileCoverage>,
+}
+
+impl CoverageSummary {
+    pub fn lines_found(&self) -> usize {
+        self.files.iter().map(|f| f.lines_found).sum()
+    }
+
+    pub fn lines_hit(&self) -> usize {
+        self.files.iter().map(|f| f.lines_hit).sum()
+    }
+}
+
+impl fmt::Display for CoverageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:.1}% line coverage: {} of {} lines in {} files",
+            percent(self.lines_hit(), self.lines_found()),
+            self.lines_hit(),
+            self.lines_found(),
+            self.files.len()
+        )?;
+
+        // Least covered files first, fully covered ones are left out
+        let mut files: Vec<&FileCoverage> = self
+            .files
+            .iter()
+            .filter(|file| !file.uncovered_lines.is_empty())
+            .collect();
+        files.sort_by(|a, b| a.percent().total_cmp(&b.percent()));
+        for file in files {
+            write!(
+                f,
+                "\n{} {:.1}% uncovered lines: {}",
+                file.path,
+                file.percent(),
+                format_ranges(&file.uncovered_lines)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse an LCOV tracefile, using the `DA` line records for coverage
+pub fn parse_lcov(text: &str) -> Result<CoverageSummary, String> {
+    let mut summary = CoverageSummary::default();
+    let mut current: Option<FileCoverage> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let invalid = || format!("Invalid LCOV record on line {}: {}", index + 1, line);
+
+        if let Some(path) = line.strip_prefix("SF:") {
+            current = Some(FileCoverage {
+                path: path.to_string(),
+                ..Default::default()
+            });
+        } else if let Some(record) = line.strip_prefix("DA:") {
+            let file = current.as_mut().ok_or_else(invalid)?;
+            let mut fields = record.split(',');
+            let line_number: u32 = fields
+                .next()
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(invalid)?;
+            let hits: u64 = fields
+                .next()
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(invalid)?;
+            file.lines_found += 1;
+            if hits > 0 {
+                file.lines_hit += 1;
+            } else {
+                file.uncovered_lines.push(line_number);
+            }
+        } else if line == "end_of_record" {
+            summary.files.extend(current.take());
+        }
+    }
+    // Tolerate a truncated file missing its last end_of_record
+    summary.files.extend(current);
+    Ok(summary)
+}
+
+fn percent(hit: usize, found: usize) -> f64 {
+    if found == 0 {
+        100.0
+    } else {
+        hit as f64 * 100.0 / found as f64
+    }
+}
+
+/// Format sorted line numbers as compact ranges, like `3-5, 9`
+fn format_ranges(lines: &[u32]) -> String {
+    let mut lines = lines.to_vec();
+    lines.sort_unstable();
+
+    let mut output = String::new();
+    let mut iter = lines.into_iter().peekable();
+    while let Some(start) = iter.next() {
+        let mut end = start;
+        while iter.peek() == Some(&(end + 1)) {
+            end = iter.next().unwrap();
+        }
+        if !output.is_empty() {
+            output.push_str(", ");
+        }
+        if start == end {
+            let _ = write!(output, "{}", start);
+        } else {
+            let _ = write!(output, "{}-{}", start, end);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JUNIT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="parser" tests="3">
+    <testcase classname="parser::tests" name="parses_empty" time="0.01"/>
+    <testcase classname="parser::tests" name="parses_nested" time="0.02">
+      <failure message="assertion failed: left == right">expected 2, got 3</failure>
+    </testcase>
+    <testcase classname="parser::tests" name="parses_unicode">
+      <skipped/>
+    </testcase>
+  </testsuite>
+  <testsuite name="network">
+    <testcase name="connects">
+      <error>connection refused</error>
+    </testcase>
+  </testsuite>
+</testsuites>"#;
+
+    const LCOV: &str = "TN:
+SF:src/lib.rs
+DA:1,4
+DA:2,4
+DA:3,0
+DA:4,0
+DA:5,0
+DA:9,0
+DA:10,1
+end_of_record
+SF:src/main.rs
+DA:1,1
+DA:2,1
+end_of_record
+";
+
+    #[test]
+    fn test_parse_junit() {
+        let summary = parse_junit(JUNIT).unwrap();
+        assert_eq!(summary.tests, 4);
+        assert_eq!(summary.passed(), 1);
+        assert_eq!(summary.failures, 1);
+        assert_eq!(summary.errors, 1);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(
+            summary.failed_tests,
+            vec![
+                FailedTest {
+                    suite: "parser::tests".to_string(),
+                    name: "parses_nested".to_string(),
+                    message: "assertion failed: left == right".to_string(),
+                },
+                FailedTest {
+                    suite: "network".to_string(),
+                    name: "connects".to_string(),
+                    message: "connection refused".to_string(),
+                },
+            ]
+        );
+        assert!(summary
+            .to_string()
+            .starts_with("4 tests: 1 passed, 1 failed, 1 errors, 1 skipped"));
+
+        assert!(parse_junit("<testsuite>").is_err());
+    }
+
+    #[test]
+    fn test_parse_lcov() {
+        let summary = parse_lcov(LCOV).unwrap();
+        assert_eq!(summary.files.len(), 2);
+        assert_eq!(summary.lines_found(), 9);
+        assert_eq!(summary.lines_hit(), 5);
+
+        let lib = &summary.files[0];
+        assert_eq!(lib.path, "src/lib.rs");
+        assert_eq!(lib.uncovered_lines, vec![3, 4, 5, 9]);
+
+        let output = summary.to_string();
+        assert!(output.starts_with("55.6% line coverage: 5 of 9 lines in 2 files"));
+        assert!(output.contains("src/lib.rs 42.9% uncovered lines: 3-5, 9"));
+        // Fully covered files are not listed
+        assert!(!output.contains("src/main.rs"));
+
+        assert!(parse_lcov("DA:1,1").is_err());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            ReportFormat::from_path(Path::new("target/junit.xml")),
+            Some(ReportFormat::Junit)
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("coverage/lcov.info")),
+            Some(ReportFormat::Lcov)
+        );
+        assert_eq!(ReportFormat::from_path(Path::new("report.txt")), None);
+    }
+}
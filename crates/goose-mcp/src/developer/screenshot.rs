@@ -0,0 +1,279 @@
+use mcp_core::handler::ToolError;
+use std::io::Cursor;
+use xcap::image::codecs::jpeg::JpegEncoder;
+use xcap::image::codecs::png::PngEncoder;
+use xcap::image::codecs::webp::WebPEncoder;
+use xcap::image::imageops::{self, FilterType};
+use xcap::image::{DynamicImage, ImageEncoder, RgbaImage};
+
+/// Default width screenshots are scaled down to, preserving the aspect ratio
+pub const DEFAULT_MAX_WIDTH: u32 = 768;
+
+/// Default JPEG quality, from 1 (smallest) to 100 (best)
+pub const DEFAULT_JPEG_QUALITY: u8 = 80;
+
+/// Image formats the screen_capture tool can encode to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenshotFormat {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl ScreenshotFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpeg" | "jpg" => Some(Self::Jpeg),
+            "webp" => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+/// How a captured screenshot is scaled and encoded
+#[derive(Debug, Clone, Copy)]
+pub struct EncodeOptions {
+    pub format: ScreenshotFormat,
+    pub max_width: u32,
+    /// Only used for JPEG, WebP is always encoded losslessly
+    pub quality: u8,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            format: ScreenshotFormat::Png,
+            max_width: DEFAULT_MAX_WIDTH,
+            quality: DEFAULT_JPEG_QUALITY,
+        }
+    }
+}
+
+/// A rectangle of a captured image, in pixels from the top left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Crop the image to `region`, which must lie within the image bounds
+pub fn crop_region(mut image: RgbaImage, region: &Region) -> Result<RgbaImage, ToolError> {
+    let (width, height) = image.dimensions();
+    let fits = region.width > 0
+        && region.height > 0
+        && region
+            .x
+            .checked_add(region.width)
+            .is_some_and(|r| r <= width)
+        && region
+            .y
+            .checked_add(region.height)
+            .is_some_and(|b| b <= height);
+    if !fits {
+        return Err(ToolError::ExecutionError(format!(
+            "Region (x: {}, y: {}, width: {}, height: {}) is outside the captured image, which is {}x{}",
+            region.x, region.y, region.width, region.height, width, height
+        )));
+    }
+
+    Ok(imageops::crop(&mut image, region.x, region.y, region.width, region.height).to_image())
+}
+
+/// Resize the image to at most `max_width` while maintaining the aspect ratio
+pub fn resize_to_width(image: RgbaImage, max_width: u32) -> RgbaImage {
+    if image.width() <= max_width {
+        return image;
+    }
+    let scale = max_width as f32 / image.width() as f32;
+    let new_height = ((image.height() as f32 * scale) as u32).max(1);
+    imageops::resize(&image, max_width, new_height, FilterType::Lanczos3)
+}
+
+/// Scale and encode a screenshot, returning the encoded bytes
+pub fn encode_screenshot(image: RgbaImage, options: &EncodeOptions) -> Result<Vec<u8>, ToolError> {
+    let image = resize_to_width(image, options.max_width);
+    let (width, height) = image.dimensions();
+
+    let mut bytes: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(&mut bytes);
+    let result = match options.format {
+        ScreenshotFormat::Png => PngEncoder::new(cursor).write_image(
+            image.as_raw(),
+            width,
+            height,
+            xcap::image::ExtendedColorType::Rgba8,
+        ),
+        ScreenshotFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let rgb = DynamicImage::ImageRgba8(image).to_rgb8();
+            JpegEncoder::new_with_quality(cursor, options.quality.clamp(1, 100)).write_image(
+                rgb.as_raw(),
+                width,
+                height,
+                xcap::image::ExtendedColorType::Rgb8,
+            )
+        }
+        ScreenshotFormat::Webp => WebPEncoder::new_lossless(cursor).write_image(
+            image.as_raw(),
+            width,
+            height,
+            xcap::image::ExtendedColorType::Rgba8,
+        ),
+    };
+    result.map_err(|e| ToolError::ExecutionError(format!("Failed to write image buffer {}", e)))?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xcap::image::Rgba;
+
+    /// A noisy gradient, closer to a real screenshot than a flat color
+    fn synthetic_image(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let noise = ((x * 7919 + y * 104729) % 64) as u8;
+            Rgba([(x % 256) as u8, (y % 256) as u8, noise, 255])
+        })
+    }
+
+    #[test]
+    fn test_format_names_and_mime_types() {
+        assert_eq!(
+            ScreenshotFormat::from_name("JPG"),
+            Some(ScreenshotFormat::Jpeg)
+        );
+        assert_eq!(ScreenshotFormat::from_name("gif"), None);
+        assert_eq!(ScreenshotFormat::Png.mime_type(), "image/png");
+        assert_eq!(ScreenshotFormat::Jpeg.mime_type(), "image/jpeg");
+        assert_eq!(ScreenshotFormat::Webp.mime_type(), "image/webp");
+    }
+
+    #[test]
+    fn test_encoded_formats() {
+        let image = synthetic_image(400, 300);
+
+        let png = encode_screenshot(image.clone(), &EncodeOptions::default()).unwrap();
+        let jpeg = encode_screenshot(
+            image.clone(),
+            &EncodeOptions {
+                format: ScreenshotFormat::Jpeg,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let webp = encode_screenshot(
+            image,
+            &EncodeOptions {
+                format: ScreenshotFormat::Webp,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            xcap::image::guess_format(&png).unwrap(),
+            xcap::image::ImageFormat::Png
+        );
+        assert_eq!(
+            xcap::image::guess_format(&jpeg).unwrap(),
+            xcap::image::ImageFormat::Jpeg
+        );
+        assert_eq!(
+            xcap::image::guess_format(&webp).unwrap(),
+            xcap::image::ImageFormat::WebP
+        );
+        assert!(jpeg.len() < png.len());
+    }
+
+    #[test]
+    fn test_crop_region() {
+        let image = synthetic_image(400, 300);
+        let region = Region {
+            x: 50,
+            y: 20,
+            width: 100,
+            height: 80,
+        };
+
+        let cropped = crop_region(image.clone(), &region).unwrap();
+        assert_eq!(cropped.dimensions(), (100, 80));
+        assert_eq!(cropped.get_pixel(0, 0), image.get_pixel(50, 20));
+        assert_eq!(cropped.get_pixel(99, 79), image.get_pixel(149, 99));
+
+        // A region touching the bottom right edge is still inside the image
+        let edge = Region {
+            x: 300,
+            y: 200,
+            width: 100,
+            height: 100,
+        };
+        assert_eq!(
+            crop_region(image.clone(), &edge).unwrap().dimensions(),
+            (100, 100)
+        );
+    }
+
+    #[test]
+    fn test_crop_region_out_of_bounds() {
+        let image = synthetic_image(400, 300);
+        let region = Region {
+            x: 350,
+            y: 0,
+            width: 100,
+            height: 100,
+        };
+
+        let err = crop_region(image.clone(), &region).unwrap_err();
+        assert!(matches!(err, ToolError::ExecutionError(_)));
+        assert!(err.to_string().contains("which is 400x300"));
+
+        let empty = Region {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 10,
+        };
+        assert!(crop_region(image, &empty).is_err());
+    }
+
+    #[test]
+    fn test_jpeg_quality_and_max_width() {
+        let image = synthetic_image(1600, 900);
+
+        let low = encode_screenshot(
+            image.clone(),
+            &EncodeOptions {
+                format: ScreenshotFormat::Jpeg,
+                quality: 10,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let high = encode_screenshot(
+            image.clone(),
+            &EncodeOptions {
+                format: ScreenshotFormat::Jpeg,
+                quality: 95,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(low.len() < high.len());
+
+        let resized = resize_to_width(image, 800);
+        assert_eq!(resized.dimensions(), (800, 450));
+    }
+}
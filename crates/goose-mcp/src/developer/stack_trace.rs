@@ -0,0 +1,332 @@
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Most frames kept in a summary
+const MAX_FRAMES: usize = 5;
+
+/// Path fragments of dependencies and runtimes, whose frames are not in the project
+const LIBRARY_PATHS: &[&str] = &[
+    "site-packages",
+    "dist-packages",
+    "/lib/python",
+    "node_modules",
+    "node:",
+    "/rustc/",
+    ".cargo/registry",
+    ".cargo/git",
+    "/library/std/",
+    "/library/core/",
+];
+
+lazy_static! {
+    static ref PYTHON_FRAME: Regex =
+        Regex::new(r#"^\s*File "(?P<path>[^"]+)", line (?P<line>\d+)(?:, in (?P<function>.+))?"#)
+            .unwrap();
+    static ref RUST_PANIC: Regex =
+        Regex::new(r"^thread '(?P<thread>[^']*)' panicked at (?P<rest>.*)$").unwrap();
+    static ref RUST_LOCATION: Regex = Regex::new(r"(?P<location>[^\s',]+:\d+:\d+)").unwrap();
+    static ref RUST_FRAME: Regex = Regex::new(r"^\s*\d+: (?P<function>.+)$").unwrap();
+    static ref RUST_FRAME_LOCATION: Regex =
+        Regex::new(r"^\s*at (?P<location>.+:\d+(?::\d+)?)$").unwrap();
+    static ref NODE_ERROR: Regex =
+        Regex::new(r"^(?:Uncaught )?(?P<message>[A-Za-z_$][\w$.]*(?:Error|Exception)\b.*)$")
+            .unwrap();
+    static ref NODE_FRAME: Regex = Regex::new(
+        r"^\s+at (?:(?P<function>.+?) \((?P<location>.+:\d+:\d+)\)|(?P<bare>.+:\d+:\d+))$"
+    )
+    .unwrap();
+}
+
+/// The language runtime that printed a stack trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceKind {
+    RustPanic,
+    Python,
+    Node,
+}
+
+impl TraceKind {
+    fn describe(self) -> &'static str {
+        match self {
+            Self::RustPanic => "Rust panic",
+            Self::Python => "Python traceback",
+            Self::Node => "Node.js error",
+        }
+    }
+}
+
+/// A single frame of a stack trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Where the frame is, as `path:line` or `path:line:column`
+    pub location: String,
+    pub function: Option<String>,
+}
+
+/// The essential parts of a stack trace: what went wrong and where in the project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceSummary {
+    pub kind: TraceKind,
+    pub message: String,
+    /// The frames closest to the error, in project files when there are any
+    pub frames: Vec<Frame>,
+}
+
+impl TraceSummary {
+    pub fn render(&self) -> String {
+        let mut summary = format!("{}: {}", self.kind.describe(), self.message);
+        for frame in &self.frames {
+            match &frame.function {
+                Some(function) => {
+                    summary.push_str(&format!("\n  at {} ({})", function, frame.location))
+                }
+                None => summary.push_str(&format!("\n  at {}", frame.location)),
+            }
+        }
+        summary
+    }
+}
+
+/// Find the first Rust panic, Python traceback or Node.js error in command output
+///
+/// Frames in `project_root`, or with relative paths, are preferred over frames in
+/// dependencies and the standard library.
+pub fn find_trace(output: &str, project_root: &Path) -> Option<TraceSummary> {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut summary = python_trace(&lines)
+        .or_else(|| rust_panic(&lines))
+        .or_else(|| node_error(&lines))?;
+
+    let project: Vec<Frame> = summary
+        .frames
+        .iter()
+        .filter(|frame| is_project_location(&frame.location, project_root))
+        .cloned()
+        .collect();
+    if !project.is_empty() {
+        summary.frames = project;
+    }
+    summary.frames.truncate(MAX_FRAMES);
+    Some(summary)
+}
+
+fn is_project_location(location: &str, project_root: &Path) -> bool {
+    if LIBRARY_PATHS
+        .iter()
+        .any(|library| location.contains(library))
+    {
+        return false;
+    }
+    let path = location.trim_start_matches("file://");
+    !Path::new(path).is_absolute() || Path::new(path).starts_with(project_root)
+}
+
+/// A Python traceback, whose frames are listed with the most recent call last
+fn python_trace(lines: &[&str]) -> Option<TraceSummary> {
+    let start = lines.iter().position(|line| {
+        line.trim_start()
+            .starts_with("Traceback (most recent call last):")
+    })?;
+
+    let mut frames = Vec::new();
+    let mut message = None;
+    for line in &lines[start + 1..] {
+        if let Some(captures) = PYTHON_FRAME.captures(line) {
+            frames.push(Frame {
+                location: format!("{}:{}", &captures["path"], &captures["line"]),
+                function: captures.name("function").map(|f| f.as_str().to_string()),
+            });
+        } else if !line.starts_with(' ') && !line.trim().is_empty() {
+            // The exception follows the frames, unindented
+            message = Some(line.trim().to_string());
+            break;
+        }
+    }
+
+    frames.reverse();
+    Some(TraceSummary {
+        kind: TraceKind::Python,
+        message: message.unwrap_or_else(|| "unknown exception".to_string()),
+        frames,
+    })
+}
+
+/// A Rust panic, with the frames of its backtrace if one was printed
+fn rust_panic(lines: &[&str]) -> Option<TraceSummary> {
+    let start = lines.iter().position(|line| RUST_PANIC.is_match(line))?;
+    let captures = RUST_PANIC.captures(lines[start])?;
+    let rest = captures["rest"].trim_end_matches(':');
+
+    // Panics print `at 'message', location` before Rust 1.73, and `at location:` then the
+    // message on the following lines since
+    let mut frames = Vec::new();
+    let location = RUST_LOCATION
+        .captures(rest)
+        .map(|c| c["location"].to_string());
+    let message = if let Some(quoted) = rest.strip_prefix('\'') {
+        quoted
+            .rsplit_once("', ")
+            .map_or(quoted, |(message, _)| message)
+            .to_string()
+    } else {
+        lines[start + 1..]
+            .iter()
+            .take_while(|line| !line.starts_with("note:") && !line.starts_with("stack backtrace:"))
+            .map(|line| line.trim())
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    if let Some(location) = location {
+        frames.push(Frame {
+            location,
+            function: None,
+        });
+    }
+
+    let mut function = None;
+    for line in &lines[start + 1..] {
+        if let Some(captures) = RUST_FRAME.captures(line) {
+            function = Some(captures["function"].to_string());
+        } else if let Some(captures) = RUST_FRAME_LOCATION.captures(line) {
+            let location = captures["location"].trim_start_matches("./").to_string();
+            // The panic location is already the first frame, it only lacked the function
+            match frames.iter_mut().find(|frame| frame.location == location) {
+                Some(frame) => frame.function = function.take(),
+                None => frames.push(Frame {
+                    location,
+                    function: function.take(),
+                }),
+            }
+        }
+    }
+
+    Some(TraceSummary {
+        kind: TraceKind::RustPanic,
+        message: if message.is_empty() {
+            format!("thread '{}' panicked", &captures["thread"])
+        } else {
+            message
+        },
+        frames,
+    })
+}
+
+/// A Node.js error followed by its `at` frames
+fn node_error(lines: &[&str]) -> Option<TraceSummary> {
+    let start = lines.iter().enumerate().position(|(i, line)| {
+        NODE_ERROR.is_match(line)
+            && lines
+                .get(i + 1)
+                .is_some_and(|next| NODE_FRAME.is_match(next))
+    })?;
+    let message = NODE_ERROR.captures(lines[start])?["message"].to_string();
+
+    let frames = lines[start + 1..]
+        .iter()
+        .map_while(|line| NODE_FRAME.captures(line))
+        .map(|captures| match captures.name("bare") {
+            Some(location) => Frame {
+                location: location.as_str().to_string(),
+                function: None,
+            },
+            None => Frame {
+                location: captures["location"].to_string(),
+                function: Some(captures["function"].to_string()),
+            },
+        })
+        .collect();
+
+    Some(TraceSummary {
+        kind: TraceKind::Node,
+        message,
+        frames,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_python_traceback() {
+        let output = indoc! {r#"
+            Running migrations...
+            Traceback (most recent call last):
+              File "/repo/manage.py", line 22, in <module>
+                main()
+              File "/repo/app/migrate.py", line 41, in apply
+                version = int(row["version"])
+              File "/usr/lib/python3.12/site-packages/sqlalchemy/engine/row.py", line 12, in __getitem__
+                return self._data[key]
+            KeyError: 'version'
+        "#};
+
+        let summary = find_trace(output, Path::new("/repo")).unwrap();
+        assert_eq!(summary.kind, TraceKind::Python);
+        assert_eq!(summary.message, "KeyError: 'version'");
+        assert_eq!(
+            summary.frames,
+            vec![
+                Frame {
+                    location: "/repo/app/migrate.py:41".to_string(),
+                    function: Some("apply".to_string()),
+                },
+                Frame {
+                    location: "/repo/manage.py:22".to_string(),
+                    function: Some("<module>".to_string()),
+                },
+            ]
+        );
+        assert!(summary.render().starts_with(
+            "Python traceback: KeyError: 'version'\n  at apply (/repo/app/migrate.py:41)"
+        ));
+    }
+
+    #[test]
+    fn test_rust_panic_and_node_error() {
+        let output = indoc! {r#"
+            thread 'main' panicked at src/config.rs:17:36:
+            called `Option::unwrap()` on a `None` value
+            stack backtrace:
+               0: rust_begin_unwind
+                         at /rustc/abc/library/std/src/panicking.rs:645:5
+               1: app::config::load
+                         at ./src/config.rs:17:36
+               2: app::main
+                         at ./src/main.rs:4:5
+            note: Some details are omitted
+        "#};
+        let summary = find_trace(output, Path::new("/repo")).unwrap();
+        assert_eq!(summary.kind, TraceKind::RustPanic);
+        assert_eq!(
+            summary.message,
+            "called `Option::unwrap()` on a `None` value"
+        );
+        let locations: Vec<&str> = summary.frames.iter().map(|f| f.location.as_str()).collect();
+        assert_eq!(locations, vec!["src/config.rs:17:36", "src/main.rs:4:5"]);
+        assert_eq!(
+            summary.frames[0].function.as_deref(),
+            Some("app::config::load")
+        );
+
+        let output = indoc! {r#"
+            TypeError: Cannot read properties of undefined (reading 'id')
+                at getUser (/repo/src/users.js:12:20)
+                at /repo/node_modules/express/lib/router/layer.js:95:5
+                at process.processTicksAndRejections (node:internal/process/task_queues:95:5)
+        "#};
+        let summary = find_trace(output, Path::new("/repo")).unwrap();
+        assert_eq!(summary.kind, TraceKind::Node);
+        assert_eq!(
+            summary.message,
+            "TypeError: Cannot read properties of undefined (reading 'id')"
+        );
+        assert_eq!(summary.frames.len(), 1);
+        assert_eq!(summary.frames[0].location, "/repo/src/users.js:12:20");
+
+        assert!(find_trace("all tests passed", Path::new("/repo")).is_none());
+    }
+}
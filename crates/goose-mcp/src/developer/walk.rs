@@ -0,0 +1,278 @@
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+
+/// Options controlling how the developer tools walk a directory tree
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// Follow symlinked files and directories. Off by default, since links can form cycles
+    /// or point outside of the directory being searched.
+    pub follow_symlinks: bool,
+    /// Only include files matching this glob, e.g. `*.rs`
+    pub glob: Option<String>,
+}
+
+/// Walk all files under `root`, respecting `.gitignore` and other ignore files
+///
+/// When symlinks are followed, cycles are detected by the walker and skipped, and any link
+/// that resolves outside of `root` is pruned so a search cannot escape the requested tree.
+/// Returns an error if the glob is invalid.
+pub fn walk_files(
+    root: &Path,
+    options: &WalkOptions,
+) -> Result<impl Iterator<Item = PathBuf>, ignore::Error> {
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .follow_links(options.follow_symlinks)
+        // Respect .gitignore files even when the root is not inside a git repository
+        .require_git(false);
+    if let Some(glob) = &options.glob {
+        let overrides = OverrideBuilder::new(root).add(glob)?.build()?;
+        builder.overrides(overrides);
+    }
+    if options.follow_symlinks {
+        builder.filter_entry(move |entry| {
+            // Only links can leave the tree, everything else is nested under an accepted entry
+            !entry.path_is_symlink()
+                || entry
+                    .path()
+                    .canonicalize()
+                    .is_ok_and(|target| target.starts_with(&canonical_root))
+        });
+    }
+
+    Ok(builder
+        .build()
+        // Errors include symlink loops and unreadable entries, neither of which we can search
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path()))
+}
+
+/// A listing of the files and directories under a root, as returned by [`directory_tree`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryTree {
+    /// One line per entry, indented by depth, with directories ending in `/`
+    pub lines: Vec<String>,
+    /// How many entries were left out after reaching the entry limit
+    pub omitted: usize,
+}
+
+impl std::fmt::Display for DirectoryTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.lines.join("\n"))?;
+        if self.omitted > 0 {
+            write!(
+                f,
+                "\n[... {} more entries not shown, view a subdirectory or lower max_depth to see them ...]",
+                self.omitted
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// List the entries under `root` up to `max_depth` levels deep, respecting `.gitignore`
+///
+/// Entries are sorted by name within each directory, and files are shown with their size.
+/// At most `max_entries` entries are listed, the rest are only counted.
+pub fn directory_tree(root: &Path, max_depth: usize, max_entries: usize) -> DirectoryTree {
+    let walker = WalkBuilder::new(root)
+        .max_depth(Some(max_depth))
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+
+    let mut tree = DirectoryTree {
+        lines: vec![format!("{}/", root.display())],
+        omitted: 0,
+    };
+    // The root itself is the first entry, and is not counted
+    for entry in walker.filter_map(|entry| entry.ok()).skip(1) {
+        if tree.lines.len() > max_entries {
+            tree.omitted += 1;
+            continue;
+        }
+        let indent = "  ".repeat(entry.depth());
+        let name = entry.file_name().to_string_lossy();
+        let line = if entry.file_type().is_some_and(|t| t.is_dir()) {
+            format!("{}{}/", indent, name)
+        } else {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            format!("{}{} ({})", indent, name, format_size(size))
+        };
+        tree.lines.push(line);
+    }
+    tree
+}
+
+/// Format a number of bytes for display, like `512 B` or `1.5 KB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    fn relative_paths(root: &Path, options: WalkOptions) -> Vec<String> {
+        let mut paths: Vec<String> = walk_files(root, &options)
+            .unwrap()
+            .map(|p| p.strip_prefix(root).unwrap().to_string_lossy().to_string())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_walk_does_not_follow_symlinks_by_default() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("real")).unwrap();
+        fs::write(root.join("real/file.txt"), "hello").unwrap();
+        symlink(root.join("real"), root.join("linked")).unwrap();
+
+        assert_eq!(
+            relative_paths(root, WalkOptions::default()),
+            vec!["real/file.txt"]
+        );
+        assert_eq!(
+            relative_paths(
+                root,
+                WalkOptions {
+                    follow_symlinks: true,
+                    ..Default::default()
+                }
+            ),
+            vec!["linked/file.txt", "real/file.txt"]
+        );
+    }
+
+    #[test]
+    fn test_walk_terminates_on_symlink_cycle() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("a/b/file.txt"), "hello").unwrap();
+        // a/b/loop -> a, which would recurse forever without cycle detection
+        symlink(root.join("a"), root.join("a/b/loop")).unwrap();
+
+        assert_eq!(
+            relative_paths(
+                root,
+                WalkOptions {
+                    follow_symlinks: true,
+                    ..Default::default()
+                }
+            ),
+            vec!["a/b/file.txt"]
+        );
+    }
+
+    #[test]
+    fn test_walk_does_not_escape_root() {
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("inside.txt"), "hello").unwrap();
+        symlink(outside.path(), root.join("escape")).unwrap();
+        symlink(outside.path().join("secret.txt"), root.join("secret.txt")).unwrap();
+
+        assert_eq!(
+            relative_paths(
+                root,
+                WalkOptions {
+                    follow_symlinks: true,
+                    ..Default::default()
+                }
+            ),
+            vec!["inside.txt"]
+        );
+    }
+
+    #[test]
+    fn test_walk_glob_filter() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("README.md"), "# readme").unwrap();
+
+        assert_eq!(
+            relative_paths(
+                root,
+                WalkOptions {
+                    glob: Some("*.rs".to_string()),
+                    ..Default::default()
+                }
+            ),
+            vec!["src/main.rs"]
+        );
+
+        let invalid = WalkOptions {
+            glob: Some("[".to_string()),
+            ..Default::default()
+        };
+        assert!(walk_files(root, &invalid).is_err());
+    }
+
+    #[test]
+    fn test_directory_tree_limits_depth() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src/nested/deeper")).unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+        fs::write(root.join("src/main.rs"), "x".repeat(2048)).unwrap();
+        fs::write(root.join("src/nested/deeper/hidden.rs"), "").unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::create_dir(root.join("target")).unwrap();
+        fs::write(root.join("target/build.o"), "").unwrap();
+
+        let tree = directory_tree(root, 2, 100);
+        assert_eq!(
+            tree.lines[1..],
+            [
+                "  Cargo.toml (9 B)",
+                "  src/",
+                "    main.rs (2.0 KB)",
+                "    nested/",
+            ]
+        );
+        assert_eq!(tree.omitted, 0);
+        assert!(!tree.to_string().contains("more entries"));
+    }
+
+    #[test]
+    fn test_directory_tree_caps_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        for i in 0..10 {
+            fs::write(root.join(format!("file{}.txt", i)), "").unwrap();
+        }
+
+        let tree = directory_tree(root, 1, 3);
+        // The root line and then three entries
+        assert_eq!(tree.lines.len(), 4);
+        assert_eq!(tree.lines[1], "  file0.txt (0 B)");
+        assert_eq!(tree.omitted, 7);
+        assert!(tree.to_string().ends_with(
+            "[... 7 more entries not shown, view a subdirectory or lower max_depth to see them ...]"
+        ));
+    }
+}
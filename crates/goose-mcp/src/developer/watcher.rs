@@ -0,0 +1,176 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use mcp_core::resource::Resource;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use url::Url;
+
+/// Environment variable that turns on watching the files listed as resources, off by default
+pub const WATCH_RESOURCES_ENV: &str = "GOOSE_WATCH_RESOURCES";
+
+/// Whether `GOOSE_WATCH_RESOURCES` asks for the files listed as resources to be watched
+pub fn watch_resources_enabled() -> bool {
+    matches!(
+        std::env::var(WATCH_RESOURCES_ENV).as_deref(),
+        Ok("1") | Ok("true")
+    )
+}
+
+/// Keeps the timestamps of file resources current when their files change on disk
+///
+/// Changes made outside the agent, by a build step or another editor, then count as a fresh
+/// access of the resource. Only the directories of listed files are watched, and not
+/// recursively, so the cost does not grow with the size of the tree.
+pub struct ResourceWatcher {
+    watcher: Mutex<RecommendedWatcher>,
+    watched_dirs: Mutex<HashSet<PathBuf>>,
+    resources: Arc<Mutex<HashMap<String, Resource>>>,
+}
+
+impl ResourceWatcher {
+    /// Start a watcher that updates the timestamps of `resources`, which are keyed by URI
+    pub fn new(resources: Arc<Mutex<HashMap<String, Resource>>>) -> Result<Self, String> {
+        let listed = Arc::clone(&resources);
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::warn!("Error watching resource files: {}", e);
+                    return;
+                }
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                return;
+            }
+
+            let mut resources = listed.lock().unwrap();
+            for path in &event.paths {
+                let Ok(uri) = Url::from_file_path(path) else {
+                    continue;
+                };
+                if let Some(resource) = resources.get_mut(uri.as_str()) {
+                    resource.update_timestamp();
+                }
+            }
+        })
+        .map_err(|e| format!("Failed to start watching resource files: {}", e))?;
+
+        Ok(Self {
+            watcher: Mutex::new(watcher),
+            watched_dirs: Mutex::new(HashSet::new()),
+            resources,
+        })
+    }
+
+    /// Watch the file at `path` for changes
+    ///
+    /// Its directory is watched rather than the file, so the file is still followed after it
+    /// is replaced by an atomic write.
+    pub fn watch(&self, path: &Path) -> Result<(), String> {
+        let Some(dir) = path.parent() else {
+            return Ok(());
+        };
+        let mut watched_dirs = self.watched_dirs.lock().unwrap();
+        if watched_dirs.contains(dir) {
+            return Ok(());
+        }
+        self.watcher
+            .lock()
+            .unwrap()
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch {}: {}", dir.display(), e))?;
+        watched_dirs.insert(dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Stop watching for changes to the file at `path`, once it is no longer listed
+    ///
+    /// Its directory stays watched while another listed file is in it.
+    pub fn unwatch(&self, path: &Path) -> Result<(), String> {
+        let Some(dir) = path.parent() else {
+            return Ok(());
+        };
+        let dir_still_listed = self.resources.lock().unwrap().keys().any(|uri| {
+            Url::parse(uri)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .is_some_and(|listed| listed.parent() == Some(dir))
+        });
+        let mut watched_dirs = self.watched_dirs.lock().unwrap();
+        if dir_still_listed || !watched_dirs.remove(dir) {
+            return Ok(());
+        }
+        self.watcher
+            .lock()
+            .unwrap()
+            .unwatch(dir)
+            .map_err(|e| format!("Failed to stop watching {}: {}", dir.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_external_change_advances_resource_timestamp() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        std::fs::write(&path, "debug = false\n").unwrap();
+
+        let uri = Url::from_file_path(&path).unwrap().to_string();
+        let resource = Resource::new(&uri, Some("text".to_string()), None).unwrap();
+        let registered_at = resource.timestamp().unwrap();
+        let resources = Arc::new(Mutex::new(HashMap::from([(uri.clone(), resource)])));
+
+        let watcher = ResourceWatcher::new(Arc::clone(&resources)).unwrap();
+        watcher.watch(&path).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+
+        // Changed by something other than the agent
+        std::fs::write(&path, "debug = true\n").unwrap();
+
+        let timestamp = || resources.lock().unwrap()[&uri].timestamp().unwrap();
+        for _ in 0..50 {
+            if timestamp() > registered_at {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert!(timestamp() > registered_at);
+    }
+
+    #[test]
+    fn test_unwatch_keeps_directories_with_listed_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let first = temp_dir.path().join("first.txt");
+        let second = temp_dir.path().join("second.txt");
+        let uri = |path: &Path| Url::from_file_path(path).unwrap().to_string();
+        let resources = Arc::new(Mutex::new(HashMap::new()));
+        for path in [&first, &second] {
+            let resource = Resource::new(uri(path), Some("text".to_string()), None).unwrap();
+            resources.lock().unwrap().insert(uri(path), resource);
+        }
+
+        let watcher = ResourceWatcher::new(Arc::clone(&resources)).unwrap();
+        watcher.watch(&first).unwrap();
+        watcher.watch(&second).unwrap();
+        let watched = || {
+            watcher
+                .watched_dirs
+                .lock()
+                .unwrap()
+                .contains(temp_dir.path())
+        };
+
+        resources.lock().unwrap().remove(&uri(&first));
+        watcher.unwatch(&first).unwrap();
+        assert!(watched());
+
+        resources.lock().unwrap().remove(&uri(&second));
+        watcher.unwatch(&second).unwrap();
+        assert!(!watched());
+    }
+}
@@ -5,7 +5,7 @@ mod jetbrains;
 mod memory;
 
 pub use computercontroller::ComputerControllerRouter;
-pub use developer::DeveloperRouter;
+pub use developer::{DeveloperRouter, ViewLimits, EDIT_HISTORY_DIR_ENV};
 pub use google_drive::GoogleDriveRouter;
 pub use jetbrains::JetBrainsRouter;
 pub use memory::MemoryRouter;
@@ -6,6 +6,8 @@ use axum::{
     Json, Router,
 };
 use goose::config::Config;
+use goose::providers::moderation::ModerationConfig;
+use goose::providers::retry::RetryConfig;
 use goose::{agents::AgentFactory, model::ModelConfig, providers};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -61,19 +63,49 @@ async fn get_versions() -> Json<VersionsResponse> {
     })
 }
 
+/// Why a request failed, as the JSON body of the error response
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+type ErrorReply = (StatusCode, Json<ErrorResponse>);
+
+fn error_reply(status: StatusCode, error: impl ToString) -> ErrorReply {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+        }),
+    )
+}
+
 async fn create_agent(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(payload): Json<CreateAgentRequest>,
-) -> Result<Json<CreateAgentResponse>, StatusCode> {
+) -> Result<Json<CreateAgentResponse>, ErrorReply> {
     // Verify secret key
     let secret_key = headers
         .get("X-Secret-Key")
-        .and_then(|value| value.to_str().ok())
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .and_then(|value| value.to_str().ok());
+    if secret_key != Some(state.secret_key.as_str()) {
+        return Err(error_reply(StatusCode::UNAUTHORIZED, "Invalid secret key"));
+    }
 
-    if secret_key != state.secret_key {
-        return Err(StatusCode::UNAUTHORIZED);
+    let version = payload
+        .version
+        .unwrap_or_else(|| AgentFactory::default_version().to_string());
+    let available_versions = AgentFactory::available_versions();
+    if !available_versions.contains(&version.as_str()) {
+        return Err(error_reply(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Unknown agent version '{}', available versions are: {}",
+                version,
+                available_versions.join(", ")
+            ),
+        ));
     }
 
     // Set the environment variable for the model if provided
@@ -84,20 +116,45 @@ async fn create_agent(
     }
 
     let config = Config::global();
-    let model = payload.model.unwrap_or_else(|| {
-        config
-            .get("GOOSE_MODEL")
-            .expect("Did not find a model on payload or in env")
-    });
-    let model_config = ModelConfig::new(model);
-    let provider =
-        providers::create(&payload.provider, model_config).expect("Failed to create provider");
+    let model = match payload.model {
+        Some(model) => model,
+        None => config.get("GOOSE_MODEL").map_err(|_| {
+            error_reply(
+                StatusCode::BAD_REQUEST,
+                "No model was given in the request and GOOSE_MODEL is not set",
+            )
+        })?,
+    };
+    let max_request_bytes: Option<usize> = config.get("GOOSE_MAX_REQUEST_BYTES").ok();
+    let model_config = ModelConfig::new(model)
+        .with_max_tokens(config.get("GOOSE_MAX_TOKENS").ok())
+        .with_stop(config.get("GOOSE_STOP").ok())
+        .with_seed(config.get("GOOSE_SEED").ok())
+        .with_target_ratio(config.get("GOOSE_CONTEXT_TARGET_RATIO").ok())
+        .with_retry(RetryConfig::from_config(config))
+        .with_cache_control(config.get("GOOSE_PROMPT_CACHE").unwrap_or(false))
+        .with_strict_tools(config.get("GOOSE_STRICT_TOOLS").unwrap_or(false))
+        .with_max_request_bytes(max_request_bytes);
+    let provider = providers::create(&payload.provider, model_config).map_err(|e| {
+        error_reply(
+            StatusCode::BAD_REQUEST,
+            format!("Failed to create provider '{}': {}", payload.provider, e),
+        )
+    })?;
 
-    let version = payload
-        .version
-        .unwrap_or_else(|| AgentFactory::default_version().to_string());
-
-    let new_agent = AgentFactory::create(&version, provider).expect("Failed to create agent");
+    let mut new_agent = AgentFactory::create(&version, provider).ok_or_else(|| {
+        error_reply(
+            StatusCode::BAD_REQUEST,
+            format!("Unknown agent version '{}'", version),
+        )
+    })?;
+    let moderation = ModerationConfig::from_env().create().map_err(|e| {
+        error_reply(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to create moderation provider: {}", e),
+        )
+    })?;
+    new_agent.set_moderation(moderation).await;
 
     let mut agent = state.agent.lock().await;
     *agent = Some(new_agent);
@@ -105,6 +162,28 @@ async fn create_agent(
     Ok(Json(CreateAgentResponse { version }))
 }
 
+/// Remove the agent, so routes that need one fail until another is created
+async fn delete_agent(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let mut agent = state.agent.lock().await;
+    match agent.take() {
+        Some(_) => Ok(StatusCode::NO_CONTENT),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
 async fn list_providers() -> Json<Vec<ProviderList>> {
     let contents = include_str!("providers_and_keys.json");
 
@@ -132,6 +211,135 @@ pub fn routes(state: AppState) -> Router {
     Router::new()
         .route("/agent/versions", get(get_versions))
         .route("/agent/providers", get(list_providers))
-        .route("/agent", post(create_agent))
+        .route("/agent", post(create_agent).delete(delete_agent))
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use goose::agents::replay::SessionRecording;
+    use serde_json::json;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    use tower::ServiceExt;
+
+    fn state_with_agent() -> AppState {
+        let agent = AgentFactory::create(
+            AgentFactory::default_version(),
+            SessionRecording::default().replay_provider(),
+        )
+        .unwrap();
+        AppState {
+            agent: Arc::new(Mutex::new(Some(agent))),
+            secret_key: "test-secret".to_string(),
+        }
+    }
+
+    fn request(method: &str, uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header("x-secret-key", "test-secret")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn response_json(response: axum::response::Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_delete_agent() {
+        let state = state_with_agent();
+        let app = routes(state.clone());
+
+        let response = app
+            .clone()
+            .oneshot(request("DELETE", "/agent", json!(null)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(state.agent.lock().await.is_none());
+
+        let response = app
+            .oneshot(request("DELETE", "/agent", json!(null)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_create_agent_with_unknown_version() {
+        let state = state_with_agent();
+        let response = routes(state.clone())
+            .oneshot(request(
+                "POST",
+                "/agent",
+                json!({"version": "no-such-version", "provider": "openai", "model": "gpt-4o"}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error = response_json(response).await;
+        assert!(error["error"]
+            .as_str()
+            .unwrap()
+            .contains("Unknown agent version 'no-such-version'"));
+        // The existing agent is kept
+        assert!(state.agent.lock().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_create_agent_with_unknown_provider() {
+        let state = state_with_agent();
+        let app = routes(state.clone());
+        let response = app
+            .clone()
+            .oneshot(request(
+                "POST",
+                "/agent",
+                json!({"provider": "no-such-provider", "model": "gpt-4o"}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error = response_json(response).await;
+        assert!(error["error"]
+            .as_str()
+            .unwrap()
+            .contains("Failed to create provider 'no-such-provider'"));
+        assert!(state.agent.lock().await.is_some());
+
+        // The server still answers
+        let response = app
+            .oneshot(request("GET", "/agent/versions", json!(null)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_routes_without_an_agent() {
+        let state = state_with_agent();
+        *state.agent.lock().await = None;
+        let app = crate::routes::configure(state);
+
+        for request in [
+            request("POST", "/reply", json!({"messages": []})),
+            request("POST", "/ask", json!({"prompt": "Hi"})),
+            request("POST", "/session/tokens", json!({"messages": []})),
+            request("GET", "/session/usage", json!(null)),
+            request("POST", "/extensions/remove", json!("developer")),
+        ] {
+            let uri = request.uri().to_string();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::CONFLICT, "{}", uri);
+        }
+    }
+}
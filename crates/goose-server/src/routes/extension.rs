@@ -1,7 +1,11 @@
 use std::collections::HashMap;
 
 use crate::state::AppState;
-use axum::{extract::State, routing::post, Json, Router};
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get, post},
+    Json, Router,
+};
 use goose::{
     agents::{extension::Envs, ExtensionConfig},
     config::Config,
@@ -148,12 +152,12 @@ async fn add_extension(
                 envs: Envs::new(env_map),
             }
         }
-        ExtensionConfigRequest::Builtin { name } => ExtensionConfig::Builtin { name },
+        ExtensionConfigRequest::Builtin { name } => ExtensionConfig::builtin(name),
     };
 
     // Acquire a lock on the agent and attempt to add the extension.
     let mut agent = state.agent.lock().await;
-    let agent = agent.as_mut().ok_or(StatusCode::PRECONDITION_REQUIRED)?;
+    let agent = agent.as_mut().ok_or(StatusCode::CONFLICT)?;
     let response = agent.add_extension(extension_config).await;
 
     // Respond with the result.
@@ -193,7 +197,36 @@ async fn remove_extension(
 
     // Acquire a lock on the agent and attempt to remove the extension
     let mut agent = state.agent.lock().await;
-    let agent = agent.as_mut().ok_or(StatusCode::PRECONDITION_REQUIRED)?;
+    let agent = agent.as_mut().ok_or(StatusCode::CONFLICT)?;
+    agent.remove_extension(&name).await;
+
+    Ok(Json(ExtensionResponse {
+        error: false,
+        message: None,
+    }))
+}
+
+/// Handler for removing the extension named in the path, which is not found unless it is loaded.
+async fn delete_extension(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<ExtensionResponse>, StatusCode> {
+    // Verify the presence and validity of the secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let mut agent = state.agent.lock().await;
+    let agent = agent.as_mut().ok_or(StatusCode::CONFLICT)?;
+    if !agent.list_extensions().await.contains(&name) {
+        return Err(StatusCode::NOT_FOUND);
+    }
     agent.remove_extension(&name).await;
 
     Ok(Json(ExtensionResponse {
@@ -202,10 +235,131 @@ async fn remove_extension(
     }))
 }
 
+/// Handler for listing the names of the agent's extensions, sorted.
+async fn list_extensions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    // Verify the presence and validity of the secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let agent = state.agent.lock().await;
+    let agent = agent.as_ref().ok_or(StatusCode::CONFLICT)?;
+    let mut names = agent.list_extensions().await;
+    names.sort();
+    Ok(Json(names))
+}
+
 /// Registers the extension management routes with the Axum router.
 pub fn routes(state: AppState) -> Router {
     Router::new()
+        .route("/extensions", get(list_extensions).post(add_extension))
+        .route("/extensions/:name", delete(delete_extension))
         .route("/extensions/add", post(add_extension))
         .route("/extensions/remove", post(remove_extension))
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use goose::agents::{replay::SessionRecording, AgentFactory};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    use tower::ServiceExt;
+
+    /// A stdio MCP server with no tools, enough to be added as an extension
+    const EMPTY_SERVER: &str = r#"
+        while read -r line; do
+            method=$(echo "$line" | sed -n 's/.*"method":"\([^"]*\)".*/\1/p')
+            id=$(echo "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            [ -z "$id" ] && continue
+            case "$method" in
+                initialize)
+                    echo '{"jsonrpc":"2.0","id":'"$id"',"result":{"protocolVersion":"1.0.0","capabilities":{"tools":{}},"serverInfo":{"name":"empty","version":"1.0.0"}}}' ;;
+                tools/list)
+                    echo '{"jsonrpc":"2.0","id":'"$id"',"result":{"tools":[]}}' ;;
+                *)
+                    echo '{"jsonrpc":"2.0","id":'"$id"',"error":{"code":-32601,"message":"Method not found"}}' ;;
+            esac
+        done
+    "#;
+
+    fn request(method: &str, uri: &str, body: Value) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header("x-secret-key", "test-secret")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn response_json(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_add_list_and_delete_extension() {
+        let agent = AgentFactory::create(
+            AgentFactory::default_version(),
+            SessionRecording::default().replay_provider(),
+        )
+        .unwrap();
+        let state = AppState {
+            agent: Arc::new(Mutex::new(Some(agent))),
+            secret_key: "test-secret".to_string(),
+        };
+        let app = routes(state.clone());
+
+        let (status, added) = response_json(
+            &app,
+            request(
+                "POST",
+                "/extensions",
+                json!({"type": "stdio", "name": "empty", "cmd": "sh", "args": ["-c", EMPTY_SERVER]}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(added["error"], false, "{}", added);
+
+        let (status, names) = response_json(&app, request("GET", "/extensions", json!(null))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(names, json!(["empty"]));
+
+        let (status, removed) =
+            response_json(&app, request("DELETE", "/extensions/empty", json!(null))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(removed["error"], false);
+        let (_, names) = response_json(&app, request("GET", "/extensions", json!(null))).await;
+        assert_eq!(names, json!([]));
+
+        // Once removed it is no longer found
+        let (status, _) =
+            response_json(&app, request("DELETE", "/extensions/empty", json!(null))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Without an agent there is nothing to attach extensions to
+        *state.agent.lock().await = None;
+        let (status, _) = response_json(&app, request("GET", "/extensions", json!(null))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _) =
+            response_json(&app, request("DELETE", "/extensions/empty", json!(null))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+}
@@ -2,16 +2,20 @@ use crate::state::AppState;
 use axum::{
     extract::State,
     http::{self, HeaderMap, StatusCode},
-    response::IntoResponse,
-    routing::post,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    routing::{get, post},
     Json, Router,
 };
 use bytes::Bytes;
 use futures::{stream::StreamExt, Stream};
+use goose::agents::{ReplyEvent, TokenCount, UsageSummary};
 use goose::message::{Message, MessageContent};
 
 use mcp_core::{content::Content, role::Role};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::{
     convert::Infallible,
@@ -29,6 +33,12 @@ struct ChatRequest {
     messages: Vec<IncomingMessage>,
 }
 
+// A conversation in goose's own message format, as returned by /chat
+#[derive(Debug, Deserialize)]
+struct ConversationRequest {
+    messages: Vec<Message>,
+}
+
 #[derive(Debug, Deserialize)]
 struct IncomingMessage {
     role: String,
@@ -284,6 +294,11 @@ async fn handler(
         }
     }
 
+    // Fail before streaming when there is no agent to reply, as other routes do
+    if state.agent.lock().await.is_none() {
+        return Err(StatusCode::CONFLICT);
+    }
+
     // Create channel for streaming
     let (tx, rx) = mpsc::channel(100);
     let stream = ReceiverStream::new(rx);
@@ -388,7 +403,7 @@ async fn ask_handler(
 
     let agent = state.agent.clone();
     let agent = agent.lock().await;
-    let agent = agent.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let agent = agent.as_ref().ok_or(StatusCode::CONFLICT)?;
 
     // Create a single message for the prompt
     let messages = vec![Message::user().with_text(request.prompt)];
@@ -427,11 +442,151 @@ async fn ask_handler(
     }))
 }
 
+// Estimate the tokens of a conversation, for showing how much of the context it uses.
+// The server keeps no conversation, so the messages are sent like they are for /reply.
+async fn tokens_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<ChatRequest>,
+) -> Result<Json<TokenCount>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let agent = state.agent.lock().await;
+    let agent = agent.as_ref().ok_or(StatusCode::CONFLICT)?;
+
+    let messages = convert_messages(request.messages);
+    match agent.count_tokens(&messages).await {
+        Ok(count) => Ok(Json(count)),
+        Err(e) => {
+            tracing::error!("Failed to count tokens: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// An SSE event carrying `data` as JSON
+fn sse_event(name: &str, data: impl Serialize) -> Result<Event, Infallible> {
+    Ok(Event::default()
+        .event(name)
+        .json_data(data)
+        .unwrap_or_else(|e| {
+            Event::default()
+                .event("error")
+                .data(json!({"error": e.to_string()}).to_string())
+        }))
+}
+
+// Stream the agent's reply as standard Server-Sent Events, for clients that don't use the
+// Vercel AI SDK. Each `text` event has the next text of the assistant's response as it is
+// generated, each `message` event a complete message in goose's format, and the stream ends
+// with a `finish` event, after an `error` event if the reply failed.
+async fn chat_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<ConversationRequest>,
+) -> Result<Sse<ReceiverStream<Result<Event, Infallible>>>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    if state.agent.lock().await.is_none() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let (tx, rx) = mpsc::channel(100);
+    let agent = state.agent.clone();
+    tokio::spawn(async move {
+        let agent = agent.lock().await;
+        let Some(agent) = agent.as_ref() else {
+            let _ = tx
+                .send(sse_event("error", json!({"error": "No agent configured"})))
+                .await;
+            let _ = tx
+                .send(sse_event("finish", json!({"reason": "error"})))
+                .await;
+            return;
+        };
+
+        let mut reason = "stop";
+        match agent.reply_stream(&request.messages).await {
+            Ok(mut stream) => {
+                while let Some(event) = stream.next().await {
+                    let event = match event {
+                        Ok(ReplyEvent::Text(text)) => sse_event("text", json!({"text": text})),
+                        Ok(ReplyEvent::Message(message)) => sse_event("message", &message),
+                        Err(e) => {
+                            tracing::error!("Error in reply stream: {}", e);
+                            reason = "error";
+                            sse_event("error", json!({"error": e.to_string()}))
+                        }
+                    };
+                    // Stop replying once the client has gone away
+                    if tx.send(event).await.is_err() {
+                        return;
+                    }
+                    if reason == "error" {
+                        break;
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to start reply stream: {}", e);
+                reason = "error";
+                let _ = tx
+                    .send(sse_event("error", json!({"error": e.to_string()})))
+                    .await;
+            }
+        }
+        let _ = tx
+            .send(sse_event("finish", json!({"reason": reason})))
+            .await;
+    });
+
+    Ok(Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default()))
+}
+
+// Report the tokens the agent has used and what they cost, in total and by model
+async fn usage_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<UsageSummary>, StatusCode> {
+    // Verify secret key
+    let secret_key = headers
+        .get("X-Secret-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if secret_key != state.secret_key {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let agent = state.agent.lock().await;
+    let agent = agent.as_ref().ok_or(StatusCode::CONFLICT)?;
+    Ok(Json(agent.usage_summary().await))
+}
+
 // Configure routes for this module
 pub fn routes(state: AppState) -> Router {
     Router::new()
         .route("/reply", post(handler))
         .route("/ask", post(ask_handler))
+        .route("/chat", post(chat_handler))
+        .route("/session/tokens", post(tokens_handler))
+        .route("/session/usage", get(usage_handler))
         .with_state(state)
 }
 
@@ -472,7 +627,11 @@ mod tests {
         ) -> anyhow::Result<(Message, ProviderUsage), ProviderError> {
             Ok((
                 Message::assistant().with_text("Mock response"),
-                ProviderUsage::new("mock".to_string(), Usage::default()),
+                ProviderUsage::new(
+                    "gpt-4o".to_string(),
+                    Usage::new(Some(800), Some(200), Some(1000)),
+                )
+                .with_cost(),
             ))
         }
     }
@@ -600,5 +759,160 @@ mod tests {
             // Assert response status
             assert_eq!(response.status(), StatusCode::OK);
         }
+
+        #[tokio::test]
+        async fn test_session_tokens_endpoint() {
+            let mock_model_config =
+                ModelConfig::new("test-model".to_string()).with_context_limit(Some(8000));
+            let mock_provider = Box::new(MockProvider {
+                model_config: mock_model_config,
+            });
+            let agent = AgentFactory::create("reference", mock_provider).unwrap();
+            let state = AppState {
+                agent: Arc::new(Mutex::new(Some(agent))),
+                secret_key: "test-secret".to_string(),
+            };
+            let app = routes(state);
+
+            let request = Request::builder()
+                .uri("/session/tokens")
+                .method("POST")
+                .header("content-type", "application/json")
+                .header("x-secret-key", "test-secret")
+                .body(Body::from(
+                    json!({"messages": [{"role": "user", "content": "How many tokens is this?"}]})
+                        .to_string(),
+                ))
+                .unwrap();
+
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let count: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(count["context_limit"], 8000);
+            assert!(count["tokens"].as_u64().unwrap() > 0);
+        }
+
+        #[tokio::test]
+        async fn test_chat_endpoint_streams_sse() {
+            use goose::agents::replay::{RecordedCompletion, SessionRecording};
+            use mcp_core::tool::ToolCall;
+
+            let completion = |message: Message| RecordedCompletion {
+                message,
+                usage: ProviderUsage::new("replay".to_string(), Usage::default()),
+            };
+            let recording = SessionRecording {
+                completions: vec![
+                    completion(Message::assistant().with_tool_request(
+                        "1",
+                        Ok(ToolCall::new("files__read", json!({"path": "a.txt"}))),
+                    )),
+                    completion(Message::assistant().with_text("There is no files extension.")),
+                ],
+                ..Default::default()
+            };
+            let agent = AgentFactory::create("reference", recording.replay_provider()).unwrap();
+            let state = AppState {
+                agent: Arc::new(Mutex::new(Some(agent))),
+                secret_key: "test-secret".to_string(),
+            };
+            let app = routes(state);
+
+            let messages = vec![Message::user().with_text("Read a.txt")];
+            let request = |secret: &str| {
+                Request::builder()
+                    .uri("/chat")
+                    .method("POST")
+                    .header("content-type", "application/json")
+                    .header("x-secret-key", secret)
+                    .body(Body::from(json!({ "messages": messages }).to_string()))
+                    .unwrap()
+            };
+
+            let response = app.clone().oneshot(request("wrong")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = app.oneshot(request("test-secret")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-type"], "text/event-stream");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body = String::from_utf8(body.to_vec()).unwrap();
+
+            let frames: Vec<(&str, Value)> = body
+                .split("\n\n")
+                .filter(|frame| !frame.is_empty())
+                .map(|frame| {
+                    let mut lines = frame.lines();
+                    let event = lines.next().unwrap().strip_prefix("event: ").unwrap();
+                    let data = lines.next().unwrap().strip_prefix("data: ").unwrap();
+                    (event, serde_json::from_str(data).unwrap())
+                })
+                .collect();
+            let events: Vec<&str> = frames.iter().map(|(event, _)| *event).collect();
+            assert_eq!(
+                events,
+                vec!["message", "message", "text", "message", "finish"]
+            );
+
+            let request: Message = serde_json::from_value(frames[0].1.clone()).unwrap();
+            assert!(request.content[0].as_tool_request().is_some());
+            let response: Message = serde_json::from_value(frames[1].1.clone()).unwrap();
+            assert_eq!(response.role, Role::User);
+            assert!(response.is_tool_response());
+            assert_eq!(frames[2].1["text"], "There is no files extension.");
+            let reply: Message = serde_json::from_value(frames[3].1.clone()).unwrap();
+            assert_eq!(reply.as_concat_text(), "There is no files extension.");
+            assert_eq!(frames[4].1["reason"], "stop");
+        }
+
+        #[tokio::test]
+        async fn test_session_usage_endpoint() {
+            let mock_provider = Box::new(MockProvider {
+                model_config: ModelConfig::new("gpt-4o".to_string()),
+            });
+            let agent = AgentFactory::create("reference", mock_provider).unwrap();
+            let state = AppState {
+                agent: Arc::new(Mutex::new(Some(agent))),
+                secret_key: "test-secret".to_string(),
+            };
+            let app = routes(state);
+
+            for _ in 0..2 {
+                let request = Request::builder()
+                    .uri("/ask")
+                    .method("POST")
+                    .header("content-type", "application/json")
+                    .header("x-secret-key", "test-secret")
+                    .body(Body::from(json!({"prompt": "Hi"}).to_string()))
+                    .unwrap();
+                let response = app.clone().oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+            }
+
+            let request = Request::builder()
+                .uri("/session/usage")
+                .header("x-secret-key", "test-secret")
+                .body(Body::empty())
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let summary: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(summary["input_tokens"], 1600);
+            assert_eq!(summary["output_tokens"], 400);
+            assert_eq!(summary["models"][0]["model"], "gpt-4o");
+            // 1600 input tokens at $2.50 and 400 output tokens at $10.00 per million
+            assert!((summary["cost"].as_f64().unwrap() - 0.008).abs() < 1e-9);
+            assert_eq!(summary["cost_is_partial"], false);
+        }
     }
 }
@@ -1,21 +1,138 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use futures::stream::BoxStream;
+use futures::stream::{BoxStream, StreamExt};
+use mcp_client::client::McpClientTrait;
+use serde::Serialize;
 use serde_json::Value;
+use std::collections::BTreeMap;
 
+use super::approval::ToolApprover;
+use super::capabilities::PreparedRequest;
 use super::extension::{ExtensionConfig, ExtensionResult};
 use crate::message::Message;
-use crate::providers::base::ProviderUsage;
+use crate::model::ModelConfig;
+use crate::providers::base::{Provider, ProviderUsage};
+use crate::providers::moderation::Moderation;
+use crate::providers::rate_limit::RateLimitInfo;
+use crate::providers::router::Tier;
+use crate::providers::{create, validate_model};
+
+/// An update from the agent while it replies
+#[derive(Debug, Clone)]
+pub enum ReplyEvent {
+    /// Text of the assistant's response as it is generated, ahead of the complete message
+    Text(String),
+    /// A complete message to add to the conversation
+    Message(Message),
+}
+
+/// The estimated tokens of a conversation, against the context limit of the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TokenCount {
+    pub tokens: usize,
+    pub context_limit: usize,
+}
+
+/// The tokens used and what they cost, over the whole session and for each model
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageSummary {
+    pub input_tokens: i32,
+    pub output_tokens: i32,
+    pub total_tokens: i32,
+    /// The cost in US dollars of the models whose pricing is known, None if no model's is
+    pub cost: Option<f64>,
+    /// Whether `cost` leaves out models whose pricing is unknown, so the session cost more
+    pub cost_is_partial: bool,
+    /// The usage of each model, sorted by name
+    pub models: Vec<ProviderUsage>,
+}
+
+impl UsageSummary {
+    /// Add up `usage`, merging the entries of the same model
+    pub fn new(usage: Vec<ProviderUsage>) -> Self {
+        let mut by_model: BTreeMap<String, ProviderUsage> = BTreeMap::new();
+        for entry in usage {
+            match by_model.get_mut(&entry.model) {
+                Some(merged) => {
+                    let add = |a: Option<i32>, b: Option<i32>| match (a, b) {
+                        (None, None) => None,
+                        (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+                    };
+                    merged.usage.input_tokens =
+                        add(merged.usage.input_tokens, entry.usage.input_tokens);
+                    merged.usage.output_tokens =
+                        add(merged.usage.output_tokens, entry.usage.output_tokens);
+                    merged.usage.total_tokens =
+                        add(merged.usage.total_tokens, entry.usage.total_tokens);
+                    merged.cost = match (merged.cost, entry.cost) {
+                        (None, None) => None,
+                        (a, b) => Some(a.unwrap_or(0.0) + b.unwrap_or(0.0)),
+                    };
+                    if entry.rate_limit.is_some() {
+                        merged.rate_limit = entry.rate_limit;
+                    }
+                }
+                None => {
+                    by_model.insert(entry.model.clone(), entry);
+                }
+            }
+        }
+
+        let models: Vec<ProviderUsage> = by_model.into_values().collect();
+        let sum = |tokens: fn(&ProviderUsage) -> Option<i32>| {
+            models.iter().filter_map(tokens).sum::<i32>()
+        };
+        let costs: Vec<f64> = models.iter().filter_map(|model| model.cost).collect();
+        Self {
+            input_tokens: sum(|model| model.usage.input_tokens),
+            output_tokens: sum(|model| model.usage.output_tokens),
+            total_tokens: sum(|model| model.usage.total_tokens),
+            cost: (!costs.is_empty()).then(|| costs.iter().sum()),
+            cost_is_partial: !costs.is_empty() && costs.len() < models.len(),
+            models,
+        }
+    }
+}
 
 /// Core trait defining the behavior of an Agent
 #[async_trait]
 pub trait Agent: Send + Sync {
     /// Create a stream that yields each message as it's generated by the agent
-    async fn reply(&self, messages: &[Message]) -> Result<BoxStream<'_, Result<Message>>>;
+    async fn reply(&self, messages: &[Message]) -> Result<BoxStream<'_, Result<Message>>> {
+        let events = self.reply_stream(messages).await?;
+        Ok(Box::pin(events.filter_map(|event| async move {
+            match event {
+                Ok(ReplyEvent::Message(message)) => Some(Ok(message)),
+                Ok(ReplyEvent::Text(_)) => None,
+                Err(e) => Some(Err(e)),
+            }
+        })))
+    }
+
+    /// Like `reply`, but also yields the text of the assistant's responses as it is generated
+    async fn reply_stream(&self, messages: &[Message])
+        -> Result<BoxStream<'_, Result<ReplyEvent>>>;
+
+    /// Assemble the request `reply` would send to the provider, without calling it
+    ///
+    /// Useful for debugging prompts, as it shows the exact system prompt, messages and tools.
+    async fn dry_run(&self, messages: &[Message]) -> Result<PreparedRequest>;
+
+    /// Estimate the tokens a reply to `messages` would send, with the model's context limit
+    ///
+    /// Includes the system prompt, active resources and tools, as they are before any trimming.
+    async fn count_tokens(&self, messages: &[Message]) -> Result<TokenCount>;
 
     /// Add a new MCP client to the agent
     async fn add_extension(&mut self, config: ExtensionConfig) -> ExtensionResult<()>;
 
+    /// Add an extension served by an existing client, which is initialized as it is added
+    async fn add_client(
+        &mut self,
+        name: &str,
+        client: Box<dyn McpClientTrait>,
+    ) -> ExtensionResult<()>;
+
     /// Remove an extension by name
     async fn remove_extension(&mut self, name: &str);
 
@@ -28,4 +145,108 @@ pub trait Agent: Send + Sync {
 
     /// Get the total usage of the agent
     async fn usage(&self) -> Vec<ProviderUsage>;
+
+    /// Get the total usage of the agent added up, with what it cost where the pricing is known
+    async fn usage_summary(&self) -> UsageSummary {
+        UsageSummary::new(self.usage().await)
+    }
+
+    /// Get the rate limit quota most recently reported by the provider
+    async fn rate_limit(&self) -> Option<RateLimitInfo>;
+
+    /// Get the configuration of the model used for replies
+    async fn model_config(&self) -> ModelConfig;
+
+    /// Send the next turn to the model of `tier` when the provider routes between models
+    ///
+    /// Providers with a single model ignore the hint, see [`Provider::set_route_hint`].
+    async fn set_route_hint(&self, tier: Option<Tier>);
+
+    /// Replace the provider used for subsequent replies
+    async fn set_provider(&mut self, provider: Box<dyn Provider>);
+
+    /// Check user input with `moderation` before replying, or stop moderating with None
+    ///
+    /// A flagged message is answered with a refusal instead of being sent to the provider.
+    async fn set_moderation(&mut self, moderation: Option<Box<dyn Moderation>>);
+
+    /// Consult `approver` before running each tool call the model requests, or run every call
+    /// with None, which is the default
+    ///
+    /// A denied call is answered with an error explaining the denial instead of being run.
+    async fn set_approver(&mut self, approver: Option<Box<dyn ToolApprover>>);
+
+    /// Switch to another of the provider's known models for subsequent replies
+    ///
+    /// The context limit follows the new model, while other settings such as temperature
+    /// are kept from the current model.
+    async fn switch_model(&mut self, provider_name: &str, model_name: &str) -> Result<()> {
+        validate_model(provider_name, model_name)?;
+
+        let current = self.model_config().await;
+        let model = ModelConfig::new(model_name.to_string())
+            .with_temperature(current.temperature)
+            .with_max_tokens(current.max_tokens)
+            .with_stop(current.stop)
+            .with_seed(current.seed)
+            .with_target_ratio(current.target_ratio)
+            .with_retry(current.retry)
+            .with_cache_control(current.supports_cache_control)
+            .with_strict_tools(current.strict_tools)
+            .with_max_request_bytes(current.max_request_bytes);
+        let provider = create(provider_name, model)?;
+        self.set_provider(provider).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::Usage;
+
+    #[test]
+    fn test_usage_summary_adds_up_usage() {
+        let usage = |model: &str, input, output| {
+            ProviderUsage::new(
+                model.to_string(),
+                Usage::new(Some(input), Some(output), Some(input + output)),
+            )
+            .with_cost()
+        };
+        let summary = UsageSummary::new(vec![
+            usage("gpt-4o", 1000, 200),
+            usage("claude-3-5-sonnet-latest", 2000, 500),
+            usage("gpt-4o", 3000, 800),
+            usage("llama3.3", 100, 50),
+        ]);
+
+        assert_eq!(summary.input_tokens, 6100);
+        assert_eq!(summary.output_tokens, 1550);
+        assert_eq!(summary.total_tokens, 7650);
+
+        let models: Vec<&str> = summary.models.iter().map(|m| m.model.as_str()).collect();
+        assert_eq!(
+            models,
+            vec!["claude-3-5-sonnet-latest", "gpt-4o", "llama3.3"]
+        );
+        let gpt = &summary.models[1];
+        assert_eq!(gpt.usage.input_tokens, Some(4000));
+        assert_eq!(gpt.usage.total_tokens, Some(5000));
+        // 4000 input tokens at $2.50 and 1000 output tokens at $10.00 per million
+        assert!((gpt.cost.unwrap() - 0.02).abs() < 1e-9);
+        assert_eq!(summary.models[2].cost, None);
+
+        // The sonnet usage costs $0.0135, and the unpriced llama usage nothing
+        assert!((summary.cost.unwrap() - 0.0335).abs() < 1e-9);
+        assert!(summary.cost_is_partial);
+
+        let priced = UsageSummary::new(vec![usage("gpt-4o", 10, 5)]);
+        assert!(!priced.cost_is_partial);
+
+        let unpriced = UsageSummary::new(vec![usage("llama3.3", 10, 5)]);
+        assert_eq!(unpriced.cost, None);
+        assert!(!unpriced.cost_is_partial);
+        assert_eq!(unpriced.total_tokens, 15);
+    }
 }
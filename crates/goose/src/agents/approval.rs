@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use mcp_core::{ToolCall, ToolError};
+use serde_json::Value;
+
+/// The decision on a tool call requested by the model
+#[derive(Debug, Clone, PartialEq)]
+pub enum Approval {
+    /// Run the tool call as requested
+    Approve,
+    /// Run the tool call with these arguments instead
+    Edit(Value),
+    /// Do not run the tool call, telling the model why
+    Deny(String),
+}
+
+impl Approval {
+    /// The tool call to run, or the error to answer the model with when it was denied
+    pub fn apply(self, tool_call: ToolCall) -> Result<ToolCall, ToolError> {
+        match self {
+            Self::Approve => Ok(tool_call),
+            Self::Edit(arguments) => Ok(ToolCall::new(tool_call.name, arguments)),
+            Self::Deny(reason) => Err(ToolError::ExecutionError(format!(
+                "The tool call was denied and did not run: {}",
+                reason
+            ))),
+        }
+    }
+}
+
+/// Decides whether tool calls requested by the model are run, before they are dispatched
+///
+/// Calls are approved one at a time in the order the model requested them, so an approver
+/// can prompt the user. Without an approver every call is run.
+#[async_trait]
+pub trait ToolApprover: Send + Sync {
+    async fn approve(&self, tool_call: &ToolCall) -> Approval;
+}
@@ -1,26 +1,57 @@
 use chrono::{DateTime, TimeZone, Utc};
 use futures::stream::{FuturesUnordered, StreamExt};
-use mcp_client::McpService;
+use mcp_client::{McpService, DEFAULT_REQUEST_TIMEOUT};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::sync::LazyLock;
-use std::time::Duration;
 use tokio::sync::Mutex;
-use tracing::{debug, instrument};
+use tracing::{debug, instrument, warn};
 
+use super::approval::ToolApprover;
 use super::extension::{ExtensionConfig, ExtensionError, ExtensionInfo, ExtensionResult};
+use super::summarize::Summarizer;
+use super::trim::{
+    cap_tool_outputs, evict_idle, trim_to_budget, TrimPriority, MAX_TOOL_OUTPUT_SHARE,
+};
+use crate::config::Config;
+use crate::message::{Message, ToolRequest};
 use crate::prompt_template::load_prompt_file;
 use crate::providers::base::{Provider, ProviderUsage};
-use mcp_client::client::{ClientCapabilities, ClientInfo, McpClient, McpClientTrait};
+use crate::providers::errors::ProviderError;
+use crate::providers::moderation::{Moderation, ModerationResult};
+use crate::providers::rate_limit::RateLimitInfo;
+use crate::token_counter::TokenCounter;
+use indoc::indoc;
+use mcp_client::client::{
+    ClientCapabilities, ClientInfo, Error as ClientError, McpClient, McpClientTrait,
+};
 use mcp_client::transport::{SseTransport, StdioTransport, Transport};
-use mcp_core::{Content, Tool, ToolCall, ToolError, ToolResult};
-use serde_json::Value;
+use mcp_core::protocol::INVALID_REQUEST;
+use mcp_core::text::truncate_str;
+use mcp_core::{Content, Role, Tool, ToolCall, ToolError, ToolResult};
+use serde::Serialize;
+use serde_json::{json, Value};
 
 // By default, we set it to Jan 1, 2020 if the resource does not have a timestamp
 // This is to ensure that the resource is considered less important than resources with a more recent timestamp
 static DEFAULT_TIMESTAMP: LazyLock<DateTime<Utc>> =
     LazyLock::new(|| Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap());
 
+/// URI of the pinned task resource set with the platform__set_task tool
+const TASK_URI: &str = "str:///task";
+
+/// Error code MCP servers return for a resource they don't have, besides an invalid request
+const RESOURCE_NOT_FOUND: i32 = -32002;
+
+/// Most bytes of a single active resource's content included in the system prompt
+pub const MAX_RESOURCE_PROMPT_BYTES: usize = 16_000;
+
+/// Default cap on the tool calls honored from a single assistant message
+pub const DEFAULT_MAX_TOOL_CALLS_PER_MESSAGE: usize = 10;
+
+/// Default cap on the tool calls from one message that run at the same time
+pub const DEFAULT_MAX_CONCURRENT_TOOL_CALLS: usize = 5;
+
 type McpClientBox = Arc<Mutex<Box<dyn McpClientTrait>>>;
 
 /// Manages MCP clients and their interactions
@@ -30,6 +61,26 @@ pub struct Capabilities {
     resource_capable_extensions: HashSet<String>,
     provider: Box<dyn Provider>,
     provider_usage: Mutex<Vec<ProviderUsage>>,
+    rate_limit: Mutex<Option<RateLimitInfo>>,
+    trim_priority: TrimPriority,
+    resources_in_prompt: bool,
+    resource_idle_timeout: Option<chrono::Duration>,
+    /// When each resource was last read with platform__read_resource, by URI
+    resource_access: Mutex<HashMap<String, DateTime<Utc>>>,
+    task: Mutex<Option<String>>,
+    moderation: Option<Box<dyn Moderation>>,
+    approver: Option<Box<dyn ToolApprover>>,
+    max_tool_calls_per_message: usize,
+    max_concurrent_tool_calls: usize,
+    summarizer: Summarizer,
+}
+
+/// Everything that is sent to the provider for a completion
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PreparedRequest {
+    pub system_prompt: String,
+    pub messages: Vec<Message>,
+    pub tools: Vec<Tool>,
 }
 
 /// A flattened representation of a resource used by the agent to prepare inference
@@ -42,6 +93,7 @@ pub struct ResourceItem {
     pub timestamp: DateTime<Utc>, // The timestamp of the resource
     pub priority: f32,            // The priority of the resource
     pub token_count: Option<u32>, // The token count of the resource (filled in by the agent)
+    pub pinned: bool,             // Pinned resources are never dropped when trimming the context
 }
 
 impl ResourceItem {
@@ -61,8 +113,14 @@ impl ResourceItem {
             timestamp,
             priority,
             token_count: None,
+            pinned: false,
         }
     }
+
+    pub fn with_pinned(mut self, pinned: bool) -> Self {
+        self.pinned = pinned;
+        self
+    }
 }
 
 /// Sanitizes a string by replacing invalid characters with underscores.
@@ -88,6 +146,28 @@ impl Capabilities {
             resource_capable_extensions: HashSet::new(),
             provider,
             provider_usage: Mutex::new(Vec::new()),
+            rate_limit: Mutex::new(None),
+            trim_priority: Config::global()
+                .get("GOOSE_TRIM_PRIORITY")
+                .unwrap_or_default(),
+            resources_in_prompt: Config::global()
+                .get("GOOSE_RESOURCES_IN_PROMPT")
+                .unwrap_or(false),
+            resource_idle_timeout: Config::global()
+                .get::<i64>("GOOSE_RESOURCE_IDLE_SECS")
+                .ok()
+                .map(chrono::Duration::seconds),
+            resource_access: Mutex::new(HashMap::new()),
+            task: Mutex::new(None),
+            moderation: None,
+            approver: None,
+            max_tool_calls_per_message: Config::global()
+                .get("GOOSE_MAX_TOOL_CALLS_PER_MESSAGE")
+                .unwrap_or(DEFAULT_MAX_TOOL_CALLS_PER_MESSAGE),
+            max_concurrent_tool_calls: Config::global()
+                .get("GOOSE_MAX_CONCURRENT_TOOL_CALLS")
+                .unwrap_or(DEFAULT_MAX_CONCURRENT_TOOL_CALLS),
+            summarizer: Summarizer::from_config(),
         }
     }
 
@@ -98,22 +178,22 @@ impl Capabilities {
     /// Add a new MCP extension based on the provided client type
     // TODO IMPORTANT need to ensure this times out if the extension command is broken!
     pub async fn add_extension(&mut self, config: ExtensionConfig) -> ExtensionResult<()> {
-        let mut client: Box<dyn McpClientTrait> = match &config {
+        let client: Box<dyn McpClientTrait> = match &config {
             ExtensionConfig::Sse { uri, envs, .. } => {
-                let transport = SseTransport::new(uri, envs.get_env());
+                let transport = SseTransport::new(uri, envs.get_env()?);
                 let handle = transport.start().await?;
-                let service = McpService::with_timeout(handle, Duration::from_secs(300));
+                let service = McpService::with_timeout(handle, DEFAULT_REQUEST_TIMEOUT);
                 Box::new(McpClient::new(service))
             }
             ExtensionConfig::Stdio {
                 cmd, args, envs, ..
             } => {
-                let transport = StdioTransport::new(cmd, args.to_vec(), envs.get_env());
+                let transport = StdioTransport::new(cmd, args.to_vec(), envs.get_env()?);
                 let handle = transport.start().await?;
-                let service = McpService::with_timeout(handle, Duration::from_secs(300));
+                let service = McpService::with_timeout(handle, DEFAULT_REQUEST_TIMEOUT);
                 Box::new(McpClient::new(service))
             }
-            ExtensionConfig::Builtin { name } => {
+            ExtensionConfig::Builtin { name, envs } => {
                 // For builtin extensions, we run the current executable with mcp and extension name
                 let cmd = std::env::current_exe()
                     .expect("should find the current executable")
@@ -123,14 +203,34 @@ impl Capabilities {
                 let transport = StdioTransport::new(
                     &cmd,
                     vec!["mcp".to_string(), name.clone()],
-                    HashMap::new(),
+                    envs.get_env()?,
                 );
                 let handle = transport.start().await?;
-                let service = McpService::with_timeout(handle, Duration::from_secs(300));
+                let service = McpService::with_timeout(handle, DEFAULT_REQUEST_TIMEOUT);
                 Box::new(McpClient::new(service))
             }
         };
 
+        self.register_client(config.name(), client)
+            .await
+            .map_err(|e| ExtensionError::Initialization(config.clone(), e))
+    }
+
+    /// Add an extension served by an existing client, such as one that replays a recording
+    pub async fn add_client(
+        &mut self,
+        name: &str,
+        client: Box<dyn McpClientTrait>,
+    ) -> ExtensionResult<()> {
+        Ok(self.register_client(name, client).await?)
+    }
+
+    /// Initialize the client and start using it for the extension called `name`
+    async fn register_client(
+        &mut self,
+        name: &str,
+        mut client: Box<dyn McpClientTrait>,
+    ) -> Result<(), ClientError> {
         // Initialize the client with default capabilities
         let info = ClientInfo {
             name: "goose".to_string(),
@@ -138,12 +238,9 @@ impl Capabilities {
         };
         let capabilities = ClientCapabilities::default();
 
-        let init_result = client
-            .initialize(info, capabilities)
-            .await
-            .map_err(|e| ExtensionError::Initialization(config.clone(), e))?;
+        let init_result = client.initialize(info, capabilities).await?;
 
-        let sanitized_name = normalize(config.name().to_string());
+        let sanitized_name = normalize(name.to_string());
 
         // Store instructions if provided
         if let Some(instructions) = init_result.instructions {
@@ -169,12 +266,97 @@ impl Capabilities {
         &*self.provider
     }
 
+    /// Replace the provider, keeping the usage recorded so far
+    pub fn set_provider(&mut self, provider: Box<dyn Provider>) {
+        self.provider = provider;
+    }
+
+    /// Set the moderation provider that checks user input before it reaches the model
+    pub fn set_moderation(&mut self, moderation: Option<Box<dyn Moderation>>) {
+        self.moderation = moderation;
+    }
+
+    /// Set the approver consulted before each tool call is run, or run every call with None
+    pub fn set_approver(&mut self, approver: Option<Box<dyn ToolApprover>>) {
+        self.approver = approver;
+    }
+
+    /// Include the content of active resources in the system prompt of every request
+    ///
+    /// Defaults to the `GOOSE_RESOURCES_IN_PROMPT` config key, and is off when it is unset, as
+    /// the model can read resources with the platform tools when it needs them. Each resource
+    /// is cut to [`MAX_RESOURCE_PROMPT_BYTES`].
+    pub fn set_resources_in_prompt(&mut self, enabled: bool) {
+        self.resources_in_prompt = enabled;
+    }
+
+    /// Drop resources not accessed within `timeout` from the context, or keep them with None
+    ///
+    /// Defaults to the `GOOSE_RESOURCE_IDLE_SECS` config key, and is off when it is unset.
+    pub fn set_resource_idle_timeout(&mut self, timeout: Option<chrono::Duration>) {
+        self.resource_idle_timeout = timeout;
+    }
+
+    /// Set how many tool calls from a single assistant message are run
+    pub fn set_max_tool_calls_per_message(&mut self, max: usize) {
+        self.max_tool_calls_per_message = max;
+    }
+
+    /// Set how many tool calls from a single assistant message run at the same time
+    pub fn set_max_concurrent_tool_calls(&mut self, max: usize) {
+        self.max_concurrent_tool_calls = max;
+    }
+
+    /// Check the latest user message with the moderation provider, if one is set
+    ///
+    /// Returns the moderation result when the message is flagged.
+    pub async fn moderate(
+        &self,
+        messages: &[Message],
+    ) -> Result<Option<ModerationResult>, ProviderError> {
+        let Some(moderation) = &self.moderation else {
+            return Ok(None);
+        };
+        let Some(text) = messages
+            .last()
+            .filter(|message| message.role == Role::User)
+            .map(|message| message.as_concat_text())
+            .filter(|text| !text.is_empty())
+        else {
+            return Ok(None);
+        };
+
+        let result = moderation.moderate_content(&text).await?;
+        Ok(result.flagged.then_some(result))
+    }
+
     /// Record provider usage
     // TODO consider moving this off to the provider or as a form of logging
     pub async fn record_usage(&self, usage: ProviderUsage) {
+        if usage.rate_limit.is_some() {
+            *self.rate_limit.lock().await = usage.rate_limit.clone();
+        }
         self.provider_usage.lock().await.push(usage);
     }
 
+    /// The rate limit quota reported with the most recent response that included one
+    pub async fn rate_limit(&self) -> Option<RateLimitInfo> {
+        self.rate_limit.lock().await.clone()
+    }
+
+    /// Pin a description of the current task into the context, or clear it with None
+    ///
+    /// The task is included with every request as a pinned resource, so it survives trimming
+    /// and keeps multi-step work on track.
+    pub async fn set_task(&self, task: Option<String>) {
+        *self.task.lock().await = task.filter(|t| !t.trim().is_empty());
+    }
+
+    /// The task currently pinned into the context
+    pub async fn task(&self) -> Option<String> {
+        self.task.lock().await.clone()
+    }
+
     /// Get aggregated usage statistics
     pub async fn remove_extension(&mut self, name: &str) -> ExtensionResult<()> {
         let sanitized_name = normalize(name.to_string());
@@ -206,6 +388,12 @@ impl Capabilities {
                     e.usage.total_tokens = Some(
                         e.usage.total_tokens.unwrap_or(0) + usage.usage.total_tokens.unwrap_or(0),
                     );
+                    if usage.rate_limit.is_some() {
+                        e.rate_limit = usage.rate_limit.clone();
+                    }
+                    if let Some(cost) = usage.cost {
+                        e.cost = Some(e.cost.unwrap_or(0.0) + cost);
+                    }
                 })
                 .or_insert_with(|| usage.clone());
         });
@@ -240,10 +428,17 @@ impl Capabilities {
     }
 
     /// Get client resources and their contents
+    ///
+    /// The timestamp of each resource is when it was last changed by its extension or read by
+    /// the model, whichever is later.
     pub async fn get_resources(&self) -> ExtensionResult<Vec<ResourceItem>> {
         let mut result: Vec<ResourceItem> = Vec::new();
+        let resource_access = self.resource_access.lock().await;
 
         for (name, client) in &self.clients {
+            if !self.resource_capable_extensions.contains(name) {
+                continue;
+            }
             let client_guard = client.lock().await;
             let resources = client_guard.list_resources(None).await?;
 
@@ -254,6 +449,12 @@ impl Capabilities {
                     continue;
                 }
 
+                let timestamp = resource
+                    .timestamp()
+                    .into_iter()
+                    .chain(resource_access.get(&resource.uri).copied())
+                    .max()
+                    .unwrap_or(*DEFAULT_TIMESTAMP);
                 if let Ok(contents) = client_guard.read_resource(&resource.uri).await {
                     for content in contents.contents {
                         let (uri, content_str) = match content {
@@ -269,14 +470,17 @@ impl Capabilities {
                             } => (uri, blob),
                         };
 
-                        result.push(ResourceItem::new(
-                            name.clone(),
-                            uri,
-                            resource.name.clone(),
-                            content_str,
-                            resource.timestamp().unwrap_or(*DEFAULT_TIMESTAMP),
-                            resource.priority().unwrap_or(0.0),
-                        ));
+                        result.push(
+                            ResourceItem::new(
+                                name.clone(),
+                                uri,
+                                resource.name.clone(),
+                                content_str,
+                                timestamp,
+                                resource.priority().unwrap_or(0.0),
+                            )
+                            .with_pinned(resource.is_pinned()),
+                        );
                     }
                 }
             }
@@ -301,6 +505,164 @@ impl Capabilities {
         load_prompt_file("system.md", &context).expect("Prompt should render")
     }
 
+    /// The system prompt, tools and active resources a request starts from
+    ///
+    /// The tools include the platform resource tools when any extension supports resources.
+    /// Active resources are only included when they are put in the prompt, each cut to
+    /// [`MAX_RESOURCE_PROMPT_BYTES`]. Resources not accessed within the idle timeout are left
+    /// out, and the task is not included.
+    async fn request_context(&mut self) -> ExtensionResult<(String, Vec<Tool>, Vec<ResourceItem>)> {
+        let mut tools = self.get_prefixed_tools().await?;
+        let system_prompt = self.get_system_prompt().await;
+        let mut resources = Vec::new();
+
+        if self.supports_resources() {
+            tools.push(read_resource_tool());
+            tools.push(list_resources_tool());
+        }
+        if self.supports_resources() && self.resources_in_prompt {
+            // Resources are extra context, so a failing extension should not block the reply
+            resources = self.get_resources().await.unwrap_or_else(|e| {
+                warn!("Failed to read active resources: {}", e);
+                Vec::new()
+            });
+            if let Some(timeout) = self.resource_idle_timeout {
+                evict_idle(&mut resources, Utc::now(), timeout);
+            }
+            for resource in &mut resources {
+                cap_resource_content(resource);
+            }
+        }
+        tools.push(set_task_tool());
+        Ok((system_prompt, tools, resources))
+    }
+
+    /// The pinned task as a resource, if one is set
+    async fn task_resource(&self) -> Option<ResourceItem> {
+        let task = self.task().await?;
+        Some(
+            ResourceItem::new(
+                "platform".to_string(),
+                TASK_URI.to_string(),
+                "task".to_string(),
+                task,
+                Utc::now(),
+                1.0,
+            )
+            .with_pinned(true),
+        )
+    }
+
+    /// Estimate the tokens of a request for the conversation in `messages`
+    ///
+    /// Counts the system prompt with the active resources and the task, the messages and the
+    /// tools, as they are before any trimming or summarizing, so the count shows how much of the
+    /// context limit the conversation would take up as is.
+    pub async fn count_tokens(
+        &mut self,
+        messages: &[Message],
+        token_counter: &TokenCounter,
+    ) -> ExtensionResult<usize> {
+        let (mut system_prompt, tools, mut resources) = self.request_context().await?;
+        resources.extend(self.task_resource().await);
+        system_prompt.push_str(&format_resources(&resources));
+        Ok(token_counter.count_chat_tokens(&system_prompt, messages, &tools))
+    }
+
+    /// Assemble the request sent to the provider for the conversation in `messages`
+    ///
+    /// The tools include the platform resource tools when any extension supports resources, and
+    /// the pinned task, plus the active resources when enabled, is added to the system prompt.
+    /// Resources not accessed within the idle timeout are dropped, and any tool output is cut to
+    /// half of the budget. When the request would not fit in the model's target limit, its
+    /// context limit scaled by the target ratio, large tool outputs are shortened and resources
+    /// dropped in the order set by the trim priority.
+    pub async fn prepare_inference(
+        &mut self,
+        messages: &[Message],
+        token_counter: &TokenCounter,
+    ) -> ExtensionResult<PreparedRequest> {
+        self.prepare_request(messages, token_counter, true).await
+    }
+
+    /// Assemble the request `prepare_inference` would, without calling the provider
+    ///
+    /// A conversation that needs a new summary is left whole, as writing one calls the provider.
+    pub async fn preview_inference(
+        &mut self,
+        messages: &[Message],
+        token_counter: &TokenCounter,
+    ) -> ExtensionResult<PreparedRequest> {
+        self.prepare_request(messages, token_counter, false).await
+    }
+
+    async fn prepare_request(
+        &mut self,
+        messages: &[Message],
+        token_counter: &TokenCounter,
+        summarize: bool,
+    ) -> ExtensionResult<PreparedRequest> {
+        let (mut system_prompt, tools, mut resources) = self.request_context().await?;
+        let mut messages = messages.to_vec();
+
+        let reserved = token_counter.count_chat_tokens(&system_prompt, &[], &tools);
+        let budget = self
+            .provider
+            .get_model_config()
+            .target_limit()
+            .saturating_sub(reserved);
+
+        // A single tool output too large for the context would fail the whole request
+        let max_tool_output = (budget as f32 * MAX_TOOL_OUTPUT_SHARE) as usize;
+        cap_tool_outputs(&mut messages, token_counter, max_tool_output);
+
+        // Summarize the oldest messages once the conversation alone nears the context limit
+        let provider: Option<&dyn Provider> = summarize.then_some(self.provider.as_ref());
+        match self
+            .summarizer
+            .summarize(provider, &mut messages, token_counter, budget)
+            .await
+        {
+            Ok(Some(usage)) => self.record_usage(usage).await,
+            Ok(None) => {}
+            Err(e) => warn!("Failed to summarize the conversation: {}", e),
+        }
+
+        resources.extend(self.task_resource().await);
+
+        if !resources.is_empty() {
+            trim_to_budget(
+                &mut messages,
+                &mut resources,
+                token_counter,
+                budget,
+                self.trim_priority,
+            );
+            system_prompt.push_str(&format_resources(&resources));
+        }
+
+        Ok(PreparedRequest {
+            system_prompt,
+            messages,
+            tools,
+        })
+    }
+
+    // Function that gets executed for set_task tool
+    async fn set_task_tool_call(&self, params: Value) -> Result<Vec<Content>, ToolError> {
+        let task = params
+            .get("task")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidParameters("Missing 'task' parameter".to_string()))?;
+
+        self.set_task(Some(task.to_string())).await;
+        let message = match self.task().await {
+            Some(_) => "The task is pinned into the context",
+            None => "The task has been cleared",
+        };
+        Ok(vec![Content::text(message)])
+    }
+
     /// Find and return a reference to the appropriate client for a tool call
     fn get_client_for_tool(&self, prefixed_name: &str) -> Option<(&str, McpClientBox)> {
         self.clients
@@ -316,37 +678,30 @@ impl Capabilities {
             .and_then(|v| v.as_str())
             .ok_or_else(|| ToolError::InvalidParameters("Missing 'uri' parameter".to_string()))?;
 
-        let extension_name = params.get("extension_name").and_then(|v| v.as_str());
-
         // If extension name is provided, we can just look it up
-        if extension_name.is_some() {
-            let result = self
-                .read_resource_from_extension(uri, extension_name.unwrap())
-                .await?;
-            return Ok(result);
+        if let Some(extension_name) = params.get("extension_name").and_then(|v| v.as_str()) {
+            return self.read_resource_from_extension(uri, extension_name).await;
         }
 
-        // If extension name is not provided, we need to search for the resource across all extensions
-        // Loop through each extension and try to read the resource, don't raise an error if the resource is not found
-        // TODO: do we want to find if a provided uri is in multiple extensions?
-        // currently it will return the first match and skip any others
-        for extension_name in self.resource_capable_extensions.iter() {
-            let result = self.read_resource_from_extension(uri, extension_name).await;
-            match result {
+        // If extension name is not provided, try each extension that supports resources in
+        // name order, and return the first that has the resource
+        let mut extensions: Vec<&String> = self.resource_capable_extensions.iter().collect();
+        extensions.sort();
+        for extension_name in &extensions {
+            match self.read_resource_from_extension(uri, extension_name).await {
                 Ok(result) => return Ok(result),
-                Err(_) => continue,
+                Err(e) => debug!("Resource {} not read from {}: {}", uri, extension_name, e),
             }
         }
 
         // None of the extensions had the resource so we raise an error
-        let available_extensions = self
-            .clients
-            .keys()
+        let available_extensions = extensions
+            .iter()
             .map(|s| s.as_str())
             .collect::<Vec<&str>>()
             .join(", ");
         let error_msg = format!(
-            "Resource with uri '{}' not found. Here are the available extensions: {}",
+            "Resource with uri '{}' not found in any extension. Here are the extensions with resources: {}",
             uri, available_extensions
         );
 
@@ -375,9 +730,24 @@ impl Capabilities {
             .ok_or(ToolError::InvalidParameters(error_msg))?;
 
         let client_guard = client.lock().await;
-        let read_result = client_guard.read_resource(uri).await.map_err(|_| {
-            ToolError::ExecutionError(format!("Could not read resource with uri: {}", uri))
+        let read_result = client_guard.read_resource(uri).await.map_err(|e| {
+            debug!("Failed to read {} from {}: {}", uri, extension_name, e);
+            if is_resource_not_found(&e) {
+                ToolError::InvalidParameters(format!(
+                    "Resource with uri '{}' not found in {}",
+                    uri, extension_name
+                ))
+            } else {
+                ToolError::ExecutionError(format!(
+                    "Failed to read resource with uri '{}' from {}: {}",
+                    uri, extension_name, e
+                ))
+            }
         })?;
+        self.resource_access
+            .lock()
+            .await
+            .insert(uri.to_string(), Utc::now());
 
         let mut result = Vec::new();
         for content in read_result.contents {
@@ -472,6 +842,67 @@ impl Capabilities {
         }
     }
 
+    /// Dispatch the tool requests of one assistant message in parallel
+    ///
+    /// Only the first `GOOSE_MAX_TOOL_CALLS_PER_MESSAGE` requests are run, the rest are answered
+    /// with an error asking the model to request fewer tools at a time. When an approver is set,
+    /// each call is approved in turn before any of them run. At most
+    /// `GOOSE_MAX_CONCURRENT_TOOL_CALLS` calls run at once. Returns the message with a response
+    /// to every request, in the order of the requests.
+    pub async fn dispatch_tool_requests(&self, requests: &[&ToolRequest]) -> Message {
+        let max = self.max_tool_calls_per_message;
+        if requests.len() > max {
+            warn!(
+                "Model requested {} tool calls in one message, only running the first {}",
+                requests.len(),
+                max
+            );
+        }
+
+        let mut approved = Vec::with_capacity(requests.len());
+        for (index, request) in requests.iter().enumerate() {
+            let tool_call = if index >= max {
+                Err(ToolError::ExecutionError(format!(
+                    "Too many tool calls in one message, only the first {} were run. \
+                     Request at most {} tools at a time and retry this one.",
+                    max, max
+                )))
+            } else {
+                match (&request.tool_call, &self.approver) {
+                    (Ok(tool_call), Some(approver)) => {
+                        approver.approve(tool_call).await.apply(tool_call.clone())
+                    }
+                    (Ok(tool_call), None) => Ok(tool_call.clone()),
+                    (Err(e), _) => Err(e.clone()),
+                }
+            };
+            approved.push(tool_call);
+        }
+
+        // Run the calls a few at a time, so a burst of shell commands can't exhaust the system
+        let mut outputs: Vec<_> = futures::stream::iter(approved.into_iter().enumerate())
+            .map(|(index, tool_call)| async move {
+                let output = match tool_call {
+                    Ok(tool_call) => self.dispatch_tool_call(tool_call).await,
+                    Err(e) => Err(e),
+                };
+                (index, output)
+            })
+            .buffer_unordered(self.max_concurrent_tool_calls.max(1))
+            .collect()
+            .await;
+        outputs.sort_by_key(|(index, _)| *index);
+        let outputs = outputs.into_iter().map(|(_, output)| output);
+
+        // Combine these into MessageContent::ToolResponse using the original ID
+        requests
+            .iter()
+            .zip(outputs)
+            .fold(Message::user(), |message, (request, output)| {
+                message.with_tool_response(request.id.clone(), output)
+            })
+    }
+
     /// Dispatch a single tool call to the appropriate client
     #[instrument(skip(self, tool_call), fields(input, output))]
     pub async fn dispatch_tool_call(&self, tool_call: ToolCall) -> ToolResult<Vec<Content>> {
@@ -480,6 +911,8 @@ impl Capabilities {
             self.read_resource(tool_call.arguments.clone()).await
         } else if tool_call.name == "platform__list_resources" {
             self.list_resources(tool_call.arguments.clone()).await
+        } else if tool_call.name == "platform__set_task" {
+            self.set_task_tool_call(tool_call.arguments.clone()).await
         } else {
             // Else, dispatch tool call based on the prefix naming convention
             let (client_name, client) = self
@@ -511,85 +944,559 @@ impl Capabilities {
     }
 }
 
+/// Whether `error` is an extension saying it has no resource with the requested uri
+fn is_resource_not_found(error: &ClientError) -> bool {
+    matches!(
+        error,
+        ClientError::RpcError { code, .. }
+            if *code == INVALID_REQUEST || *code == RESOURCE_NOT_FOUND
+    )
+}
+
+/// Cut the content of a resource to [`MAX_RESOURCE_PROMPT_BYTES`], pointing the model at the
+/// read_resource tool for the rest
+fn cap_resource_content(resource: &mut ResourceItem) {
+    let kept = truncate_str(&resource.content, MAX_RESOURCE_PROMPT_BYTES).len();
+    if kept < resource.content.len() {
+        resource.content.truncate(kept);
+        resource.content.push_str(&format!(
+            "\n[... truncated, read {} with platform__read_resource for the rest]",
+            resource.uri
+        ));
+    }
+}
+
+/// Render active resources as a section appended to the system prompt
+fn format_resources(resources: &[ResourceItem]) -> String {
+    let mut section = String::new();
+    if resources.is_empty() {
+        return section;
+    }
+    section.push_str("\n\n# Active Resources\n");
+    for resource in resources {
+        section.push_str(&format!(
+            "\n## {} ({})\n{}\n",
+            resource.name, resource.uri, resource.content
+        ));
+    }
+    section
+}
+
+// TODO: make sure there is no collision with another extension's tool name
+fn read_resource_tool() -> Tool {
+    Tool::new(
+        "platform__read_resource".to_string(),
+        indoc! {r#"
+            Read a resource from an extension.
+
+            Resources allow extensions to share data that provide context to LLMs, such as
+            files, database schemas, or application-specific information. This tool searches for the
+            resource URI in the provided extension, and reads in the resource content. If no extension
+            is provided, the tool will search all extensions for the resource.
+        "#}.to_string(),
+        json!({
+            "type": "object",
+            "required": ["uri"],
+            "properties": {
+                "uri": {"type": "string", "description": "Resource URI"},
+                "extension_name": {"type": "string", "description": "Optional extension name"}
+            }
+        }),
+    )
+}
+
+fn set_task_tool() -> Tool {
+    Tool::new(
+        "platform__set_task".to_string(),
+        indoc! {r#"
+            Pin a description of the task you are working on into your context.
+
+            Use this for multi-step work so the goal stays in view across turns. The task is
+            always included in your context, even when older messages and resources are trimmed.
+            Call it again to update the task, or with an empty string to clear it.
+        "#}
+        .to_string(),
+        json!({
+            "type": "object",
+            "required": ["task"],
+            "properties": {
+                "task": {"type": "string", "description": "The goal of the current task"}
+            }
+        }),
+    )
+}
+
+fn list_resources_tool() -> Tool {
+    Tool::new(
+        "platform__list_resources".to_string(),
+        indoc! {r#"
+            List resources from an extension(s).
+
+            Resources allow extensions to share data that provide context to LLMs, such as
+            files, database schemas, or application-specific information. This tool lists resources
+            in the provided extension, and returns a list for the user to browse. If no extension
+            is provided, the tool will search all extensions for the resource.
+        "#}
+        .to_string(),
+        json!({
+            "type": "object",
+            "properties": {
+                "extension_name": {"type": "string", "description": "Optional extension name"}
+            }
+        }),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::message::Message;
-    use crate::model::ModelConfig;
-    use crate::providers::base::{Provider, ProviderMetadata, ProviderUsage, Usage};
-    use crate::providers::errors::ProviderError;
-    use mcp_client::client::Error;
+    use crate::model::{ModelConfig, GPT_4O_TOKENIZER};
+    use crate::testing::{MockClient, MockProvider};
     use mcp_client::client::McpClientTrait;
-    use mcp_core::protocol::{
-        CallToolResult, InitializeResult, ListResourcesResult, ListToolsResult, ReadResourceResult,
-    };
     use serde_json::json;
 
-    // Mock Provider implementation for testing
-    #[derive(Clone)]
-    struct MockProvider {
-        model_config: ModelConfig,
+    // Mock client with the tools "tool" and "test__tool"
+    fn tool_client() -> Arc<Mutex<Box<dyn McpClientTrait>>> {
+        Arc::new(Mutex::new(Box::new(
+            MockClient::new("test").with_tools(["tool", "test__tool"]),
+        )))
     }
 
-    #[async_trait::async_trait]
-    impl Provider for MockProvider {
-        fn metadata() -> ProviderMetadata {
-            ProviderMetadata::empty()
+    /// A resource that every [`resource_client`] fails to read
+    const LOCKED_URI: &str = "file:///locked.toml";
+
+    // Mock client that has a config file at each of `uris`
+    fn resource_client(uris: Vec<&str>) -> Arc<Mutex<Box<dyn McpClientTrait>>> {
+        let client = uris.into_iter().fold(
+            MockClient::new("resources").with_unreadable_resource(LOCKED_URI),
+            |client, uri| client.with_resource(uri, "db_url = \"postgres://localhost\""),
+        );
+        Arc::new(Mutex::new(Box::new(client)))
+    }
+
+    #[tokio::test]
+    async fn test_read_resource_searches_extensions() {
+        let mut capabilities = Capabilities::new(Box::new(MockProvider::new("test-model")));
+        for (name, uris) in [("alpha", vec![]), ("beta", vec!["file:///config.toml"])] {
+            capabilities
+                .clients
+                .insert(name.to_string(), resource_client(uris));
+            capabilities
+                .resource_capable_extensions
+                .insert(name.to_string());
         }
 
-        fn get_model_config(&self) -> ModelConfig {
-            self.model_config.clone()
+        // Without an extension, the one that has the resource is found
+        let result = capabilities
+            .read_resource(json!({"uri": "file:///config.toml"}))
+            .await
+            .unwrap();
+        assert!(result[0]
+            .as_text()
+            .unwrap()
+            .contains("db_url = \"postgres://localhost\""));
+
+        let result = capabilities
+            .read_resource(json!({"uri": "file:///config.toml", "extension_name": "beta"}))
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+
+        // A named extension that lacks the resource says so
+        let err = capabilities
+            .read_resource(json!({"uri": "file:///config.toml", "extension_name": "alpha"}))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid parameters: Resource with uri 'file:///config.toml' not found in alpha"
+        );
+
+        // Other failures are reported as they are
+        let err = capabilities
+            .read_resource(json!({"uri": LOCKED_URI, "extension_name": "beta"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::ExecutionError(_)));
+        assert!(err.to_string().contains("Request timed out"));
+
+        let err = capabilities
+            .read_resource(json!({"uri": "file:///missing.toml"}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not found in any extension"));
+        assert!(err.to_string().contains("alpha, beta"));
+    }
+
+    #[tokio::test]
+    async fn test_resources_in_prompt_are_opt_in() {
+        let token_counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let mut capabilities = Capabilities::new(Box::new(MockProvider::new("test-model")));
+        capabilities.clients.insert(
+            "beta".to_string(),
+            resource_client(vec!["file:///config.toml"]),
+        );
+        capabilities
+            .resource_capable_extensions
+            .insert("beta".to_string());
+        let messages = vec![Message::user().with_text("Which database is used?")];
+
+        // By default the model reads resources with the platform tools when it needs them
+        capabilities.set_resources_in_prompt(false);
+        let request = capabilities
+            .prepare_inference(&messages, &token_counter)
+            .await
+            .unwrap();
+        assert!(!request.system_prompt.contains("Active Resources"));
+
+        capabilities.set_resources_in_prompt(true);
+        let request = capabilities
+            .prepare_inference(&messages, &token_counter)
+            .await
+            .unwrap();
+        assert!(request.system_prompt.contains("Active Resources"));
+        assert!(request.system_prompt.contains("postgres://localhost"));
+    }
+
+    #[tokio::test]
+    async fn test_reading_a_resource_keeps_it_from_going_idle() {
+        let mut capabilities = Capabilities::new(Box::new(MockProvider::new("test-model")));
+        capabilities.clients.insert(
+            "beta".to_string(),
+            resource_client(vec!["file:///config.toml"]),
+        );
+        capabilities
+            .resource_capable_extensions
+            .insert("beta".to_string());
+        capabilities.set_resources_in_prompt(true);
+        capabilities.set_resource_idle_timeout(Some(chrono::Duration::minutes(10)));
+
+        let (_, _, resources) = capabilities.request_context().await.unwrap();
+        assert!(resources.is_empty());
+
+        capabilities
+            .read_resource(json!({"uri": "file:///config.toml"}))
+            .await
+            .unwrap();
+        let (_, _, resources) = capabilities.request_context().await.unwrap();
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].uri, "file:///config.toml");
+    }
+
+    #[test]
+    fn test_resource_content_is_capped() {
+        let mut resource = ResourceItem::new(
+            "developer".to_string(),
+            "file:///big.log".to_string(),
+            "big.log".to_string(),
+            "é".repeat(MAX_RESOURCE_PROMPT_BYTES),
+            Utc::now(),
+            0.5,
+        );
+        cap_resource_content(&mut resource);
+        let (kept, note) = resource.content.split_once("\n[... truncated").unwrap();
+        assert_eq!(kept.len(), MAX_RESOURCE_PROMPT_BYTES);
+        assert!(note.contains("file:///big.log"));
+
+        let mut small = resource.clone();
+        small.content = "small".to_string();
+        cap_resource_content(&mut small);
+        assert_eq!(small.content, "small");
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_run_at_most_limit_at_once() {
+        let mut capabilities = Capabilities::new(Box::new(MockProvider::new("test-model")));
+        capabilities.set_max_concurrent_tool_calls(2);
+
+        // Calls to one extension wait for each other, so each call goes to its own extension
+        let napper = MockClient::new("napper")
+            .with_tools(["nap"])
+            .with_tool_delay(std::time::Duration::from_millis(50));
+        let mut requests = Vec::new();
+        for i in 0..6 {
+            capabilities.clients.insert(
+                format!("napper{}", i),
+                Arc::new(Mutex::new(Box::new(napper.clone()))),
+            );
+            requests.push(ToolRequest {
+                id: i.to_string(),
+                tool_call: Ok(ToolCall::new(format!("napper{}__nap", i), json!({}))),
+            });
         }
 
-        async fn complete(
-            &self,
-            _system: &str,
-            _messages: &[Message],
-            _tools: &[Tool],
-        ) -> anyhow::Result<(Message, ProviderUsage), ProviderError> {
-            Ok((
-                Message::assistant().with_text("Mock response"),
-                ProviderUsage::new("mock".to_string(), Usage::default()),
+        let requests: Vec<&ToolRequest> = requests.iter().collect();
+        let message = capabilities.dispatch_tool_requests(&requests).await;
+        assert_eq!(napper.most_concurrent_calls(), 2);
+
+        // The responses keep the order of the requests
+        let ids: Vec<&str> = message
+            .content
+            .iter()
+            .map(|content| content.as_tool_response().unwrap().id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["0", "1", "2", "3", "4", "5"]);
+        assert!(message.content.iter().all(|content| content
+            .as_tool_response()
+            .unwrap()
+            .tool_result
+            .is_ok()));
+    }
+
+    #[tokio::test]
+    async fn test_set_task_is_pinned_into_context() {
+        let mut capabilities = Capabilities::new(Box::new(MockProvider::from_config(
+            ModelConfig::new("test-model".to_string()).with_context_limit(Some(10)),
+        )));
+        let token_counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let messages = vec![Message::user().with_text("Hello")];
+
+        let result = capabilities
+            .dispatch_tool_call(ToolCall::new(
+                "platform__set_task",
+                json!({"task": "Migrate the config loader to YAML"}),
             ))
-        }
+            .await
+            .unwrap();
+        assert_eq!(
+            result[0].as_text().unwrap(),
+            "The task is pinned into the context"
+        );
+
+        // The context limit is far too small, but the pinned task is still included
+        let request = capabilities
+            .prepare_inference(&messages, &token_counter)
+            .await
+            .unwrap();
+        assert!(request
+            .system_prompt
+            .contains("Migrate the config loader to YAML"));
+
+        // Setting it again replaces the task, and an empty task clears it
+        capabilities
+            .set_task(Some("Write the changelog".to_string()))
+            .await;
+        assert_eq!(
+            capabilities.task().await.as_deref(),
+            Some("Write the changelog")
+        );
+        capabilities
+            .dispatch_tool_call(ToolCall::new("platform__set_task", json!({"task": ""})))
+            .await
+            .unwrap();
+        assert_eq!(capabilities.task().await, None);
+        let request = capabilities
+            .prepare_inference(&messages, &token_counter)
+            .await
+            .unwrap();
+        assert!(!request.system_prompt.contains("Active Resources"));
     }
 
-    struct MockClient {}
+    #[tokio::test]
+    async fn test_prepare_inference() {
+        let mut capabilities = Capabilities::new(Box::new(MockProvider::new("test-model")));
+        capabilities.clients.insert(
+            "developer".to_string(),
+            Arc::new(Mutex::new(Box::new(
+                MockClient::new("developer").with_tools(["shell"]),
+            ))),
+        );
+        capabilities.instructions.insert(
+            "developer".to_string(),
+            "Use the shell carefully".to_string(),
+        );
+        let messages = vec![Message::user().with_text("Hello")];
+        let token_counter = TokenCounter::new(GPT_4O_TOKENIZER);
 
-    #[async_trait::async_trait]
-    impl McpClientTrait for MockClient {
-        async fn initialize(
-            &mut self,
-            _info: ClientInfo,
-            _capabilities: ClientCapabilities,
-        ) -> Result<InitializeResult, Error> {
-            Err(Error::NotInitialized)
-        }
+        let request = capabilities
+            .prepare_inference(&messages, &token_counter)
+            .await
+            .unwrap();
+        assert_eq!(request.messages, messages);
+        assert_eq!(
+            request.system_prompt,
+            capabilities.get_system_prompt().await
+        );
+        assert!(request.system_prompt.contains("Use the shell carefully"));
+        let tool_names: Vec<&str> = request.tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(tool_names, vec!["developer__shell", "platform__set_task"]);
+
+        // Resource tools are only offered once an extension supports resources
+        capabilities
+            .resource_capable_extensions
+            .insert("developer".to_string());
+        let request = capabilities
+            .prepare_inference(&messages, &token_counter)
+            .await
+            .unwrap();
+        let tool_names: Vec<&str> = request.tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            tool_names,
+            vec![
+                "developer__shell",
+                "platform__read_resource",
+                "platform__list_resources",
+                "platform__set_task"
+            ]
+        );
+        assert!(request
+            .system_prompt
+            .contains("developer supports resources"));
+
+        // The request serializes for inspection
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["tools"].as_array().unwrap().len(), 4);
+        assert_eq!(value["messages"][0]["role"], "user");
+    }
 
-        async fn list_resources(
-            &self,
-            _next_cursor: Option<String>,
-        ) -> Result<ListResourcesResult, Error> {
-            Err(Error::NotInitialized)
-        }
+    #[tokio::test]
+    async fn test_oversized_tool_output_fits_in_request() {
+        let token_counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let model_config =
+            ModelConfig::new("test-model".to_string()).with_context_limit(Some(10_000));
+        let mut capabilities = Capabilities::new(Box::new(MockProvider::from_config(model_config)));
+
+        // Far larger than the whole context window
+        let output = "warning: unused variable `x`\n".repeat(5000);
+        assert!(token_counter.count_tokens(&output) > 10_000);
+        let messages = vec![
+            Message::user().with_text("Why is the build so noisy?"),
+            Message::assistant().with_tool_request(
+                "1",
+                Ok(ToolCall::new(
+                    "developer__shell",
+                    json!({"command": "make"}),
+                )),
+            ),
+            Message::user().with_tool_response("1", Ok(vec![Content::text(output)])),
+        ];
+
+        let request = capabilities
+            .prepare_inference(&messages, &token_counter)
+            .await
+            .unwrap();
+        let trimmed = request.messages[2].content[0]
+            .as_tool_response_text()
+            .unwrap();
+        assert!(trimmed.starts_with("warning: unused variable `x`"));
+        assert!(trimmed.contains("[... truncated to fit context"));
+        let total = token_counter.count_chat_tokens(
+            &request.system_prompt,
+            &request.messages,
+            &request.tools,
+        );
+        assert!(total <= 10_000, "{} tokens", total);
+    }
 
-        async fn read_resource(&self, _uri: &str) -> Result<ReadResourceResult, Error> {
-            Err(Error::NotInitialized)
-        }
+    #[tokio::test]
+    async fn test_count_tokens() {
+        let token_counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let mut capabilities = Capabilities::new(Box::new(MockProvider::new("test-model")));
+        let messages = vec![
+            Message::user().with_text("What is in this directory?"),
+            Message::assistant().with_text("A Cargo.toml and a src directory."),
+        ];
+
+        // The system prompt and the platform tools are counted along with the messages
+        let system_prompt = capabilities.get_system_prompt().await;
+        let expected =
+            token_counter.count_chat_tokens(&system_prompt, &messages, &[set_task_tool()]);
+        let count = capabilities
+            .count_tokens(&messages, &token_counter)
+            .await
+            .unwrap();
+        assert_eq!(count, expected);
+        assert!(count > token_counter.count_chat_tokens("", &messages, &[]));
+
+        // So is the task, which is pinned into the system prompt
+        let task = "Document the layout of the repository";
+        capabilities.set_task(Some(task.to_string())).await;
+        let with_task = capabilities
+            .count_tokens(&messages, &token_counter)
+            .await
+            .unwrap();
+        assert!(with_task >= count + token_counter.count_tokens(task));
+    }
 
-        async fn list_tools(&self, _next_cursor: Option<String>) -> Result<ListToolsResult, Error> {
-            Err(Error::NotInitialized)
+    #[tokio::test]
+    async fn test_trimming_stops_at_target_ratio() {
+        let token_counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let output = "error: something went wrong\n".repeat(300);
+        let output_tokens = token_counter.count_tokens(&output);
+        let mut messages = vec![Message::user().with_text("Why does the build fail?")];
+        for i in 0..8 {
+            let id = i.to_string();
+            messages.push(Message::assistant().with_tool_request(
+                &id,
+                Ok(ToolCall::new(
+                    "developer__shell",
+                    json!({"command": "make"}),
+                )),
+            ));
+            messages
+                .push(Message::user().with_tool_response(&id, Ok(vec![Content::text(&output)])));
         }
 
-        async fn call_tool(&self, name: &str, _arguments: Value) -> Result<CallToolResult, Error> {
-            match name {
-                "tool" | "test__tool" => Ok(CallToolResult {
-                    content: vec![],
-                    is_error: None,
-                }),
-                _ => Err(Error::NotInitialized),
+        let prepare = |ratio: Option<f32>| {
+            let messages = messages.clone();
+            let token_counter = &token_counter;
+            async move {
+                let model_config = ModelConfig::new("test-model".to_string())
+                    .with_context_limit(Some(output_tokens * 10))
+                    .with_target_ratio(ratio);
+                let target = model_config.target_limit();
+                let mut capabilities =
+                    Capabilities::new(Box::new(MockProvider::from_config(model_config)));
+                capabilities.summarizer = Summarizer::new(f32::MAX);
+                // Trimming applies once there are resources, such as the task
+                capabilities
+                    .set_task(Some("Fix the build".to_string()))
+                    .await;
+                let request = capabilities
+                    .prepare_inference(&messages, token_counter)
+                    .await
+                    .unwrap();
+                let total = token_counter.count_chat_tokens(
+                    &request.system_prompt,
+                    &request.messages,
+                    &request.tools,
+                );
+                let trimmed = request
+                    .messages
+                    .iter()
+                    .filter(|m| {
+                        m.content
+                            .first()
+                            .and_then(|c| c.as_tool_response_text())
+                            .is_some_and(|text| text.contains("tool output trimmed"))
+                    })
+                    .count();
+                (target, total, trimmed)
             }
-        }
+        };
+
+        // The whole conversation fits in the context limit
+        let (target, total, trimmed) = prepare(None).await;
+        assert_eq!(target, output_tokens * 10);
+        assert!(total <= target);
+        assert_eq!(trimmed, 0);
+
+        // At half the limit, outputs are trimmed until the request fits and no further
+        let (target, total, trimmed) = prepare(Some(0.5)).await;
+        assert_eq!(target, output_tokens * 5);
+        assert!(
+            total <= target,
+            "{} tokens over the target of {}",
+            total,
+            target
+        );
+        assert!(
+            total > target - output_tokens,
+            "trimmed to {} tokens",
+            total
+        );
+        assert!(trimmed > 0 && trimmed < 8, "{} outputs trimmed", trimmed);
     }
 
     #[test]
@@ -597,30 +1504,25 @@ mod tests {
         let mock_model_config =
             ModelConfig::new("test-model".to_string()).with_context_limit(200_000.into());
 
-        let mut capabilities = Capabilities::new(Box::new(MockProvider {
-            model_config: mock_model_config,
-        }));
+        let mut capabilities =
+            Capabilities::new(Box::new(MockProvider::from_config(mock_model_config)));
 
         // Add some mock clients
-        capabilities.clients.insert(
-            normalize("test_client".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
-        );
+        capabilities
+            .clients
+            .insert(normalize("test_client".to_string()), tool_client());
 
-        capabilities.clients.insert(
-            normalize("__client".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
-        );
+        capabilities
+            .clients
+            .insert(normalize("__client".to_string()), tool_client());
 
-        capabilities.clients.insert(
-            normalize("__cli__ent__".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
-        );
+        capabilities
+            .clients
+            .insert(normalize("__cli__ent__".to_string()), tool_client());
 
-        capabilities.clients.insert(
-            normalize("client 🚀".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
-        );
+        capabilities
+            .clients
+            .insert(normalize("client 🚀".to_string()), tool_client());
 
         // Test basic case
         assert!(capabilities
@@ -646,25 +1548,21 @@ mod tests {
         let mock_model_config =
             ModelConfig::new("test-model".to_string()).with_context_limit(200_000.into());
 
-        let mut capabilities = Capabilities::new(Box::new(MockProvider {
-            model_config: mock_model_config,
-        }));
+        let mut capabilities =
+            Capabilities::new(Box::new(MockProvider::from_config(mock_model_config)));
 
         // Add some mock clients
-        capabilities.clients.insert(
-            normalize("test_client".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
-        );
+        capabilities
+            .clients
+            .insert(normalize("test_client".to_string()), tool_client());
 
-        capabilities.clients.insert(
-            normalize("__cli__ent__".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
-        );
+        capabilities
+            .clients
+            .insert(normalize("__cli__ent__".to_string()), tool_client());
 
-        capabilities.clients.insert(
-            normalize("client 🚀".to_string()),
-            Arc::new(Mutex::new(Box::new(MockClient {}))),
-        );
+        capabilities
+            .clients
+            .insert(normalize("client 🚀".to_string()), tool_client());
 
         // verify a normal tool call
         let tool_call = ToolCall {
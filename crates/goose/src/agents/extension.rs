@@ -4,6 +4,8 @@ use mcp_client::client::Error as ClientError;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::config::{Config, ConfigError};
+
 /// Errors from Extension operation
 #[derive(Error, Debug)]
 pub enum ExtensionError {
@@ -15,27 +17,83 @@ pub enum ExtensionError {
     ContextLimit,
     #[error("Transport error: {0}")]
     Transport(#[from] mcp_client::transport::Error),
+    #[error("Failed to resolve a secret for the extension environment: {0}")]
+    Secret(#[from] ConfigError),
 }
 
 pub type ExtensionResult<T> = Result<T, ExtensionError>;
 
+/// The value of an environment variable passed to an extension
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum EnvValue {
+    /// A plain value, stored as is in the config
+    Value(String),
+    /// A secret looked up by name when the extension starts, e.g. `{"from_keyring": "MY_KEY"}`
+    ///
+    /// Secrets are resolved like any other config secret, from an environment variable of the
+    /// same name or else from the system keyring, so they never have to be stored in plaintext.
+    Keyring { from_keyring: String },
+}
+
+impl From<String> for EnvValue {
+    fn from(value: String) -> Self {
+        Self::Value(value)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct Envs {
     /// A map of environment variables to set, e.g. API_KEY -> some_secret, HOST -> host
     #[serde(default)]
     #[serde(flatten)]
-    map: HashMap<String, String>,
+    map: HashMap<String, EnvValue>,
 }
 
 impl Envs {
     pub fn new(map: HashMap<String, String>) -> Self {
-        Self { map }
+        Self {
+            map: map.into_iter().map(|(k, v)| (k, v.into())).collect(),
+        }
+    }
+
+    /// Set an environment variable to a plain value
+    pub fn with_value<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.map.insert(key.into(), EnvValue::Value(value.into()));
+        self
+    }
+
+    /// Set an environment variable to the secret stored under `secret` in the keyring
+    pub fn with_keyring_secret<K: Into<String>, S: Into<String>>(
+        mut self,
+        key: K,
+        secret: S,
+    ) -> Self {
+        self.map.insert(
+            key.into(),
+            EnvValue::Keyring {
+                from_keyring: secret.into(),
+            },
+        );
+        self
     }
 
-    pub fn get_env(&self) -> HashMap<String, String> {
+    /// Get the environment variables to set, with any secrets resolved from the global config
+    pub fn get_env(&self) -> Result<HashMap<String, String>, ConfigError> {
+        self.resolve(Config::global())
+    }
+
+    /// Get the environment variables to set, resolving secrets from the given config
+    pub fn resolve(&self, config: &Config) -> Result<HashMap<String, String>, ConfigError> {
         self.map
             .iter()
-            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .map(|(key, value)| {
+                let value = match value {
+                    EnvValue::Value(value) => value.clone(),
+                    EnvValue::Keyring { from_keyring } => config.get_secret(from_keyring)?,
+                };
+                Ok((key.clone(), value))
+            })
             .collect()
     }
 }
@@ -68,14 +126,14 @@ pub enum ExtensionConfig {
     Builtin {
         /// The name used to identify this extension
         name: String,
+        #[serde(default)]
+        envs: Envs,
     },
 }
 
 impl Default for ExtensionConfig {
     fn default() -> Self {
-        Self::Builtin {
-            name: String::from("default"),
-        }
+        Self::builtin("default")
     }
 }
 
@@ -97,6 +155,39 @@ impl ExtensionConfig {
         }
     }
 
+    pub fn builtin<S: Into<String>>(name: S) -> Self {
+        Self::Builtin {
+            name: name.into(),
+            envs: Envs::default(),
+        }
+    }
+
+    /// Set an environment variable for the extension's process, or its requests for SSE
+    pub fn with_env<K: Into<String>, V: Into<String>>(self, key: K, value: V) -> Self {
+        match self {
+            Self::Sse { name, uri, envs } => Self::Sse {
+                name,
+                uri,
+                envs: envs.with_value(key, value),
+            },
+            Self::Stdio {
+                name,
+                cmd,
+                args,
+                envs,
+            } => Self::Stdio {
+                name,
+                cmd,
+                args,
+                envs: envs.with_value(key, value),
+            },
+            Self::Builtin { name, envs } => Self::Builtin {
+                name,
+                envs: envs.with_value(key, value),
+            },
+        }
+    }
+
     pub fn with_args<I, S>(self, args: I) -> Self
     where
         I: IntoIterator<Item = S>,
@@ -120,7 +211,7 @@ impl ExtensionConfig {
         match self {
             Self::Sse { name, .. } => name,
             Self::Stdio { name, .. } => name,
-            Self::Builtin { name } => name,
+            Self::Builtin { name, .. } => name,
         }
     }
 }
@@ -134,7 +225,7 @@ impl std::fmt::Display for ExtensionConfig {
             } => {
                 write!(f, "Stdio({}: {} {})", name, cmd, args.join(" "))
             }
-            ExtensionConfig::Builtin { name } => write!(f, "Builtin({})", name),
+            ExtensionConfig::Builtin { name, .. } => write!(f, "Builtin({})", name),
         }
     }
 }
@@ -156,3 +247,42 @@ impl ExtensionInfo {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_keyring_env_is_resolved_to_secret() {
+        let envs: Envs = serde_json::from_value(json!({
+            "HOST": "localhost",
+            "API_KEY": {"from_keyring": "GOOSE_TEST_EXTENSION_KEY"}
+        }))
+        .unwrap();
+        assert_eq!(
+            envs.map["API_KEY"],
+            EnvValue::Keyring {
+                from_keyring: "GOOSE_TEST_EXTENSION_KEY".to_string()
+            }
+        );
+
+        // Secrets are looked up through the config, which checks the environment first
+        let temp_file = NamedTempFile::new().unwrap();
+        let config = Config::new(temp_file.path(), "goose-test").unwrap();
+        std::env::set_var("GOOSE_TEST_EXTENSION_KEY", "secret123");
+        let resolved = envs.resolve(&config).unwrap();
+        std::env::remove_var("GOOSE_TEST_EXTENSION_KEY");
+
+        assert_eq!(resolved["HOST"], "localhost");
+        assert_eq!(resolved["API_KEY"], "secret123");
+
+        // The reference, not the secret, is what gets written back to the config
+        let saved = serde_json::to_value(&envs).unwrap();
+        assert_eq!(
+            saved["API_KEY"],
+            json!({"from_keyring": "GOOSE_TEST_EXTENSION_KEY"})
+        );
+    }
+}
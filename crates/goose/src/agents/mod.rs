@@ -1,11 +1,17 @@
 mod agent;
+mod approval;
 mod capabilities;
 pub mod extension;
 mod factory;
 mod reference;
+pub mod replay;
+mod summarize;
+mod trim;
 mod truncate;
 
-pub use agent::Agent;
-pub use capabilities::Capabilities;
+pub use agent::{Agent, ReplyEvent, TokenCount, UsageSummary};
+pub use approval::{Approval, ToolApprover};
+pub use capabilities::{Capabilities, PreparedRequest};
 pub use extension::ExtensionConfig;
 pub use factory::{register_agent, AgentFactory};
+pub use trim::TrimPriority;
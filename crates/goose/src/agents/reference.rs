@@ -1,26 +1,29 @@
 /// A simplified agent implementation used as a reference
 /// It makes no attempt to handle context limits, and cannot read resources
 use async_trait::async_trait;
-use futures::stream::BoxStream;
+use futures::stream::{self, BoxStream, StreamExt};
 use tokio::sync::Mutex;
 use tracing::{debug, instrument};
 
-use super::Agent;
-use crate::agents::capabilities::Capabilities;
+use super::{Agent, ReplyEvent, TokenCount, ToolApprover};
+use crate::agents::capabilities::{Capabilities, PreparedRequest};
 use crate::agents::extension::{ExtensionConfig, ExtensionResult};
 use crate::message::{Message, ToolRequest};
+use crate::model::ModelConfig;
 use crate::providers::base::Provider;
-use crate::providers::base::ProviderUsage;
+use crate::providers::base::{MessageAccumulator, ProviderUsage};
+use crate::providers::moderation::Moderation;
+use crate::providers::rate_limit::RateLimitInfo;
+use crate::providers::router::Tier;
 use crate::register_agent;
 use crate::token_counter::TokenCounter;
-use indoc::indoc;
-use mcp_core::tool::Tool;
-use serde_json::{json, Value};
+use mcp_client::client::McpClientTrait;
+use serde_json::Value;
 
 /// Reference implementation of an Agent
 pub struct ReferenceAgent {
     capabilities: Mutex<Capabilities>,
-    _token_counter: TokenCounter,
+    token_counter: TokenCounter,
 }
 
 impl ReferenceAgent {
@@ -28,7 +31,7 @@ impl ReferenceAgent {
         let token_counter = TokenCounter::new(provider.get_model_config().tokenizer_name());
         Self {
             capabilities: Mutex::new(Capabilities::new(provider)),
-            _token_counter: token_counter,
+            token_counter,
         }
     }
 }
@@ -40,6 +43,15 @@ impl Agent for ReferenceAgent {
         capabilities.add_extension(extension).await
     }
 
+    async fn add_client(
+        &mut self,
+        name: &str,
+        client: Box<dyn McpClientTrait>,
+    ) -> ExtensionResult<()> {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.add_client(name, client).await
+    }
+
     async fn remove_extension(&mut self, name: &str) {
         let mut capabilities = self.capabilities.lock().await;
         capabilities
@@ -61,61 +73,47 @@ impl Agent for ReferenceAgent {
         Ok(Value::Null)
     }
 
+    async fn dry_run(&self, messages: &[Message]) -> anyhow::Result<PreparedRequest> {
+        let mut capabilities = self.capabilities.lock().await;
+        Ok(capabilities
+            .preview_inference(messages, &self.token_counter)
+            .await?)
+    }
+
+    async fn count_tokens(&self, messages: &[Message]) -> anyhow::Result<TokenCount> {
+        let mut capabilities = self.capabilities.lock().await;
+        let tokens = capabilities
+            .count_tokens(messages, &self.token_counter)
+            .await?;
+        Ok(TokenCount {
+            tokens,
+            context_limit: capabilities.provider().get_model_config().context_limit(),
+        })
+    }
+
     #[instrument(skip(self, messages), fields(user_message))]
-    async fn reply(
+    async fn reply_stream(
         &self,
         messages: &[Message],
-    ) -> anyhow::Result<BoxStream<'_, anyhow::Result<Message>>> {
-        let mut messages = messages.to_vec();
+    ) -> anyhow::Result<BoxStream<'_, anyhow::Result<ReplyEvent>>> {
         let reply_span = tracing::Span::current();
         let mut capabilities = self.capabilities.lock().await;
-        let mut tools = capabilities.get_prefixed_tools().await?;
-        // we add in the read_resource tool by default
-        // TODO: make sure there is no collision with another extension's tool name
-        let read_resource_tool = Tool::new(
-            "platform__read_resource".to_string(),
-            indoc! {r#"
-                Read a resource from an extension.
-
-                Resources allow extensions to share data that provide context to LLMs, such as
-                files, database schemas, or application-specific information. This tool searches for the
-                resource URI in the provided extension, and reads in the resource content. If no extension
-                is provided, the tool will search all extensions for the resource.
-            "#}.to_string(),
-            json!({
-                "type": "object",
-                "required": ["uri"],
-                "properties": {
-                    "uri": {"type": "string", "description": "Resource URI"},
-                    "extension_name": {"type": "string", "description": "Optional extension name"}
-                }
-            }),
-        );
-
-        let list_resources_tool = Tool::new(
-            "platform__list_resources".to_string(),
-            indoc! {r#"
-                List resources from an extension(s).
-
-                Resources allow extensions to share data that provide context to LLMs, such as
-                files, database schemas, or application-specific information. This tool lists resources
-                in the provided extension, and returns a list for the user to browse. If no extension
-                is provided, the tool will search all extensions for the resource.
-            "#}.to_string(),
-            json!({
-                "type": "object",
-                "properties": {
-                    "extension_name": {"type": "string", "description": "Optional extension name"}
-                }
-            }),
-        );
 
-        if capabilities.supports_resources() {
-            tools.push(read_resource_tool);
-            tools.push(list_resources_tool);
+        // Flagged input is never sent to the model
+        if let Some(result) = capabilities.moderate(messages).await? {
+            let refusal = Message::assistant().with_text(result.refusal());
+            return Ok(Box::pin(stream::once(async move {
+                Ok(ReplyEvent::Message(refusal))
+            })));
         }
 
-        let system_prompt = capabilities.get_system_prompt().await;
+        let PreparedRequest {
+            system_prompt,
+            mut messages,
+            tools,
+        } = capabilities
+            .prepare_inference(messages, &self.token_counter)
+            .await?;
 
         // Set the user_message field in the span instead of creating a new event
         if let Some(content) = messages
@@ -129,16 +127,29 @@ impl Agent for ReferenceAgent {
         Ok(Box::pin(async_stream::try_stream! {
             let _reply_guard = reply_span.enter();
             loop {
-                // Get completion from provider
-                let (response, usage) = capabilities.provider().complete(
+                // Get completion from provider, passing the text on as it arrives
+                let mut deltas = capabilities.provider().complete_stream(
                     &system_prompt,
                     &messages,
                     &tools,
                 ).await?;
-                capabilities.record_usage(usage).await;
+                let mut accumulator = MessageAccumulator::default();
+                while let Some(delta) = deltas.next().await {
+                    let delta = delta?;
+                    if let Some(text) = delta.as_text() {
+                        yield ReplyEvent::Text(text.to_string());
+                    }
+                    accumulator.push(delta);
+                }
+                drop(deltas);
+
+                let (response, usage) = accumulator.finish();
+                if let Some(usage) = usage {
+                    capabilities.record_usage(usage).await;
+                }
 
                 // Yield the assistant's response
-                yield response.clone();
+                yield ReplyEvent::Message(response.clone());
 
                 tokio::task::yield_now().await;
 
@@ -152,27 +163,10 @@ impl Agent for ReferenceAgent {
                     break;
                 }
 
-                // Then dispatch each in parallel
-                let futures: Vec<_> = tool_requests
-                    .iter()
-                    .filter_map(|request| request.tool_call.clone().ok())
-                    .map(|tool_call| capabilities.dispatch_tool_call(tool_call))
-                    .collect();
-
-                // Process all the futures in parallel but wait until all are finished
-                let outputs = futures::future::join_all(futures).await;
-
-                // Create a message with the responses
-                let mut message_tool_response = Message::user();
-                // Now combine these into MessageContent::ToolResponse using the original ID
-                for (request, output) in tool_requests.iter().zip(outputs.into_iter()) {
-                    message_tool_response = message_tool_response.with_tool_response(
-                        request.id.clone(),
-                        output,
-                    );
-                }
+                // Then dispatch them in parallel, up to the per message cap
+                let message_tool_response = capabilities.dispatch_tool_requests(&tool_requests).await;
 
-                yield message_tool_response.clone();
+                yield ReplyEvent::Message(message_tool_response.clone());
 
                 messages.push(response);
                 messages.push(message_tool_response);
@@ -184,6 +178,37 @@ impl Agent for ReferenceAgent {
         let capabilities = self.capabilities.lock().await;
         capabilities.get_usage().await
     }
+
+    async fn rate_limit(&self) -> Option<RateLimitInfo> {
+        let capabilities = self.capabilities.lock().await;
+        capabilities.rate_limit().await
+    }
+
+    async fn model_config(&self) -> ModelConfig {
+        let capabilities = self.capabilities.lock().await;
+        capabilities.provider().get_model_config()
+    }
+
+    async fn set_route_hint(&self, tier: Option<Tier>) {
+        let capabilities = self.capabilities.lock().await;
+        capabilities.provider().set_route_hint(tier);
+    }
+
+    async fn set_provider(&mut self, provider: Box<dyn Provider>) {
+        self.token_counter = TokenCounter::new(provider.get_model_config().tokenizer_name());
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_provider(provider);
+    }
+
+    async fn set_moderation(&mut self, moderation: Option<Box<dyn Moderation>>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_moderation(moderation);
+    }
+
+    async fn set_approver(&mut self, approver: Option<Box<dyn ToolApprover>>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_approver(approver);
+    }
 }
 
 register_agent!("reference", ReferenceAgent);
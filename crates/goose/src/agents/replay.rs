@@ -0,0 +1,477 @@
+//! Capture an agent session and replay it for regression testing
+//!
+//! A [`SessionRecorder`] wraps the provider and the extension clients of a live session and
+//! records what they return. The [`SessionRecording`] can be saved as a JSON fixture, and later
+//! stands in for the provider and extensions so the session runs again without either. Any
+//! change in the agent's control flow then shows up as a different message sequence, or as a
+//! tool call that was never recorded.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use mcp_client::client::{ClientCapabilities, ClientInfo, Error as ClientError, McpClientTrait};
+use mcp_core::protocol::{
+    CallToolResult, InitializeResult, ListResourcesResult, ListToolsResult, ReadResourceResult,
+};
+use mcp_core::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::message::Message;
+use crate::model::ModelConfig;
+use crate::providers::base::{Provider, ProviderMetadata, ProviderUsage};
+use crate::providers::errors::ProviderError;
+
+/// A completion returned by the provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedCompletion {
+    pub message: Message,
+    pub usage: ProviderUsage,
+}
+
+/// What an extension answered to a tool call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedToolOutput {
+    Result(CallToolResult),
+    /// An error reported by the MCP server, replayed exactly
+    RpcError {
+        code: i32,
+        message: String,
+    },
+    /// Any other failure, such as a timeout, replayed with the same message
+    Error(String),
+}
+
+/// A tool call made to an extension, with the name the extension knows the tool by
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedToolCall {
+    pub name: String,
+    pub arguments: Value,
+    pub output: RecordedToolOutput,
+}
+
+/// An extension as the session saw it
+///
+/// Resources are not recorded, as they only add context to requests that are replayed anyway.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedExtension {
+    pub name: String,
+    pub initialize: InitializeResult,
+    pub tools: Vec<Tool>,
+    pub calls: Vec<RecordedToolCall>,
+}
+
+/// Everything the provider and extensions returned during a session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionRecording {
+    pub model: Option<ModelConfig>,
+    pub completions: Vec<RecordedCompletion>,
+    pub extensions: Vec<RecordedExtension>,
+}
+
+impl SessionRecording {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// A provider that returns the recorded completions in order, whatever it is asked
+    pub fn replay_provider(&self) -> Box<dyn Provider> {
+        Box::new(ReplayProvider {
+            model: self
+                .model
+                .clone()
+                .unwrap_or_else(|| ModelConfig::new("replay".to_string())),
+            completions: Mutex::new(self.completions.iter().cloned().collect()),
+        })
+    }
+
+    /// A client for each recorded extension, by name, that answers with the recorded outputs
+    ///
+    /// Each recorded call is answered once, when a call is made with the same tool and
+    /// arguments, so calls dispatched concurrently may arrive in any order.
+    pub fn replay_clients(&self) -> Vec<(String, Box<dyn McpClientTrait>)> {
+        self.extensions
+            .iter()
+            .map(|extension| {
+                let client: Box<dyn McpClientTrait> = Box::new(ReplayClient {
+                    extension: extension.clone(),
+                    answered: Mutex::new(vec![false; extension.calls.len()]),
+                });
+                (extension.name.clone(), client)
+            })
+            .collect()
+    }
+}
+
+/// Records a live session as the agent runs it
+#[derive(Debug, Clone, Default)]
+pub struct SessionRecorder {
+    recording: Arc<Mutex<SessionRecording>>,
+}
+
+impl SessionRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wrap the session's provider to record its completions
+    ///
+    /// Completions are recorded whole, so a streaming provider is not streamed while recording.
+    pub fn provider(&self, inner: Box<dyn Provider>) -> Box<dyn Provider> {
+        self.recording.lock().unwrap().model = Some(inner.get_model_config());
+        Box::new(RecordingProvider {
+            inner,
+            recording: Arc::clone(&self.recording),
+        })
+    }
+
+    /// Wrap the client of the extension called `name` to record its tools and tool calls
+    pub fn client(&self, name: &str, inner: Box<dyn McpClientTrait>) -> Box<dyn McpClientTrait> {
+        Box::new(RecordingClient {
+            name: name.to_string(),
+            inner,
+            recording: Arc::clone(&self.recording),
+        })
+    }
+
+    /// What has been recorded so far
+    pub fn recording(&self) -> SessionRecording {
+        self.recording.lock().unwrap().clone()
+    }
+}
+
+struct RecordingProvider {
+    inner: Box<dyn Provider>,
+    recording: Arc<Mutex<SessionRecording>>,
+}
+
+#[async_trait]
+impl Provider for RecordingProvider {
+    fn metadata() -> ProviderMetadata {
+        ProviderMetadata::empty()
+    }
+
+    async fn complete(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<(Message, ProviderUsage), ProviderError> {
+        let (message, usage) = self.inner.complete(system, messages, tools).await?;
+        self.recording
+            .lock()
+            .unwrap()
+            .completions
+            .push(RecordedCompletion {
+                message: message.clone(),
+                usage: usage.clone(),
+            });
+        Ok((message, usage))
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.inner.get_model_config()
+    }
+}
+
+struct ReplayProvider {
+    model: ModelConfig,
+    completions: Mutex<VecDeque<RecordedCompletion>>,
+}
+
+#[async_trait]
+impl Provider for ReplayProvider {
+    fn metadata() -> ProviderMetadata {
+        ProviderMetadata::empty()
+    }
+
+    async fn complete(
+        &self,
+        _system: &str,
+        _messages: &[Message],
+        _tools: &[Tool],
+    ) -> Result<(Message, ProviderUsage), ProviderError> {
+        let completion = self
+            .completions
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| {
+                ProviderError::ExecutionError(
+                    "The session asked for more completions than were recorded".to_string(),
+                )
+            })?;
+        Ok((completion.message, completion.usage))
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.model.clone()
+    }
+}
+
+struct RecordingClient {
+    name: String,
+    inner: Box<dyn McpClientTrait>,
+    recording: Arc<Mutex<SessionRecording>>,
+}
+
+impl RecordingClient {
+    fn with_extension(&self, update: impl FnOnce(&mut RecordedExtension)) {
+        let mut recording = self.recording.lock().unwrap();
+        if let Some(extension) = recording
+            .extensions
+            .iter_mut()
+            .find(|extension| extension.name == self.name)
+        {
+            update(extension);
+        }
+    }
+}
+
+#[async_trait]
+impl McpClientTrait for RecordingClient {
+    async fn initialize(
+        &mut self,
+        info: ClientInfo,
+        capabilities: ClientCapabilities,
+    ) -> Result<InitializeResult, ClientError> {
+        let result = self.inner.initialize(info, capabilities).await?;
+        let mut recording = self.recording.lock().unwrap();
+        recording
+            .extensions
+            .retain(|extension| extension.name != self.name);
+        recording.extensions.push(RecordedExtension {
+            name: self.name.clone(),
+            initialize: result.clone(),
+            tools: Vec::new(),
+            calls: Vec::new(),
+        });
+        Ok(result)
+    }
+
+    async fn list_resources(
+        &self,
+        next_cursor: Option<String>,
+    ) -> Result<ListResourcesResult, ClientError> {
+        self.inner.list_resources(next_cursor).await
+    }
+
+    async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult, ClientError> {
+        self.inner.read_resource(uri).await
+    }
+
+    async fn list_tools(
+        &self,
+        next_cursor: Option<String>,
+    ) -> Result<ListToolsResult, ClientError> {
+        let first_page = next_cursor.is_none();
+        let result = self.inner.list_tools(next_cursor).await?;
+        self.with_extension(|extension| {
+            if first_page {
+                extension.tools.clear();
+            }
+            extension.tools.extend(result.tools.iter().cloned());
+        });
+        Ok(result)
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult, ClientError> {
+        let result = self.inner.call_tool(name, arguments.clone()).await;
+        let output = match &result {
+            Ok(result) => RecordedToolOutput::Result(result.clone()),
+            Err(ClientError::RpcError { code, message }) => RecordedToolOutput::RpcError {
+                code: *code,
+                message: message.clone(),
+            },
+            Err(e) => RecordedToolOutput::Error(e.to_string()),
+        };
+        self.with_extension(|extension| {
+            extension.calls.push(RecordedToolCall {
+                name: name.to_string(),
+                arguments,
+                output,
+            })
+        });
+        result
+    }
+}
+
+struct ReplayClient {
+    extension: RecordedExtension,
+    answered: Mutex<Vec<bool>>,
+}
+
+#[async_trait]
+impl McpClientTrait for ReplayClient {
+    async fn initialize(
+        &mut self,
+        _info: ClientInfo,
+        _capabilities: ClientCapabilities,
+    ) -> Result<InitializeResult, ClientError> {
+        Ok(self.extension.initialize.clone())
+    }
+
+    async fn list_resources(
+        &self,
+        _next_cursor: Option<String>,
+    ) -> Result<ListResourcesResult, ClientError> {
+        Ok(ListResourcesResult {
+            resources: Vec::new(),
+            next_cursor: None,
+        })
+    }
+
+    async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult, ClientError> {
+        Err(ClientError::UnexpectedResponse(format!(
+            "Resources are not replayed, cannot read {}",
+            uri
+        )))
+    }
+
+    async fn list_tools(
+        &self,
+        _next_cursor: Option<String>,
+    ) -> Result<ListToolsResult, ClientError> {
+        Ok(ListToolsResult {
+            tools: self.extension.tools.clone(),
+            next_cursor: None,
+        })
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult, ClientError> {
+        let mut answered = self.answered.lock().unwrap();
+        let index = self
+            .extension
+            .calls
+            .iter()
+            .enumerate()
+            .position(|(i, call)| !answered[i] && call.name == name && call.arguments == arguments)
+            .ok_or_else(|| {
+                ClientError::UnexpectedResponse(format!(
+                    "No recorded call to {} in {} with arguments {}",
+                    name, self.extension.name, arguments
+                ))
+            })?;
+        answered[index] = true;
+
+        match &self.extension.calls[index].output {
+            RecordedToolOutput::Result(result) => Ok(result.clone()),
+            RecordedToolOutput::RpcError { code, message } => Err(ClientError::RpcError {
+                code: *code,
+                message: message.clone(),
+            }),
+            RecordedToolOutput::Error(message) => {
+                Err(ClientError::UnexpectedResponse(message.clone()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::{Agent, AgentFactory};
+    use crate::testing::{MockClient, MockProvider};
+    use futures::StreamExt;
+    use mcp_core::{Content, ToolCall};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A files extension whose outputs change with every call, so a replay that called it
+    /// again would differ from the recording
+    fn files_client() -> MockClient {
+        let calls = AtomicUsize::new(0);
+        MockClient::new("files")
+            .with_instructions("Read and write files")
+            .with_tools(["read", "write"])
+            .with_tool_handler(move |name, arguments| {
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                match name {
+                    "read" => Ok(vec![Content::text(format!(
+                        "contents of {} (call {})",
+                        arguments["path"], call
+                    ))]),
+                    _ => Err(ClientError::RpcError {
+                        code: -32602,
+                        message: format!("{} is read only", arguments["path"]),
+                    }),
+                }
+            })
+    }
+
+    fn tool_request(id: &str, name: &str, path: &str) -> Message {
+        Message::assistant().with_tool_request(id, Ok(ToolCall::new(name, json!({"path": path}))))
+    }
+
+    /// Run two user turns, returning every message without its timestamp
+    async fn run_session(agent: &dyn Agent) -> Vec<Value> {
+        let mut messages = Vec::new();
+        for text in ["Compare a.txt and b.txt", "Now update c.txt"] {
+            messages.push(Message::user().with_text(text));
+            let mut reply = agent.reply(&messages).await.unwrap();
+            while let Some(message) = reply.next().await {
+                messages.push(message.unwrap());
+            }
+        }
+        messages
+            .iter()
+            .map(|message| {
+                let mut value = serde_json::to_value(message).unwrap();
+                value.as_object_mut().unwrap().remove("created");
+                value
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_recorded_session_replays_identically() {
+        let first = tool_request("1", "files__read", "a.txt").with_tool_request(
+            "2",
+            Ok(ToolCall::new("files__read", json!({"path": "b.txt"}))),
+        );
+        let script = vec![
+            first,
+            Message::assistant().with_text("They differ in one line."),
+            tool_request("3", "files__write", "c.txt"),
+            Message::assistant().with_text("c.txt is read only."),
+        ];
+
+        let recorder = SessionRecorder::new();
+        let provider =
+            recorder.provider(Box::new(MockProvider::new("scripted").with_replies(script)));
+        let mut agent = AgentFactory::create("reference", provider).unwrap();
+        agent
+            .add_client("files", recorder.client("files", Box::new(files_client())))
+            .await
+            .unwrap();
+        let recorded = run_session(agent.as_ref()).await;
+        assert_eq!(recorded.len(), 8);
+
+        // Through a fixture file, as a regression test would keep it
+        let fixture = tempfile::NamedTempFile::new().unwrap();
+        recorder.recording().save(fixture.path()).unwrap();
+        let recording = SessionRecording::load(fixture.path()).unwrap();
+        assert_eq!(recording.completions.len(), 4);
+        assert_eq!(recording.extensions[0].calls.len(), 3);
+
+        let mut agent = AgentFactory::create("reference", recording.replay_provider()).unwrap();
+        for (name, client) in recording.replay_clients() {
+            agent.add_client(&name, client).await.unwrap();
+        }
+        let replayed = run_session(agent.as_ref()).await;
+        assert_eq!(replayed, recorded);
+        let text = serde_json::to_string(&replayed).unwrap();
+        assert!(text.contains("contents of \\\"b.txt\\\" (call"));
+        assert!(text.contains("c.txt\\\" is read only"));
+    }
+}
@@ -0,0 +1,399 @@
+use mcp_core::text::truncate_str;
+use mcp_core::Role;
+use std::collections::HashSet;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::config::Config;
+use crate::message::{Message, MessageContent};
+use crate::providers::base::{Provider, ProviderUsage};
+use crate::providers::errors::ProviderError;
+use crate::token_counter::TokenCounter;
+
+/// Default share of the context window the messages may use before older ones are summarized
+pub const DEFAULT_SUMMARIZE_THRESHOLD: f32 = 0.8;
+
+/// How many bytes of each tool output are included in the transcript sent for summarizing
+const MAX_TOOL_OUTPUT_BYTES: usize = 2000;
+
+/// Prefix of the message that stands in for the summarized part of the conversation
+pub const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:";
+
+const SUMMARIZE_PROMPT: &str = "You summarize conversations between a user and an AI \
+assistant that uses tools. Write a concise summary of the transcript you are given, keeping \
+the user's goals and instructions, decisions made, files and commands involved, and any results \
+or open problems needed to continue the work. Reply with the summary only.";
+
+/// A summary of the start of a conversation, reused until it no longer keeps it under target
+#[derive(Debug, Clone)]
+struct Summary {
+    /// How many messages from the start of the conversation the summary replaces
+    replaced: usize,
+    /// The last replaced message, to check the summary still matches the conversation
+    last_replaced: Message,
+    message: Message,
+    tokens: usize,
+}
+
+/// Replaces the oldest messages with a summary when the conversation grows too long
+///
+/// The threshold is the share of the token budget the messages may use before they are
+/// summarized, set with the `GOOSE_SUMMARIZE_THRESHOLD` config key.
+#[derive(Debug)]
+pub struct Summarizer {
+    threshold: f32,
+    summary: Mutex<Option<Summary>>,
+}
+
+impl Default for Summarizer {
+    fn default() -> Self {
+        Self::new(DEFAULT_SUMMARIZE_THRESHOLD)
+    }
+}
+
+impl Summarizer {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            summary: Mutex::new(None),
+        }
+    }
+
+    pub fn from_config() -> Self {
+        Self::new(
+            Config::global()
+                .get("GOOSE_SUMMARIZE_THRESHOLD")
+                .unwrap_or(DEFAULT_SUMMARIZE_THRESHOLD),
+        )
+    }
+
+    /// Summarize the oldest messages if the conversation uses more than its share of `budget`
+    ///
+    /// The oldest run of messages is replaced by a single user message holding a summary written
+    /// by the provider, leaving the newest messages to fill about half of the target. The split
+    /// never separates a tool request from its response. The summary is reused on later calls
+    /// as long as the rest of the conversation still fits. Without a provider, as for a dry run,
+    /// only a summary written earlier is applied. Returns the usage of the call that wrote a new
+    /// summary.
+    pub async fn summarize(
+        &self,
+        provider: Option<&dyn Provider>,
+        messages: &mut Vec<Message>,
+        token_counter: &TokenCounter,
+        budget: usize,
+    ) -> Result<Option<ProviderUsage>, ProviderError> {
+        let target = (budget as f32 * self.threshold) as usize;
+        let token_counts: Vec<usize> = messages
+            .iter()
+            .map(|message| message_tokens(token_counter, message))
+            .collect();
+        if token_counts.iter().sum::<usize>() <= target {
+            return Ok(None);
+        }
+
+        let mut cached = self.summary.lock().await;
+        if let Some(summary) = cached.as_ref().filter(|summary| {
+            summary.replaced < messages.len()
+                && messages[summary.replaced - 1] == summary.last_replaced
+                && summary.tokens + token_counts[summary.replaced..].iter().sum::<usize>() <= target
+        }) {
+            messages.splice(..summary.replaced, [summary.message.clone()]);
+            return Ok(None);
+        }
+        let Some(provider) = provider else {
+            return Ok(None);
+        };
+
+        let Some(split) = summary_split(messages, &token_counts, target / 2) else {
+            debug!("No place to split the conversation for a summary, leaving it as is");
+            return Ok(None);
+        };
+
+        let request = Message::user().with_text(transcript(&messages[..split]));
+        let (response, usage) = provider.complete(SUMMARIZE_PROMPT, &[request], &[]).await?;
+        let message = Message::user().with_text(format!(
+            "{}\n\n{}",
+            SUMMARY_PREFIX,
+            response.as_concat_text()
+        ));
+        let tokens = message_tokens(token_counter, &message);
+        debug!(
+            "Summarized {} messages of {} tokens into {} tokens",
+            split,
+            token_counts[..split].iter().sum::<usize>(),
+            tokens
+        );
+
+        *cached = Some(Summary {
+            replaced: split,
+            last_replaced: messages[split - 1].clone(),
+            message: message.clone(),
+            tokens,
+        });
+        messages.splice(..split, [message]);
+        Ok(Some(usage))
+    }
+}
+
+fn message_tokens(token_counter: &TokenCounter, message: &Message) -> usize {
+    token_counter.count_chat_tokens("", std::slice::from_ref(message), &[])
+}
+
+/// Find how many of the oldest messages to summarize so the rest fit in `keep_budget` tokens
+///
+/// The rest has to start with an assistant message, to follow the user message holding the
+/// summary, and no tool call may have its request and response on different sides of the
+/// split. The newest message is always kept. Returns None if there is no such split.
+fn summary_split(
+    messages: &[Message],
+    token_counts: &[usize],
+    keep_budget: usize,
+) -> Option<usize> {
+    let mut kept: usize = token_counts.iter().sum();
+    (1..messages.len()).find(|&split| {
+        kept -= token_counts[split - 1];
+        if kept > keep_budget || messages[split].role != Role::Assistant {
+            return false;
+        }
+        let summarized: HashSet<&str> = messages[..split]
+            .iter()
+            .flat_map(|message| message.get_tool_ids())
+            .collect();
+        messages[split..]
+            .iter()
+            .all(|message| message.get_tool_ids().is_disjoint(&summarized))
+    })
+}
+
+/// Render messages as plain text for the summarizing call, shortening long tool outputs
+fn transcript(messages: &[Message]) -> String {
+    let mut lines = Vec::new();
+    for message in messages {
+        let role = match message.role {
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+        };
+        for content in &message.content {
+            match content {
+                MessageContent::Text(text) => lines.push(format!("{}: {}", role, text.text)),
+                MessageContent::ToolRequest(request) => match &request.tool_call {
+                    Ok(call) => lines.push(format!(
+                        "{} called {} with {}",
+                        role, call.name, call.arguments
+                    )),
+                    Err(e) => lines.push(format!("{} made an invalid tool call: {}", role, e)),
+                },
+                MessageContent::ToolResponse(_) => {
+                    let output = content.as_tool_response_text().unwrap_or_default();
+                    let mut shortened = truncate_str(&output, MAX_TOOL_OUTPUT_BYTES).to_string();
+                    if shortened.len() < output.len() {
+                        shortened.push_str(" [...]");
+                    }
+                    lines.push(format!("Tool result: {}", shortened));
+                }
+                MessageContent::Image(_) => lines.push(format!("{}: [image]", role)),
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::GPT_4O_TOKENIZER;
+    use crate::testing::MockProvider;
+    use mcp_core::{Content, ToolCall};
+    use serde_json::json;
+
+    fn summary_provider() -> MockProvider {
+        MockProvider::new("gpt-4o-mini").with_replies([
+            Message::assistant().with_text("The user is fixing a build step by step.")
+        ])
+    }
+
+    // Each step is a user request, a tool call and its response, and an assistant reply
+    fn long_history(steps: usize) -> Vec<Message> {
+        let mut messages = Vec::new();
+        for i in 0..steps {
+            let id = format!("call_{}", i);
+            messages.push(Message::user().with_text(format!("Step {}: run the build", i)));
+            messages.push(
+                Message::assistant()
+                    .with_text("Running it")
+                    .with_tool_request(
+                        &id,
+                        Ok(ToolCall::new(
+                            "developer__shell",
+                            json!({"command": "make"}),
+                        )),
+                    ),
+            );
+            messages.push(Message::user().with_tool_response(
+                &id,
+                Ok(vec![Content::text("compiling module\n".repeat(100))]),
+            ));
+            messages.push(Message::assistant().with_text(format!("Step {} is done", i)));
+        }
+        messages.push(Message::user().with_text("What is left to do?"));
+        messages
+    }
+
+    fn assert_tool_pairs_intact(messages: &[Message]) {
+        let requests: HashSet<&str> = messages
+            .iter()
+            .flat_map(|m| m.get_tool_request_ids())
+            .collect();
+        let responses: HashSet<&str> = messages
+            .iter()
+            .flat_map(|m| m.get_tool_response_ids())
+            .collect();
+        assert_eq!(requests, responses);
+    }
+
+    fn total_tokens(counter: &TokenCounter, messages: &[Message]) -> usize {
+        messages.iter().map(|m| message_tokens(counter, m)).sum()
+    }
+
+    #[tokio::test]
+    async fn test_summarizes_oldest_messages_under_target() {
+        let counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let provider = summary_provider();
+        let summarizer = Summarizer::new(0.5);
+        let original = long_history(20);
+        let budget = total_tokens(&counter, &original);
+        let target = budget / 2;
+
+        let mut messages = original.clone();
+        let usage = summarizer
+            .summarize(Some(&provider), &mut messages, &counter, budget)
+            .await
+            .unwrap();
+
+        assert!(usage.is_some());
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].system, SUMMARIZE_PROMPT);
+        assert!(requests[0].tools.is_empty());
+        assert!(requests[0].messages[0]
+            .as_concat_text()
+            .contains("User: Step 0"));
+        assert!(total_tokens(&counter, &messages) <= target);
+        assert!(messages.len() < original.len());
+        assert!(messages[0].as_concat_text().starts_with(SUMMARY_PREFIX));
+        assert_eq!(messages[1].role, Role::Assistant);
+        assert_eq!(messages.last(), original.last());
+        assert_tool_pairs_intact(&messages);
+    }
+
+    #[tokio::test]
+    async fn test_summary_is_reused_while_conversation_fits() {
+        let counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let provider = summary_provider();
+        let summarizer = Summarizer::new(0.5);
+        let mut history = long_history(20);
+        let budget = total_tokens(&counter, &history);
+
+        let mut first = history.clone();
+        summarizer
+            .summarize(Some(&provider), &mut first, &counter, budget)
+            .await
+            .unwrap();
+
+        // The next turn adds a little to the conversation, which still fits with the summary
+        history.push(Message::assistant().with_text("Only the tests are left"));
+        history.push(Message::user().with_text("Run them"));
+        let mut second = history.clone();
+        let usage = summarizer
+            .summarize(Some(&provider), &mut second, &counter, budget)
+            .await
+            .unwrap();
+
+        assert!(usage.is_none());
+        assert_eq!(provider.requests().len(), 1);
+        assert_eq!(second[0], first[0]);
+        assert_eq!(second.len(), first.len() + 2);
+        assert_tool_pairs_intact(&second);
+    }
+
+    #[tokio::test]
+    async fn test_without_provider_only_reuses_summary() {
+        let counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let provider = summary_provider();
+        let summarizer = Summarizer::new(0.5);
+        let original = long_history(20);
+        let budget = total_tokens(&counter, &original);
+
+        // Nothing is summarized before the provider has written a summary
+        let mut messages = original.clone();
+        summarizer
+            .summarize(None, &mut messages, &counter, budget)
+            .await
+            .unwrap();
+        assert_eq!(messages, original);
+
+        let mut summarized = original.clone();
+        summarizer
+            .summarize(Some(&provider), &mut summarized, &counter, budget)
+            .await
+            .unwrap();
+        summarizer
+            .summarize(None, &mut messages, &counter, budget)
+            .await
+            .unwrap();
+        assert_eq!(messages, summarized);
+        assert_eq!(provider.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_under_threshold_is_unchanged() {
+        let counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let provider = summary_provider();
+        let original = long_history(3);
+        let mut messages = original.clone();
+
+        Summarizer::default()
+            .summarize(Some(&provider), &mut messages, &counter, 100_000)
+            .await
+            .unwrap();
+
+        assert_eq!(messages, original);
+        assert_eq!(provider.requests().len(), 0);
+    }
+
+    #[test]
+    fn test_split_never_orphans_tool_requests() {
+        let counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        // One tool call whose response only arrives after several other messages
+        let mut messages = vec![
+            Message::user().with_text("Start the server and check the logs"),
+            Message::assistant().with_tool_request(
+                "server",
+                Ok(ToolCall::new(
+                    "developer__shell",
+                    json!({"command": "serve"}),
+                )),
+            ),
+            Message::assistant().with_text("Waiting for the server"),
+            Message::assistant().with_text("Still waiting"),
+            Message::user().with_tool_response("server", Ok(vec![Content::text("listening")])),
+            Message::assistant().with_text("The server is up"),
+        ];
+        messages.push(Message::user().with_text("Great"));
+        let token_counts: Vec<usize> = messages
+            .iter()
+            .map(|m| message_tokens(&counter, m))
+            .collect();
+
+        // The budget allows keeping everything from the second assistant message, but the split
+        // has to move past the tool response
+        let keep_budget = token_counts[2..].iter().sum::<usize>();
+        assert_eq!(
+            summary_split(&messages, &token_counts, keep_budget),
+            Some(5)
+        );
+
+        // Nothing fits in a tiny budget without splitting the newest message off
+        assert_eq!(summary_split(&messages, &token_counts, 1), None);
+    }
+}
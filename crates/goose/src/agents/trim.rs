@@ -0,0 +1,400 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::capabilities::ResourceItem;
+use crate::message::{Message, MessageContent};
+use crate::token_counter::TokenCounter;
+use mcp_core::text::truncate_str;
+use mcp_core::Content;
+
+/// Tool outputs smaller than this are never shortened, they are cheap to keep
+pub const MIN_TRIMMABLE_TOOL_OUTPUT_TOKENS: usize = 500;
+
+/// How much of the start of a tool output is kept when it is shortened
+const TRIMMED_TOOL_OUTPUT_KEEP_TOKENS: usize = 200;
+
+/// Largest share of the token budget that a single tool output may take up
+pub const MAX_TOOL_OUTPUT_SHARE: f32 = 0.5;
+
+/// Which content is given up first when a request is over the token budget
+///
+/// Set with the `GOOSE_TRIM_PRIORITY` config key, as `tool_outputs_first` or
+/// `resources_first`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrimPriority {
+    /// Shorten large tool outputs before dropping any resources
+    #[default]
+    ToolOutputsFirst,
+    /// Drop resources before shortening any tool outputs
+    ResourcesFirst,
+}
+
+/// Trim the conversation and resources until they fit in `budget` tokens
+///
+/// Large tool outputs in the messages are shortened to their first few hundred tokens, largest
+/// first. Resources are dropped by lowest priority and then oldest first, except pinned ones
+/// which always stay. `priority` decides which of the two is trimmed before the other is touched.
+/// Returns the tokens used after trimming, which may still exceed the budget if everything
+/// trimmable is gone.
+pub fn trim_to_budget(
+    messages: &mut [Message],
+    resources: &mut Vec<ResourceItem>,
+    token_counter: &TokenCounter,
+    budget: usize,
+    priority: TrimPriority,
+) -> usize {
+    for resource in resources.iter_mut() {
+        if resource.token_count.is_none() {
+            resource.token_count = Some(token_counter.count_tokens(&resource.content) as u32);
+        }
+    }
+
+    let mut total = token_counter.count_chat_tokens("", messages, &[])
+        + resources
+            .iter()
+            .map(|r| r.token_count.unwrap_or(0) as usize)
+            .sum::<usize>();
+
+    match priority {
+        TrimPriority::ToolOutputsFirst => {
+            total = trim_tool_outputs(messages, token_counter, total, budget);
+            total = drop_resources(resources, total, budget);
+        }
+        TrimPriority::ResourcesFirst => {
+            total = drop_resources(resources, total, budget);
+            total = trim_tool_outputs(messages, token_counter, total, budget);
+        }
+    }
+    total
+}
+
+/// Truncate every tool output larger than `max_tokens` to about that size
+///
+/// A tool output larger than the whole context can never be sent, and trimming it down to a few
+/// hundred tokens like [`trim_to_budget`] does would throw away more than needed, so this applies
+/// whether or not the request is over budget. Returns how many outputs were truncated.
+pub fn cap_tool_outputs(
+    messages: &mut [Message],
+    token_counter: &TokenCounter,
+    max_tokens: usize,
+) -> usize {
+    let mut capped = 0;
+    for message in messages.iter_mut() {
+        for content in message.content.iter_mut() {
+            let MessageContent::ToolResponse(response) = content else {
+                continue;
+            };
+            let Ok(contents) = &mut response.tool_result else {
+                continue;
+            };
+            for item in contents.iter_mut() {
+                let Content::Text(text) = item else {
+                    continue;
+                };
+                let tokens = token_counter.count_tokens(&text.text);
+                if tokens <= max_tokens {
+                    continue;
+                }
+
+                let marker = format!(
+                    "\n[... truncated to fit context, the output was {} tokens ...]",
+                    tokens
+                );
+                // Tokens are estimated from bytes, with a margin since they are not spread evenly
+                let keep_tokens = max_tokens.saturating_sub(token_counter.count_tokens(&marker));
+                let keep = text.text.len() * keep_tokens / tokens * 9 / 10;
+                let mut truncated = truncate_str(&text.text, keep).to_string();
+                truncated.push_str(&marker);
+                debug!(
+                    "Truncated a tool output of {} tokens to fit the context window",
+                    tokens
+                );
+                text.text = truncated;
+                capped += 1;
+            }
+        }
+    }
+    capped
+}
+
+/// Drop resources that have not been accessed within `window` of `now`
+///
+/// The model has most likely moved on from resources it has not touched in a while, so they are
+/// dropped even when the request is within budget. Pinned resources are never dropped.
+pub fn evict_idle(resources: &mut Vec<ResourceItem>, now: DateTime<Utc>, window: Duration) {
+    resources.retain(|resource| {
+        let keep = resource.pinned || now - resource.timestamp <= window;
+        if !keep {
+            debug!(
+                "Evicting resource {} not accessed since {}",
+                resource.uri, resource.timestamp
+            );
+        }
+        keep
+    });
+}
+
+/// Shorten tool outputs, largest first, until `total` fits in the budget
+fn trim_tool_outputs(
+    messages: &mut [Message],
+    token_counter: &TokenCounter,
+    mut total: usize,
+    budget: usize,
+) -> usize {
+    // (message, content, tool output item, tokens) for every text in a tool output worth trimming
+    let mut candidates = Vec::new();
+    for (i, message) in messages.iter().enumerate() {
+        for (j, content) in message.content.iter().enumerate() {
+            let Some(Ok(contents)) = content.as_tool_response().map(|r| &r.tool_result) else {
+                continue;
+            };
+            for (k, item) in contents.iter().enumerate() {
+                if let Some(text) = item.as_text() {
+                    let tokens = token_counter.count_tokens(text);
+                    if tokens >= MIN_TRIMMABLE_TOOL_OUTPUT_TOKENS {
+                        candidates.push((i, j, k, tokens));
+                    }
+                }
+            }
+        }
+    }
+    candidates.sort_by_key(|&(.., tokens)| std::cmp::Reverse(tokens));
+
+    for (i, j, k, tokens) in candidates {
+        if total <= budget {
+            break;
+        }
+        let MessageContent::ToolResponse(response) = &mut messages[i].content[j] else {
+            continue;
+        };
+        let Ok(contents) = &mut response.tool_result else {
+            continue;
+        };
+        let Content::Text(text) = &mut contents[k] else {
+            continue;
+        };
+
+        // Keep roughly the first tokens, by bytes since the text is only estimated
+        let keep = text.text.len() * TRIMMED_TOOL_OUTPUT_KEEP_TOKENS / tokens;
+        let mut trimmed = truncate_str(&text.text, keep).to_string();
+        trimmed.push_str(&format!(
+            "\n[... tool output trimmed from {} tokens to fit the context window ...]",
+            tokens
+        ));
+
+        let trimmed_tokens = token_counter.count_tokens(&trimmed);
+        debug!(
+            "Trimmed tool output in message {} from {} to {} tokens",
+            i, tokens, trimmed_tokens
+        );
+        text.text = trimmed;
+        total = total - tokens + trimmed_tokens;
+    }
+    total
+}
+
+/// Drop resources by lowest priority and then oldest, until `total` fits in the budget
+///
+/// Pinned resources are never dropped.
+fn drop_resources(resources: &mut Vec<ResourceItem>, mut total: usize, budget: usize) -> usize {
+    resources.sort_by(|a, b| {
+        a.pinned
+            .cmp(&b.pinned)
+            .then(a.priority.total_cmp(&b.priority))
+            .then(a.timestamp.cmp(&b.timestamp))
+    });
+
+    let mut dropped = 0;
+    for resource in resources.iter().take_while(|r| !r.pinned) {
+        if total <= budget {
+            break;
+        }
+        debug!(
+            "Dropping resource {} to fit the context window",
+            resource.uri
+        );
+        total -= resource.token_count.unwrap_or(0) as usize;
+        dropped += 1;
+    }
+    resources.drain(..dropped);
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::GPT_4O_TOKENIZER;
+    use mcp_core::ToolCall;
+    use serde_json::json;
+
+    fn file_resource(name: &str, content: &str, priority: f32) -> ResourceItem {
+        ResourceItem::new(
+            "developer".to_string(),
+            format!("file:///{}", name),
+            name.to_string(),
+            content.to_string(),
+            Utc::now(),
+            priority,
+        )
+    }
+
+    fn conversation(tool_output: &str) -> Vec<Message> {
+        vec![
+            Message::user().with_text("Fix the bug in main.rs"),
+            Message::assistant().with_tool_request(
+                "1",
+                Ok(ToolCall::new(
+                    "developer__shell",
+                    json!({"command": "cat log"}),
+                )),
+            ),
+            Message::user().with_tool_response("1", Ok(vec![Content::text(tool_output)])),
+        ]
+    }
+
+    fn tool_output(messages: &[Message]) -> String {
+        messages[2].content[0].as_tool_response_text().unwrap()
+    }
+
+    #[test]
+    fn test_huge_tool_output_is_trimmed_before_important_file() {
+        let counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let huge_output = "error: something went wrong\n".repeat(2000);
+        let mut messages = conversation(&huge_output);
+        let mut resources = vec![file_resource("main.rs", "fn main() { bug(); }", 1.0)];
+
+        let total = trim_to_budget(
+            &mut messages,
+            &mut resources,
+            &counter,
+            1000,
+            TrimPriority::ToolOutputsFirst,
+        );
+
+        assert!(total <= 1000);
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].name, "main.rs");
+        let output = tool_output(&messages);
+        assert!(output.starts_with("error: something went wrong"));
+        assert!(output.contains("tool output trimmed"));
+        assert!(counter.count_tokens(&output) < 300);
+    }
+
+    #[test]
+    fn test_oversized_tool_output_is_capped() {
+        let counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let huge_output = "error: something went wrong\n".repeat(2000);
+        let mut messages = conversation(&huge_output);
+        messages.push(Message::user().with_tool_response("2", Ok(vec![Content::text("ok")])));
+
+        assert_eq!(cap_tool_outputs(&mut messages, &counter, 1000), 1);
+        let output = tool_output(&messages);
+        assert!(output.starts_with("error: something went wrong"));
+        assert!(output.ends_with(&format!(
+            "[... truncated to fit context, the output was {} tokens ...]",
+            counter.count_tokens(&huge_output)
+        )));
+        let tokens = counter.count_tokens(&output);
+        assert!(tokens <= 1000 && tokens > 800, "{} tokens", tokens);
+        assert_eq!(
+            messages[3].content[0].as_tool_response_text().unwrap(),
+            "ok"
+        );
+
+        // Outputs within the cap are left alone
+        assert_eq!(cap_tool_outputs(&mut messages, &counter, 1000), 0);
+    }
+
+    #[test]
+    fn test_resources_first_drops_least_important_resources() {
+        let counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let output = "line of output\n".repeat(200);
+        let mut messages = conversation(&output);
+        let mut resources = vec![
+            file_resource("notes.md", &"some notes ".repeat(300), 0.2),
+            file_resource("main.rs", "fn main() { bug(); }", 1.0),
+        ];
+        let budget = counter.count_chat_tokens("", &messages, &[]) + 100;
+
+        let total = trim_to_budget(
+            &mut messages,
+            &mut resources,
+            &counter,
+            budget,
+            TrimPriority::ResourcesFirst,
+        );
+
+        assert!(total <= budget);
+        let names: Vec<&str> = resources.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["main.rs"]);
+        // Dropping the resource was enough, so the tool output is untouched
+        assert_eq!(tool_output(&messages), output);
+    }
+
+    #[test]
+    fn test_within_budget_is_unchanged() {
+        let counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let output = "x ".repeat(1000);
+        let mut messages = conversation(&output);
+        let mut resources = vec![file_resource("main.rs", "fn main() {}", 0.5)];
+
+        trim_to_budget(
+            &mut messages,
+            &mut resources,
+            &counter,
+            100_000,
+            TrimPriority::ToolOutputsFirst,
+        );
+
+        assert_eq!(resources.len(), 1);
+        assert_eq!(tool_output(&messages), output);
+    }
+
+    #[test]
+    fn test_pinned_task_survives_trimming() {
+        let counter = TokenCounter::new(GPT_4O_TOKENIZER);
+        let mut messages = conversation("done");
+        let task =
+            file_resource("task", "Migrate the config loader to YAML", 1.0).with_pinned(true);
+        let mut resources = vec![
+            task,
+            file_resource("main.rs", &"fn main() {}\n".repeat(500), 1.0),
+            file_resource("lib.rs", &"pub mod config;\n".repeat(500), 1.0),
+        ];
+
+        // Not even the task fits, but it is still kept while everything else goes
+        trim_to_budget(
+            &mut messages,
+            &mut resources,
+            &counter,
+            10,
+            TrimPriority::ResourcesFirst,
+        );
+
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].content, "Migrate the config loader to YAML");
+    }
+
+    #[test]
+    fn test_idle_resources_are_evicted() {
+        let now = Utc::now();
+        let mut untouched = file_resource("old.rs", "fn old() {}", 1.0);
+        untouched.timestamp = now - Duration::hours(2);
+        let mut cwd = file_resource("cwd", "/repo", 1.0).with_pinned(true);
+        cwd.timestamp = now - Duration::hours(2);
+        let mut resources = vec![
+            untouched,
+            cwd,
+            file_resource("main.rs", "fn main() {}", 1.0),
+        ];
+
+        evict_idle(&mut resources, now, Duration::hours(3));
+        assert_eq!(resources.len(), 3);
+
+        evict_idle(&mut resources, now, Duration::minutes(30));
+        let names: Vec<&str> = resources.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["cwd", "main.rs"]);
+    }
+}
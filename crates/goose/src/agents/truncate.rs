@@ -1,23 +1,26 @@
 /// A truncate agent that truncates the conversation history when it exceeds the model's context limit
 /// It makes no attempt to handle context limits, and cannot read resources
 use async_trait::async_trait;
-use futures::stream::BoxStream;
+use futures::stream::{self, BoxStream, StreamExt};
 use tokio::sync::Mutex;
 use tracing::{debug, error, instrument, warn};
 
-use super::Agent;
-use crate::agents::capabilities::Capabilities;
+use super::{Agent, ReplyEvent, TokenCount, ToolApprover};
+use crate::agents::capabilities::{Capabilities, PreparedRequest};
 use crate::agents::extension::{ExtensionConfig, ExtensionResult};
 use crate::message::{Message, ToolRequest};
+use crate::model::ModelConfig;
 use crate::providers::base::Provider;
-use crate::providers::base::ProviderUsage;
+use crate::providers::base::{MessageAccumulator, ProviderUsage};
 use crate::providers::errors::ProviderError;
+use crate::providers::moderation::Moderation;
+use crate::providers::rate_limit::RateLimitInfo;
+use crate::providers::router::Tier;
 use crate::register_agent;
 use crate::token_counter::TokenCounter;
 use crate::truncate::{truncate_messages, OldestFirstTruncation};
-use indoc::indoc;
-use mcp_core::tool::Tool;
-use serde_json::{json, Value};
+use mcp_client::client::McpClientTrait;
+use serde_json::Value;
 
 const MAX_TRUNCATION_ATTEMPTS: usize = 3;
 const ESTIMATE_FACTOR_DECAY: f32 = 0.9;
@@ -81,6 +84,15 @@ impl Agent for TruncateAgent {
         capabilities.add_extension(extension).await
     }
 
+    async fn add_client(
+        &mut self,
+        name: &str,
+        client: Box<dyn McpClientTrait>,
+    ) -> ExtensionResult<()> {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.add_client(name, client).await
+    }
+
     async fn remove_extension(&mut self, name: &str) {
         let mut capabilities = self.capabilities.lock().await;
         capabilities
@@ -102,63 +114,48 @@ impl Agent for TruncateAgent {
         Ok(Value::Null)
     }
 
+    async fn dry_run(&self, messages: &[Message]) -> anyhow::Result<PreparedRequest> {
+        let mut capabilities = self.capabilities.lock().await;
+        Ok(capabilities
+            .preview_inference(messages, &self.token_counter)
+            .await?)
+    }
+
+    async fn count_tokens(&self, messages: &[Message]) -> anyhow::Result<TokenCount> {
+        let mut capabilities = self.capabilities.lock().await;
+        let tokens = capabilities
+            .count_tokens(messages, &self.token_counter)
+            .await?;
+        Ok(TokenCount {
+            tokens,
+            context_limit: capabilities.provider().get_model_config().context_limit(),
+        })
+    }
+
     #[instrument(skip(self, messages), fields(user_message))]
-    async fn reply(
+    async fn reply_stream(
         &self,
         messages: &[Message],
-    ) -> anyhow::Result<BoxStream<'_, anyhow::Result<Message>>> {
-        let mut messages = messages.to_vec();
+    ) -> anyhow::Result<BoxStream<'_, anyhow::Result<ReplyEvent>>> {
         let reply_span = tracing::Span::current();
         let mut capabilities = self.capabilities.lock().await;
-        let mut tools = capabilities.get_prefixed_tools().await?;
-        let mut truncation_attempt: usize = 0;
-
-        // we add in the read_resource tool by default
-        // TODO: make sure there is no collision with another extension's tool name
-        let read_resource_tool = Tool::new(
-            "platform__read_resource".to_string(),
-            indoc! {r#"
-                Read a resource from an extension.
-
-                Resources allow extensions to share data that provide context to LLMs, such as
-                files, database schemas, or application-specific information. This tool searches for the
-                resource URI in the provided extension, and reads in the resource content. If no extension
-                is provided, the tool will search all extensions for the resource.
-            "#}.to_string(),
-            json!({
-                "type": "object",
-                "required": ["uri"],
-                "properties": {
-                    "uri": {"type": "string", "description": "Resource URI"},
-                    "extension_name": {"type": "string", "description": "Optional extension name"}
-                }
-            }),
-        );
 
-        let list_resources_tool = Tool::new(
-            "platform__list_resources".to_string(),
-            indoc! {r#"
-                List resources from an extension(s).
-
-                Resources allow extensions to share data that provide context to LLMs, such as
-                files, database schemas, or application-specific information. This tool lists resources
-                in the provided extension, and returns a list for the user to browse. If no extension
-                is provided, the tool will search all extensions for the resource.
-            "#}.to_string(),
-            json!({
-                "type": "object",
-                "properties": {
-                    "extension_name": {"type": "string", "description": "Optional extension name"}
-                }
-            }),
-        );
-
-        if capabilities.supports_resources() {
-            tools.push(read_resource_tool);
-            tools.push(list_resources_tool);
+        // Flagged input is never sent to the model
+        if let Some(result) = capabilities.moderate(messages).await? {
+            let refusal = Message::assistant().with_text(result.refusal());
+            return Ok(Box::pin(stream::once(async move {
+                Ok(ReplyEvent::Message(refusal))
+            })));
         }
 
-        let system_prompt = capabilities.get_system_prompt().await;
+        let PreparedRequest {
+            system_prompt,
+            mut messages,
+            tools,
+        } = capabilities
+            .prepare_inference(messages, &self.token_counter)
+            .await?;
+        let mut truncation_attempt: usize = 0;
 
         // Set the user_message field in the span instead of creating a new event
         if let Some(content) = messages
@@ -172,20 +169,53 @@ impl Agent for TruncateAgent {
         Ok(Box::pin(async_stream::try_stream! {
             let _reply_guard = reply_span.enter();
             loop {
-                // Attempt to get completion from provider
-                match capabilities.provider().complete(
+                // Attempt to get completion from provider, passing the text on as it arrives
+                let completion = match capabilities.provider().complete_stream(
                     &system_prompt,
                     &messages,
                     &tools,
                 ).await {
+                    Ok(mut deltas) => {
+                        let mut accumulator = MessageAccumulator::default();
+                        let mut stream_error = None;
+                        while let Some(delta) = deltas.next().await {
+                            match delta {
+                                Ok(delta) => {
+                                    if let Some(text) = delta.as_text() {
+                                        yield ReplyEvent::Text(text.to_string());
+                                    }
+                                    accumulator.push(delta);
+                                }
+                                Err(e) => {
+                                    stream_error = Some(e);
+                                    break;
+                                }
+                            }
+                        }
+                        match stream_error {
+                            Some(e) => Err(e),
+                            None => Ok(accumulator.finish()),
+                        }
+                    }
+                    Err(e) => Err(e),
+                };
+
+                match completion {
                     Ok((response, usage)) => {
-                        capabilities.record_usage(usage).await;
+                        // Slow down before the next request when the quota is running low
+                        let rate_limit_delay = usage
+                            .as_ref()
+                            .and_then(|usage| usage.rate_limit.as_ref())
+                            .and_then(|rate_limit| rate_limit.suggested_delay());
+                        if let Some(usage) = usage {
+                            capabilities.record_usage(usage).await;
+                        }
 
                         // Reset truncation attempt
                         truncation_attempt = 0;
 
                         // Yield the assistant's response
-                        yield response.clone();
+                        yield ReplyEvent::Message(response.clone());
 
                         tokio::task::yield_now().await;
 
@@ -199,37 +229,25 @@ impl Agent for TruncateAgent {
                             break;
                         }
 
-                        // Then dispatch each in parallel
-                        let futures: Vec<_> = tool_requests
-                            .iter()
-                            .filter_map(|request| request.tool_call.clone().ok())
-                            .map(|tool_call| capabilities.dispatch_tool_call(tool_call))
-                            .collect();
-
-                        // Process all the futures in parallel but wait until all are finished
-                        let outputs = futures::future::join_all(futures).await;
-
-                        // Create a message with the responses
-                        let mut message_tool_response = Message::user();
-                        // Now combine these into MessageContent::ToolResponse using the original ID
-                        for (request, output) in tool_requests.iter().zip(outputs.into_iter()) {
-                            message_tool_response = message_tool_response.with_tool_response(
-                                request.id.clone(),
-                                output,
-                            );
-                        }
+                        // Then dispatch them in parallel, up to the per message cap
+                        let message_tool_response = capabilities.dispatch_tool_requests(&tool_requests).await;
 
-                        yield message_tool_response.clone();
+                        yield ReplyEvent::Message(message_tool_response.clone());
 
                         messages.push(response);
                         messages.push(message_tool_response);
+
+                        if let Some(delay) = rate_limit_delay {
+                            warn!("Approaching the provider rate limit, waiting {:?} before the next request", delay);
+                            tokio::time::sleep(delay).await;
+                        }
                     },
                     Err(ProviderError::ContextLengthExceeded(_)) => {
                         if truncation_attempt >= MAX_TRUNCATION_ATTEMPTS {
                             // Create an error message & terminate the stream
                             // the previous message would have been a user message (e.g. before any tool calls, this is just after the input message.
                             // at the start of a loop after a tool call, it would be after a tool_use assistant followed by a tool_result user)
-                            yield Message::assistant().with_text("Error: Context length exceeds limits even after multiple attempts to truncate.");
+                            yield ReplyEvent::Message(Message::assistant().with_text("Error: Context length exceeds limits even after multiple attempts to truncate."));
                             break;
                         }
 
@@ -254,7 +272,7 @@ impl Agent for TruncateAgent {
                     Err(e) => {
                         // Create an error message & terminate the stream
                         error!("Error: {}", e);
-                        yield Message::assistant().with_text(format!("Ran into this error: {e}.\n\nPlease retry if you think this is a transient or recoverable error."));
+                        yield ReplyEvent::Message(Message::assistant().with_text(format!("Ran into this error: {e}.\n\nPlease retry if you think this is a transient or recoverable error.")));
                         break;
                     }
                 }
@@ -269,6 +287,305 @@ impl Agent for TruncateAgent {
         let capabilities = self.capabilities.lock().await;
         capabilities.get_usage().await
     }
+
+    async fn rate_limit(&self) -> Option<RateLimitInfo> {
+        let capabilities = self.capabilities.lock().await;
+        capabilities.rate_limit().await
+    }
+
+    async fn model_config(&self) -> ModelConfig {
+        let capabilities = self.capabilities.lock().await;
+        capabilities.provider().get_model_config()
+    }
+
+    async fn set_route_hint(&self, tier: Option<Tier>) {
+        let capabilities = self.capabilities.lock().await;
+        capabilities.provider().set_route_hint(tier);
+    }
+
+    async fn set_provider(&mut self, provider: Box<dyn Provider>) {
+        self.token_counter = TokenCounter::new(provider.get_model_config().tokenizer_name());
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_provider(provider);
+    }
+
+    async fn set_moderation(&mut self, moderation: Option<Box<dyn Moderation>>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_moderation(moderation);
+    }
+
+    async fn set_approver(&mut self, approver: Option<Box<dyn ToolApprover>>) {
+        let mut capabilities = self.capabilities.lock().await;
+        capabilities.set_approver(approver);
+    }
 }
 
 register_agent!("truncate", TruncateAgent);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::Approval;
+    use crate::providers::moderation::ModerationResult;
+    use crate::providers::router::RoutingProvider;
+    use crate::testing::MockProvider;
+    use futures::StreamExt;
+    use mcp_core::{ToolCall, ToolError};
+    use serde_json::json;
+
+    async fn reply_text(agent: &TruncateAgent) -> String {
+        let messages = vec![Message::user().with_text("Which model are you?")];
+        let mut stream = agent.reply(&messages).await.unwrap();
+        let reply = stream.next().await.unwrap().unwrap();
+        reply.as_concat_text()
+    }
+
+    #[tokio::test]
+    async fn test_set_provider_switches_model_for_later_replies() {
+        let mut agent = TruncateAgent::new(Box::new(MockProvider::new("gpt-4o-mini")));
+        assert_eq!(reply_text(&agent).await, "gpt-4o-mini");
+
+        agent
+            .set_provider(Box::new(MockProvider::new("claude-3-opus")))
+            .await;
+        let model_config = agent.model_config().await;
+        assert_eq!(model_config.model_name, "claude-3-opus");
+        assert_eq!(model_config.context_limit(), 200_000);
+        assert_eq!(reply_text(&agent).await, "claude-3-opus");
+
+        // Usage from before the switch is kept
+        let models: Vec<String> = agent.usage().await.into_iter().map(|u| u.model).collect();
+        assert!(models.contains(&"gpt-4o-mini".to_string()));
+        assert!(models.contains(&"claude-3-opus".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_route_hint_reaches_routing_provider() {
+        let router = RoutingProvider::new(
+            Box::new(MockProvider::new("gpt-4o-mini")),
+            Box::new(MockProvider::new("o3")),
+        );
+        let agent = TruncateAgent::new(Box::new(router));
+        assert_eq!(reply_text(&agent).await, "gpt-4o-mini");
+
+        agent.set_route_hint(Some(Tier::Strong)).await;
+        assert_eq!(reply_text(&agent).await, "o3");
+        assert_eq!(reply_text(&agent).await, "gpt-4o-mini");
+    }
+
+    #[tokio::test]
+    async fn test_switch_model_validates_known_models() {
+        let mut agent = TruncateAgent::new(Box::new(MockProvider::new("gpt-4o-mini")));
+
+        let err = agent
+            .switch_model("ollama", "not-a-model")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("known models are: qwen2.5"));
+        assert_eq!(agent.model_config().await.model_name, "gpt-4o-mini");
+
+        agent.switch_model("ollama", "qwen2.5").await.unwrap();
+        assert_eq!(agent.model_config().await.model_name, "qwen2.5");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_does_not_call_provider() {
+        let agent = TruncateAgent::new(Box::new(MockProvider::new("gpt-4o-mini")));
+        let messages = vec![
+            Message::user().with_text("What files are here?"),
+            Message::assistant().with_text("Let me check"),
+        ];
+
+        let request = agent.dry_run(&messages).await.unwrap();
+        assert_eq!(request.messages, messages);
+        let tool_names: Vec<&str> = request.tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(tool_names, vec!["platform__set_task"]);
+        assert!(request.system_prompt.contains("No extensions are defined"));
+        assert!(agent.usage().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reply_stream_yields_text_before_message() {
+        let agent = TruncateAgent::new(Box::new(MockProvider::new("gpt-4o-mini")));
+        let messages = vec![Message::user().with_text("Which model are you?")];
+
+        let events: Vec<ReplyEvent> = agent
+            .reply_stream(&messages)
+            .await
+            .unwrap()
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], ReplyEvent::Text(text) if text == "gpt-4o-mini"));
+        assert!(
+            matches!(&events[1], ReplyEvent::Message(message) if message.as_concat_text() == "gpt-4o-mini")
+        );
+    }
+
+    // Mock moderation that flags any message containing a word
+    struct WordModeration {
+        word: &'static str,
+    }
+
+    #[async_trait::async_trait]
+    impl Moderation for WordModeration {
+        async fn moderate_content(&self, content: &str) -> Result<ModerationResult, ProviderError> {
+            let flagged = content.contains(self.word);
+            Ok(ModerationResult::new(
+                flagged,
+                Some(vec!["violence".to_string()]).filter(|_| flagged),
+                None,
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flagged_input_short_circuits_reply() {
+        let mut agent = TruncateAgent::new(Box::new(MockProvider::new("gpt-4o-mini")));
+        agent
+            .set_moderation(Some(Box::new(WordModeration { word: "attack" })))
+            .await;
+
+        let messages = vec![Message::user().with_text("Plan an attack")];
+        let replies: Vec<Message> = agent
+            .reply(&messages)
+            .await
+            .unwrap()
+            .map(|message| message.unwrap())
+            .collect()
+            .await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(
+            replies[0].as_concat_text(),
+            "Your message was flagged by moderation (violence) and was not sent to the model."
+        );
+        assert!(agent.usage().await.is_empty());
+
+        // Input that is not flagged is answered by the provider as usual
+        assert_eq!(reply_text(&agent).await, "gpt-4o-mini");
+        assert_eq!(agent.usage().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_beyond_cap_are_rejected() {
+        // The model asks to set the task five times at once, then answers with the results
+        let tool_calls = (0..5).fold(Message::assistant(), |message, i| {
+            message.with_tool_request(
+                i.to_string(),
+                Ok(ToolCall::new(
+                    "platform__set_task",
+                    json!({"task": format!("task {}", i)}),
+                )),
+            )
+        });
+        let provider = MockProvider::new("gpt-4o-mini")
+            .with_replies([tool_calls, Message::assistant().with_text("done")]);
+        let agent = TruncateAgent::new(Box::new(provider));
+        agent
+            .capabilities
+            .lock()
+            .await
+            .set_max_tool_calls_per_message(2);
+
+        let messages = vec![Message::user().with_text("Set some tasks")];
+        let replies: Vec<Message> = agent
+            .reply(&messages)
+            .await
+            .unwrap()
+            .map(|message| message.unwrap())
+            .collect()
+            .await;
+        assert_eq!(replies.len(), 3);
+
+        let responses: Vec<_> = replies[1]
+            .content
+            .iter()
+            .map(|content| content.as_tool_response().unwrap())
+            .collect();
+        let ids: Vec<&str> = responses.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["0", "1", "2", "3", "4"]);
+        assert!(responses[..2].iter().all(|r| r.tool_result.is_ok()));
+        for response in &responses[2..] {
+            let err = response.tool_result.as_ref().unwrap_err();
+            assert!(err
+                .to_string()
+                .contains("Request at most 2 tools at a time"));
+        }
+
+        // Only the calls within the cap ran
+        let task = agent.capabilities.lock().await.task().await.unwrap();
+        assert!(task == "task 0" || task == "task 1");
+        assert_eq!(replies[2].as_concat_text(), "done");
+    }
+
+    // Mock approver that denies shell commands and rewrites the task
+    struct DenyShellApprover;
+
+    #[async_trait::async_trait]
+    impl ToolApprover for DenyShellApprover {
+        async fn approve(&self, tool_call: &ToolCall) -> Approval {
+            if tool_call.name.ends_with("__shell") {
+                Approval::Deny("shell commands are not allowed".to_string())
+            } else {
+                Approval::Edit(json!({"task": "clean up carefully"}))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_approver_denies_and_edits_tool_calls() {
+        // The model asks to run a shell command and set the task, then finishes
+        let tool_calls = Message::assistant()
+            .with_tool_request(
+                "0",
+                Ok(ToolCall::new(
+                    "developer__shell",
+                    json!({"command": "rm -rf /"}),
+                )),
+            )
+            .with_tool_request(
+                "1",
+                Ok(ToolCall::new(
+                    "platform__set_task",
+                    json!({"task": "clean up"}),
+                )),
+            );
+        let provider = MockProvider::new("gpt-4o-mini")
+            .with_replies([tool_calls, Message::assistant().with_text("done")]);
+        let mut agent = TruncateAgent::new(Box::new(provider));
+        agent.set_approver(Some(Box::new(DenyShellApprover))).await;
+
+        let messages = vec![Message::user().with_text("Clean up")];
+        let replies: Vec<Message> = agent
+            .reply(&messages)
+            .await
+            .unwrap()
+            .map(|message| message.unwrap())
+            .collect()
+            .await;
+        assert_eq!(replies.len(), 3);
+
+        let responses: Vec<_> = replies[1]
+            .content
+            .iter()
+            .map(|content| content.as_tool_response().unwrap())
+            .collect();
+        // The shell call was answered with the denial rather than dispatched, which would
+        // have failed to find the developer extension
+        let err = responses[0].tool_result.as_ref().unwrap_err();
+        assert_eq!(
+            err,
+            &ToolError::ExecutionError(
+                "The tool call was denied and did not run: shell commands are not allowed"
+                    .to_string()
+            )
+        );
+
+        // The edited call ran with the approver's arguments
+        assert!(responses[1].tool_result.is_ok());
+        let task = agent.capabilities.lock().await.task().await;
+        assert_eq!(task.as_deref(), Some("clean up carefully"));
+        assert_eq!(replies[2].as_concat_text(), "done");
+    }
+}
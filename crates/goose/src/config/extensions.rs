@@ -30,9 +30,7 @@ impl ExtensionManager {
                     DEFAULT_EXTENSION.to_string(),
                     ExtensionEntry {
                         enabled: true,
-                        config: ExtensionConfig::Builtin {
-                            name: DEFAULT_EXTENSION.to_string(),
-                        },
+                        config: ExtensionConfig::builtin(DEFAULT_EXTENSION),
                     },
                 )]);
                 config.set("extensions", serde_json::to_value(&defaults)?)?;
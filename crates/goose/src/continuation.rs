@@ -0,0 +1,146 @@
+//! Helpers for resuming an assistant response that was cut off by the max tokens limit
+
+use anyhow::{anyhow, Result};
+use mcp_core::role::Role;
+
+use crate::message::{Message, MessageContent};
+
+/// The instruction sent to the model when asking it to resume a truncated response
+pub const CONTINUE_PROMPT: &str =
+    "Your previous response was cut off because it reached the maximum output length. \
+Continue exactly where you left off, without repeating anything you already wrote.";
+
+/// Check whether the conversation ends with an assistant message that was truncated
+pub fn can_continue(messages: &[Message]) -> bool {
+    messages
+        .last()
+        .is_some_and(|m| m.role == Role::Assistant && m.is_truncated())
+}
+
+/// Build the conversation sent to the agent to request a continuation
+pub fn continuation_request(messages: &[Message]) -> Vec<Message> {
+    let mut request = messages.to_vec();
+    request.push(Message::user().with_text(CONTINUE_PROMPT));
+    request
+}
+
+/// Append a continuation onto the truncated assistant message at the end of the conversation
+///
+/// Leading text in the continuation is joined directly onto trailing text in the truncated
+/// message so that words split across the cut are stitched back together. The stop reason
+/// of the continuation replaces the original one, so a continuation that is itself cut off
+/// can be continued again.
+pub fn stitch_continuation(messages: &mut [Message], continuation: Message) -> Result<()> {
+    let truncated = messages
+        .last_mut()
+        .filter(|m| m.role == Role::Assistant)
+        .ok_or_else(|| anyhow!("No assistant message to continue"))?;
+
+    let mut content = continuation.content.into_iter();
+    if let Some(first) = content.next() {
+        match (truncated.content.last_mut(), first) {
+            (Some(MessageContent::Text(existing)), MessageContent::Text(next)) => {
+                existing.text.push_str(&next.text);
+            }
+            (_, first) => truncated.content.push(first),
+        }
+    }
+    truncated.content.extend(content);
+    truncated.stop_reason = continuation.stop_reason;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::AgentFactory;
+    use crate::message::StopReason;
+    use crate::model::ModelConfig;
+    use crate::testing::MockProvider;
+    use futures::StreamExt;
+
+    fn text_message(text: &str, stop_reason: Option<StopReason>) -> Message {
+        Message::assistant()
+            .with_text(text)
+            .with_stop_reason(stop_reason)
+    }
+
+    #[test]
+    fn test_can_continue() {
+        let mut messages = vec![Message::user().with_text("Hi")];
+        assert!(!can_continue(&messages));
+
+        messages.push(text_message("Hel", Some(StopReason::EndTurn)));
+        assert!(!can_continue(&messages));
+
+        messages.last_mut().unwrap().stop_reason = Some(StopReason::MaxTokens);
+        assert!(can_continue(&messages));
+    }
+
+    #[test]
+    fn test_stitch_continuation_joins_text() -> Result<()> {
+        let mut messages = vec![
+            Message::user().with_text("Hi"),
+            text_message("Hel", Some(StopReason::MaxTokens)),
+        ];
+
+        stitch_continuation(
+            &mut messages,
+            text_message("lo!", Some(StopReason::EndTurn)),
+        )?;
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].content.len(), 1);
+        assert_eq!(messages[1].as_concat_text(), "Hello!");
+        assert_eq!(messages[1].stop_reason, Some(StopReason::EndTurn));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stitch_continuation_requires_assistant() {
+        let mut messages = vec![Message::user().with_text("Hi")];
+        assert!(stitch_continuation(&mut messages, text_message("lo!", None)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_continuation_with_mock_provider() -> Result<()> {
+        // The first response is cut off and the next one finishes it
+        let provider = MockProvider::from_config(
+            ModelConfig::new("test-model".to_string()).with_context_limit(200_000.into()),
+        )
+        .with_replies([
+            text_message("The quick brown fox jum", Some(StopReason::MaxTokens)),
+            text_message("ps over the lazy dog.", Some(StopReason::EndTurn)),
+        ]);
+        let agent = AgentFactory::create("truncate", Box::new(provider.clone())).unwrap();
+
+        let mut messages = vec![Message::user().with_text("Tell me about the fox")];
+        let mut stream = agent.reply(&messages).await?;
+        while let Some(message) = stream.next().await {
+            messages.push(message?);
+        }
+        drop(stream);
+        assert!(can_continue(&messages));
+
+        let mut stream = agent.reply(&continuation_request(&messages)).await?;
+        while let Some(message) = stream.next().await {
+            stitch_continuation(&mut messages, message?)?;
+        }
+        drop(stream);
+
+        let requests = provider.requests();
+        assert_eq!(
+            requests[1].messages.last().unwrap().as_concat_text(),
+            CONTINUE_PROMPT
+        );
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            messages[1].as_concat_text(),
+            "The quick brown fox jumps over the lazy dog."
+        );
+        assert_eq!(messages[1].stop_reason, Some(StopReason::EndTurn));
+        assert!(!can_continue(&messages));
+        Ok(())
+    }
+}
@@ -1,9 +1,12 @@
 pub mod agents;
 pub mod config;
+pub mod continuation;
 pub mod message;
 pub mod model;
 pub mod prompt_template;
 pub mod providers;
+#[cfg(test)]
+mod testing;
 pub mod token_counter;
 pub mod tracing;
 pub mod truncate;
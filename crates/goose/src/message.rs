@@ -117,12 +117,31 @@ impl From<Content> for MessageContent {
     }
 }
 
+/// The reason a provider stopped generating a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// The model reached a natural stopping point
+    EndTurn,
+    /// The response was cut off by the max tokens limit
+    MaxTokens,
+    /// The model stopped to request one or more tool calls
+    ToolUse,
+    /// The model emitted one of the configured stop sequences
+    StopSequence,
+    /// The response was withheld or cut off by a content filter
+    ContentFilter,
+}
+
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 /// A message to or from an LLM
 pub struct Message {
     pub role: Role,
     pub created: i64,
     pub content: Vec<MessageContent>,
+    /// Why the provider stopped generating, only set on assistant messages from a provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<StopReason>,
 }
 
 impl Message {
@@ -132,6 +151,7 @@ impl Message {
             role: Role::User,
             created: Utc::now().timestamp(),
             content: Vec::new(),
+            stop_reason: None,
         }
     }
 
@@ -141,6 +161,7 @@ impl Message {
             role: Role::Assistant,
             created: Utc::now().timestamp(),
             content: Vec::new(),
+            stop_reason: None,
         }
     }
 
@@ -178,6 +199,17 @@ impl Message {
         self.with_content(MessageContent::tool_response(id, result))
     }
 
+    /// Set the reason the provider stopped generating this message
+    pub fn with_stop_reason(mut self, stop_reason: Option<StopReason>) -> Self {
+        self.stop_reason = stop_reason;
+        self
+    }
+
+    /// Check if the message was cut off by the max tokens limit
+    pub fn is_truncated(&self) -> bool {
+        self.stop_reason == Some(StopReason::MaxTokens)
+    }
+
     /// Get the concatenated text content of the message, separated by newlines
     pub fn as_concat_text(&self) -> String {
         self.content
@@ -1,11 +1,55 @@
 use serde::{Deserialize, Serialize};
 
+use crate::providers::retry::RetryConfig;
+
 const DEFAULT_CONTEXT_LIMIT: usize = 128_000;
 
 // Tokenizer names, used to infer from model name
 pub const GPT_4O_TOKENIZER: &str = "Xenova--gpt-4o";
 pub const CLAUDE_TOKENIZER: &str = "Xenova--claude-tokenizer";
 
+/// The context window of `model_name` in tokens, or a safe default for models that aren't known
+pub fn context_limit_for(model_name: &str) -> usize {
+    known_context_limit(model_name).unwrap_or(DEFAULT_CONTEXT_LIMIT)
+}
+
+/// The context window of a known model, matched by name so that dated versions and the names
+/// used by hosting providers (such as `anthropic.claude-3-5-sonnet-20240620-v1:0`) are found too
+fn known_context_limit(model_name: &str) -> Option<usize> {
+    let name = model_name.to_lowercase();
+    // Gateways such as OpenRouter prefix the model with its vendor, as in `openai/o3-mini`
+    let base = name.rsplit('/').next().unwrap_or(&name);
+    match base {
+        // OpenAI models, https://platform.openai.com/docs/models
+        // Every named variant comes before the family names it starts with, such as `gpt-4`
+        n if n.contains("gpt-4.1") => Some(1_047_576),
+        n if n.contains("gpt-4.5") => Some(128_000),
+        n if n.contains("gpt-4o") => Some(128_000),
+        n if n.contains("gpt-4-turbo") || n.contains("gpt-4-vision") => Some(128_000),
+        n if n.contains("gpt-4-1106") || n.contains("gpt-4-0125") => Some(128_000),
+        n if n.contains("gpt-4-32k") => Some(32_768),
+        n if n.contains("gpt-4") => Some(8_192),
+        n if n.contains("gpt-3.5-turbo") => Some(16_385),
+        n if n.starts_with("o1-mini") || n.starts_with("o1-preview") => Some(128_000),
+        n if n.starts_with("o1") || n.starts_with("o3") || n.starts_with("o4") => Some(200_000),
+
+        // Anthropic models, https://docs.anthropic.com/en/docs/about-claude/models
+        n if n.contains("claude-2.0") || n.contains("claude-instant") => Some(100_000),
+        n if n.contains("claude") => Some(200_000),
+
+        // Google models, https://ai.google.dev/gemini-api/docs/models
+        n if n.contains("gemini-1.5-pro") => Some(2_097_152),
+        n if n.contains("gemini-1.5-flash") => Some(1_048_576),
+        n if n.contains("gemini-2") => Some(1_048_576),
+        n if n.contains("gemini-1.0-pro") || n.contains("gemini-pro") => Some(32_760),
+
+        // Meta Llama models, https://github.com/meta-llama/llama-models/tree/main?tab=readme-ov-file#llama-models-1
+        n if n.contains("llama3.2") => Some(128_000),
+        n if n.contains("llama3.3") => Some(128_000),
+        _ => None,
+    }
+}
+
 /// Configuration for model-specific settings and limits
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelConfig {
@@ -21,6 +65,26 @@ pub struct ModelConfig {
     pub temperature: Option<f32>,
     /// Optional maximum tokens to generate
     pub max_tokens: Option<i32>,
+    /// Optional sequences where the model stops generating
+    pub stop: Option<Vec<String>>,
+    /// Whether to mark requests for Anthropic prompt caching, for Claude models reached through
+    /// OpenRouter or Databricks. Other providers ignore it
+    #[serde(default)]
+    pub supports_cache_control: bool,
+    /// Whether to send tools in OpenAI's strict mode, so tool arguments always match the schema
+    #[serde(default)]
+    pub strict_tools: bool,
+    /// Optional cap on the serialized request body, overriding the provider's known maximum
+    pub max_request_bytes: Option<usize>,
+    /// Optional seed for sampling, so repeated requests give the same output where the
+    /// provider supports it
+    pub seed: Option<u64>,
+    /// Optional fraction of the context limit that requests are trimmed to fit in, leaving
+    /// headroom below the model's actual limit
+    pub target_ratio: Option<f32>,
+    /// How requests are retried when rate limited or the server fails
+    #[serde(default)]
+    pub retry: RetryConfig,
 }
 
 impl ModelConfig {
@@ -31,7 +95,7 @@ impl ModelConfig {
     /// 2. Model-specific default based on model name
     /// 3. Global default (128_000) (in get_context_limit)
     pub fn new(model_name: String) -> Self {
-        let context_limit = Self::get_model_specific_limit(&model_name);
+        let context_limit = known_context_limit(&model_name);
         let tokenizer_name = Self::infer_tokenizer_name(&model_name);
 
         Self {
@@ -40,6 +104,13 @@ impl ModelConfig {
             context_limit,
             temperature: None,
             max_tokens: None,
+            stop: None,
+            supports_cache_control: false,
+            strict_tools: false,
+            max_request_bytes: None,
+            seed: None,
+            target_ratio: None,
+            retry: RetryConfig::default(),
         }
     }
 
@@ -52,31 +123,23 @@ impl ModelConfig {
         }
     }
 
-    /// Get model-specific context limit based on model name
-    fn get_model_specific_limit(model_name: &str) -> Option<usize> {
-        // Implement some sensible defaults
-        match model_name {
-            // OpenAI models, https://platform.openai.com/docs/models#models-overview
-            name if name.contains("gpt-4o") => Some(128_000),
-            name if name.contains("gpt-4-turbo") => Some(128_000),
-
-            // Anthropic models, https://docs.anthropic.com/en/docs/about-claude/models
-            name if name.contains("claude-3") => Some(200_000),
-
-            // Meta Llama models, https://github.com/meta-llama/llama-models/tree/main?tab=readme-ov-file#llama-models-1
-            name if name.contains("llama3.2") => Some(128_000),
-            name if name.contains("llama3.3") => Some(128_000),
-            _ => None,
-        }
-    }
-
     /// Set an explicit context limit
     pub fn with_context_limit(mut self, limit: Option<usize>) -> Self {
         // Default is None and therefore DEFAULT_CONTEXT_LIMIT, only set
         // if input is Some to allow passing through with_context_limit in
         // configuration cases
-        if limit.is_some() {
-            self.context_limit = limit;
+        if let Some(limit) = limit {
+            if let Some(known) = known_context_limit(&self.model_name) {
+                if limit > known {
+                    tracing::warn!(
+                        "Context limit {} is larger than the {} tokens {} is known to accept, requests may be rejected",
+                        limit,
+                        known,
+                        self.model_name
+                    );
+                }
+            }
+            self.context_limit = Some(limit);
         }
         self
     }
@@ -93,6 +156,57 @@ impl ModelConfig {
         self
     }
 
+    /// Set the stop sequences
+    pub fn with_stop(mut self, stop: Option<Vec<String>>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Set whether requests are marked for Anthropic prompt caching
+    pub fn with_cache_control(mut self, supports_cache_control: bool) -> Self {
+        self.supports_cache_control = supports_cache_control;
+        self
+    }
+
+    /// Set whether tools are sent in OpenAI's strict mode
+    pub fn with_strict_tools(mut self, strict_tools: bool) -> Self {
+        self.strict_tools = strict_tools;
+        self
+    }
+
+    /// Set the maximum request body size in bytes
+    pub fn with_max_request_bytes(mut self, bytes: Option<usize>) -> Self {
+        self.max_request_bytes = bytes;
+        self
+    }
+
+    /// Set the sampling seed
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set the fraction of the context limit to trim requests to, ignoring values outside (0.0, 1.0]
+    pub fn with_target_ratio(mut self, ratio: Option<f32>) -> Self {
+        self.target_ratio = ratio.filter(|ratio| {
+            let valid = *ratio > 0.0 && *ratio <= 1.0;
+            if !valid {
+                tracing::warn!(
+                    "Ignoring context target ratio {}, it must be in (0.0, 1.0]",
+                    ratio
+                );
+            }
+            valid
+        });
+        self
+    }
+
+    /// Set how requests are retried
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
     // Get the tokenizer name
     pub fn tokenizer_name(&self) -> &str {
         &self.tokenizer_name
@@ -103,6 +217,14 @@ impl ModelConfig {
     pub fn context_limit(&self) -> usize {
         self.context_limit.unwrap_or(DEFAULT_CONTEXT_LIMIT)
     }
+
+    /// The tokens that requests are trimmed to fit in, the context limit scaled by the target ratio
+    pub fn target_limit(&self) -> usize {
+        match self.target_ratio {
+            Some(ratio) => (self.context_limit() as f64 * ratio as f64).round() as usize,
+            None => self.context_limit(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -128,15 +250,71 @@ mod tests {
         assert_eq!(config.context_limit(), DEFAULT_CONTEXT_LIMIT);
     }
 
+    #[test]
+    fn test_context_limit_for_known_models() {
+        for (model, limit) in [
+            ("gpt-4o-2024-08-06", 128_000),
+            ("gpt-4o-mini", 128_000),
+            ("gpt-4.1-mini", 1_047_576),
+            ("gpt-4-turbo", 128_000),
+            ("gpt-4.5-preview", 128_000),
+            ("gpt-4-vision-preview", 128_000),
+            ("gpt-4-1106-preview", 128_000),
+            ("gpt-4-32k-0613", 32_768),
+            ("gpt-4", 8_192),
+            ("gpt-4-0613", 8_192),
+            ("gpt-3.5-turbo", 16_385),
+            ("o1-mini", 128_000),
+            ("o1-preview", 128_000),
+            ("o3-mini", 200_000),
+            ("openai/o1", 200_000),
+            ("claude-3-5-sonnet-latest", 200_000),
+            ("anthropic.claude-3-7-sonnet-20250219-v1:0", 200_000),
+            ("claude-sonnet-4-20250514", 200_000),
+            ("claude-instant-1.2", 100_000),
+            ("gemini-1.5-pro", 2_097_152),
+            ("gemini-1.5-flash-8b", 1_048_576),
+            ("gemini-2.0-flash", 1_048_576),
+            ("models/gemini-2.5-pro-preview-03-25", 1_048_576),
+            ("gemini-pro", 32_760),
+            ("llama3.3", 128_000),
+        ] {
+            assert_eq!(context_limit_for(model), limit, "{}", model);
+            assert_eq!(ModelConfig::new(model.to_string()).context_limit(), limit);
+        }
+
+        for model in ["unknown-model", "mistral-large", "o-series", ""] {
+            assert_eq!(context_limit_for(model), DEFAULT_CONTEXT_LIMIT, "{}", model);
+        }
+    }
+
     #[test]
     fn test_model_config_settings() {
         let config = ModelConfig::new("test-model".to_string())
             .with_temperature(Some(0.7))
             .with_max_tokens(Some(1000))
+            .with_stop(Some(vec!["###".to_string()]))
             .with_context_limit(Some(50_000));
 
         assert_eq!(config.temperature, Some(0.7));
         assert_eq!(config.max_tokens, Some(1000));
+        assert_eq!(config.stop, Some(vec!["###".to_string()]));
         assert_eq!(config.context_limit, Some(50_000));
     }
+
+    #[test]
+    fn test_target_limit_scales_with_ratio() {
+        let config = ModelConfig::new("test-model".to_string()).with_context_limit(Some(100_000));
+        assert_eq!(config.target_limit(), 100_000);
+
+        let config = config.with_target_ratio(Some(0.7));
+        assert_eq!(config.target_limit(), 70_000);
+        assert_eq!(config.context_limit(), 100_000);
+
+        // Ratios outside (0.0, 1.0] are ignored
+        for ratio in [0.0, -0.5, 1.5] {
+            let config = config.clone().with_target_ratio(Some(ratio));
+            assert_eq!(config.target_limit(), 100_000);
+        }
+    }
 }
@@ -1,11 +1,76 @@
-use include_dir::{include_dir, Dir};
+use include_dir::{include_dir, Dir, DirEntry};
 use serde::Serialize;
+use std::error::Error;
 use std::path::PathBuf;
+use std::sync::LazyLock;
 use tera::{Context, Error as TeraError, Tera};
 
 // The prompts directory needs to be embedded in the binary (so it works when distributed)
 static PROMPTS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/prompts");
 
+static PROMPT_LOAD_ERRORS: LazyLock<Vec<PromptLoadError>> = LazyLock::new(|| {
+    let errors = load_prompt_files(&PROMPTS_DIR);
+    for error in &errors {
+        tracing::warn!(path = %error.path, "Failed to load prompt template: {}", error.message);
+    }
+    errors
+});
+
+/// A template in the prompts directory that could not be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptLoadError {
+    /// The path of the template within the prompts directory
+    pub path: String,
+    pub message: String,
+}
+
+/// The templates in the prompts directory that failed to load, each logged once as a warning
+///
+/// The directory is embedded at build time, so a missing directory fails the build, but a
+/// malformed template is only found when it is parsed. Rendering such a template fails with
+/// the same error.
+pub fn prompt_load_errors() -> &'static [PromptLoadError] {
+    &PROMPT_LOAD_ERRORS
+}
+
+/// Parse every template in `dir` and its subdirectories, returning the errors of those that fail
+fn load_prompt_files(dir: &Dir) -> Vec<PromptLoadError> {
+    let mut errors = Vec::new();
+    for entry in dir.entries() {
+        match entry {
+            DirEntry::Dir(dir) => errors.extend(load_prompt_files(dir)),
+            DirEntry::File(file) => {
+                let path = file.path().display().to_string();
+                let Some(content) = file.contents_utf8() else {
+                    errors.push(PromptLoadError {
+                        path,
+                        message: "The template is not valid UTF-8".to_string(),
+                    });
+                    continue;
+                };
+                if let Err(e) = Tera::default().add_raw_template(&path, content) {
+                    errors.push(PromptLoadError {
+                        path,
+                        message: error_chain(&e),
+                    });
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// The message of `error` followed by those of its sources, which hold the details of parse errors
+fn error_chain(error: &TeraError) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
 pub fn load_prompt<T: Serialize>(template: &str, context_data: &T) -> Result<String, TeraError> {
     let mut tera = Tera::default();
     tera.add_raw_template("inline_template", template)?;
@@ -79,6 +144,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_malformed_prompt_is_reported() {
+        static DIR: Dir = Dir::new(
+            "",
+            &[
+                DirEntry::File(include_dir::File::new("ok.md", b"Hello, {{ name }}!")),
+                DirEntry::Dir(Dir::new(
+                    "nested",
+                    &[DirEntry::File(include_dir::File::new(
+                        "nested/broken.md",
+                        b"Hello, {{ name !",
+                    ))],
+                )),
+            ],
+        );
+
+        let errors = load_prompt_files(&DIR);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "nested/broken.md");
+        assert!(errors[0].message.contains("nested/broken.md"));
+        assert!(errors[0].message.len() > "Failed to parse 'nested/broken.md'".len());
+
+        // The prompts shipped with goose all load
+        assert_eq!(prompt_load_errors(), &[]);
+    }
+
     #[test]
     fn test_load_prompt_file_missing_file() {
         let file_path = PathBuf::from("non_existent_template.txt");
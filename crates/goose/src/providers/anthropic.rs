@@ -1,13 +1,19 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use reqwest::{Client, StatusCode};
-use serde_json::Value;
+use futures::StreamExt;
+use reqwest::{Client, Response, StatusCode};
+use serde_json::{json, Value};
 use std::time::Duration;
 
-use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage};
+use super::base::{
+    CompletionDelta, CompletionStream, ConfigKey, Provider, ProviderMetadata, ProviderUsage,
+};
 use super::errors::ProviderError;
-use super::formats::anthropic::{create_request, get_usage, response_to_message};
-use super::utils::{emit_debug_trace, get_model};
+use super::formats::anthropic::{create_request, get_usage, response_to_message, StreamState};
+use super::rate_limit::RateLimitInfo;
+use super::retry::send_with_retry;
+use super::sse::sse_events;
+use super::utils::{check_payload_size, emit_debug_trace, get_model};
 use crate::message::Message;
 use crate::model::ModelConfig;
 use mcp_core::tool::Tool;
@@ -19,6 +25,9 @@ pub const ANTHROPIC_KNOWN_MODELS: &[&str] = &[
     "claude-3-opus-latest",
 ];
 
+/// The Messages API rejects request bodies larger than 32 MB
+pub const ANTHROPIC_MAX_REQUEST_BYTES: usize = 32 * 1024 * 1024;
+
 pub const ANTHROPIC_DOC_URL: &str = "https://docs.anthropic.com/en/docs/about-claude/models";
 
 #[derive(serde::Serialize)]
@@ -44,11 +53,22 @@ impl AnthropicProvider {
         let host: String = config
             .get("ANTHROPIC_HOST")
             .unwrap_or_else(|_| "https://api.anthropic.com".to_string());
+        // Kept from before retries were configured for every provider
+        let mut model = model;
+        if let Ok(max_retries) = config.get("ANTHROPIC_MAX_RETRIES") {
+            model.retry.max_retries = max_retries;
+        }
 
         let client = Client::builder()
             .timeout(Duration::from_secs(600))
             .build()?;
 
+        if model.seed.is_some() {
+            tracing::warn!(
+                "Anthropic does not support a sampling seed, ignoring the configured seed"
+            );
+        }
+
         Ok(Self {
             client,
             host,
@@ -57,58 +77,96 @@ impl AnthropicProvider {
         })
     }
 
-    async fn post(&self, payload: Value) -> Result<Value, ProviderError> {
-        let url = format!("{}/v1/messages", self.host.trim_end_matches('/'));
+    async fn post(&self, payload: Value) -> Result<(Value, Option<RateLimitInfo>), ProviderError> {
+        let response = self.send(&payload).await?;
+        let rate_limit = RateLimitInfo::from_headers(response.headers());
+        let payload: Value = response.json().await.map_err(|_| {
+            ProviderError::RequestFailed("Response body is not valid JSON".to_string())
+        })?;
+        Ok((payload, rate_limit))
+    }
 
-        let response = self
+    /// Send the request, retrying rate limited (429) and server error (5xx) responses as set
+    /// by the model's retry config
+    ///
+    /// The error is only returned once retries are exhausted.
+    async fn send(&self, payload: &Value) -> Result<Response, ProviderError> {
+        let url = format!("{}/v1/messages", self.host.trim_end_matches('/'));
+        let request = self
             .client
             .post(&url)
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
-            .json(&payload)
-            .send()
-            .await?;
+            .json(payload);
 
+        let response = send_with_retry(&self.model.retry, request).await?;
         let status = response.status();
-        let payload: Option<Value> = response.json().await.ok();
+        if status == StatusCode::OK {
+            return Ok(response);
+        }
+        let body: Option<Value> = response.json().await.ok();
+        Err(Self::error_for_status(status, body))
+    }
 
+    fn error_for_status(status: StatusCode, payload: Option<Value>) -> ProviderError {
         // https://docs.anthropic.com/en/api/errors
         match status {
-            StatusCode::OK => payload.ok_or_else( || ProviderError::RequestFailed("Response body is not valid JSON".to_string()) ),
             StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
-                Err(ProviderError::Authentication(format!("Authentication failed. Please ensure your API keys are valid and have the required permissions. \
-                    Status: {}. Response: {:?}", status, payload)))
+                ProviderError::Authentication(format!("Authentication failed. Please ensure your API keys are valid and have the required permissions. \
+                    Status: {}. Response: {:?}", status, payload))
             }
             StatusCode::BAD_REQUEST => {
                 if let Some(payload) = &payload {
                     if let Some(error) = payload.get("error") {
                     tracing::debug!("Bad Request Error: {error:?}");
-                    let error_msg = error.get("message").and_then(|m| m.as_str()).unwrap_or("Unknown error");
-                    if error_msg.to_lowercase().contains("too long") || error_msg.to_lowercase().contains("too many") {
-                        return Err(ProviderError::ContextLengthExceeded(error_msg.to_string()));
+                    if is_context_length_error(error) {
+                        let error_msg = error.get("message").and_then(|m| m.as_str()).unwrap_or("Unknown error");
+                        return ProviderError::ContextLengthExceeded(error_msg.to_string());
                     }
                 }}
                 tracing::debug!(
                     "{}", format!("Provider request failed with status: {}. Payload: {:?}", status, payload)
                 );
-                Err(ProviderError::RequestFailed(format!("Request failed with status: {}", status)))
+                ProviderError::RequestFailed(format!("Request failed with status: {}", status))
             }
             StatusCode::TOO_MANY_REQUESTS => {
-                Err(ProviderError::RateLimitExceeded(format!("{:?}", payload)))
+                ProviderError::RateLimitExceeded(format!("{:?}", payload))
             }
-            StatusCode::INTERNAL_SERVER_ERROR | StatusCode::SERVICE_UNAVAILABLE => {
-                Err(ProviderError::ServerError(format!("{:?}", payload)))
+            // Includes 529 when the API is overloaded
+            _ if status.is_server_error() => {
+                ProviderError::ServerError(format!("{:?}", payload))
             }
             _ => {
                 tracing::debug!(
                     "{}", format!("Provider request failed with status: {}. Payload: {:?}", status, payload)
                 );
-                Err(ProviderError::RequestFailed(format!("Request failed with status: {}", status)))
+                ProviderError::RequestFailed(format!("Request failed with status: {}", status))
             }
         }
     }
 }
 
+/// Whether an Anthropic `error` object reports a request too large for the model's context
+///
+/// These come back as an `invalid_request_error`, such as "prompt is too long: 210000 tokens >
+/// 200000 maximum" or "input length and `max_tokens` exceed context limit", so the message is
+/// what tells them apart from other invalid requests.
+fn is_context_length_error(error: &Value) -> bool {
+    if error.get("type").and_then(|t| t.as_str()) != Some("invalid_request_error") {
+        return false;
+    }
+    let message = error
+        .get("message")
+        .and_then(|m| m.as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    message.contains("prompt is too long")
+        || message.contains("context limit")
+        || message.contains("context window")
+        || message.contains("context length")
+        || (message.contains("max_tokens") && message.contains("exceed"))
+}
+
 #[async_trait]
 impl Provider for AnthropicProvider {
     fn metadata() -> ProviderMetadata {
@@ -138,6 +196,49 @@ impl Provider for AnthropicProvider {
         self.model.clone()
     }
 
+    async fn complete_stream(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<CompletionStream<'_>, ProviderError> {
+        let mut payload = create_request(&self.model, system, messages, tools)?;
+        payload["stream"] = json!(true);
+
+        check_payload_size(
+            &payload,
+            self.model
+                .max_request_bytes
+                .unwrap_or(ANTHROPIC_MAX_REQUEST_BYTES),
+        )?;
+
+        // Only the initial request is retried, errors part way through the stream are returned
+        let response = self.send(&payload).await?;
+        let rate_limit = RateLimitInfo::from_headers(response.headers());
+
+        Ok(Box::pin(async_stream::try_stream! {
+            let mut events = sse_events(response);
+            let mut state = StreamState::default();
+            while let Some(event) = events.next().await {
+                let event = event?;
+                let data: Value = serde_json::from_str(&event.data).map_err(|e| {
+                    ProviderError::RequestFailed(format!("Invalid event in streamed response: {}", e))
+                })?;
+                for delta in state.process_event(&data)? {
+                    yield delta;
+                }
+            }
+            for delta in state.finish() {
+                yield match delta {
+                    CompletionDelta::Usage(usage) => CompletionDelta::Usage(
+                        usage.with_rate_limit(rate_limit.clone()).with_cost(),
+                    ),
+                    delta => delta,
+                };
+            }
+        }))
+    }
+
     #[tracing::instrument(
         skip(self, system, messages, tools),
         fields(model_config, input, output, input_tokens, output_tokens, total_tokens)
@@ -150,8 +251,15 @@ impl Provider for AnthropicProvider {
     ) -> Result<(Message, ProviderUsage), ProviderError> {
         let payload = create_request(&self.model, system, messages, tools)?;
 
+        check_payload_size(
+            &payload,
+            self.model
+                .max_request_bytes
+                .unwrap_or(ANTHROPIC_MAX_REQUEST_BYTES),
+        )?;
+
         // Make request
-        let response = self.post(payload.clone()).await?;
+        let (response, rate_limit) = self.post(payload.clone()).await?;
 
         // Parse response
         let message = response_to_message(response.clone())?;
@@ -159,6 +267,236 @@ impl Provider for AnthropicProvider {
 
         let model = get_model(&response);
         emit_debug_trace(self, &payload, &response, &usage);
-        Ok((message, ProviderUsage::new(model, usage)))
+        Ok((
+            message,
+            ProviderUsage::new(model, usage)
+                .with_rate_limit(rate_limit)
+                .with_cost(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::message::{MessageContent, StopReason};
+    use crate::providers::base::MessageAccumulator;
+    use crate::providers::retry::RetryConfig;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_provider(host: String, max_retries: usize) -> AnthropicProvider {
+        let retry = RetryConfig {
+            max_retries,
+            initial_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+        provider_with_retry(host, retry)
+    }
+
+    fn provider_with_retry(host: String, retry: RetryConfig) -> AnthropicProvider {
+        AnthropicProvider {
+            client: Client::new(),
+            host,
+            api_key: "test-key".to_string(),
+            model: ModelConfig::new(ANTHROPIC_DEFAULT_MODEL.to_string()).with_retry(retry),
+        }
+    }
+
+    fn success_body() -> Value {
+        json!({
+            "id": "msg_123",
+            "type": "message",
+            "role": "assistant",
+            "model": ANTHROPIC_DEFAULT_MODEL,
+            "content": [{"type": "text", "text": "Hello!"}],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 10, "output_tokens": 2}
+        })
+    }
+
+    #[tokio::test]
+    async fn test_retries_rate_limited_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "0")
+                    .set_body_json(json!({"type": "error", "error": {"type": "rate_limit_error"}})),
+            )
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(success_body()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = test_provider(server.uri(), 3);
+        let (message, usage) = provider
+            .complete("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap();
+        assert_eq!(message.as_concat_text(), "Hello!");
+        assert_eq!(usage.usage.input_tokens, Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_error_after_retries_exhausted() {
+        let server = MockServer::start().await;
+        // No retry-after, so this also exercises the exponential backoff
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .respond_with(
+                ResponseTemplate::new(529)
+                    .set_body_json(json!({"type": "error", "error": {"type": "overloaded_error"}})),
+            )
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let provider = test_provider(server.uri(), 2);
+        let err = provider
+            .complete("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ProviderError::ServerError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_context_length_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "type": "error",
+                "error": {
+                    "type": "invalid_request_error",
+                    "message": "prompt is too long: 210000 tokens > 200000 maximum"
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = test_provider(server.uri(), 3);
+        let err = provider
+            .complete("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ProviderError::ContextLengthExceeded(msg) if msg.contains("prompt is too long"))
+        );
+
+        // Other invalid requests stay generic failures
+        assert!(!is_context_length_error(&json!({
+            "type": "invalid_request_error",
+            "message": "messages: roles must alternate between \"user\" and \"assistant\""
+        })));
+        assert!(is_context_length_error(&json!({
+            "type": "invalid_request_error",
+            "message": "input length and `max_tokens` exceed context limit: 198000 + 8192 > 200000"
+        })));
+    }
+
+    #[tokio::test]
+    async fn test_configured_retries_reach_provider() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let config = Config::new(temp_file.path(), "goose-test").unwrap();
+        config.set("GOOSE_MAX_RETRIES", json!(1)).unwrap();
+        config
+            .set("GOOSE_RETRY_INITIAL_DELAY_MS", json!(1))
+            .unwrap();
+
+        let server = MockServer::start().await;
+        // The first attempt and the one configured retry
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .respond_with(
+                ResponseTemplate::new(500)
+                    .set_body_json(json!({"type": "error", "error": {"type": "api_error"}})),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let provider = provider_with_retry(server.uri(), RetryConfig::from_config(&config));
+        let err = provider
+            .complete("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ProviderError::ServerError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_complete_stream() {
+        let events = [
+            (
+                "message_start",
+                json!({"type": "message_start", "message": {"model": ANTHROPIC_DEFAULT_MODEL, "content": [], "usage": {"input_tokens": 10, "output_tokens": 1}}}),
+            ),
+            (
+                "content_block_start",
+                json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            ),
+            (
+                "content_block_delta",
+                json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Hel"}}),
+            ),
+            (
+                "content_block_delta",
+                json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "lo!"}}),
+            ),
+            (
+                "content_block_stop",
+                json!({"type": "content_block_stop", "index": 0}),
+            ),
+            (
+                "message_delta",
+                json!({"type": "message_delta", "delta": {"stop_reason": "max_tokens"}, "usage": {"output_tokens": 2}}),
+            ),
+            ("message_stop", json!({"type": "message_stop"})),
+        ];
+        let body: String = events
+            .iter()
+            .map(|(name, data)| format!("event: {}\ndata: {}\n\n", name, data))
+            .collect();
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(body_partial_json(json!({"stream": true})))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&server)
+            .await;
+
+        let provider = test_provider(server.uri(), 0);
+        let mut stream = provider
+            .complete_stream("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap();
+
+        let mut texts = Vec::new();
+        let mut accumulator = MessageAccumulator::default();
+        while let Some(delta) = stream.next().await {
+            let delta = delta.unwrap();
+            if let Some(text) = delta.as_text() {
+                texts.push(text.to_string());
+            }
+            accumulator.push(delta);
+        }
+        assert_eq!(texts, vec!["Hel", "lo!"]);
+
+        let (message, usage) = accumulator.finish();
+        assert_eq!(message.content, vec![MessageContent::text("Hello!")]);
+        assert_eq!(message.stop_reason, Some(StopReason::MaxTokens));
+        let usage = usage.unwrap();
+        assert_eq!(usage.model, ANTHROPIC_DEFAULT_MODEL);
+        assert_eq!(usage.usage.total_tokens, Some(12));
     }
 }
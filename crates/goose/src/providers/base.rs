@@ -1,8 +1,12 @@
 use anyhow::Result;
+use futures::stream::{self, BoxStream};
 use serde::{Deserialize, Serialize};
 
 use super::errors::ProviderError;
-use crate::message::Message;
+use super::pricing::model_pricing_for;
+use super::rate_limit::RateLimitInfo;
+use super::router::Tier;
+use crate::message::{Message, MessageContent, StopReason};
 use crate::model::ModelConfig;
 use mcp_core::tool::Tool;
 
@@ -83,11 +87,34 @@ impl ConfigKey {
 pub struct ProviderUsage {
     pub model: String,
     pub usage: Usage,
+    /// Rate limit quota reported alongside the response, if the provider sends it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitInfo>,
+    /// What the completion cost in US dollars, when the model's pricing is known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
 }
 
 impl ProviderUsage {
     pub fn new(model: String, usage: Usage) -> Self {
-        Self { model, usage }
+        Self {
+            model,
+            usage,
+            rate_limit: None,
+            cost: None,
+        }
+    }
+
+    /// Price the usage at the model's list price, leaving the cost unset for unknown models
+    pub fn with_cost(mut self) -> Self {
+        self.cost = model_pricing_for(&self.model).and_then(|pricing| pricing.cost(&self.usage));
+        self
+    }
+
+    /// Attach the rate limit quota reported with the response
+    pub fn with_rate_limit(mut self, rate_limit: Option<RateLimitInfo>) -> Self {
+        self.rate_limit = rate_limit;
+        self
     }
 }
 
@@ -112,6 +139,69 @@ impl Usage {
     }
 }
 
+/// An incremental update of a streamed completion
+#[derive(Debug, Clone)]
+pub enum CompletionDelta {
+    /// More content of the message, text is split across deltas that are appended in order
+    Content(MessageContent),
+    /// Why the provider stopped generating
+    Stop(StopReason),
+    /// Token usage of the whole completion, sent once the response is complete
+    Usage(ProviderUsage),
+}
+
+impl CompletionDelta {
+    /// The text of a text content delta
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            CompletionDelta::Content(content) => content.as_text(),
+            _ => None,
+        }
+    }
+}
+
+/// A stream of the deltas of a completion, in the order they were generated
+pub type CompletionStream<'a> = BoxStream<'a, Result<CompletionDelta, ProviderError>>;
+
+/// Builds the full message and usage from the deltas of a streamed completion
+#[derive(Debug)]
+pub struct MessageAccumulator {
+    message: Message,
+    usage: Option<ProviderUsage>,
+}
+
+impl Default for MessageAccumulator {
+    fn default() -> Self {
+        Self {
+            message: Message::assistant(),
+            usage: None,
+        }
+    }
+}
+
+impl MessageAccumulator {
+    pub fn push(&mut self, delta: CompletionDelta) {
+        match delta {
+            CompletionDelta::Content(MessageContent::Text(text)) => {
+                // Merge consecutive text into a single block
+                if let Some(MessageContent::Text(last)) = self.message.content.last_mut() {
+                    last.text.push_str(&text.text);
+                } else {
+                    self.message.content.push(MessageContent::Text(text));
+                }
+            }
+            CompletionDelta::Content(content) => self.message.content.push(content),
+            CompletionDelta::Stop(stop_reason) => self.message.stop_reason = Some(stop_reason),
+            CompletionDelta::Usage(usage) => self.usage = Some(usage),
+        }
+    }
+
+    /// The completed message, and the usage if the provider reported it
+    pub fn finish(self) -> (Message, Option<ProviderUsage>) {
+        (self.message, self.usage)
+    }
+}
+
 use async_trait::async_trait;
 
 /// Base trait for AI providers (OpenAI, Anthropic, etc)
@@ -142,14 +232,44 @@ pub trait Provider: Send + Sync {
         tools: &[Tool],
     ) -> Result<(Message, ProviderUsage), ProviderError>;
 
+    /// Generate the next message like `complete`, streaming it as incremental deltas
+    ///
+    /// Text is yielded as it is generated, while tool requests are only yielded once their
+    /// arguments are complete. The default implementation calls `complete` and yields the
+    /// whole message at once, for providers that don't support streaming.
+    async fn complete_stream(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<CompletionStream<'_>, ProviderError> {
+        let (message, usage) = self.complete(system, messages, tools).await?;
+
+        let mut deltas: Vec<CompletionDelta> = message
+            .content
+            .into_iter()
+            .map(CompletionDelta::Content)
+            .collect();
+        deltas.extend(message.stop_reason.map(CompletionDelta::Stop));
+        deltas.push(CompletionDelta::Usage(usage));
+        Ok(Box::pin(stream::iter(deltas.into_iter().map(Ok))))
+    }
+
     /// Get the model config from the provider
     fn get_model_config(&self) -> ModelConfig;
+
+    /// Send the next turn to the model of `tier`, for providers that route between models
+    ///
+    /// The hint only applies to one completion. Providers with a single model ignore it.
+    fn set_route_hint(&self, _tier: Option<Tier>) {}
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::testing::MockProvider;
+    use futures::StreamExt;
     use serde_json::json;
 
     #[test]
@@ -178,4 +298,47 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_default_complete_stream_yields_whole_message() {
+        let provider = MockProvider::new("fixed")
+            .with_replies([Message::assistant()
+                .with_text("Hello")
+                .with_stop_reason(Some(StopReason::EndTurn))])
+            .with_usage(Usage::new(Some(3), Some(1), None));
+        let mut deltas = provider.complete_stream("", &[], &[]).await.unwrap();
+        let mut accumulator = MessageAccumulator::default();
+        let mut count = 0;
+        while let Some(delta) = deltas.next().await {
+            accumulator.push(delta.unwrap());
+            count += 1;
+        }
+        assert_eq!(count, 3);
+
+        let (message, usage) = accumulator.finish();
+        assert_eq!(message.as_concat_text(), "Hello");
+        assert_eq!(message.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(usage.unwrap().usage.input_tokens, Some(3));
+    }
+
+    #[test]
+    fn test_accumulator_merges_text() {
+        let mut accumulator = MessageAccumulator::default();
+        accumulator.push(CompletionDelta::Content(MessageContent::text("Hel")));
+        accumulator.push(CompletionDelta::Content(MessageContent::text("lo")));
+        accumulator.push(CompletionDelta::Content(MessageContent::tool_request(
+            "1",
+            Ok(mcp_core::tool::ToolCall::new(
+                "shell",
+                json!({"command": "ls"}),
+            )),
+        )));
+        accumulator.push(CompletionDelta::Content(MessageContent::text("Done")));
+
+        let (message, usage) = accumulator.finish();
+        assert_eq!(message.content.len(), 3);
+        assert_eq!(message.content[0].as_text(), Some("Hello"));
+        assert_eq!(message.content[2].as_text(), Some("Done"));
+        assert!(usage.is_none());
+    }
 }
@@ -7,9 +7,13 @@ use std::time::Duration;
 
 use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage};
 use super::errors::ProviderError;
-use super::formats::openai::{create_request, get_usage, response_to_message};
+use super::formats::openai::{
+    apply_anthropic_prompt_cache, create_request, get_usage, response_to_message,
+};
 use super::oauth;
-use super::utils::{get_model, ImageFormat};
+use super::rate_limit::RateLimitInfo;
+use super::retry::send_with_retry;
+use super::utils::{check_payload_size, get_model, ImageFormat, DEFAULT_MAX_REQUEST_BYTES};
 use crate::config::ConfigError;
 use crate::message::Message;
 use crate::model::ModelConfig;
@@ -136,7 +140,7 @@ impl DatabricksProvider {
         }
     }
 
-    async fn post(&self, payload: Value) -> Result<Value, ProviderError> {
+    async fn post(&self, payload: Value) -> Result<(Value, Option<RateLimitInfo>), ProviderError> {
         let url = format!(
             "{}/serving-endpoints/{}/invocations",
             self.host.trim_end_matches('/'),
@@ -144,19 +148,19 @@ impl DatabricksProvider {
         );
 
         let auth_header = self.ensure_auth_header().await?;
-        let response = self
+        let request = self
             .client
             .post(&url)
             .header("Authorization", auth_header)
-            .json(&payload)
-            .send()
-            .await?;
+            .json(&payload);
+        let response = send_with_retry(&self.model.retry, request).await?;
 
         let status = response.status();
+        let rate_limit = RateLimitInfo::from_headers(response.headers());
         let payload: Option<Value> = response.json().await.ok();
 
         match status {
-            StatusCode::OK => payload.ok_or_else( || ProviderError::RequestFailed("Response body is not valid JSON".to_string()) ),
+            StatusCode::OK => payload.map(|payload| (payload, rate_limit)).ok_or_else( || ProviderError::RequestFailed("Response body is not valid JSON".to_string()) ),
             StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
                 Err(ProviderError::Authentication(format!("Authentication failed. Please ensure your API keys are valid and have the required permissions. \
                     Status: {}. Response: {:?}", status, payload)))
@@ -233,13 +237,23 @@ impl Provider for DatabricksProvider {
         tools: &[Tool],
     ) -> Result<(Message, ProviderUsage), ProviderError> {
         let mut payload = create_request(&self.model, system, messages, tools, &self.image_format)?;
+        if self.model.supports_cache_control {
+            apply_anthropic_prompt_cache(&mut payload);
+        }
         // Remove the model key which is part of the url with databricks
         payload
             .as_object_mut()
             .expect("payload should have model key")
             .remove("model");
 
-        let response = self.post(payload.clone()).await?;
+        check_payload_size(
+            &payload,
+            self.model
+                .max_request_bytes
+                .unwrap_or(DEFAULT_MAX_REQUEST_BYTES),
+        )?;
+
+        let (response, rate_limit) = self.post(payload.clone()).await?;
 
         // Parse response
         let message = response_to_message(response.clone())?;
@@ -247,6 +261,9 @@ impl Provider for DatabricksProvider {
         let model = get_model(&response);
         super::utils::emit_debug_trace(self, &payload, &response, &usage);
 
-        Ok((message, ProviderUsage::new(model, usage)))
+        Ok((
+            message,
+            ProviderUsage::new(model, usage).with_rate_limit(rate_limit),
+        ))
     }
 }
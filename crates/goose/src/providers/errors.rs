@@ -14,6 +14,9 @@ pub enum ProviderError {
     #[error("Server error: {0}")]
     ServerError(String),
 
+    #[error("Request too large: {0}")]
+    RequestTooLarge(String),
+
     #[error("Request failed: {0}")]
     RequestFailed(String),
 
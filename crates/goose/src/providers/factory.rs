@@ -7,7 +7,10 @@ use super::{
     ollama::OllamaProvider,
     openai::OpenAiProvider,
     openrouter::OpenRouterProvider,
+    rate_limiter::{global_limiter, RateLimitedProvider},
+    router::{RoutingProvider, DEFAULT_LONG_MESSAGE_CHARS},
 };
+use crate::config::Config;
 use crate::model::ModelConfig;
 use anyhow::Result;
 
@@ -23,7 +26,70 @@ pub fn providers() -> Vec<ProviderMetadata> {
     ]
 }
 
+/// Check that `model` is one of the known models of the provider `name`
+pub fn validate_model(name: &str, model: &str) -> Result<()> {
+    let metadata = providers()
+        .into_iter()
+        .find(|metadata| metadata.name == name)
+        .ok_or_else(|| anyhow::anyhow!("Unknown provider: {}", name))?;
+
+    if metadata.known_models.iter().any(|known| known == model) {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Unknown model '{}' for provider '{}', known models are: {}",
+            model,
+            name,
+            metadata.known_models.join(", ")
+        ))
+    }
+}
+
+/// Create the provider `name`, gated by the global rate limiter when one is configured
+///
+/// When `GOOSE_STRONG_MODEL` is configured, turns are routed between this provider and a
+/// stronger one, see [`create_routed`].
 pub fn create(name: &str, model: ModelConfig) -> Result<Box<dyn Provider + Send + Sync>> {
+    let provider = create_routed(name, model)?;
+    Ok(match global_limiter() {
+        Some(limiter) => Box::new(RateLimitedProvider::new(provider, limiter)),
+        None => provider,
+    })
+}
+
+/// Create the provider `name`, routing hard turns to a strong provider if one is configured
+///
+/// The strong provider uses the `GOOSE_STRONG_MODEL` model of the `GOOSE_STRONG_PROVIDER`
+/// provider, which defaults to `name`, with the other settings of `model`. Turns with a user
+/// message longer than `GOOSE_ROUTER_LONG_MESSAGE_CHARS` characters or with a code block go to
+/// the strong provider. Without a strong model, the provider is used for every turn.
+fn create_routed(name: &str, model: ModelConfig) -> Result<Box<dyn Provider + Send + Sync>> {
+    let config = Config::global();
+    let Ok(strong_model) = config.get::<String>("GOOSE_STRONG_MODEL") else {
+        return create_unlimited(name, model);
+    };
+    let strong_name: String = config
+        .get("GOOSE_STRONG_PROVIDER")
+        .unwrap_or_else(|_| name.to_string());
+    let long_message_chars = config
+        .get("GOOSE_ROUTER_LONG_MESSAGE_CHARS")
+        .unwrap_or(DEFAULT_LONG_MESSAGE_CHARS);
+
+    let defaults = ModelConfig::new(strong_model);
+    let strong_config = ModelConfig {
+        model_name: defaults.model_name,
+        tokenizer_name: defaults.tokenizer_name,
+        context_limit: defaults.context_limit,
+        ..model.clone()
+    };
+    let strong = create_unlimited(&strong_name, strong_config)?;
+    let cheap = create_unlimited(name, model)?;
+    Ok(Box::new(
+        RoutingProvider::new(cheap, strong).with_long_message_chars(long_message_chars),
+    ))
+}
+
+fn create_unlimited(name: &str, model: ModelConfig) -> Result<Box<dyn Provider + Send + Sync>> {
     match name {
         "openai" => Ok(Box::new(OpenAiProvider::from_env(model)?)),
         "anthropic" => Ok(Box::new(AnthropicProvider::from_env(model)?)),
@@ -1,12 +1,14 @@
-use crate::message::{Message, MessageContent};
+use crate::message::{Message, MessageContent, StopReason};
 use crate::model::ModelConfig;
-use crate::providers::base::Usage;
+use crate::providers::base::{CompletionDelta, ProviderUsage, Usage};
+use crate::providers::errors::ProviderError;
+use crate::providers::utils::{convert_image, ImageFormat};
 use anyhow::{anyhow, Result};
 use mcp_core::content::Content;
 use mcp_core::role::Role;
 use mcp_core::tool::{Tool, ToolCall};
 use serde_json::{json, Value};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 /// Convert internal Message format to Anthropic's API message specification
 pub fn format_messages(messages: &[Message]) -> Vec<Value> {
@@ -49,14 +51,37 @@ pub fn format_messages(messages: &[Message]) -> Vec<Value> {
                             .collect::<Vec<_>>()
                             .join("\n");
 
+                        // Tool results can hold images directly, e.g. from screen_capture
+                        let images: Vec<Value> = result
+                            .iter()
+                            .filter_map(|c| match c {
+                                Content::Image(image) => {
+                                    Some(convert_image(image, &ImageFormat::Anthropic))
+                                }
+                                _ => None,
+                            })
+                            .collect();
+                        let result_content = if images.is_empty() {
+                            json!(text)
+                        } else {
+                            let mut blocks = Vec::new();
+                            if !text.is_empty() {
+                                blocks.push(json!({"type": "text", "text": text}));
+                            }
+                            blocks.extend(images);
+                            json!(blocks)
+                        };
+
                         content.push(json!({
                             "type": "tool_result",
                             "tool_use_id": tool_response.id,
-                            "content": text
+                            "content": result_content
                         }));
                     }
                 }
-                MessageContent::Image(_) => continue, // Anthropic doesn't support image content yet
+                MessageContent::Image(image) => {
+                    content.push(convert_image(image, &ImageFormat::Anthropic));
+                }
             }
         }
 
@@ -179,7 +204,18 @@ pub fn response_to_message(response: Value) -> Result<Message> {
         }
     }
 
-    Ok(message)
+    Ok(message.with_stop_reason(get_stop_reason(&response)))
+}
+
+/// Extract the reason generation stopped from Anthropic's API response
+pub fn get_stop_reason(data: &Value) -> Option<StopReason> {
+    match data.get("stop_reason")?.as_str()? {
+        "end_turn" => Some(StopReason::EndTurn),
+        "max_tokens" => Some(StopReason::MaxTokens),
+        "tool_use" => Some(StopReason::ToolUse),
+        "stop_sequence" => Some(StopReason::StopSequence),
+        _ => None,
+    }
 }
 
 /// Extract usage information from Anthropic's API response
@@ -206,6 +242,147 @@ pub fn get_usage(data: &Value) -> Result<Usage> {
     }
 }
 
+/// A tool use block whose input is still being streamed
+#[derive(Debug)]
+struct PartialToolUse {
+    id: String,
+    name: String,
+    input: String,
+}
+
+/// Turns the events of a streamed Anthropic response into message deltas
+///
+/// Tool use input arrives as fragments of JSON, so tool requests are only emitted once their
+/// content block is complete.
+#[derive(Debug, Default)]
+pub struct StreamState {
+    tool_uses: HashMap<u64, PartialToolUse>,
+    model: Option<String>,
+    input_tokens: Option<i32>,
+    output_tokens: Option<i32>,
+}
+
+impl StreamState {
+    /// Process the data of the next event, returning the deltas it completes
+    ///
+    /// See https://docs.anthropic.com/en/api/messages-streaming
+    pub fn process_event(&mut self, event: &Value) -> Result<Vec<CompletionDelta>, ProviderError> {
+        let mut deltas = Vec::new();
+        let index = event
+            .get("index")
+            .and_then(|i| i.as_u64())
+            .unwrap_or_default();
+
+        match event.get("type").and_then(|t| t.as_str()) {
+            Some("message_start") => {
+                let message = &event["message"];
+                self.model = message
+                    .get("model")
+                    .and_then(|m| m.as_str())
+                    .map(String::from);
+                let usage = get_usage(message)?;
+                self.input_tokens = usage.input_tokens;
+                self.output_tokens = usage.output_tokens;
+            }
+            Some("content_block_start") => {
+                let block = &event["content_block"];
+                match block.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => {
+                        if let Some(text) = block["text"].as_str().filter(|t| !t.is_empty()) {
+                            deltas.push(CompletionDelta::Content(MessageContent::text(text)));
+                        }
+                    }
+                    Some("tool_use") => {
+                        self.tool_uses.insert(
+                            index,
+                            PartialToolUse {
+                                id: block["id"].as_str().unwrap_or_default().to_string(),
+                                name: block["name"].as_str().unwrap_or_default().to_string(),
+                                input: String::new(),
+                            },
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            Some("content_block_delta") => {
+                let delta = &event["delta"];
+                match delta.get("type").and_then(|t| t.as_str()) {
+                    Some("text_delta") => {
+                        if let Some(text) = delta["text"].as_str() {
+                            deltas.push(CompletionDelta::Content(MessageContent::text(text)));
+                        }
+                    }
+                    Some("input_json_delta") => {
+                        if let (Some(tool_use), Some(json)) = (
+                            self.tool_uses.get_mut(&index),
+                            delta["partial_json"].as_str(),
+                        ) {
+                            tool_use.input.push_str(json);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some("content_block_stop") => {
+                if let Some(tool_use) = self.tool_uses.remove(&index) {
+                    // Tools without parameters stream no input at all
+                    let input = if tool_use.input.trim().is_empty() {
+                        json!({})
+                    } else {
+                        serde_json::from_str(&tool_use.input).map_err(|e| {
+                            ProviderError::RequestFailed(format!(
+                                "Could not parse streamed tool use input for id {}: {}",
+                                tool_use.id, e
+                            ))
+                        })?
+                    };
+                    deltas.push(CompletionDelta::Content(MessageContent::tool_request(
+                        tool_use.id,
+                        Ok(ToolCall::new(&tool_use.name, input)),
+                    )));
+                }
+            }
+            Some("message_delta") => {
+                if let Some(stop_reason) = get_stop_reason(&event["delta"]) {
+                    deltas.push(CompletionDelta::Stop(stop_reason));
+                }
+                if let Some(output_tokens) = event["usage"]["output_tokens"].as_u64() {
+                    self.output_tokens = Some(output_tokens as i32);
+                }
+            }
+            Some("error") => {
+                let error = &event["error"];
+                let message = error["message"]
+                    .as_str()
+                    .unwrap_or("Unknown error")
+                    .to_string();
+                return Err(match error["type"].as_str() {
+                    Some("overloaded_error") | Some("api_error") => {
+                        ProviderError::ServerError(message)
+                    }
+                    Some("rate_limit_error") => ProviderError::RateLimitExceeded(message),
+                    _ => ProviderError::RequestFailed(message),
+                });
+            }
+            // message_stop and ping carry nothing we need
+            _ => {}
+        }
+        Ok(deltas)
+    }
+
+    /// The usage of the whole response, once the stream has ended
+    pub fn finish(self) -> Vec<CompletionDelta> {
+        let total_tokens = match (self.input_tokens, self.output_tokens) {
+            (Some(i), Some(o)) => Some(i + o),
+            _ => None,
+        };
+        let usage = Usage::new(self.input_tokens, self.output_tokens, total_tokens);
+        let model = self.model.unwrap_or_else(|| "Unknown".to_string());
+        vec![CompletionDelta::Usage(ProviderUsage::new(model, usage))]
+    }
+}
+
 /// Create a complete request payload for Anthropic's API
 pub fn create_request(
     model_config: &ModelConfig,
@@ -290,6 +467,7 @@ mod tests {
             panic!("Expected Text content");
         }
 
+        assert_eq!(message.stop_reason, Some(StopReason::EndTurn));
         assert_eq!(usage.input_tokens, Some(12));
         assert_eq!(usage.output_tokens, Some(15));
         assert_eq!(usage.total_tokens, Some(27));
@@ -297,6 +475,31 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_stop_reason() -> Result<()> {
+        let cases = [
+            ("end_turn", Some(StopReason::EndTurn)),
+            ("max_tokens", Some(StopReason::MaxTokens)),
+            ("tool_use", Some(StopReason::ToolUse)),
+            ("stop_sequence", Some(StopReason::StopSequence)),
+            ("something_new", None),
+        ];
+
+        for (raw, expected) in cases {
+            let response = json!({
+                "content": [{"type": "text", "text": "Partial answer"}],
+                "stop_reason": raw,
+            });
+            let message = response_to_message(response)?;
+            assert_eq!(message.stop_reason, expected);
+        }
+
+        let message = response_to_message(json!({"content": []}))?;
+        assert_eq!(message.stop_reason, None);
+
+        Ok(())
+    }
+
     #[test]
     fn test_parse_tool_response() -> Result<()> {
         let response = json!({
@@ -360,6 +563,46 @@ mod tests {
         assert_eq!(spec[2]["content"][0]["text"], "How are you?");
     }
 
+    #[test]
+    fn test_image_to_anthropic_spec() {
+        let messages = vec![Message::user()
+            .with_text("What is in this image?")
+            .with_image("iVBORw0KGgo=", "image/png")];
+
+        let spec = format_messages(&messages);
+
+        assert_eq!(spec.len(), 1);
+        assert_eq!(spec[0]["content"][0]["type"], "text");
+        let block = &spec[0]["content"][1];
+        assert_eq!(block["type"], "image");
+        assert_eq!(block["source"]["type"], "base64");
+        assert_eq!(block["source"]["media_type"], "image/png");
+        assert_eq!(block["source"]["data"], "iVBORw0KGgo=");
+
+        // Images returned by tools are forwarded inside the tool result
+        let messages = vec![
+            Message::assistant().with_tool_request(
+                "1",
+                Ok(ToolCall::new("screen_capture", json!({"display": 0}))),
+            ),
+            Message::user().with_tool_response(
+                "1",
+                Ok(vec![
+                    Content::text("Captured display 0"),
+                    Content::image("iVBORw0KGgo=", "image/png"),
+                ]),
+            ),
+        ];
+
+        let spec = format_messages(&messages);
+
+        let result = &spec[1]["content"][0];
+        assert_eq!(result["type"], "tool_result");
+        assert_eq!(result["content"][0]["text"], "Captured display 0");
+        assert_eq!(result["content"][1]["type"], "image");
+        assert_eq!(result["content"][1]["source"]["data"], "iVBORw0KGgo=");
+    }
+
     #[test]
     fn test_tools_to_anthropic_spec() {
         let tools = vec![
@@ -415,4 +658,62 @@ mod tests {
         assert_eq!(spec_array[0]["text"], system);
         assert!(spec_array[0].get("cache_control").is_some());
     }
+
+    #[test]
+    fn test_stream_state_text_and_tool_use() -> Result<()> {
+        let events = [
+            json!({"type": "message_start", "message": {"id": "msg_1", "model": "claude-3-5-sonnet-latest", "content": [], "usage": {"input_tokens": 25, "output_tokens": 1}}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            json!({"type": "ping"}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Let me "}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "check"}}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "shell", "input": {}}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"command\": "}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "\"ls\"}"}}),
+            json!({"type": "content_block_stop", "index": 1}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 15}}),
+            json!({"type": "message_stop"}),
+        ];
+
+        let mut state = StreamState::default();
+        let mut deltas = Vec::new();
+        for event in &events {
+            deltas.extend(state.process_event(event)?);
+        }
+        deltas.extend(state.finish());
+
+        let texts: Vec<&str> = deltas.iter().filter_map(|d| d.as_text()).collect();
+        assert_eq!(texts, vec!["Let me ", "check"]);
+
+        let CompletionDelta::Content(MessageContent::ToolRequest(request)) = &deltas[2] else {
+            panic!("Expected a tool request, got {:?}", deltas[2]);
+        };
+        assert_eq!(request.id, "toolu_1");
+        let tool_call = request.tool_call.as_ref().unwrap();
+        assert_eq!(tool_call.name, "shell");
+        assert_eq!(tool_call.arguments, json!({"command": "ls"}));
+
+        assert!(matches!(
+            deltas[3],
+            CompletionDelta::Stop(StopReason::ToolUse)
+        ));
+        let CompletionDelta::Usage(usage) = &deltas[4] else {
+            panic!("Expected usage, got {:?}", deltas[4]);
+        };
+        assert_eq!(usage.model, "claude-3-5-sonnet-latest");
+        assert_eq!(usage.usage.input_tokens, Some(25));
+        assert_eq!(usage.usage.output_tokens, Some(15));
+        assert_eq!(usage.usage.total_tokens, Some(40));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_state_error_event() {
+        let mut state = StreamState::default();
+        let err = state
+            .process_event(&json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}))
+            .unwrap_err();
+        assert!(matches!(err, ProviderError::ServerError(message) if message == "Overloaded"));
+    }
 }
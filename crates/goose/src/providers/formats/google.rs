@@ -1,4 +1,4 @@
-use crate::message::{Message, MessageContent};
+use crate::message::{Message, MessageContent, StopReason};
 use crate::model::ModelConfig;
 use crate::providers::base::Usage;
 use crate::providers::utils::{is_valid_function_name, sanitize_function_name};
@@ -191,6 +191,7 @@ pub fn response_to_message(response: Value) -> Result<Message> {
             role,
             created,
             content,
+            stop_reason: None,
         });
     }
     let candidate = candidate.unwrap();
@@ -234,9 +235,22 @@ pub fn response_to_message(response: Value) -> Result<Message> {
         role,
         created,
         content,
+        stop_reason: get_stop_reason(&response),
     })
 }
 
+/// Extract the reason generation stopped from Google's API response
+pub fn get_stop_reason(data: &Value) -> Option<StopReason> {
+    match data["candidates"][0]["finishReason"].as_str()? {
+        "STOP" => Some(StopReason::EndTurn),
+        "MAX_TOKENS" => Some(StopReason::MaxTokens),
+        "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" => {
+            Some(StopReason::ContentFilter)
+        }
+        _ => None,
+    }
+}
+
 /// Extract usage information from Google's API response
 pub fn get_usage(data: &Value) -> Result<Usage> {
     if let Some(usage_meta_data) = data.get("usageMetadata") {
@@ -285,6 +299,9 @@ pub fn create_request(
     if let Some(tokens) = model_config.max_tokens {
         generation_config.insert("maxOutputTokens".to_string(), json!(tokens));
     }
+    if let Some(seed) = model_config.seed {
+        generation_config.insert("seed".to_string(), json!(seed));
+    }
     if !generation_config.is_empty() {
         payload.insert("generationConfig".to_string(), json!(generation_config));
     }
@@ -302,6 +319,7 @@ mod tests {
             role,
             created: 0,
             content: vec![MessageContent::text(text.to_string())],
+            stop_reason: None,
         }
     }
 
@@ -310,6 +328,7 @@ mod tests {
             role: Role::User,
             created: 0,
             content: vec![MessageContent::tool_request(id.to_string(), Ok(tool_call))],
+            stop_reason: None,
         }
     }
 
@@ -321,6 +340,7 @@ mod tests {
                 id.to_string(),
                 Ok(tool_response),
             )],
+            stop_reason: None,
         }
     }
 
@@ -458,6 +478,32 @@ mod tests {
         let message = response_to_message(response).unwrap();
         assert_eq!(message.role, Role::Assistant);
         assert!(message.content.is_empty());
+        assert_eq!(message.stop_reason, None);
+    }
+
+    #[test]
+    fn test_response_to_message_stop_reason() {
+        let cases = [
+            ("STOP", Some(StopReason::EndTurn)),
+            ("MAX_TOKENS", Some(StopReason::MaxTokens)),
+            ("SAFETY", Some(StopReason::ContentFilter)),
+            ("OTHER", None),
+        ];
+
+        for (raw, expected) in cases {
+            let response = json!({
+                "candidates": [{
+                    "content": {
+                        "parts": [{
+                            "text": "Partial answer"
+                        }]
+                    },
+                    "finishReason": raw
+                }]
+            });
+            let message = response_to_message(response).unwrap();
+            assert_eq!(message.stop_reason, expected);
+        }
     }
 
     #[test]
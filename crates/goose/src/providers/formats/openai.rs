@@ -1,13 +1,14 @@
-use crate::message::{Message, MessageContent};
+use crate::message::{Message, MessageContent, StopReason};
 use crate::model::ModelConfig;
-use crate::providers::base::Usage;
+use crate::providers::base::{CompletionDelta, ProviderUsage, Usage};
 use crate::providers::utils::{
-    convert_image, is_valid_function_name, sanitize_function_name, ImageFormat,
+    convert_image, get_model, is_valid_function_name, sanitize_function_name, ImageFormat,
 };
 use anyhow::{anyhow, Error};
 use mcp_core::ToolError;
 use mcp_core::{Content, Role, Tool, ToolCall};
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 
 /// Convert internal Message format to OpenAI's API message specification
 ///   some openai compatible endpoints use the anthropic image spec at the content level
@@ -177,39 +178,12 @@ pub fn response_to_message(response: Value) -> anyhow::Result<Message> {
     if let Some(tool_calls) = original.get("tool_calls") {
         if let Some(tool_calls_array) = tool_calls.as_array() {
             for tool_call in tool_calls_array {
-                let id = tool_call["id"].as_str().unwrap_or_default().to_string();
-                let function_name = tool_call["function"]["name"]
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_string();
+                let id = tool_call["id"].as_str().unwrap_or_default();
+                let function_name = tool_call["function"]["name"].as_str().unwrap_or_default();
                 let arguments = tool_call["function"]["arguments"]
                     .as_str()
-                    .unwrap_or_default()
-                    .to_string();
-
-                if !is_valid_function_name(&function_name) {
-                    let error = ToolError::NotFound(format!(
-                        "The provided function name '{}' had invalid characters, it must match this regex [a-zA-Z0-9_-]+",
-                        function_name
-                    ));
-                    content.push(MessageContent::tool_request(id, Err(error)));
-                } else {
-                    match serde_json::from_str::<Value>(&arguments) {
-                        Ok(params) => {
-                            content.push(MessageContent::tool_request(
-                                id,
-                                Ok(ToolCall::new(&function_name, params)),
-                            ));
-                        }
-                        Err(e) => {
-                            let error = ToolError::InvalidParameters(format!(
-                                "Could not interpret tool use parameters for id {}: {}",
-                                id, e
-                            ));
-                            content.push(MessageContent::tool_request(id, Err(error)));
-                        }
-                    }
-                }
+                    .unwrap_or_default();
+                content.push(tool_call_to_content(id, function_name, arguments));
             }
         }
     }
@@ -218,9 +192,125 @@ pub fn response_to_message(response: Value) -> anyhow::Result<Message> {
         role: Role::Assistant,
         created: chrono::Utc::now().timestamp(),
         content,
+        stop_reason: get_stop_reason(&response),
     })
 }
 
+/// Convert a tool call, whose arguments are a JSON string, to a tool request
+pub fn tool_call_to_content(id: &str, function_name: &str, arguments: &str) -> MessageContent {
+    if !is_valid_function_name(function_name) {
+        let error = ToolError::NotFound(format!(
+            "The provided function name '{}' had invalid characters, it must match this regex [a-zA-Z0-9_-]+",
+            function_name
+        ));
+        return MessageContent::tool_request(id, Err(error));
+    }
+
+    match serde_json::from_str::<Value>(arguments) {
+        Ok(params) => MessageContent::tool_request(id, Ok(ToolCall::new(function_name, params))),
+        Err(e) => {
+            let error = ToolError::InvalidParameters(format!(
+                "Could not interpret tool use parameters for id {}: {}",
+                id, e
+            ));
+            MessageContent::tool_request(id, Err(error))
+        }
+    }
+}
+
+/// A tool call whose name and arguments are still being streamed
+#[derive(Debug, Default)]
+struct PartialToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Turns the chunks of a streamed chat completion into message deltas
+///
+/// Tool call arguments arrive in fragments, so tool requests are only emitted once the
+/// model has finished generating.
+#[derive(Debug, Default)]
+pub struct StreamState {
+    tool_calls: BTreeMap<u64, PartialToolCall>,
+    model: Option<String>,
+    usage: Option<Usage>,
+}
+
+impl StreamState {
+    /// Process the next chunk, returning the deltas it completes
+    pub fn process_chunk(&mut self, chunk: &Value) -> Vec<CompletionDelta> {
+        let mut deltas = Vec::new();
+        if chunk.get("model").is_some() {
+            self.model = Some(get_model(chunk));
+        }
+        // The usage is sent in a final chunk without choices
+        if chunk.get("usage").is_some_and(|usage| !usage.is_null()) {
+            self.usage = get_usage(chunk).ok();
+        }
+
+        let delta = &chunk["choices"][0]["delta"];
+        if let Some(text) = delta["content"].as_str().filter(|text| !text.is_empty()) {
+            deltas.push(CompletionDelta::Content(MessageContent::text(text)));
+        }
+        for fragment in delta["tool_calls"].as_array().into_iter().flatten() {
+            let index = fragment["index"].as_u64().unwrap_or_default();
+            let tool_call = self.tool_calls.entry(index).or_default();
+            if let Some(id) = fragment["id"].as_str() {
+                tool_call.id.push_str(id);
+            }
+            if let Some(name) = fragment["function"]["name"].as_str() {
+                tool_call.name.push_str(name);
+            }
+            if let Some(arguments) = fragment["function"]["arguments"].as_str() {
+                tool_call.arguments.push_str(arguments);
+            }
+        }
+
+        if let Some(stop_reason) = get_stop_reason(chunk) {
+            deltas.extend(self.take_tool_requests());
+            deltas.push(CompletionDelta::Stop(stop_reason));
+        }
+        deltas
+    }
+
+    /// The deltas that are only complete once the stream has ended
+    pub fn finish(mut self) -> Vec<CompletionDelta> {
+        let mut deltas = self.take_tool_requests();
+        if let Some(usage) = self.usage {
+            let model = self.model.unwrap_or_else(|| "Unknown".to_string());
+            deltas.push(CompletionDelta::Usage(ProviderUsage::new(model, usage)));
+        }
+        deltas
+    }
+
+    fn take_tool_requests(&mut self) -> Vec<CompletionDelta> {
+        std::mem::take(&mut self.tool_calls)
+            .into_values()
+            .map(|call| {
+                // Models can stream an empty string for tools without arguments
+                let arguments = if call.arguments.trim().is_empty() {
+                    "{}"
+                } else {
+                    &call.arguments
+                };
+                CompletionDelta::Content(tool_call_to_content(&call.id, &call.name, arguments))
+            })
+            .collect()
+    }
+}
+
+/// Extract the reason generation stopped from an OpenAI compatible response
+pub fn get_stop_reason(data: &Value) -> Option<StopReason> {
+    match data["choices"][0]["finish_reason"].as_str()? {
+        "stop" => Some(StopReason::EndTurn),
+        "length" => Some(StopReason::MaxTokens),
+        "tool_calls" | "function_call" => Some(StopReason::ToolUse),
+        "content_filter" => Some(StopReason::ContentFilter),
+        _ => None,
+    }
+}
+
 pub fn get_usage(data: &Value) -> anyhow::Result<Usage> {
     let usage = data
         .get("usage")
@@ -261,11 +351,16 @@ pub fn create_request(
     });
 
     let messages_spec = format_messages(messages, image_format);
-    let tools_spec = if !tools.is_empty() {
+    let mut tools_spec = if !tools.is_empty() {
         format_tools(tools)?
     } else {
         vec![]
     };
+    if model_config.strict_tools {
+        for tool in &mut tools_spec {
+            apply_strict_mode(tool);
+        }
+    }
 
     let mut messages_array = vec![system_message];
     messages_array.extend(messages_spec);
@@ -293,9 +388,207 @@ pub fn create_request(
             .unwrap()
             .insert("max_tokens".to_string(), json!(tokens));
     }
+    if let Some(stop) = &model_config.stop {
+        payload
+            .as_object_mut()
+            .unwrap()
+            .insert("stop".to_string(), json!(stop));
+    }
+    if let Some(seed) = model_config.seed {
+        payload
+            .as_object_mut()
+            .unwrap()
+            .insert("seed".to_string(), json!(seed));
+    }
     Ok(payload)
 }
 
+/// Schema keywords that OpenAI's strict mode rejects
+const STRICT_UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "default",
+    "format",
+    "pattern",
+    "minLength",
+    "maxLength",
+    "minimum",
+    "maximum",
+    "multipleOf",
+    "minItems",
+    "maxItems",
+    "uniqueItems",
+    "minProperties",
+    "maxProperties",
+    "patternProperties",
+];
+
+/// Switch a tool spec from `format_tools` to OpenAI's strict mode
+///
+/// Strict mode guarantees the arguments match the schema, but only accepts schemas where every
+/// object lists all of its properties as required and allows no others. Properties that were
+/// optional are made nullable instead, so the model can still leave them out by passing null.
+/// A tool whose schema can not be converted, such as one with malformed properties, is left as
+/// it is and sent without strict mode.
+pub fn apply_strict_mode(tool: &mut Value) {
+    if let Some(function) = tool.get_mut("function").and_then(|f| f.as_object_mut()) {
+        if let Some(parameters) = function.get("parameters") {
+            let mut strict = parameters.clone();
+            if !strict_schema(&mut strict) {
+                tracing::debug!(
+                    "Sending tool {} without strict mode, its schema can not be made strict",
+                    function["name"]
+                );
+                return;
+            }
+            function.insert("parameters".to_string(), strict);
+        }
+        function.insert("strict".to_string(), json!(true));
+    }
+}
+
+/// Convert `schema` for strict mode in place, returning false if it can not be converted
+fn strict_schema(schema: &mut Value) -> bool {
+    let Some(object) = schema.as_object_mut() else {
+        return true;
+    };
+    for keyword in STRICT_UNSUPPORTED_KEYWORDS {
+        object.remove(*keyword);
+    }
+
+    if object.get("type") == Some(&json!("object")) {
+        let required: Vec<String> = object
+            .get("required")
+            .and_then(|r| r.as_array())
+            .map(|r| {
+                r.iter()
+                    .filter_map(|name| name.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let Some(properties) = object
+            .entry("properties")
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+        else {
+            return false;
+        };
+        for (name, property) in properties.iter_mut() {
+            if !strict_schema(property) {
+                return false;
+            }
+            if !required.contains(name) {
+                make_nullable(property);
+            }
+        }
+        // Keep the originally required properties first, followed by the ones made nullable
+        let optional = properties.keys().filter(|name| !required.contains(name));
+        let all_properties: Vec<Value> = required
+            .iter()
+            .filter(|name| properties.contains_key(*name))
+            .chain(optional)
+            .map(|name| json!(name))
+            .collect();
+        object.insert("required".to_string(), Value::Array(all_properties));
+        object.insert("additionalProperties".to_string(), json!(false));
+    }
+
+    if let Some(items) = object.get_mut("items") {
+        if !strict_schema(items) {
+            return false;
+        }
+    }
+    for keyword in ["anyOf", "oneOf", "allOf"] {
+        if let Some(variants) = object.get_mut(keyword).and_then(|v| v.as_array_mut()) {
+            if !variants.iter_mut().all(strict_schema) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Allow null for a property that was optional before strict mode made it required
+fn make_nullable(schema: &mut Value) {
+    let Some(object) = schema.as_object_mut() else {
+        return;
+    };
+    match object.get_mut("type") {
+        Some(Value::String(kind)) => {
+            let kind = kind.clone();
+            object.insert("type".to_string(), json!([kind, "null"]));
+        }
+        Some(Value::Array(kinds)) => {
+            if !kinds.contains(&json!("null")) {
+                kinds.push(json!("null"));
+            }
+        }
+        _ => {
+            *schema = json!({"anyOf": [schema.clone(), {"type": "null"}]});
+            return;
+        }
+    }
+    if let Some(values) = object.get_mut("enum").and_then(|e| e.as_array_mut()) {
+        if !values.contains(&Value::Null) {
+            values.push(Value::Null);
+        }
+    }
+}
+
+/// Mark the request for Anthropic prompt caching
+///
+/// Anthropic models can cache the prompt to save cost, even when reached through an OpenAI
+/// compatible endpoint such as OpenRouter or a Databricks or Bedrock gateway, as long as the
+/// request carries Anthropic's `cache_control` field. Other hosts, OpenAI included, reject it,
+/// so only providers that pass it through apply this.
+pub fn apply_anthropic_prompt_cache(payload: &mut Value) {
+    if let Some(messages_spec) = payload
+        .as_object_mut()
+        .and_then(|obj| obj.get_mut("messages"))
+        .and_then(|messages| messages.as_array_mut())
+    {
+        // Add "cache_control" to the last and second-to-last "user" messages.
+        // During each turn, we mark the final message with cache_control so the conversation can be
+        // incrementally cached. The second-to-last user message is also marked for caching with the
+        // cache_control parameter, so that this checkpoint can read from the previous cache.
+        let mut user_count = 0;
+        for message in messages_spec.iter_mut().rev() {
+            if message.get("role") == Some(&json!("user")) {
+                if let Some(content) = message.get_mut("content") {
+                    if let Some(content_str) = content.as_str() {
+                        *content = json!([{
+                            "type": "text",
+                            "text": content_str,
+                            "cache_control": { "type": "ephemeral" }
+                        }]);
+                    }
+                }
+                user_count += 1;
+                if user_count >= 2 {
+                    break;
+                }
+            }
+        }
+
+        // Update the system message to have cache_control field.
+        if let Some(system_message) = messages_spec
+            .iter_mut()
+            .find(|msg| msg.get("role") == Some(&json!("system")))
+        {
+            if let Some(content) = system_message.get_mut("content") {
+                if let Some(content_str) = content.as_str() {
+                    *system_message = json!({
+                        "role": "system",
+                        "content": [{
+                            "type": "text",
+                            "text": content_str,
+                            "cache_control": { "type": "ephemeral" }
+                        }]
+                    });
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -473,6 +766,186 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_create_request_max_tokens_and_stop() -> anyhow::Result<()> {
+        let messages = vec![Message::user().with_text("Hello")];
+
+        let model_config = ModelConfig::new("gpt-4o".to_string());
+        let request = create_request(
+            &model_config,
+            "system",
+            &messages,
+            &[],
+            &ImageFormat::OpenAi,
+        )?;
+        assert!(request.get("max_tokens").is_none());
+        assert!(request.get("stop").is_none());
+
+        let model_config = model_config
+            .with_max_tokens(Some(256))
+            .with_stop(Some(vec!["END".to_string(), "\n\n".to_string()]));
+        let request = create_request(
+            &model_config,
+            "system",
+            &messages,
+            &[],
+            &ImageFormat::OpenAi,
+        )?;
+        assert_eq!(request["max_tokens"], json!(256));
+        assert_eq!(request["stop"], json!(["END", "\n\n"]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_request_seed() -> anyhow::Result<()> {
+        let messages = vec![Message::user().with_text("Hello")];
+
+        let model_config = ModelConfig::new("gpt-4o".to_string());
+        let request = create_request(
+            &model_config,
+            "system",
+            &messages,
+            &[],
+            &ImageFormat::OpenAi,
+        )?;
+        assert!(request.get("seed").is_none());
+
+        let request = create_request(
+            &model_config.with_seed(Some(42)),
+            "system",
+            &messages,
+            &[],
+            &ImageFormat::OpenAi,
+        )?;
+        assert_eq!(request["seed"], json!(42));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_request_strict_tools() -> anyhow::Result<()> {
+        let tool = Tool::new(
+            "edit",
+            "Edit a file",
+            json!({
+                "type": "object",
+                "required": ["path"],
+                "properties": {
+                    "path": {"type": "string", "description": "File to edit"},
+                    "mode": {"type": "string", "enum": ["append", "replace"], "default": "replace"},
+                    "range": {
+                        "type": "array",
+                        "items": {"type": "integer"},
+                        "minItems": 2
+                    },
+                    "options": {
+                        "type": "object",
+                        "properties": {"backup": {"type": "boolean"}}
+                    }
+                }
+            }),
+        );
+        let messages = vec![Message::user().with_text("Hello")];
+
+        let model_config = ModelConfig::new("gpt-4o".to_string());
+        let request = create_request(
+            &model_config,
+            "system",
+            &messages,
+            std::slice::from_ref(&tool),
+            &ImageFormat::OpenAi,
+        )?;
+        assert!(request["tools"][0]["function"].get("strict").is_none());
+        assert_eq!(
+            request["tools"][0]["function"]["parameters"],
+            tool.input_schema
+        );
+
+        let model_config = model_config.with_strict_tools(true);
+        let request = create_request(
+            &model_config,
+            "system",
+            &messages,
+            &[tool],
+            &ImageFormat::OpenAi,
+        )?;
+        let function = &request["tools"][0]["function"];
+        assert_eq!(function["strict"], true);
+        assert_eq!(
+            function["parameters"],
+            json!({
+                "type": "object",
+                "required": ["path", "mode", "options", "range"],
+                "additionalProperties": false,
+                "properties": {
+                    "path": {"type": "string", "description": "File to edit"},
+                    "mode": {"type": ["string", "null"], "enum": ["append", "replace", null]},
+                    "range": {"type": ["array", "null"], "items": {"type": "integer"}},
+                    "options": {
+                        "type": ["object", "null"],
+                        "required": ["backup"],
+                        "additionalProperties": false,
+                        "properties": {"backup": {"type": ["boolean", "null"]}}
+                    }
+                }
+            })
+        );
+
+        // A malformed schema is sent as it is rather than failing the request
+        let mut malformed = json!({
+            "type": "function",
+            "function": {
+                "name": "broken",
+                "parameters": {"type": "object", "properties": ["path"]}
+            }
+        });
+        let original = malformed.clone();
+        apply_strict_mode(&mut malformed);
+        assert_eq!(malformed, original);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_anthropic_prompt_cache() -> anyhow::Result<()> {
+        let messages = vec![
+            Message::user().with_text("First"),
+            Message::assistant().with_text("Reply"),
+            Message::user().with_text("Second"),
+            Message::assistant().with_text("Reply"),
+            Message::user().with_text("Third"),
+        ];
+        let cached =
+            json!([{"type": "text", "text": "Third", "cache_control": {"type": "ephemeral"}}]);
+
+        // The plain request stays valid for OpenAI even with the flag set
+        let model_config = ModelConfig::new("gpt-4o".to_string()).with_cache_control(true);
+        let mut request = create_request(
+            &model_config,
+            "system",
+            &messages,
+            &[],
+            &ImageFormat::OpenAi,
+        )?;
+        assert_eq!(request["messages"][0]["content"], "system");
+        assert_eq!(request["messages"][5]["content"], "Third");
+
+        apply_anthropic_prompt_cache(&mut request);
+        let spec = request["messages"].as_array().unwrap();
+        assert_eq!(
+            spec[0]["content"],
+            json!([{"type": "text", "text": "system", "cache_control": {"type": "ephemeral"}}])
+        );
+        // Only the last two user messages are marked
+        assert_eq!(spec[1]["content"], "First");
+        assert_eq!(spec[3]["content"][0]["cache_control"]["type"], "ephemeral");
+        assert_eq!(spec[3]["content"][0]["text"], "Second");
+        assert_eq!(spec[5]["content"], cached);
+        assert_eq!(spec[4]["content"], "Reply");
+
+        Ok(())
+    }
+
     #[test]
     fn test_response_to_message_text() -> anyhow::Result<()> {
         let response = json!({
@@ -497,6 +970,34 @@ mod tests {
             panic!("Expected Text content");
         }
         assert!(matches!(message.role, Role::Assistant));
+        assert_eq!(message.stop_reason, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_response_to_message_stop_reason() -> anyhow::Result<()> {
+        let cases = [
+            ("stop", Some(StopReason::EndTurn)),
+            ("length", Some(StopReason::MaxTokens)),
+            ("tool_calls", Some(StopReason::ToolUse)),
+            ("content_filter", Some(StopReason::ContentFilter)),
+            ("something_new", None),
+        ];
+
+        for (raw, expected) in cases {
+            let response = json!({
+                "choices": [{
+                    "message": {
+                        "role": "assistant",
+                        "content": "Partial answer"
+                    },
+                    "finish_reason": raw
+                }]
+            });
+            let message = response_to_message(response)?;
+            assert_eq!(message.stop_reason, expected);
+        }
 
         Ok(())
     }
@@ -561,4 +1062,51 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_stream_state_text_and_tool_calls() {
+        let chunks = [
+            json!({"model": "gpt-4o", "choices": [{"index": 0, "delta": {"role": "assistant", "content": "Let me "}}]}),
+            json!({"model": "gpt-4o", "choices": [{"index": 0, "delta": {"content": "check"}}]}),
+            json!({"model": "gpt-4o", "choices": [{"index": 0, "delta": {"tool_calls": [
+                {"index": 0, "id": "call_1", "type": "function", "function": {"name": "shell", "arguments": ""}}
+            ]}}]}),
+            json!({"model": "gpt-4o", "choices": [{"index": 0, "delta": {"tool_calls": [
+                {"index": 0, "function": {"arguments": "{\"command\":"}}
+            ]}}]}),
+            json!({"model": "gpt-4o", "choices": [{"index": 0, "delta": {"tool_calls": [
+                {"index": 0, "function": {"arguments": " \"ls\"}"}}
+            ]}}]}),
+            json!({"model": "gpt-4o", "choices": [{"index": 0, "delta": {}, "finish_reason": "tool_calls"}]}),
+            json!({"model": "gpt-4o", "choices": [], "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}}),
+        ];
+
+        let mut state = StreamState::default();
+        let mut deltas = Vec::new();
+        for chunk in &chunks {
+            deltas.extend(state.process_chunk(chunk));
+        }
+        deltas.extend(state.finish());
+
+        let texts: Vec<&str> = deltas.iter().filter_map(|d| d.as_text()).collect();
+        assert_eq!(texts, vec!["Let me ", "check"]);
+
+        let CompletionDelta::Content(MessageContent::ToolRequest(request)) = &deltas[2] else {
+            panic!("Expected a tool request, got {:?}", deltas[2]);
+        };
+        assert_eq!(request.id, "call_1");
+        let tool_call = request.tool_call.as_ref().unwrap();
+        assert_eq!(tool_call.name, "shell");
+        assert_eq!(tool_call.arguments, json!({"command": "ls"}));
+
+        assert!(matches!(
+            deltas[3],
+            CompletionDelta::Stop(StopReason::ToolUse)
+        ));
+        let CompletionDelta::Usage(usage) = &deltas[4] else {
+            panic!("Expected usage, got {:?}", deltas[4]);
+        };
+        assert_eq!(usage.model, "gpt-4o");
+        assert_eq!(usage.usage.total_tokens, Some(15));
+    }
 }
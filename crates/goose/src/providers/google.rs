@@ -1,9 +1,10 @@
 use super::errors::ProviderError;
+use super::retry::send_with_retry;
 use crate::message::Message;
 use crate::model::ModelConfig;
 use crate::providers::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage};
 use crate::providers::formats::google::{create_request, get_usage, response_to_message};
-use crate::providers::utils::{emit_debug_trace, unescape_json_values};
+use crate::providers::utils::{check_payload_size, emit_debug_trace, unescape_json_values};
 use anyhow::Result;
 use async_trait::async_trait;
 use mcp_core::tool::Tool;
@@ -22,6 +23,9 @@ pub const GOOGLE_KNOWN_MODELS: &[&str] = &[
     "models/gemini-2.0-flash-thinking-exp-01-21",
 ];
 
+/// Gemini rejects request bodies larger than 20 MB
+pub const GOOGLE_MAX_REQUEST_BYTES: usize = 20 * 1024 * 1024;
+
 pub const GOOGLE_DOC_URL: &str = "https://ai.google/get-started/our-models/";
 
 #[derive(Debug, serde::Serialize)]
@@ -68,13 +72,12 @@ impl GoogleProvider {
             self.api_key
         );
 
-        let response = self
+        let request = self
             .client
             .post(&url)
             .header("CONTENT_TYPE", "application/json")
-            .json(&payload)
-            .send()
-            .await?;
+            .json(&payload);
+        let response = send_with_retry(&self.model.retry, request).await?;
 
         let status = response.status();
         let payload: Option<Value> = response.json().await.ok();
@@ -149,6 +152,13 @@ impl Provider for GoogleProvider {
     ) -> Result<(Message, ProviderUsage), ProviderError> {
         let payload = create_request(&self.model, system, messages, tools)?;
 
+        check_payload_size(
+            &payload,
+            self.model
+                .max_request_bytes
+                .unwrap_or(GOOGLE_MAX_REQUEST_BYTES),
+        )?;
+
         // Make request
         let response = self.post(payload.clone()).await?;
 
@@ -1,9 +1,11 @@
 use super::errors::ProviderError;
+use super::rate_limit::RateLimitInfo;
+use super::retry::send_with_retry;
 use crate::message::Message;
 use crate::model::ModelConfig;
 use crate::providers::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage};
 use crate::providers::formats::openai::{create_request, get_usage, response_to_message};
-use crate::providers::utils::get_model;
+use crate::providers::utils::{check_payload_size, get_model, DEFAULT_MAX_REQUEST_BYTES};
 use anyhow::Result;
 use async_trait::async_trait;
 use mcp_core::Tool;
@@ -53,25 +55,28 @@ impl GroqProvider {
         })
     }
 
-    async fn post(&self, payload: Value) -> anyhow::Result<Value, ProviderError> {
+    async fn post(
+        &self,
+        payload: Value,
+    ) -> anyhow::Result<(Value, Option<RateLimitInfo>), ProviderError> {
         let url = format!(
             "{}/openai/v1/chat/completions",
             self.host.trim_end_matches('/')
         );
 
-        let response = self
+        let request = self
             .client
             .post(&url)
             .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&payload)
-            .send()
-            .await?;
+            .json(&payload);
+        let response = send_with_retry(&self.model.retry, request).await?;
 
         let status = response.status();
+        let rate_limit = RateLimitInfo::from_headers(response.headers());
         let payload: Option<Value> = response.json().await.ok();
 
         match status {
-            StatusCode::OK => payload.ok_or_else( || ProviderError::RequestFailed("Response body is not valid JSON".to_string()) ),
+            StatusCode::OK => payload.map(|payload| (payload, rate_limit)).ok_or_else( || ProviderError::RequestFailed("Response body is not valid JSON".to_string()) ),
             StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
                 Err(ProviderError::Authentication(format!("Authentication failed. Please ensure your API keys are valid and have the required permissions. \
                     Status: {}. Response: {:?}", status, payload)))
@@ -134,12 +139,68 @@ impl Provider for GroqProvider {
             &super::utils::ImageFormat::OpenAi,
         )?;
 
-        let response = self.post(payload.clone()).await?;
+        check_payload_size(
+            &payload,
+            self.model
+                .max_request_bytes
+                .unwrap_or(DEFAULT_MAX_REQUEST_BYTES),
+        )?;
+
+        let (response, rate_limit) = self.post(payload.clone()).await?;
 
         let message = response_to_message(response.clone())?;
         let usage = get_usage(&response)?;
         let model = get_model(&response);
         super::utils::emit_debug_trace(self, &payload, &response, &usage);
-        Ok((message, ProviderUsage::new(model, usage)))
+        Ok((
+            message,
+            ProviderUsage::new(model, usage).with_rate_limit(rate_limit),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_text_completion() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/openai/v1/chat/completions"))
+            .and(header("Authorization", "Bearer test-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "chatcmpl-123",
+                "object": "chat.completion",
+                "model": GROQ_DEFAULT_MODEL,
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Hello from Groq!"},
+                    "finish_reason": "stop"
+                }],
+                "usage": {"prompt_tokens": 12, "completion_tokens": 4, "total_tokens": 16}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = GroqProvider {
+            client: Client::new(),
+            host: server.uri(),
+            api_key: "test-key".to_string(),
+            model: ModelConfig::new(GROQ_DEFAULT_MODEL.to_string()),
+        };
+        let (message, usage) = provider
+            .complete("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap();
+
+        assert_eq!(message.as_concat_text(), "Hello from Groq!");
+        assert_eq!(usage.model, GROQ_DEFAULT_MODEL);
+        assert_eq!(usage.usage.input_tokens, Some(12));
+        assert_eq!(usage.usage.total_tokens, Some(16));
     }
 }
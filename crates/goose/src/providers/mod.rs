@@ -6,10 +6,17 @@ mod factory;
 pub mod formats;
 pub mod google;
 pub mod groq;
+pub mod moderation;
 pub mod oauth;
 pub mod ollama;
 pub mod openai;
 pub mod openrouter;
+pub mod pricing;
+pub mod rate_limit;
+pub mod rate_limiter;
+pub mod retry;
+pub mod router;
+pub mod sse;
 pub mod utils;
 
-pub use factory::{create, providers};
+pub use factory::{create, providers, validate_model};
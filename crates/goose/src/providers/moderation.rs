@@ -0,0 +1,218 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::errors::ProviderError;
+use super::utils::handle_response_openai_compat;
+use crate::config::Config;
+
+pub const OPENAI_MODERATION_DEFAULT_MODEL: &str = "omni-moderation-latest";
+
+/// The outcome of checking content with a moderation provider
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModerationResult {
+    /// Whether the content violates the provider's policies
+    pub flagged: bool,
+    /// The names of the categories the content was flagged for
+    pub categories: Option<Vec<String>>,
+    /// The score of each category, as reported by the provider
+    pub category_scores: Option<Value>,
+}
+
+impl ModerationResult {
+    pub fn new(
+        flagged: bool,
+        categories: Option<Vec<String>>,
+        category_scores: Option<Value>,
+    ) -> Self {
+        Self {
+            flagged,
+            categories,
+            category_scores,
+        }
+    }
+
+    /// The reply shown instead of a completion when the user's message is flagged
+    pub fn refusal(&self) -> String {
+        match self.categories.as_deref() {
+            Some(categories) if !categories.is_empty() => format!(
+                "Your message was flagged by moderation ({}) and was not sent to the model.",
+                categories.join(", ")
+            ),
+            _ => {
+                "Your message was flagged by moderation and was not sent to the model.".to_string()
+            }
+        }
+    }
+}
+
+/// Checks user input against a content policy before it is sent to the model
+#[async_trait]
+pub trait Moderation: Send + Sync {
+    async fn moderate_content(&self, content: &str) -> Result<ModerationResult, ProviderError>;
+}
+
+/// Which moderation provider checks user input, independent of the chat provider
+///
+/// Set with the `GOOSE_MODERATION` config key. Moderation is off unless configured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationConfig {
+    /// User input is not moderated
+    #[default]
+    None,
+    /// Use the OpenAI moderation endpoint, with the `OPENAI_API_KEY` and `OPENAI_HOST` config
+    OpenAi,
+}
+
+impl ModerationConfig {
+    pub fn from_env() -> Self {
+        Config::global().get("GOOSE_MODERATION").unwrap_or_default()
+    }
+
+    /// Create the configured moderation provider, or None if moderation is off
+    pub fn create(&self) -> Result<Option<Box<dyn Moderation>>> {
+        Ok(match self {
+            Self::None => None,
+            Self::OpenAi => Some(Box::new(OpenAiModeration::from_env()?)),
+        })
+    }
+}
+
+/// Moderation with OpenAI's moderation endpoint
+pub struct OpenAiModeration {
+    client: Client,
+    host: String,
+    api_key: String,
+    model: String,
+}
+
+impl OpenAiModeration {
+    pub fn from_env() -> Result<Self> {
+        let config = Config::global();
+        let api_key: String = config.get_secret("OPENAI_API_KEY")?;
+        let host: String = config
+            .get("OPENAI_HOST")
+            .unwrap_or_else(|_| "https://api.openai.com".to_string());
+        let model: String = config
+            .get("OPENAI_MODERATION_MODEL")
+            .unwrap_or_else(|_| OPENAI_MODERATION_DEFAULT_MODEL.to_string());
+        Ok(Self::new(host, api_key, model))
+    }
+
+    pub fn new(host: String, api_key: String, model: String) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .expect("should build an HTTP client");
+        Self {
+            client,
+            host,
+            api_key,
+            model,
+        }
+    }
+}
+
+#[async_trait]
+impl Moderation for OpenAiModeration {
+    async fn moderate_content(&self, content: &str) -> Result<ModerationResult, ProviderError> {
+        let url = format!("{}/v1/moderations", self.host.trim_end_matches('/'));
+        let response = self
+            .client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&json!({"model": self.model, "input": content}))
+            .send()
+            .await?;
+        let (response, _) = handle_response_openai_compat(response).await?;
+
+        let result = response
+            .get("results")
+            .and_then(|results| results.get(0))
+            .ok_or_else(|| {
+                ProviderError::RequestFailed("Moderation response has no results".to_string())
+            })?;
+        let flagged = result
+            .get("flagged")
+            .and_then(|flagged| flagged.as_bool())
+            .unwrap_or(false);
+        let categories = result
+            .get("categories")
+            .and_then(|categories| categories.as_object())
+            .map(|categories| {
+                categories
+                    .iter()
+                    .filter(|(_, flagged)| flagged.as_bool() == Some(true))
+                    .map(|(name, _)| name.clone())
+                    .collect()
+            });
+        Ok(ModerationResult::new(
+            flagged,
+            categories,
+            result.get("category_scores").cloned(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn moderate(flagged: bool) -> ModerationResult {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/moderations"))
+            .and(header("Authorization", "Bearer test-key"))
+            .and(body_partial_json(json!({"input": "some input"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "modr-123",
+                "model": "omni-moderation-latest",
+                "results": [{
+                    "flagged": flagged,
+                    "categories": {"harassment": flagged, "violence": false},
+                    "category_scores": {"harassment": 0.9, "violence": 0.01}
+                }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let moderation = OpenAiModeration::new(
+            server.uri(),
+            "test-key".to_string(),
+            OPENAI_MODERATION_DEFAULT_MODEL.to_string(),
+        );
+        moderation.moderate_content("some input").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_openai_moderation_flagged() {
+        let result = moderate(true).await;
+        assert!(result.flagged);
+        assert_eq!(result.categories, Some(vec!["harassment".to_string()]));
+        assert_eq!(result.category_scores.as_ref().unwrap()["harassment"], 0.9);
+        assert_eq!(
+            result.refusal(),
+            "Your message was flagged by moderation (harassment) and was not sent to the model."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_openai_moderation_not_flagged() {
+        let result = moderate(false).await;
+        assert!(!result.flagged);
+        assert_eq!(result.categories, Some(vec![]));
+    }
+
+    #[test]
+    fn test_moderation_is_off_by_default() {
+        assert_eq!(ModerationConfig::default(), ModerationConfig::None);
+        assert!(ModerationConfig::None.create().unwrap().is_none());
+    }
+}
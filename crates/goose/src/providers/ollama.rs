@@ -1,14 +1,20 @@
-use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage};
+use super::base::{
+    CompletionDelta, CompletionStream, ConfigKey, Provider, ProviderMetadata, ProviderUsage, Usage,
+};
 use super::errors::ProviderError;
-use super::utils::{get_model, handle_response_openai_compat};
-use crate::message::Message;
+use super::retry::send_with_retry;
+use super::utils::{check_payload_size, DEFAULT_MAX_REQUEST_BYTES};
+use crate::message::{Message, MessageContent, StopReason};
 use crate::model::ModelConfig;
-use crate::providers::formats::openai::{create_request, get_usage, response_to_message};
+use crate::providers::base::MessageAccumulator;
+use crate::providers::formats::openai::{create_request, tool_call_to_content};
 use anyhow::Result;
 use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
 use mcp_core::tool::Tool;
-use reqwest::Client;
-use serde_json::Value;
+use rand::{distributions::Alphanumeric, Rng};
+use reqwest::{Client, Response, StatusCode};
+use serde_json::{json, Map, Value};
 use std::time::Duration;
 
 pub const OLLAMA_HOST: &str = "http://localhost:11434";
@@ -23,6 +29,9 @@ pub struct OllamaProvider {
     client: Client,
     host: String,
     model: ModelConfig,
+    /// How long Ollama keeps the model loaded after a request, such as "30m", or "-1"
+    /// to keep it loaded until Ollama stops. Ollama's own default is five minutes.
+    keep_alive: Option<String>,
 }
 
 impl Default for OllamaProvider {
@@ -38,6 +47,12 @@ impl OllamaProvider {
         let host: String = config
             .get("OLLAMA_HOST")
             .unwrap_or_else(|_| OLLAMA_HOST.to_string());
+        // A number of seconds is accepted as well as a duration
+        let keep_alive: Option<Value> = config.get("OLLAMA_KEEP_ALIVE").ok();
+        let keep_alive = keep_alive.map(|value| match value {
+            Value::String(duration) => duration,
+            other => other.to_string(),
+        });
 
         let client = Client::builder()
             .timeout(Duration::from_secs(600))
@@ -47,15 +62,227 @@ impl OllamaProvider {
             client,
             host,
             model,
+            keep_alive,
         })
     }
 
-    async fn post(&self, payload: Value) -> Result<Value, ProviderError> {
-        let url = format!("{}/v1/chat/completions", self.host.trim_end_matches('/'));
+    /// Send a request to Ollama's native chat API, turning error statuses into provider errors
+    async fn post(&self, request: &Value) -> Result<Response, ProviderError> {
+        check_payload_size(
+            request,
+            self.model
+                .max_request_bytes
+                .unwrap_or(DEFAULT_MAX_REQUEST_BYTES),
+        )?;
+
+        let url = format!("{}/api/chat", self.host.trim_end_matches('/'));
+        let response =
+            send_with_retry(&self.model.retry, self.client.post(&url).json(request)).await?;
+        if response.status() == StatusCode::OK {
+            return Ok(response);
+        }
+
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or_default();
+        let message = body["error"]
+            .as_str()
+            .map_or_else(|| body.to_string(), str::to_string);
+        Err(match status {
+            StatusCode::NOT_FOUND => ProviderError::RequestFailed(format!(
+                "Ollama could not find the model: {}. Pull it with `ollama pull` first",
+                message
+            )),
+            status if status.is_server_error() => ProviderError::ServerError(message),
+            _ => ProviderError::RequestFailed(format!(
+                "Request failed with status {}: {}",
+                status, message
+            )),
+        })
+    }
+
+    /// The body of a request to Ollama's native chat API, which keeps the model loaded for
+    /// `keep_alive`. Streamed responses are newline-delimited JSON.
+    fn native_chat_request(&self, payload: &Value, stream: bool) -> Value {
+        let messages: Vec<Value> = payload["messages"]
+            .as_array()
+            .map(|messages| messages.iter().map(native_message).collect())
+            .unwrap_or_default();
+
+        let mut options = Map::new();
+        for (from, to) in [
+            ("temperature", "temperature"),
+            ("max_tokens", "num_predict"),
+            ("stop", "stop"),
+            ("seed", "seed"),
+        ] {
+            if let Some(value) = payload.get(from) {
+                options.insert(to.to_string(), value.clone());
+            }
+        }
+
+        let mut request = json!({
+            "model": payload["model"],
+            "messages": messages,
+            "stream": stream,
+        });
+        if let Some(tools) = payload.get("tools") {
+            request["tools"] = tools.clone();
+        }
+        if !options.is_empty() {
+            request["options"] = Value::Object(options);
+        }
+        if let Some(keep_alive) = &self.keep_alive {
+            // Ollama reads a bare number as seconds, and anything else as a duration
+            request["keep_alive"] = keep_alive
+                .parse::<i64>()
+                .map_or_else(|_| json!(keep_alive), |seconds| json!(seconds));
+        }
+        request
+    }
+}
+
+/// Convert a message in the OpenAI format to Ollama's native one, where images are listed
+/// apart from the text and tool call arguments are objects rather than strings
+fn native_message(message: &Value) -> Value {
+    let mut native = json!({"role": message["role"]});
+    match &message["content"] {
+        Value::Array(parts) => {
+            let text: Vec<&str> = parts
+                .iter()
+                .filter_map(|part| part["text"].as_str())
+                .collect();
+            let images: Vec<&str> = parts
+                .iter()
+                .filter_map(|part| part["image_url"]["url"].as_str())
+                .filter_map(|url| url.split_once(";base64,").map(|(_, data)| data))
+                .collect();
+            native["content"] = json!(text.join("\n"));
+            if !images.is_empty() {
+                native["images"] = json!(images);
+            }
+        }
+        Value::Null => native["content"] = json!(""),
+        content => native["content"] = content.clone(),
+    }
+    if let Some(tool_calls) = message["tool_calls"].as_array() {
+        let tool_calls: Vec<Value> = tool_calls
+            .iter()
+            .map(|tool_call| {
+                let arguments = &tool_call["function"]["arguments"];
+                let arguments = arguments
+                    .as_str()
+                    .and_then(|arguments| serde_json::from_str(arguments).ok())
+                    .unwrap_or_else(|| arguments.clone());
+                json!({"function": {"name": tool_call["function"]["name"], "arguments": arguments}})
+            })
+            .collect();
+        native["tool_calls"] = json!(tool_calls);
+    }
+    native
+}
+
+/// Stream the JSON objects of a newline-delimited JSON body
+///
+/// Chunks may end anywhere, so bytes are buffered until a full line is available.
+fn ndjson_objects(response: Response) -> BoxStream<'static, Result<Value, ProviderError>> {
+    let mut body = response.bytes_stream();
+    Box::pin(async_stream::try_stream! {
+        let mut buffer: Vec<u8> = Vec::new();
+        loop {
+            let chunk = body.next().await.transpose()?;
+            let finished = chunk.is_none();
+            buffer.extend_from_slice(&chunk.unwrap_or_default());
 
-        let response = self.client.post(&url).json(&payload).send().await?;
+            let mut lines: Vec<Vec<u8>> = Vec::new();
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                lines.push(buffer.drain(..=end).collect());
+            }
+            if finished {
+                lines.push(std::mem::take(&mut buffer));
+            }
+            for line in lines {
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let object: Value = serde_json::from_str(line).map_err(|e| {
+                    ProviderError::RequestFailed(format!("Invalid line in streamed response: {}", e))
+                })?;
+                yield object;
+            }
+            if finished {
+                break;
+            }
+        }
+    })
+}
+
+/// Builds the deltas of a completion from the chunks of Ollama's native chat stream
+#[derive(Debug, Default)]
+struct ChunkState {
+    tool_calls: usize,
+}
+
+impl ChunkState {
+    fn process_chunk(&mut self, chunk: &Value) -> Result<Vec<CompletionDelta>, ProviderError> {
+        if let Some(error) = chunk["error"].as_str() {
+            return Err(ProviderError::RequestFailed(format!(
+                "Ollama stopped streaming the response: {}",
+                error
+            )));
+        }
 
-        handle_response_openai_compat(response).await
+        let mut deltas = Vec::new();
+        if let Some(text) = chunk["message"]["content"]
+            .as_str()
+            .filter(|t| !t.is_empty())
+        {
+            deltas.push(CompletionDelta::Content(MessageContent::text(text)));
+        }
+        // Tool calls arrive whole, and without ids, so they are given random ones that stay
+        // unique across the turns of a conversation
+        for tool_call in chunk["message"]["tool_calls"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            self.tool_calls += 1;
+            let suffix: String = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(8)
+                .map(char::from)
+                .collect();
+            let id = format!("call_{}", suffix);
+            let function = &tool_call["function"];
+            deltas.push(CompletionDelta::Content(tool_call_to_content(
+                &id,
+                function["name"].as_str().unwrap_or_default(),
+                &function["arguments"].to_string(),
+            )));
+        }
+
+        if chunk["done"].as_bool() == Some(true) {
+            let stop_reason = match chunk["done_reason"].as_str() {
+                Some("length") => StopReason::MaxTokens,
+                _ if self.tool_calls > 0 => StopReason::ToolUse,
+                _ => StopReason::EndTurn,
+            };
+            deltas.push(CompletionDelta::Stop(stop_reason));
+
+            let input_tokens = chunk["prompt_eval_count"].as_i64().map(|n| n as i32);
+            let output_tokens = chunk["eval_count"].as_i64().map(|n| n as i32);
+            let total_tokens = match (input_tokens, output_tokens) {
+                (Some(input), Some(output)) => Some(input + output),
+                _ => None,
+            };
+            let model = chunk["model"].as_str().unwrap_or_default().to_string();
+            deltas.push(CompletionDelta::Usage(ProviderUsage::new(
+                model,
+                Usage::new(input_tokens, output_tokens, total_tokens),
+            )));
+        }
+        Ok(deltas)
     }
 }
 
@@ -69,12 +296,10 @@ impl Provider for OllamaProvider {
             OLLAMA_DEFAULT_MODEL,
             OLLAMA_KNOWN_MODELS.iter().map(|&s| s.to_string()).collect(),
             OLLAMA_DOC_URL,
-            vec![ConfigKey::new(
-                "OLLAMA_HOST",
-                false,
-                false,
-                Some(OLLAMA_HOST),
-            )],
+            vec![
+                ConfigKey::new("OLLAMA_HOST", false, false, Some(OLLAMA_HOST)),
+                ConfigKey::new("OLLAMA_KEEP_ALIVE", false, false, None),
+            ],
         )
     }
 
@@ -82,6 +307,35 @@ impl Provider for OllamaProvider {
         self.model.clone()
     }
 
+    async fn complete_stream(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<CompletionStream<'_>, ProviderError> {
+        let payload = create_request(
+            &self.model,
+            system,
+            messages,
+            tools,
+            &super::utils::ImageFormat::OpenAi,
+        )?;
+        let request = self.native_chat_request(&payload, true);
+
+        // Only the initial request is retried, errors part way through the stream are returned
+        let response = self.post(&request).await?;
+
+        Ok(Box::pin(async_stream::try_stream! {
+            let mut chunks = ndjson_objects(response);
+            let mut state = ChunkState::default();
+            while let Some(chunk) = chunks.next().await {
+                for delta in state.process_chunk(&chunk?)? {
+                    yield delta;
+                }
+            }
+        }))
+    }
+
     #[tracing::instrument(
         skip(self, system, messages, tools),
         fields(model_config, input, output, input_tokens, output_tokens, total_tokens)
@@ -100,13 +354,191 @@ impl Provider for OllamaProvider {
             &super::utils::ImageFormat::OpenAi,
         )?;
 
-        let response = self.post(payload.clone()).await?;
+        let request = self.native_chat_request(&payload, false);
+        let response: Value = self.post(&request).await?.json().await.map_err(|_| {
+            ProviderError::RequestFailed("Response body is not valid JSON".to_string())
+        })?;
+
+        // An unstreamed response is a single chunk holding the whole message
+        let mut state = ChunkState::default();
+        let mut accumulator = MessageAccumulator::default();
+        for delta in state.process_chunk(&response)? {
+            accumulator.push(delta);
+        }
+        let (message, usage) = accumulator.finish();
+        let usage = usage
+            .unwrap_or_else(|| ProviderUsage::new(self.model.model_name.clone(), Usage::default()));
+        super::utils::emit_debug_trace(self, &request, &response, &usage.usage);
+        Ok((message, usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::tool::ToolCall;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn provider(host: String, keep_alive: Option<&str>) -> OllamaProvider {
+        OllamaProvider {
+            client: Client::new(),
+            host,
+            model: ModelConfig::new("qwen2.5".to_string()),
+            keep_alive: keep_alive.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_complete_stream() {
+        let chunks = [
+            json!({"model": "qwen2.5", "message": {"role": "assistant", "content": "Let"}, "done": false}),
+            json!({"model": "qwen2.5", "message": {"role": "assistant", "content": " me "}, "done": false}),
+            json!({"model": "qwen2.5", "message": {"role": "assistant", "content": "check."}, "done": false}),
+            json!({"model": "qwen2.5", "message": {"role": "assistant", "content": "", "tool_calls": [
+                {"function": {"name": "developer__shell", "arguments": {"command": "ls"}}}
+            ]}, "done": false}),
+            json!({"model": "qwen2.5", "message": {"role": "assistant", "content": ""}, "done": true,
+                "done_reason": "stop", "prompt_eval_count": 26, "eval_count": 9}),
+        ];
+        let body: String = chunks.iter().map(|chunk| format!("{}\n", chunk)).collect();
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_partial_json(
+                json!({"stream": true, "keep_alive": "30m"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/x-ndjson"))
+            .mount(&server)
+            .await;
+
+        let provider = provider(server.uri(), Some("30m"));
+        let mut stream = provider
+            .complete_stream("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap();
+
+        let mut texts = Vec::new();
+        let mut accumulator = MessageAccumulator::default();
+        while let Some(delta) = stream.next().await {
+            let delta = delta.unwrap();
+            if let Some(text) = delta.as_text() {
+                texts.push(text.to_string());
+            }
+            accumulator.push(delta);
+        }
+        assert_eq!(texts, vec!["Let", " me ", "check."]);
+
+        let (message, usage) = accumulator.finish();
+        assert_eq!(message.content[0], MessageContent::text("Let me check."));
+        let request = message.content[1].as_tool_request().unwrap();
+        assert!(request.id.starts_with("call_"));
+        assert_eq!(
+            request.tool_call.as_ref().unwrap(),
+            &ToolCall::new("developer__shell", json!({"command": "ls"}))
+        );
+        assert_eq!(message.stop_reason, Some(StopReason::ToolUse));
+        let usage = usage.unwrap();
+        assert_eq!(usage.usage.input_tokens, Some(26));
+        assert_eq!(usage.usage.total_tokens, Some(35));
+    }
+
+    #[tokio::test]
+    async fn test_complete() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_partial_json(
+                json!({"stream": false, "keep_alive": "30m"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "model": "qwen2.5",
+                "message": {"role": "assistant", "content": "", "tool_calls": [
+                    {"function": {"name": "developer__shell", "arguments": {"command": "ls"}}}
+                ]},
+                "done": true,
+                "done_reason": "stop",
+                "prompt_eval_count": 26,
+                "eval_count": 9
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let provider = provider(server.uri(), Some("30m"));
+        let messages = [Message::user().with_text("Hi")];
+        let (first, usage) = provider
+            .complete("You are helpful", &messages, &[])
+            .await
+            .unwrap();
+        let (second, _) = provider
+            .complete("You are helpful", &messages, &[])
+            .await
+            .unwrap();
+
+        assert_eq!(first.stop_reason, Some(StopReason::ToolUse));
+        assert_eq!(usage.usage.total_tokens, Some(35));
+        // Each turn's tool calls get their own ids, so later turns never reuse earlier ones
+        let id = |message: &Message| message.content[0].as_tool_request().unwrap().id.clone();
+        assert_ne!(id(&first), id(&second));
+    }
+
+    #[tokio::test]
+    async fn test_complete_stream_reports_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .respond_with(
+                ResponseTemplate::new(404)
+                    .set_body_json(json!({"error": "model 'qwen9' not found"})),
+            )
+            .mount(&server)
+            .await;
+
+        let provider = provider(server.uri(), None);
+        let result = provider
+            .complete_stream("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await;
+        let Err(ProviderError::RequestFailed(message)) = result else {
+            panic!("expected the missing model to be reported");
+        };
+        assert!(message.contains("model 'qwen9' not found"));
+    }
+
+    #[test]
+    fn test_native_chat_request() {
+        let provider = provider(OLLAMA_HOST.to_string(), Some("-1"));
+        let model = ModelConfig::new("qwen2.5".to_string()).with_max_tokens(Some(100));
+        let messages = [
+            Message::user()
+                .with_text("What is this?")
+                .with_image("aGVsbG8=", "image/png"),
+            Message::assistant().with_tool_request(
+                "call_1",
+                Ok(ToolCall::new("developer__shell", json!({"command": "ls"}))),
+            ),
+        ];
+        let payload = create_request(
+            &model,
+            "system",
+            &messages,
+            &[],
+            &super::super::utils::ImageFormat::OpenAi,
+        )
+        .unwrap();
 
-        // Parse response
-        let message = response_to_message(response.clone())?;
-        let usage = get_usage(&response)?;
-        let model = get_model(&response);
-        super::utils::emit_debug_trace(self, &payload, &response, &usage);
-        Ok((message, ProviderUsage::new(model, usage)))
+        let request = provider.native_chat_request(&payload, true);
+        assert_eq!(request["keep_alive"], json!(-1));
+        assert_eq!(request["options"], json!({"num_predict": 100}));
+        assert_eq!(
+            request["messages"][0],
+            json!({"role": "system", "content": "system"})
+        );
+        assert_eq!(request["messages"][1]["images"], json!(["aGVsbG8="]));
+        assert_eq!(
+            request["messages"][2]["tool_calls"][0]["function"]["arguments"],
+            json!({"command": "ls"})
+        );
     }
 }
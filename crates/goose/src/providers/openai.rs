@@ -1,13 +1,22 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use reqwest::Client;
-use serde_json::Value;
+use futures::StreamExt;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde_json::{json, Value};
 use std::time::Duration;
 
-use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage};
+use super::base::{
+    CompletionDelta, CompletionStream, ConfigKey, Provider, ProviderMetadata, ProviderUsage,
+};
 use super::errors::ProviderError;
-use super::formats::openai::{create_request, get_usage, response_to_message};
-use super::utils::{emit_debug_trace, get_model, handle_response_openai_compat, ImageFormat};
+use super::formats::openai::{create_request, get_usage, response_to_message, StreamState};
+use super::rate_limit::RateLimitInfo;
+use super::retry::send_with_retry;
+use super::sse::sse_events;
+use super::utils::{
+    check_payload_size, emit_debug_trace, get_model, handle_response_openai_compat, ImageFormat,
+    DEFAULT_MAX_REQUEST_BYTES,
+};
 use crate::message::Message;
 use crate::model::ModelConfig;
 use mcp_core::tool::Tool;
@@ -24,6 +33,8 @@ pub const OPEN_AI_KNOWN_MODELS: &[&str] = &[
 
 pub const OPEN_AI_DOC_URL: &str = "https://platform.openai.com/docs/models";
 
+pub const OPEN_AI_HOST: &str = "https://api.openai.com";
+
 #[derive(Debug, serde::Serialize)]
 pub struct OpenAiProvider {
     #[serde(skip)]
@@ -46,7 +57,7 @@ impl OpenAiProvider {
         let api_key: String = config.get_secret("OPENAI_API_KEY")?;
         let host: String = config
             .get("OPENAI_HOST")
-            .unwrap_or_else(|_| "https://api.openai.com".to_string());
+            .unwrap_or_else(|_| OPEN_AI_HOST.to_string());
         let client = Client::builder()
             .timeout(Duration::from_secs(600))
             .build()?;
@@ -59,16 +70,27 @@ impl OpenAiProvider {
         })
     }
 
-    async fn post(&self, payload: Value) -> Result<Value, ProviderError> {
-        let url = format!("{}/v1/chat/completions", self.host.trim_end_matches('/'));
+    /// Whether the host is OpenAI's own API rather than a compatible one, which may reject
+    /// options it does not know such as `stream_options`
+    fn is_openai_host(&self) -> bool {
+        let host_of = |url: &str| {
+            url::Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+        };
+        host_of(&self.host).is_some_and(|host| Some(host) == host_of(OPEN_AI_HOST))
+    }
 
-        let response = self
-            .client
+    fn request(&self, payload: &Value) -> RequestBuilder {
+        let url = format!("{}/v1/chat/completions", self.host.trim_end_matches('/'));
+        self.client
             .post(&url)
             .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&payload)
-            .send()
-            .await?;
+            .json(payload)
+    }
+
+    async fn post(&self, payload: Value) -> Result<(Value, Option<RateLimitInfo>), ProviderError> {
+        let response = send_with_retry(&self.model.retry, self.request(&payload)).await?;
 
         handle_response_openai_compat(response).await
     }
@@ -89,7 +111,7 @@ impl Provider for OpenAiProvider {
             OPEN_AI_DOC_URL,
             vec![
                 ConfigKey::new("OPENAI_API_KEY", true, true, None),
-                ConfigKey::new("OPENAI_HOST", false, false, Some("https://api.openai.com")),
+                ConfigKey::new("OPENAI_HOST", false, false, Some(OPEN_AI_HOST)),
             ],
         )
     }
@@ -98,6 +120,82 @@ impl Provider for OpenAiProvider {
         self.model.clone()
     }
 
+    #[tracing::instrument(
+        skip(self, system, messages, tools),
+        fields(model_config, input, output, input_tokens, output_tokens, total_tokens)
+    )]
+    async fn complete_stream(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<CompletionStream<'_>, ProviderError> {
+        let mut payload =
+            create_request(&self.model, system, messages, tools, &ImageFormat::OpenAi)?;
+        payload["stream"] = json!(true);
+        // Without this OpenAI does not report the usage of streamed responses
+        if self.is_openai_host() {
+            payload["stream_options"] = json!({"include_usage": true});
+        }
+
+        check_payload_size(
+            &payload,
+            self.model
+                .max_request_bytes
+                .unwrap_or(DEFAULT_MAX_REQUEST_BYTES),
+        )?;
+
+        // Only the initial request is retried, errors part way through the stream are returned
+        let response = send_with_retry(&self.model.retry, self.request(&payload)).await?;
+        let rate_limit = RateLimitInfo::from_headers(response.headers());
+        if response.status() != StatusCode::OK {
+            // Error responses aren't streamed, so they are handled like any other request
+            handle_response_openai_compat(response).await?;
+            return Err(ProviderError::RequestFailed(
+                "Unexpected response to streamed request".to_string(),
+            ));
+        }
+
+        let span = tracing::Span::current();
+        Ok(Box::pin(async_stream::try_stream! {
+            let _span_guard = span.enter();
+            let mut events = sse_events(response);
+            let mut state = StreamState::default();
+            // The chunks as received, traced like the response of an unstreamed request
+            let mut chunks = Vec::new();
+            while let Some(event) = events.next().await {
+                let event = event?;
+                if event.data == "[DONE]" {
+                    break;
+                }
+                let chunk: Value = serde_json::from_str(&event.data).map_err(|e| {
+                    ProviderError::RequestFailed(format!("Invalid chunk in streamed response: {}", e))
+                })?;
+                for delta in state.process_chunk(&chunk) {
+                    yield delta;
+                }
+                chunks.push(chunk);
+            }
+            let deltas = state.finish();
+            let usage = deltas
+                .iter()
+                .find_map(|delta| match delta {
+                    CompletionDelta::Usage(usage) => Some(usage.usage.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            emit_debug_trace(self, &payload, &Value::Array(chunks), &usage);
+            for delta in deltas {
+                yield match delta {
+                    CompletionDelta::Usage(usage) => CompletionDelta::Usage(
+                        usage.with_rate_limit(rate_limit.clone()).with_cost(),
+                    ),
+                    delta => delta,
+                };
+            }
+        }))
+    }
+
     #[tracing::instrument(
         skip(self, system, messages, tools),
         fields(model_config, input, output, input_tokens, output_tokens, total_tokens)
@@ -110,14 +208,189 @@ impl Provider for OpenAiProvider {
     ) -> Result<(Message, ProviderUsage), ProviderError> {
         let payload = create_request(&self.model, system, messages, tools, &ImageFormat::OpenAi)?;
 
+        check_payload_size(
+            &payload,
+            self.model
+                .max_request_bytes
+                .unwrap_or(DEFAULT_MAX_REQUEST_BYTES),
+        )?;
+
         // Make request
-        let response = self.post(payload.clone()).await?;
+        let (response, rate_limit) = self.post(payload.clone()).await?;
 
         // Parse response
         let message = response_to_message(response.clone())?;
         let usage = get_usage(&response)?;
         let model = get_model(&response);
         emit_debug_trace(self, &payload, &response, &usage);
-        Ok((message, ProviderUsage::new(model, usage)))
+        Ok((
+            message,
+            ProviderUsage::new(model, usage)
+                .with_rate_limit(rate_limit)
+                .with_cost(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::MessageContent;
+    use crate::providers::base::MessageAccumulator;
+    use serde_json::json;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_rate_limit_headers_are_surfaced() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-limit-requests", "60")
+                    .insert_header("x-ratelimit-remaining-requests", "3")
+                    .insert_header("x-ratelimit-reset-requests", "20s")
+                    .insert_header("x-ratelimit-limit-tokens", "150000")
+                    .insert_header("x-ratelimit-remaining-tokens", "149000")
+                    .insert_header("x-ratelimit-reset-tokens", "6m0s")
+                    .set_body_json(json!({
+                        "id": "chatcmpl-123",
+                        "object": "chat.completion",
+                        "model": "gpt-4o",
+                        "choices": [{
+                            "index": 0,
+                            "message": {"role": "assistant", "content": "Hello!"},
+                            "finish_reason": "stop"
+                        }],
+                        "usage": {"prompt_tokens": 10, "completion_tokens": 2, "total_tokens": 12}
+                    })),
+            )
+            .mount(&server)
+            .await;
+
+        let provider = OpenAiProvider {
+            client: Client::new(),
+            host: server.uri(),
+            api_key: "test-key".to_string(),
+            model: ModelConfig::new("gpt-4o".to_string()),
+        };
+        let (message, usage) = provider
+            .complete("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap();
+        assert_eq!(message.as_concat_text(), "Hello!");
+
+        // 10 input tokens at $2.50 and 2 output tokens at $10.00 per million
+        assert!((usage.cost.unwrap() - 0.000045).abs() < 1e-12);
+
+        let rate_limit = usage.rate_limit.unwrap();
+        assert_eq!(rate_limit.requests_limit, Some(60));
+        assert_eq!(rate_limit.requests_remaining, Some(3));
+        assert_eq!(rate_limit.requests_reset, Some(Duration::from_secs(20)));
+        assert_eq!(rate_limit.tokens_remaining, Some(149000));
+        assert_eq!(rate_limit.tokens_reset, Some(Duration::from_secs(360)));
+
+        // Only the request quota is running low, so only it is waited on and reported
+        assert_eq!(rate_limit.suggested_delay(), Some(Duration::from_secs(20)));
+        assert_eq!(
+            rate_limit.warning().unwrap(),
+            "Approaching rate limit: 3 of 60 requests remaining, resets in 20s"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_complete_forwards_max_tokens_and_stop() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(
+                json!({"max_tokens": 64, "stop": ["###"]}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "model": "gpt-4o",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Short"},
+                    "finish_reason": "stop"
+                }],
+                "usage": {"prompt_tokens": 10, "completion_tokens": 1, "total_tokens": 11}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = OpenAiProvider {
+            client: Client::new(),
+            host: server.uri(),
+            api_key: "test-key".to_string(),
+            model: ModelConfig::new("gpt-4o".to_string())
+                .with_max_tokens(Some(64))
+                .with_stop(Some(vec!["###".to_string()])),
+        };
+        let (message, _) = provider
+            .complete("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap();
+        assert_eq!(message.as_concat_text(), "Short");
+    }
+
+    #[tokio::test]
+    async fn test_complete_stream() {
+        let chunks = [
+            json!({"model": "gpt-4o", "choices": [{"index": 0, "delta": {"role": "assistant", "content": "Hel"}}]}),
+            json!({"model": "gpt-4o", "choices": [{"index": 0, "delta": {"content": "lo!"}}]}),
+            json!({"model": "gpt-4o", "choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}),
+            json!({"model": "gpt-4o", "choices": [], "usage": {"prompt_tokens": 10, "completion_tokens": 2, "total_tokens": 12}}),
+        ];
+        let mut body: String = chunks
+            .iter()
+            .map(|chunk| format!("data: {}\n\n", chunk))
+            .collect();
+        body.push_str("data: [DONE]\n\n");
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(json!({"stream": true})))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-remaining-requests", "3")
+                    .set_body_raw(body, "text/event-stream"),
+            )
+            .mount(&server)
+            .await;
+
+        let provider = OpenAiProvider {
+            client: Client::new(),
+            host: server.uri(),
+            api_key: "test-key".to_string(),
+            model: ModelConfig::new("gpt-4o".to_string()),
+        };
+        // Compatible hosts are not sent options only OpenAI supports
+        assert!(!provider.is_openai_host());
+        let mut stream = provider
+            .complete_stream("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap();
+
+        let mut texts = Vec::new();
+        let mut accumulator = MessageAccumulator::default();
+        while let Some(delta) = stream.next().await {
+            let delta = delta.unwrap();
+            if let Some(text) = delta.as_text() {
+                texts.push(text.to_string());
+            }
+            accumulator.push(delta);
+        }
+        assert_eq!(texts, vec!["Hel", "lo!"]);
+
+        let (message, usage) = accumulator.finish();
+        assert_eq!(message.content, vec![MessageContent::text("Hello!")]);
+        assert!(!message.is_truncated());
+        let usage = usage.unwrap();
+        assert_eq!(usage.usage.total_tokens, Some(12));
+        assert!(usage.cost.is_some());
+        assert_eq!(usage.rate_limit.unwrap().requests_remaining, Some(3));
     }
 }
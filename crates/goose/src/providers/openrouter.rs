@@ -1,15 +1,22 @@
 use anyhow::{Error, Result};
 use async_trait::async_trait;
 use reqwest::Client;
-use serde_json::{json, Value};
+use serde_json::Value;
 use std::time::Duration;
 
 use super::base::{ConfigKey, Provider, ProviderMetadata, ProviderUsage};
 use super::errors::ProviderError;
-use super::utils::{emit_debug_trace, get_model, handle_response_openai_compat};
+use super::rate_limit::RateLimitInfo;
+use super::retry::send_with_retry;
+use super::utils::{
+    check_payload_size, emit_debug_trace, get_model, handle_response_openai_compat,
+    DEFAULT_MAX_REQUEST_BYTES,
+};
 use crate::message::Message;
 use crate::model::ModelConfig;
-use crate::providers::formats::openai::{create_request, get_usage, response_to_message};
+use crate::providers::formats::openai::{
+    apply_anthropic_prompt_cache, create_request, get_usage, response_to_message,
+};
 use mcp_core::tool::Tool;
 
 pub const OPENROUTER_DEFAULT_MODEL: &str = "anthropic/claude-3.5-sonnet";
@@ -55,83 +62,26 @@ impl OpenRouterProvider {
         })
     }
 
-    async fn post(&self, payload: Value) -> Result<Value, ProviderError> {
+    async fn post(&self, payload: Value) -> Result<(Value, Option<RateLimitInfo>), ProviderError> {
         let url = format!(
             "{}/api/v1/chat/completions",
             self.host.trim_end_matches('/')
         );
 
-        let response = self
+        let request = self
             .client
             .post(&url)
             .header("Content-Type", "application/json")
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("HTTP-Referer", "https://github.com/block/goose")
             .header("X-Title", "Goose")
-            .json(&payload)
-            .send()
-            .await?;
+            .json(&payload);
+        let response = send_with_retry(&self.model.retry, request).await?;
 
         handle_response_openai_compat(response).await
     }
 }
 
-/// Update the request when using anthropic model.
-/// For anthropic model, we can enable prompt caching to save cost. Since openrouter is the OpenAI compatible
-/// endpoint, we need to modify the open ai request to have anthropic cache control field.
-fn update_request_for_anthropic(original_payload: &Value) -> Value {
-    let mut payload = original_payload.clone();
-
-    if let Some(messages_spec) = payload
-        .as_object_mut()
-        .and_then(|obj| obj.get_mut("messages"))
-        .and_then(|messages| messages.as_array_mut())
-    {
-        // Add "cache_control" to the last and second-to-last "user" messages.
-        // During each turn, we mark the final message with cache_control so the conversation can be
-        // incrementally cached. The second-to-last user message is also marked for caching with the
-        // cache_control parameter, so that this checkpoint can read from the previous cache.
-        let mut user_count = 0;
-        for message in messages_spec.iter_mut().rev() {
-            if message.get("role") == Some(&json!("user")) {
-                if let Some(content) = message.get_mut("content") {
-                    if let Some(content_str) = content.as_str() {
-                        *content = json!([{
-                            "type": "text",
-                            "text": content_str,
-                            "cache_control": { "type": "ephemeral" }
-                        }]);
-                    }
-                }
-                user_count += 1;
-                if user_count >= 2 {
-                    break;
-                }
-            }
-        }
-
-        // Update the system message to have cache_control field.
-        if let Some(system_message) = messages_spec
-            .iter_mut()
-            .find(|msg| msg.get("role") == Some(&json!("system")))
-        {
-            if let Some(content) = system_message.get_mut("content") {
-                if let Some(content_str) = content.as_str() {
-                    *system_message = json!({
-                        "role": "system",
-                        "content": [{
-                            "type": "text",
-                            "text": content_str,
-                            "cache_control": { "type": "ephemeral" }
-                        }]
-                    });
-                }
-            }
-        }
-    }
-    payload
-}
-
 fn create_request_based_on_model(
     model_config: &ModelConfig,
     system: &str,
@@ -146,11 +96,13 @@ fn create_request_based_on_model(
         &super::utils::ImageFormat::OpenAi,
     )?;
 
-    if model_config
-        .model_name
-        .starts_with(OPENROUTER_MODEL_PREFIX_ANTHROPIC)
+    // Anthropic models on OpenRouter support prompt caching, so it is on for them by default
+    if model_config.supports_cache_control
+        || model_config
+            .model_name
+            .starts_with(OPENROUTER_MODEL_PREFIX_ANTHROPIC)
     {
-        payload = update_request_for_anthropic(&payload);
+        apply_anthropic_prompt_cache(&mut payload);
     }
 
     Ok(payload)
@@ -198,14 +150,96 @@ impl Provider for OpenRouterProvider {
         // Create the base payload
         let payload = create_request_based_on_model(&self.model, system, messages, tools)?;
 
+        check_payload_size(
+            &payload,
+            self.model
+                .max_request_bytes
+                .unwrap_or(DEFAULT_MAX_REQUEST_BYTES),
+        )?;
+
         // Make request
-        let response = self.post(payload.clone()).await?;
+        let (response, rate_limit) = self.post(payload.clone()).await?;
 
         // Parse response
         let message = response_to_message(response.clone())?;
         let usage = get_usage(&response)?;
         let model = get_model(&response);
         emit_debug_trace(self, &payload, &response, &usage);
-        Ok((message, ProviderUsage::new(model, usage)))
+        Ok((
+            message,
+            ProviderUsage::new(model, usage)
+                .with_rate_limit(rate_limit)
+                .with_cost(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_rate_limit_headers_are_surfaced() {
+        let reset = chrono::Utc::now() + chrono::Duration::seconds(30);
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-limit", "20")
+                    .insert_header("x-ratelimit-remaining", "1")
+                    .insert_header("x-ratelimit-reset", reset.timestamp_millis().to_string())
+                    .set_body_json(json!({
+                        "id": "gen-123",
+                        "model": "anthropic/claude-3.5-sonnet",
+                        "choices": [{
+                            "index": 0,
+                            "message": {"role": "assistant", "content": "Hello!"},
+                            "finish_reason": "stop"
+                        }],
+                        "usage": {"prompt_tokens": 10, "completion_tokens": 2, "total_tokens": 12}
+                    })),
+            )
+            .mount(&server)
+            .await;
+
+        let provider = OpenRouterProvider {
+            client: Client::new(),
+            host: server.uri(),
+            api_key: "test-key".to_string(),
+            model: ModelConfig::new("anthropic/claude-3.5-sonnet".to_string()),
+        };
+        let (_, usage) = provider
+            .complete("You are helpful", &[Message::user().with_text("Hi")], &[])
+            .await
+            .unwrap();
+
+        let rate_limit = usage.rate_limit.unwrap();
+        assert_eq!(rate_limit.requests_limit, Some(20));
+        assert_eq!(rate_limit.requests_remaining, Some(1));
+        assert!(rate_limit.requests_reset.unwrap() <= Duration::from_secs(30));
+        assert!(rate_limit.is_approaching_limit());
+    }
+
+    #[test]
+    fn test_prompt_cache_for_anthropic_models() {
+        let messages = [Message::user().with_text("Hi")];
+        let is_cached = |model_config: &ModelConfig| {
+            let payload =
+                create_request_based_on_model(model_config, "system", &messages, &[]).unwrap();
+            payload["messages"][1]["content"][0]["cache_control"] == json!({"type": "ephemeral"})
+        };
+
+        assert!(is_cached(&ModelConfig::new(
+            "anthropic/claude-3.5-sonnet".to_string()
+        )));
+        assert!(!is_cached(&ModelConfig::new("openai/gpt-4o".to_string())));
+        // A Claude model under another name can opt in
+        assert!(is_cached(
+            &ModelConfig::new("custom/claude".to_string()).with_cache_control(true)
+        ));
     }
 }
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+use super::base::Usage;
+
+/// What a model charges, in US dollars per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPricing {
+    const fn new(input_per_million: f64, output_per_million: f64) -> Self {
+        Self {
+            input_per_million,
+            output_per_million,
+        }
+    }
+
+    /// The cost of `usage` in US dollars, if the provider reported its input or output tokens
+    pub fn cost(&self, usage: &Usage) -> Option<f64> {
+        if usage.input_tokens.is_none() && usage.output_tokens.is_none() {
+            return None;
+        }
+        let tokens = |count: Option<i32>| count.unwrap_or(0).max(0) as f64;
+        Some(
+            (tokens(usage.input_tokens) * self.input_per_million
+                + tokens(usage.output_tokens) * self.output_per_million)
+                / 1_000_000.0,
+        )
+    }
+}
+
+/// The list price of a known model, matched by name so that dated versions and the names used
+/// by gateways (such as `openai/gpt-4o` on OpenRouter) are found too
+pub fn model_pricing_for(model_name: &str) -> Option<ModelPricing> {
+    let name = model_name.to_lowercase();
+    let base = name.rsplit('/').next().unwrap_or(&name);
+    let pricing = match base {
+        // OpenAI models, https://openai.com/api/pricing
+        n if n.contains("gpt-4o-mini") => ModelPricing::new(0.15, 0.60),
+        n if n.contains("gpt-4o") => ModelPricing::new(2.50, 10.00),
+        n if n.contains("gpt-4.1-nano") => ModelPricing::new(0.10, 0.40),
+        n if n.contains("gpt-4.1-mini") => ModelPricing::new(0.40, 1.60),
+        n if n.contains("gpt-4.1") => ModelPricing::new(2.00, 8.00),
+        n if n.contains("gpt-4.5") => ModelPricing::new(75.00, 150.00),
+        n if n.contains("gpt-4-turbo") || n.contains("gpt-4-vision") => {
+            ModelPricing::new(10.00, 30.00)
+        }
+        n if n.contains("gpt-4-1106") || n.contains("gpt-4-0125") => {
+            ModelPricing::new(10.00, 30.00)
+        }
+        n if n.contains("gpt-4-32k") => ModelPricing::new(60.00, 120.00),
+        n if n.contains("gpt-4") => ModelPricing::new(30.00, 60.00),
+        n if n.contains("gpt-3.5-turbo") => ModelPricing::new(0.50, 1.50),
+        n if n.starts_with("o1-mini") || n.starts_with("o3-mini") || n.starts_with("o4-mini") => {
+            ModelPricing::new(1.10, 4.40)
+        }
+        n if n.starts_with("o1") => ModelPricing::new(15.00, 60.00),
+        n if n.starts_with("o3") => ModelPricing::new(2.00, 8.00),
+
+        // Anthropic models, https://www.anthropic.com/pricing#anthropic-api
+        n if n.contains("claude-3-5-haiku") || n.contains("claude-3.5-haiku") => {
+            ModelPricing::new(0.80, 4.00)
+        }
+        n if n.contains("haiku") && n.contains("claude") => ModelPricing::new(0.25, 1.25),
+        n if n.contains("opus") && n.contains("claude") => ModelPricing::new(15.00, 75.00),
+        n if n.contains("sonnet") && n.contains("claude") => ModelPricing::new(3.00, 15.00),
+        _ => return None,
+    };
+    Some(pricing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_cost(model: &str, usage: Usage, expected: f64) {
+        let cost = model_pricing_for(model).unwrap().cost(&usage).unwrap();
+        assert!((cost - expected).abs() < 1e-9, "{}: {}", model, cost);
+    }
+
+    #[test]
+    fn test_model_pricing_for() {
+        let usage = Usage::new(Some(1_000_000), Some(1_000_000), Some(2_000_000));
+        assert_cost("gpt-4o-2024-08-06", usage.clone(), 12.50);
+        assert_cost("gpt-4o-mini", usage.clone(), 0.75);
+        assert_cost("openai/gpt-4.1-mini", usage.clone(), 2.00);
+        assert_cost("gpt-4-turbo", usage.clone(), 40.00);
+        assert_cost("gpt-4.5-preview", usage.clone(), 225.00);
+        assert_cost("gpt-4-vision-preview", usage.clone(), 40.00);
+        assert_cost("gpt-4-0125-preview", usage.clone(), 40.00);
+        assert_cost("gpt-4-32k", usage.clone(), 180.00);
+        assert_cost("gpt-4-0613", usage.clone(), 90.00);
+        assert_cost("o3-mini", usage.clone(), 5.50);
+        assert_cost("claude-3-5-sonnet-latest", usage.clone(), 18.00);
+        assert_cost("anthropic/claude-3.5-haiku", usage.clone(), 4.80);
+        assert_cost("claude-3-haiku-20240307", usage, 1.50);
+
+        assert!(model_pricing_for("llama3.3").is_none());
+        assert!(model_pricing_for("unknown-model").is_none());
+    }
+
+    #[test]
+    fn test_cost_of_usage() {
+        let pricing = model_pricing_for("gpt-4o").unwrap();
+        assert_cost(
+            "gpt-4o",
+            Usage::new(Some(1200), Some(300), Some(1500)),
+            0.006,
+        );
+        assert_eq!(
+            pricing.cost(&Usage::new(Some(1000), None, None)),
+            Some(0.0025)
+        );
+        assert_eq!(pricing.cost(&Usage::default()), None);
+    }
+}
@@ -0,0 +1,271 @@
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Remaining quota at or below this fraction of the limit counts as approaching the limit
+const APPROACHING_LIMIT_FRACTION: f64 = 0.1;
+
+/// Upper bound on how long the agent waits before a request to stay under the limit
+pub const MAX_PROACTIVE_DELAY: Duration = Duration::from_secs(30);
+
+/// Rate limit quota reported by a provider in the headers of a response
+///
+/// Providers report these on successful responses too, which lets the agent slow down before
+/// it is rejected with a 429 rather than only reacting afterwards.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitInfo {
+    pub requests_limit: Option<u64>,
+    pub requests_remaining: Option<u64>,
+    /// Time until the request quota is fully replenished
+    pub requests_reset: Option<Duration>,
+    pub tokens_limit: Option<u64>,
+    pub tokens_remaining: Option<u64>,
+    /// Time until the token quota is fully replenished
+    pub tokens_reset: Option<Duration>,
+    /// How long the provider asked us to wait before the next request
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitInfo {
+    /// Parse the rate limit headers of a response, returning None if there are none
+    ///
+    /// Supports the `x-ratelimit-*` headers used by OpenAI compatible APIs, the unsuffixed
+    /// request quota headers of OpenRouter, the `anthropic-ratelimit-*` headers and
+    /// `retry-after`.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let number = |name: &str| header(name).and_then(|v| v.trim().parse::<u64>().ok());
+        let reset = |name: &str| header(name).and_then(parse_reset);
+
+        let info = Self {
+            requests_limit: number("x-ratelimit-limit-requests")
+                .or_else(|| number("anthropic-ratelimit-requests-limit"))
+                .or_else(|| number("x-ratelimit-limit")),
+            requests_remaining: number("x-ratelimit-remaining-requests")
+                .or_else(|| number("anthropic-ratelimit-requests-remaining"))
+                .or_else(|| number("x-ratelimit-remaining")),
+            requests_reset: reset("x-ratelimit-reset-requests")
+                .or_else(|| reset("anthropic-ratelimit-requests-reset"))
+                .or_else(|| reset("x-ratelimit-reset")),
+            tokens_limit: number("x-ratelimit-limit-tokens")
+                .or_else(|| number("anthropic-ratelimit-tokens-limit")),
+            tokens_remaining: number("x-ratelimit-remaining-tokens")
+                .or_else(|| number("anthropic-ratelimit-tokens-remaining")),
+            tokens_reset: reset("x-ratelimit-reset-tokens")
+                .or_else(|| reset("anthropic-ratelimit-tokens-reset")),
+            retry_after: number("retry-after").map(Duration::from_secs),
+        };
+
+        (info != Self::default()).then_some(info)
+    }
+
+    fn requests_running_low(&self) -> bool {
+        running_low(self.requests_remaining, self.requests_limit)
+    }
+
+    fn tokens_running_low(&self) -> bool {
+        running_low(self.tokens_remaining, self.tokens_limit)
+    }
+
+    /// Whether the remaining quota is low enough that requests should slow down
+    pub fn is_approaching_limit(&self) -> bool {
+        self.retry_after.is_some() || self.requests_running_low() || self.tokens_running_low()
+    }
+
+    /// How long to wait before the next request to avoid being rate limited, if at all
+    ///
+    /// Waits for the quota that is running low to reset, capped at [`MAX_PROACTIVE_DELAY`].
+    pub fn suggested_delay(&self) -> Option<Duration> {
+        let delay = self.retry_after.or_else(|| {
+            let requests = self.requests_reset.filter(|_| self.requests_running_low());
+            let tokens = self.tokens_reset.filter(|_| self.tokens_running_low());
+            requests.max(tokens)
+        })?;
+        Some(delay.min(MAX_PROACTIVE_DELAY))
+    }
+
+    /// A warning to show the user when approaching the rate limit
+    pub fn warning(&self) -> Option<String> {
+        if !self.is_approaching_limit() {
+            return None;
+        }
+
+        let mut details = Vec::new();
+        if self.requests_running_low() {
+            details.push(quota_detail(
+                "requests",
+                self.requests_remaining,
+                self.requests_limit,
+                self.requests_reset,
+            ));
+        }
+        if self.tokens_running_low() {
+            details.push(quota_detail(
+                "tokens",
+                self.tokens_remaining,
+                self.tokens_limit,
+                self.tokens_reset,
+            ));
+        }
+        if let Some(retry_after) = self.retry_after {
+            details.push(format!(
+                "the provider asked to retry after {}s",
+                retry_after.as_secs()
+            ));
+        }
+        Some(format!("Approaching rate limit: {}", details.join(", ")))
+    }
+}
+
+fn running_low(remaining: Option<u64>, limit: Option<u64>) -> bool {
+    match (remaining, limit) {
+        (Some(remaining), Some(limit)) if limit > 0 => {
+            (remaining as f64) <= (limit as f64) * APPROACHING_LIMIT_FRACTION
+        }
+        (Some(remaining), _) => remaining == 0,
+        _ => false,
+    }
+}
+
+fn quota_detail(
+    name: &str,
+    remaining: Option<u64>,
+    limit: Option<u64>,
+    reset: Option<Duration>,
+) -> String {
+    let mut detail = match (remaining, limit) {
+        (Some(remaining), Some(limit)) => format!("{} of {} {} remaining", remaining, limit, name),
+        (Some(remaining), None) => format!("{} {} remaining", remaining, name),
+        _ => format!("{} running low", name),
+    };
+    if let Some(reset) = reset {
+        detail.push_str(&format!(", resets in {:.0}s", reset.as_secs_f64().ceil()));
+    }
+    detail
+}
+
+/// Parse a reset value, either a duration like `6m0s` or `20ms` (OpenAI, Groq), an RFC 3339
+/// timestamp (Anthropic) or a Unix timestamp in milliseconds (OpenRouter)
+fn parse_reset(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        let remaining = timestamp.with_timezone(&Utc) - Utc::now();
+        return Some(remaining.to_std().unwrap_or_default());
+    }
+    // Small numbers are not timestamps, as 10^12 milliseconds is already September 2001
+    if let Some(timestamp) = value
+        .parse::<i64>()
+        .ok()
+        .filter(|&millis| millis >= 1_000_000_000_000)
+        .and_then(DateTime::from_timestamp_millis)
+    {
+        let remaining = timestamp - Utc::now();
+        return Some(remaining.to_std().unwrap_or_default());
+    }
+
+    let component = Regex::new(r"(\d+(?:\.\d+)?)(ms|h|m|s)").expect("valid regex");
+    let mut total = 0.0;
+    let mut matched_len = 0;
+    for captures in component.captures_iter(value) {
+        let amount: f64 = captures[1].parse().ok()?;
+        total += match &captures[2] {
+            "h" => amount * 3600.0,
+            "m" => amount * 60.0,
+            "s" => amount,
+            _ => amount / 1000.0,
+        };
+        matched_len += captures[0].len();
+    }
+    // Reject values with anything besides duration components
+    (matched_len > 0 && matched_len == value.len()).then(|| Duration::from_secs_f64(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_parse_reset_durations() {
+        assert_eq!(parse_reset("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_reset("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_reset("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(
+            parse_reset("1h2m3.5s"),
+            Some(Duration::from_secs_f64(3723.5))
+        );
+        assert_eq!(parse_reset("soon"), None);
+
+        let in_a_minute = (Utc::now() + chrono::Duration::seconds(60)).to_rfc3339();
+        let reset = parse_reset(&in_a_minute).unwrap();
+        assert!(reset > Duration::from_secs(55) && reset <= Duration::from_secs(60));
+        assert_eq!(parse_reset("2000-01-01T00:00:00Z"), Some(Duration::ZERO));
+
+        let in_a_minute = (Utc::now() + chrono::Duration::seconds(60)).timestamp_millis();
+        let reset = parse_reset(&in_a_minute.to_string()).unwrap();
+        assert!(reset > Duration::from_secs(55) && reset <= Duration::from_secs(60));
+        assert_eq!(parse_reset("60"), None);
+    }
+
+    #[test]
+    fn test_from_headers_openai() {
+        let info = RateLimitInfo::from_headers(&headers(&[
+            ("x-ratelimit-limit-requests", "100"),
+            ("x-ratelimit-remaining-requests", "99"),
+            ("x-ratelimit-reset-requests", "600ms"),
+            ("x-ratelimit-limit-tokens", "10000"),
+            ("x-ratelimit-remaining-tokens", "500"),
+            ("x-ratelimit-reset-tokens", "12s"),
+        ]))
+        .unwrap();
+
+        assert_eq!(info.requests_remaining, Some(99));
+        assert_eq!(info.tokens_limit, Some(10000));
+        assert_eq!(info.tokens_reset, Some(Duration::from_secs(12)));
+        assert!(info.is_approaching_limit());
+        assert_eq!(info.suggested_delay(), Some(Duration::from_secs(12)));
+        assert_eq!(
+            info.warning().unwrap(),
+            "Approaching rate limit: 500 of 10000 tokens remaining, resets in 12s"
+        );
+    }
+
+    #[test]
+    fn test_from_headers_anthropic_and_retry_after() {
+        let info = RateLimitInfo::from_headers(&headers(&[
+            ("anthropic-ratelimit-requests-limit", "50"),
+            ("anthropic-ratelimit-requests-remaining", "40"),
+            ("retry-after", "120"),
+        ]))
+        .unwrap();
+
+        assert_eq!(info.requests_limit, Some(50));
+        assert_eq!(info.retry_after, Some(Duration::from_secs(120)));
+        assert_eq!(info.suggested_delay(), Some(MAX_PROACTIVE_DELAY));
+    }
+
+    #[test]
+    fn test_plenty_of_quota() {
+        assert_eq!(RateLimitInfo::from_headers(&HeaderMap::new()), None);
+
+        let info = RateLimitInfo::from_headers(&headers(&[
+            ("x-ratelimit-limit-requests", "100"),
+            ("x-ratelimit-remaining-requests", "80"),
+            ("x-ratelimit-reset-requests", "1s"),
+        ]))
+        .unwrap();
+        assert!(!info.is_approaching_limit());
+        assert_eq!(info.suggested_delay(), None);
+        assert_eq!(info.warning(), None);
+    }
+}
@@ -0,0 +1,296 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use super::base::{CompletionDelta, CompletionStream, Provider, ProviderMetadata, ProviderUsage};
+use super::errors::ProviderError;
+use super::router::Tier;
+use crate::config::Config;
+use crate::message::Message;
+use crate::model::ModelConfig;
+use mcp_core::tool::Tool;
+
+/// Default longest time a request waits in the queue before it fails instead
+pub const DEFAULT_MAX_QUEUE_WAIT: Duration = Duration::from_secs(60);
+
+/// The limiter shared by every provider created in this process, if a limit is configured
+///
+/// Configured with `GOOSE_REQUESTS_PER_MINUTE` and `GOOSE_TOKENS_PER_MINUTE`, and
+/// `GOOSE_RATE_LIMIT_MAX_WAIT` for how many seconds a request may queue.
+static GLOBAL_LIMITER: LazyLock<Option<Arc<RateLimiter>>> = LazyLock::new(|| {
+    let config = Config::global();
+    let requests_per_minute: Option<u32> = config.get("GOOSE_REQUESTS_PER_MINUTE").ok();
+    let tokens_per_minute: Option<u32> = config.get("GOOSE_TOKENS_PER_MINUTE").ok();
+    if requests_per_minute.is_none() && tokens_per_minute.is_none() {
+        return None;
+    }
+
+    let max_wait = config
+        .get("GOOSE_RATE_LIMIT_MAX_WAIT")
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_MAX_QUEUE_WAIT);
+    Some(Arc::new(RateLimiter::per_minute(
+        requests_per_minute,
+        tokens_per_minute,
+        max_wait,
+    )))
+});
+
+/// The process wide rate limiter, if one is configured
+pub fn global_limiter() -> Option<Arc<RateLimiter>> {
+    GLOBAL_LIMITER.clone()
+}
+
+/// A bucket holding up to `capacity` units that refills continuously
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    per_second: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket that refills `capacity` units every `period`
+    fn new(capacity: u32, period: Duration) -> Self {
+        Self {
+            capacity: capacity as f64,
+            available: capacity as f64,
+            per_second: capacity as f64 / period.as_secs_f64(),
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// How long until `amount` units are available
+    fn wait_for(&self, amount: f64) -> Duration {
+        if self.available >= amount {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((amount - self.available) / self.per_second)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+}
+
+/// Limits the rate of requests and tokens across every provider sharing it
+///
+/// Each request takes one unit from the request bucket before it is sent. Tokens are only
+/// known once the response arrives, so they are taken afterwards and can leave the token
+/// bucket in debt, which holds back the following requests until it has refilled. Requests
+/// that have to wait are queued in order, and fail with `RateLimitExceeded` if the wait
+/// would be longer than `max_wait`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    buckets: Mutex<Buckets>,
+    max_wait: Duration,
+}
+
+impl RateLimiter {
+    /// Allow up to `requests_per_minute` requests and `tokens_per_minute` tokens, where None
+    /// leaves that dimension unlimited
+    pub fn per_minute(
+        requests_per_minute: Option<u32>,
+        tokens_per_minute: Option<u32>,
+        max_wait: Duration,
+    ) -> Self {
+        let minute = Duration::from_secs(60);
+        Self {
+            buckets: Mutex::new(Buckets {
+                requests: requests_per_minute.map(|limit| TokenBucket::new(limit, minute)),
+                tokens: tokens_per_minute.map(|limit| TokenBucket::new(limit, minute)),
+            }),
+            max_wait,
+        }
+    }
+
+    /// Wait until a request may be sent, then take it from the request bucket
+    pub async fn acquire(&self) -> Result<(), ProviderError> {
+        // The lock is held while waiting, so queued requests go out in the order they arrived
+        let mut buckets = self.buckets.lock().await;
+
+        let now = Instant::now();
+        let mut wait = Duration::ZERO;
+        if let Some(requests) = &mut buckets.requests {
+            requests.refill(now);
+            wait = wait.max(requests.wait_for(1.0));
+        }
+        if let Some(tokens) = &mut buckets.tokens {
+            tokens.refill(now);
+            wait = wait.max(tokens.wait_for(0.0));
+        }
+
+        if wait > self.max_wait {
+            return Err(ProviderError::RateLimitExceeded(format!(
+                "The configured rate limit would delay this request by {:.0}s, longer than the {}s allowed",
+                wait.as_secs_f64().ceil(),
+                self.max_wait.as_secs()
+            )));
+        }
+        if !wait.is_zero() {
+            tracing::debug!("Rate limit reached, delaying request by {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+
+        if let Some(requests) = &mut buckets.requests {
+            requests.refill(Instant::now());
+            requests.available -= 1.0;
+        }
+        Ok(())
+    }
+
+    /// Take the tokens used by a completed request from the token bucket
+    pub async fn record(&self, usage: &ProviderUsage) {
+        let mut buckets = self.buckets.lock().await;
+        if let Some(tokens) = &mut buckets.tokens {
+            tokens.refill(Instant::now());
+            tokens.available -= usage.usage.total_tokens.unwrap_or(0).max(0) as f64;
+        }
+    }
+}
+
+/// A provider whose requests are gated by a [`RateLimiter`]
+pub struct RateLimitedProvider {
+    inner: Box<dyn Provider + Send + Sync>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitedProvider {
+    pub fn new(inner: Box<dyn Provider + Send + Sync>, limiter: Arc<RateLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+#[async_trait]
+impl Provider for RateLimitedProvider {
+    fn metadata() -> ProviderMetadata {
+        ProviderMetadata::empty()
+    }
+
+    async fn complete(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<(Message, ProviderUsage), ProviderError> {
+        self.limiter.acquire().await?;
+        let (message, usage) = self.inner.complete(system, messages, tools).await?;
+        self.limiter.record(&usage).await;
+        Ok((message, usage))
+    }
+
+    async fn complete_stream(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<CompletionStream<'_>, ProviderError> {
+        self.limiter.acquire().await?;
+        let stream = self.inner.complete_stream(system, messages, tools).await?;
+        let limiter = Arc::clone(&self.limiter);
+        Ok(Box::pin(stream.then(move |delta| {
+            let limiter = Arc::clone(&limiter);
+            async move {
+                if let Ok(CompletionDelta::Usage(usage)) = &delta {
+                    limiter.record(usage).await;
+                }
+                delta
+            }
+        })))
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.inner.get_model_config()
+    }
+
+    fn set_route_hint(&self, tier: Option<Tier>) {
+        self.inner.set_route_hint(tier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::Usage;
+    use crate::testing::MockProvider;
+
+    // Each completion uses 50 tokens
+    fn echo_provider() -> Box<dyn Provider> {
+        Box::new(MockProvider::new("echo").with_usage(Usage::new(Some(40), Some(10), Some(50))))
+    }
+
+    fn limited(requests: Option<TokenBucket>, tokens: Option<TokenBucket>) -> RateLimitedProvider {
+        let limiter = RateLimiter {
+            buckets: Mutex::new(Buckets { requests, tokens }),
+            max_wait: Duration::from_secs(5),
+        };
+        RateLimitedProvider::new(echo_provider(), Arc::new(limiter))
+    }
+
+    async fn complete(provider: &RateLimitedProvider) -> Result<(), ProviderError> {
+        provider
+            .complete("system", &[Message::user().with_text("hi")], &[])
+            .await
+            .map(|_| ())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_beyond_rate_are_delayed() {
+        // Two requests every 400ms, so the third has to wait for a refill of 200ms
+        let provider = limited(Some(TokenBucket::new(2, Duration::from_millis(400))), None);
+
+        let start = Instant::now();
+        complete(&provider).await.unwrap();
+        complete(&provider).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        complete(&provider).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_debt_delays_next_request() {
+        // 100 tokens every 200ms, each request uses 50 tokens
+        let provider = limited(
+            None,
+            Some(TokenBucket::new(100, Duration::from_millis(200))),
+        );
+
+        let start = Instant::now();
+        for _ in 0..2 {
+            complete(&provider).await.unwrap();
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // The bucket is empty, not in debt, so this one goes straight out and borrows
+        complete(&provider).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // Now 50 tokens in debt, which takes 100ms to pay back
+        complete(&provider).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_error_when_wait_exceeds_max() {
+        let limiter = RateLimiter::per_minute(Some(1), None, Duration::from_secs(1));
+        let provider = RateLimitedProvider::new(echo_provider(), Arc::new(limiter));
+
+        complete(&provider).await.unwrap();
+        let err = complete(&provider).await.unwrap_err();
+        assert!(matches!(err, ProviderError::RateLimitExceeded(_)));
+    }
+}
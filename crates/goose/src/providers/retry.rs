@@ -0,0 +1,192 @@
+use rand::Rng;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::errors::ProviderError;
+use super::rate_limit::RateLimitInfo;
+use crate::config::Config;
+
+/// Default number of times a rate limited or failed request is retried
+pub const DEFAULT_MAX_RETRIES: usize = 3;
+
+/// Default backoff before the first retry, doubled each attempt
+pub const DEFAULT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+/// Default cap on the backoff between retries
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// How provider requests are retried when rate limited (429) or the server fails (5xx)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Retries after the first attempt, so 0 sends each request once
+    pub max_retries: usize,
+    /// Backoff before the first retry when the response has no `retry-after`
+    pub initial_delay: Duration,
+    /// The longest backoff between retries, however many attempts have been made
+    pub max_delay: Duration,
+    /// Whether to add up to half again of each backoff at random, so that concurrent clients
+    /// don't retry in lockstep
+    pub jitter: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_delay: DEFAULT_INITIAL_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Read the retry settings from the configuration, using the defaults for any not set
+    ///
+    /// The keys are `GOOSE_MAX_RETRIES`, `GOOSE_RETRY_INITIAL_DELAY_MS`,
+    /// `GOOSE_RETRY_MAX_DELAY_MS` and `GOOSE_RETRY_JITTER`.
+    pub fn from_config(config: &Config) -> Self {
+        let defaults = Self::default();
+        Self {
+            max_retries: config
+                .get("GOOSE_MAX_RETRIES")
+                .unwrap_or(defaults.max_retries),
+            initial_delay: config
+                .get("GOOSE_RETRY_INITIAL_DELAY_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.initial_delay),
+            max_delay: config
+                .get("GOOSE_RETRY_MAX_DELAY_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.max_delay),
+            jitter: config.get("GOOSE_RETRY_JITTER").unwrap_or(defaults.jitter),
+        }
+    }
+
+    /// The backoff before the retry following `attempt`, counted from 0
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let backoff = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt as u32))
+            .min(self.max_delay);
+        if self.jitter {
+            backoff + backoff.mul_f64(rand::thread_rng().gen_range(0.0..0.5))
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Send a request, retrying it while it is rate limited (429) or the server fails (5xx)
+///
+/// Waits for the `retry-after` header when the response has one, up to the `max_delay` of
+/// `retry`, and otherwise backs off as set by `retry`. The last response is returned whatever its status, for the provider to turn
+/// into an error, so only failures to send at all are errors here.
+pub async fn send_with_retry(
+    retry: &RetryConfig,
+    request: RequestBuilder,
+) -> Result<Response, ProviderError> {
+    let mut attempt = 0;
+    loop {
+        // A request whose body can't be cloned, such as a stream, can only be sent once
+        let Some(this_request) = request.try_clone() else {
+            return Ok(request.send().await?);
+        };
+        let response = this_request.send().await?;
+
+        let status = response.status();
+        let retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+        if !retryable || attempt >= retry.max_retries {
+            return Ok(response);
+        }
+
+        let delay = RateLimitInfo::from_headers(response.headers())
+            .and_then(|rate_limit| rate_limit.retry_after)
+            .map(|retry_after| retry_after.min(retry.max_delay))
+            .unwrap_or_else(|| retry.delay(attempt));
+        attempt += 1;
+        tracing::warn!(
+            "Provider request failed with status {}, retrying in {:?} (attempt {}/{})",
+            status,
+            delay,
+            attempt,
+            retry.max_retries
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_delay_grows_exponentially_up_to_max() {
+        let retry = RetryConfig {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+            ..Default::default()
+        };
+        for attempt in 0..3 {
+            let base = Duration::from_millis(100) * 2u32.pow(attempt as u32);
+            let delay = retry.delay(attempt);
+            assert!(delay >= base && delay < base + base / 2);
+        }
+        let capped = retry.delay(10);
+        assert!(capped >= retry.max_delay && capped < retry.max_delay * 3 / 2);
+
+        let retry = RetryConfig {
+            jitter: false,
+            ..retry
+        };
+        assert_eq!(retry.delay(2), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_from_config() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let config = Config::new(temp_file.path(), "goose-test").unwrap();
+        assert_eq!(RetryConfig::from_config(&config), RetryConfig::default());
+
+        config
+            .set("GOOSE_RETRY_MAX_DELAY_MS", serde_json::json!(5000))
+            .unwrap();
+        config
+            .set("GOOSE_RETRY_JITTER", serde_json::json!(false))
+            .unwrap();
+        let retry = RetryConfig::from_config(&config);
+        assert_eq!(retry.max_delay, Duration::from_secs(5));
+        assert!(!retry.jitter);
+        assert_eq!(retry.max_retries, DEFAULT_MAX_RETRIES);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_is_capped_by_max_delay() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "3600"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let retry = RetryConfig {
+            max_delay: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let request = reqwest::Client::new().get(server.uri());
+        let response =
+            tokio::time::timeout(Duration::from_secs(5), send_with_retry(&retry, request))
+                .await
+                .expect("the retry waited for the whole retry-after")
+                .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
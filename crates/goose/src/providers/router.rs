@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+use super::base::{CompletionStream, Provider, ProviderMetadata, ProviderUsage};
+use super::errors::ProviderError;
+use crate::message::{Message, MessageContent};
+use crate::model::ModelConfig;
+use mcp_core::role::Role;
+use mcp_core::tool::Tool;
+
+/// Length of a user message, in characters, above which a turn is routed to the strong provider
+pub const DEFAULT_LONG_MESSAGE_CHARS: usize = 2000;
+
+/// Which of the routed providers handles a turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    /// The default provider, meant to be a cheaper model for simple turns
+    Cheap,
+    /// The provider for hard turns, meant to be a more capable model
+    Strong,
+}
+
+/// A provider that sends each turn to a cheap or a strong provider
+///
+/// A turn uses the tier set by [`Provider::set_route_hint`] if there is one. Otherwise a turn goes to the
+/// strong provider when the latest user message contains a code block or is longer than
+/// `long_message_chars`, and to the cheap provider when not. The model config is the cheap
+/// provider's, so context limits are sized for the smaller model.
+pub struct RoutingProvider {
+    cheap: Box<dyn Provider + Send + Sync>,
+    strong: Box<dyn Provider + Send + Sync>,
+    hint: Mutex<Option<Tier>>,
+    long_message_chars: usize,
+}
+
+impl RoutingProvider {
+    pub fn new(
+        cheap: Box<dyn Provider + Send + Sync>,
+        strong: Box<dyn Provider + Send + Sync>,
+    ) -> Self {
+        Self {
+            cheap,
+            strong,
+            hint: Mutex::new(None),
+            long_message_chars: DEFAULT_LONG_MESSAGE_CHARS,
+        }
+    }
+
+    pub fn with_long_message_chars(mut self, chars: usize) -> Self {
+        self.long_message_chars = chars;
+        self
+    }
+
+    /// Estimate how hard the turn in `messages` is from its latest user message
+    pub fn estimate_tier(&self, messages: &[Message]) -> Tier {
+        let text = messages
+            .iter()
+            .rev()
+            .filter(|message| message.role == Role::User)
+            .find_map(|message| {
+                let texts: Vec<&str> = message
+                    .content
+                    .iter()
+                    .filter_map(MessageContent::as_text)
+                    .collect();
+                (!texts.is_empty()).then(|| texts.join("\n"))
+            })
+            .unwrap_or_default();
+
+        if text.contains("```") || text.chars().count() > self.long_message_chars {
+            Tier::Strong
+        } else {
+            Tier::Cheap
+        }
+    }
+
+    fn route(&self, messages: &[Message]) -> &(dyn Provider + Send + Sync) {
+        let hint = self.hint.lock().unwrap().take();
+        let tier = hint.unwrap_or_else(|| self.estimate_tier(messages));
+        tracing::debug!("Routing turn to the {:?} provider", tier);
+        match tier {
+            Tier::Cheap => self.cheap.as_ref(),
+            Tier::Strong => self.strong.as_ref(),
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for RoutingProvider {
+    fn metadata() -> ProviderMetadata {
+        ProviderMetadata::empty()
+    }
+
+    async fn complete(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<(Message, ProviderUsage), ProviderError> {
+        self.route(messages).complete(system, messages, tools).await
+    }
+
+    async fn complete_stream(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<CompletionStream<'_>, ProviderError> {
+        self.route(messages)
+            .complete_stream(system, messages, tools)
+            .await
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.cheap.get_model_config()
+    }
+
+    fn set_route_hint(&self, tier: Option<Tier>) {
+        *self.hint.lock().unwrap() = tier;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockProvider;
+
+    async fn answered_by(router: &RoutingProvider, text: &str) -> String {
+        let (_, usage) = router
+            .complete("system", &[Message::user().with_text(text)], &[])
+            .await
+            .unwrap();
+        usage.model
+    }
+
+    #[tokio::test]
+    async fn test_routes_by_hint_and_heuristic() {
+        let router = RoutingProvider::new(
+            Box::new(MockProvider::new("cheap")),
+            Box::new(MockProvider::new("strong")),
+        )
+        .with_long_message_chars(100);
+        assert_eq!(router.get_model_config().model_name, "cheap");
+
+        assert_eq!(answered_by(&router, "What time is it?").await, "cheap");
+
+        // The hint applies to the next turn only
+        router.set_route_hint(Some(Tier::Strong));
+        assert_eq!(answered_by(&router, "What time is it?").await, "strong");
+        assert_eq!(answered_by(&router, "What time is it?").await, "cheap");
+
+        assert_eq!(
+            answered_by(
+                &router,
+                "Why does this panic?\n```rust\nNone::<u8>.unwrap();\n```"
+            )
+            .await,
+            "strong"
+        );
+        assert_eq!(answered_by(&router, &"word ".repeat(30)).await, "strong");
+
+        router.set_route_hint(Some(Tier::Cheap));
+        assert_eq!(answered_by(&router, &"word ".repeat(30)).await, "cheap");
+    }
+}
@@ -0,0 +1,132 @@
+use futures::stream::{BoxStream, StreamExt};
+use reqwest::Response;
+
+use super::errors::ProviderError;
+
+/// A server-sent event from a streamed response
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    /// The event type, if the server named one
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Incrementally splits a byte stream into server-sent events
+///
+/// Chunks may end anywhere, including in the middle of a line or a multi-byte character, so
+/// bytes are buffered until a full line is available.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseDecoder {
+    /// Add the next chunk of the body, returning any events it completes
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            events.extend(self.process_line(line.trim_end_matches(['\n', '\r'])));
+        }
+        events
+    }
+
+    /// Flush the last event when the body ends without a trailing blank line
+    pub fn finish(mut self) -> Option<SseEvent> {
+        let line = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+        let event = self.process_line(line.trim_end_matches('\r'));
+        event.or_else(|| self.dispatch())
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        // Lines starting with a colon are comments, often used as keep-alives
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(SseEvent {
+            event,
+            data: std::mem::take(&mut self.data).join("\n"),
+        })
+    }
+}
+
+/// Stream the server-sent events of a response body
+pub fn sse_events(response: Response) -> BoxStream<'static, Result<SseEvent, ProviderError>> {
+    let mut body = response.bytes_stream();
+    Box::pin(async_stream::try_stream! {
+        let mut decoder = SseDecoder::default();
+        while let Some(chunk) = body.next().await {
+            for event in decoder.push(&chunk?) {
+                yield event;
+            }
+        }
+        if let Some(event) = decoder.finish() {
+            yield event;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_events_split_across_chunks() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"event: message_start\nda").is_empty());
+
+        let events =
+            decoder.push(b"ta: {\"a\": 1}\n\n: keep-alive\n\ndata: first\r\ndata: second\n\n");
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    event: Some("message_start".to_string()),
+                    data: "{\"a\": 1}".to_string(),
+                },
+                SseEvent {
+                    event: None,
+                    data: "first\nsecond".to_string(),
+                },
+            ]
+        );
+
+        assert!(decoder.push(b"data: [DONE]").is_empty());
+        assert_eq!(decoder.finish().unwrap().data, "[DONE]");
+    }
+
+    #[test]
+    fn test_decode_multi_byte_character_split_across_chunks() {
+        let bytes = "data: héllo\n\n".as_bytes();
+        let split = bytes.iter().position(|&b| b > 127).unwrap() + 1;
+
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(&bytes[..split]).is_empty());
+        let events = decoder.push(&bytes[split..]);
+        assert_eq!(events[0].data, "héllo");
+    }
+}
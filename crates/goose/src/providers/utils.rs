@@ -1,4 +1,5 @@
 use super::base::Usage;
+use super::rate_limit::RateLimitInfo;
 use anyhow::Result;
 use regex::Regex;
 use reqwest::{Response, StatusCode};
@@ -37,13 +38,17 @@ pub fn convert_image(image: &ImageContent, image_format: &ImageFormat) -> Value
 /// Handle response from OpenAI compatible endpoints
 /// Error codes: https://platform.openai.com/docs/guides/error-codes
 /// Context window exceeded: https://community.openai.com/t/help-needed-tackling-context-length-limits-in-openai-models/617543
-pub async fn handle_response_openai_compat(response: Response) -> Result<Value, ProviderError> {
+/// Parse an OpenAI compatible response, with the rate limit quota reported in its headers
+pub async fn handle_response_openai_compat(
+    response: Response,
+) -> Result<(Value, Option<RateLimitInfo>), ProviderError> {
     let status = response.status();
+    let rate_limit = RateLimitInfo::from_headers(response.headers());
     // Try to parse the response body as JSON (if applicable)
     let payload: Option<Value> = response.json().await.ok();
 
     match status {
-        StatusCode::OK => payload.ok_or_else( || ProviderError::RequestFailed("Response body is not valid JSON".to_string()) ),
+        StatusCode::OK => payload.map(|payload| (payload, rate_limit)).ok_or_else( || ProviderError::RequestFailed("Response body is not valid JSON".to_string()) ),
         StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
             Err(ProviderError::Authentication(format!("Authentication failed. Please ensure your API keys are valid and have the required permissions. \
                 Status: {}. Response: {:?}", status, payload)))
@@ -137,6 +142,64 @@ pub fn unescape_json_values(value: &Value) -> Value {
     }
 }
 
+/// Request body cap used when neither the model config nor the provider sets one
+pub const DEFAULT_MAX_REQUEST_BYTES: usize = 32 * 1024 * 1024;
+
+/// Check the serialized size of a request payload against `max_bytes` before it is sent
+///
+/// Oversized requests are otherwise rejected by the provider or proxy with an opaque network
+/// error, so this reports which part of the request is responsible: the system prompt (which
+/// carries extension instructions), the tool definitions, or the conversation messages
+/// (which carry resource contents and tool results).
+pub fn check_payload_size(payload: &Value, max_bytes: usize) -> Result<(), ProviderError> {
+    let total = serialized_len(payload);
+    if total <= max_bytes {
+        return Ok(());
+    }
+
+    let mut system = 0;
+    let mut tools = 0;
+    let mut messages = 0;
+    if let Some(object) = payload.as_object() {
+        for (key, value) in object {
+            match key.as_str() {
+                "system" | "systemInstruction" => system += serialized_len(value),
+                "tools" => tools += serialized_len(value),
+                // OpenAI compatible formats send the system prompt as the first message
+                "messages" | "contents" => {
+                    for message in value.as_array().into_iter().flatten() {
+                        if message.get("role").and_then(|r| r.as_str()) == Some("system") {
+                            system += serialized_len(message);
+                        } else {
+                            messages += serialized_len(message);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let (component, size) = [
+        ("system prompt", system),
+        ("tool definitions", tools),
+        ("messages", messages),
+    ]
+    .into_iter()
+    .max_by_key(|(_, size)| *size)
+    .expect("components are not empty");
+
+    Err(ProviderError::RequestTooLarge(format!(
+        "The request body is {} bytes, which exceeds the limit of {} bytes. \
+         The largest part is the {} at {} bytes (system prompt: {}, tool definitions: {}, messages: {}).",
+        total, max_bytes, component, size, system, tools, messages
+    )))
+}
+
+fn serialized_len(value: &Value) -> usize {
+    serde_json::to_vec(value).map(|v| v.len()).unwrap_or(0)
+}
+
 pub fn emit_debug_trace<T: serde::Serialize>(
     model_config: &T,
     payload: &impl serde::Serialize,
@@ -228,4 +291,39 @@ mod tests {
         let unescaped_value = unescape_json_values(&value);
         assert_eq!(unescaped_value, json!({"text": "Hello World"}));
     }
+
+    #[test]
+    fn test_check_payload_size_within_limit() {
+        let payload = json!({"messages": [{"role": "user", "content": "hi"}]});
+        assert!(check_payload_size(&payload, DEFAULT_MAX_REQUEST_BYTES).is_ok());
+    }
+
+    #[test]
+    fn test_check_payload_size_reports_oversized_component() {
+        let huge_schema = "x".repeat(10_000);
+        let tools: Vec<Value> = (0..20)
+            .map(|i| json!({"name": format!("tool_{}", i), "description": huge_schema}))
+            .collect();
+        let payload = json!({
+            "system": "You are a helpful assistant",
+            "messages": [{"role": "user", "content": "hello"}],
+            "tools": tools,
+        });
+
+        let err = check_payload_size(&payload, 50_000).unwrap_err();
+        assert!(matches!(err, ProviderError::RequestTooLarge(_)));
+        let message = err.to_string();
+        assert!(message.contains("exceeds the limit of 50000 bytes"));
+        assert!(message.contains("The largest part is the tool definitions"));
+
+        // The system prompt inside OpenAI style messages is attributed separately
+        let payload = json!({
+            "messages": [
+                {"role": "system", "content": "s".repeat(1_000)},
+                {"role": "user", "content": "u".repeat(5_000)},
+            ],
+        });
+        let message = check_payload_size(&payload, 2_000).unwrap_err().to_string();
+        assert!(message.contains("The largest part is the messages"));
+    }
 }
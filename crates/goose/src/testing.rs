@@ -0,0 +1,282 @@
+//! A configurable provider and extension client for unit tests
+//!
+//! Both are cheap to clone and clones share their state, so a test can hand a clone to the
+//! code under test and inspect what happened through the original.
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use mcp_client::client::{ClientCapabilities, ClientInfo, Error as ClientError, McpClientTrait};
+use mcp_core::protocol::{
+    CallToolResult, Implementation, InitializeResult, ListResourcesResult, ListToolsResult,
+    ReadResourceResult, ResourcesCapability, ServerCapabilities, INVALID_REQUEST,
+};
+use mcp_core::resource::{Resource, ResourceContents};
+use mcp_core::{Content, Tool};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::message::Message;
+use crate::model::ModelConfig;
+use crate::providers::base::{Provider, ProviderMetadata, ProviderUsage, Usage};
+use crate::providers::errors::ProviderError;
+
+/// A request made to a [`MockProvider`]
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub system: String,
+    pub messages: Vec<Message>,
+    pub tools: Vec<Tool>,
+}
+
+/// A provider that answers with its scripted replies in turn, then with the name of its model
+#[derive(Clone)]
+pub struct MockProvider {
+    model_config: ModelConfig,
+    usage: Usage,
+    replies: Arc<Mutex<VecDeque<Message>>>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockProvider {
+    pub fn new(model_name: &str) -> Self {
+        Self::from_config(ModelConfig::new(model_name.to_string()))
+    }
+
+    pub fn from_config(model_config: ModelConfig) -> Self {
+        Self {
+            model_config,
+            usage: Usage::default(),
+            replies: Arc::default(),
+            requests: Arc::default(),
+        }
+    }
+
+    /// Answer the next requests with `replies`, one each
+    pub fn with_replies(self, replies: impl IntoIterator<Item = Message>) -> Self {
+        self.replies.lock().unwrap().extend(replies);
+        self
+    }
+
+    /// Report `usage` for every completion
+    pub fn with_usage(mut self, usage: Usage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// The requests made so far, oldest first
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Provider for MockProvider {
+    fn metadata() -> ProviderMetadata {
+        ProviderMetadata::empty()
+    }
+
+    async fn complete(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[Tool],
+    ) -> Result<(Message, ProviderUsage), ProviderError> {
+        self.requests.lock().unwrap().push(MockRequest {
+            system: system.to_string(),
+            messages: messages.to_vec(),
+            tools: tools.to_vec(),
+        });
+        let model_name = &self.model_config.model_name;
+        let message = self
+            .replies
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Message::assistant().with_text(model_name));
+        Ok((
+            message,
+            ProviderUsage::new(model_name.clone(), self.usage.clone()),
+        ))
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.model_config.clone()
+    }
+}
+
+type ToolHandler = dyn Fn(&str, &Value) -> Result<Vec<Content>, ClientError> + Send + Sync;
+
+/// An extension client with the tools and text resources it is configured with
+///
+/// Listed tools answer with no content unless a handler is set, and any other tool is an
+/// error. Resources are listed as active and as last changed long ago.
+#[derive(Clone, Default)]
+pub struct MockClient {
+    name: String,
+    instructions: Option<String>,
+    tools: Vec<Tool>,
+    resources: Vec<(String, String)>,
+    unreadable: Vec<String>,
+    handler: Option<Arc<ToolHandler>>,
+    delay: Duration,
+    running: Arc<AtomicUsize>,
+    most_running: Arc<AtomicUsize>,
+}
+
+impl MockClient {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_instructions(mut self, instructions: &str) -> Self {
+        self.instructions = Some(instructions.to_string());
+        self
+    }
+
+    /// List tools with these names, which do nothing
+    pub fn with_tools<'a>(mut self, names: impl IntoIterator<Item = &'a str>) -> Self {
+        self.tools.extend(
+            names
+                .into_iter()
+                .map(|name| Tool::new(name, format!("The {} tool", name), json!({}))),
+        );
+        self
+    }
+
+    /// Answer calls to the listed tools with `handler`, given the tool name and arguments
+    pub fn with_tool_handler(
+        mut self,
+        handler: impl Fn(&str, &Value) -> Result<Vec<Content>, ClientError> + Send + Sync + 'static,
+    ) -> Self {
+        self.handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Have a text resource at `uri`
+    pub fn with_resource(mut self, uri: &str, text: &str) -> Self {
+        self.resources.push((uri.to_string(), text.to_string()));
+        self
+    }
+
+    /// Time out reading `uri`, as a server that hangs would
+    pub fn with_unreadable_resource(mut self, uri: &str) -> Self {
+        self.unreadable.push(uri.to_string());
+        self
+    }
+
+    /// Take `delay` to answer each tool call
+    pub fn with_tool_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// The most tool calls that ran at the same time, across this client and its clones
+    pub fn most_concurrent_calls(&self) -> usize {
+        self.most_running.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl McpClientTrait for MockClient {
+    async fn initialize(
+        &mut self,
+        _info: ClientInfo,
+        _capabilities: ClientCapabilities,
+    ) -> Result<InitializeResult, ClientError> {
+        Ok(InitializeResult {
+            protocol_version: "2024-11-05".to_string(),
+            capabilities: ServerCapabilities {
+                prompts: None,
+                resources: (!self.resources.is_empty()).then_some(ResourcesCapability {
+                    subscribe: None,
+                    list_changed: None,
+                }),
+                tools: None,
+            },
+            server_info: Implementation {
+                name: self.name.clone(),
+                version: "1.0.0".to_string(),
+            },
+            instructions: self.instructions.clone(),
+        })
+    }
+
+    async fn list_resources(
+        &self,
+        _next_cursor: Option<String>,
+    ) -> Result<ListResourcesResult, ClientError> {
+        let changed = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        Ok(ListResourcesResult {
+            resources: self
+                .resources
+                .iter()
+                .map(|(uri, _)| {
+                    let mut resource = Resource::new(uri, None, None).unwrap().mark_active();
+                    resource.annotations.as_mut().unwrap().timestamp = Some(changed);
+                    resource
+                })
+                .collect(),
+            next_cursor: None,
+        })
+    }
+
+    async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult, ClientError> {
+        if self.unreadable.iter().any(|unreadable| unreadable == uri) {
+            return Err(ClientError::Timeout(Duration::from_secs(30)));
+        }
+        let (_, text) = self
+            .resources
+            .iter()
+            .find(|(resource, _)| resource == uri)
+            .ok_or_else(|| ClientError::RpcError {
+                code: INVALID_REQUEST,
+                message: format!("Resource not found: {}", uri),
+            })?;
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri: uri.to_string(),
+                mime_type: None,
+                text: text.clone(),
+            }],
+        })
+    }
+
+    async fn list_tools(
+        &self,
+        _next_cursor: Option<String>,
+    ) -> Result<ListToolsResult, ClientError> {
+        Ok(ListToolsResult {
+            tools: self.tools.clone(),
+            next_cursor: None,
+        })
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult, ClientError> {
+        if !self.tools.iter().any(|tool| tool.name == name) {
+            return Err(ClientError::RpcError {
+                code: INVALID_REQUEST,
+                message: format!("Tool not found: {}", name),
+            });
+        }
+
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.most_running.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+
+        let content = match &self.handler {
+            Some(handler) => handler(name, &arguments)?,
+            None => Vec::new(),
+        };
+        Ok(CallToolResult {
+            content,
+            is_error: None,
+        })
+    }
+}
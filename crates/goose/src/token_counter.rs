@@ -25,11 +25,11 @@ impl TokenCounter {
         match Self::load_from_embedded(tokenizer_name) {
             Ok(tokenizer) => Self { tokenizer },
             Err(e) => {
-                println!(
-                    "Tokenizer '{}' not found in embedded dir: {}",
-                    tokenizer_name, e
+                tracing::warn!(
+                    tokenizer = tokenizer_name,
+                    error = %e,
+                    "Tokenizer not found in embedded dir, attempting to download it"
                 );
-                println!("Attempting to download tokenizer and load...");
                 // Fallback to download tokenizer and load from disk
                 match Self::download_and_load(tokenizer_name) {
                     Ok(counter) => counter,
@@ -64,7 +64,10 @@ impl TokenCounter {
 
         // If the file doesn't already exist, we download from HF
         if !Path::new(&local_json_path).exists() {
-            eprintln!("Tokenizer file not on disk, downloading…");
+            tracing::info!(
+                tokenizer = tokenizer_name,
+                "Tokenizer file not on disk, downloading"
+            );
             let repo_id = tokenizer_name.replace("--", "/");
             // e.g. "Xenova--llama3-tokenizer" -> "Xenova/llama3-tokenizer"
             Self::download_tokenizer(&repo_id, &local_dir)?;
@@ -278,6 +281,7 @@ mod tests {
                 content: vec![MessageContent::text(
                     "What's the weather like in San Francisco?",
                 )],
+                stop_reason: None,
             },
             Message {
                 role: Role::Assistant,
@@ -285,11 +289,13 @@ mod tests {
                 content: vec![MessageContent::text(
                     "Looks like it's 60 degrees Fahrenheit in San Francisco.",
                 )],
+                stop_reason: None,
             },
             Message {
                 role: Role::User,
                 created: 2,
                 content: vec![MessageContent::text("How about New York?")],
+                stop_reason: None,
             },
         ];
 
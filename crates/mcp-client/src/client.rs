@@ -33,8 +33,8 @@ pub enum Error {
     #[error("Timeout or service not ready")]
     NotReady,
 
-    #[error("Request timed out")]
-    Timeout(#[from] tower::timeout::error::Elapsed),
+    #[error("Request timed out after {0:?}")]
+    Timeout(std::time::Duration),
 
     #[error("Error from mcp-server: {0}")]
     ServerBoxError(BoxError),
@@ -142,16 +142,7 @@ where
         let response_msg = service
             .call(request)
             .await
-            .map_err(|e| Error::McpServerError {
-                server: self
-                    .server_info
-                    .as_ref()
-                    .map(|s| s.name.clone())
-                    .unwrap_or("".to_string()),
-                method: method.to_string(),
-                // we don't need include params because it can be really large
-                source: Box::new(e.into()),
-            })?;
+            .map_err(|e| self.server_error(method, e.into()))?;
 
         match response_msg {
             JsonRpcMessage::Response(JsonRpcResponse {
@@ -208,7 +199,16 @@ where
         service
             .call(notification)
             .await
-            .map_err(|e| Error::McpServerError {
+            .map_err(|e| self.server_error(method, e.into()))?;
+
+        Ok(())
+    }
+
+    /// Attribute an error to the server and method, keeping timeouts as they are
+    fn server_error(&self, method: &str, error: Error) -> Error {
+        match error {
+            Error::Timeout(_) => error,
+            error => Error::McpServerError {
                 server: self
                     .server_info
                     .as_ref()
@@ -216,10 +216,9 @@ where
                     .unwrap_or("".to_string()),
                 method: method.to_string(),
                 // we don't need include params because it can be really large
-                source: Box::new(e.into()),
-            })?;
-
-        Ok(())
+                source: Box::new(error),
+            },
+        }
     }
 
     // Check if the client has completed initialization
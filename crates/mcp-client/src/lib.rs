@@ -3,5 +3,5 @@ pub mod service;
 pub mod transport;
 
 pub use client::{ClientCapabilities, ClientInfo, Error, McpClient, McpClientTrait};
-pub use service::McpService;
-pub use transport::{SseTransport, StdioTransport, Transport, TransportHandle};
+pub use service::{McpService, RequestTimeout, RequestTimeoutLayer, DEFAULT_REQUEST_TIMEOUT};
+pub use transport::{RestartPolicy, SseTransport, StdioTransport, Transport, TransportHandle};
@@ -2,10 +2,26 @@ use futures::future::BoxFuture;
 use mcp_core::protocol::JsonRpcMessage;
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use tower::{timeout::Timeout, Service, ServiceBuilder};
+use std::time::Duration;
+use tower::{Layer, Service, ServiceBuilder, ServiceExt};
+use tracing::warn;
 
+use crate::client::Error as ClientError;
 use crate::transport::{Error, TransportHandle};
 
+/// How long a request may wait for its response by default
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Methods that only read state from the server, so they can safely be sent again
+const IDEMPOTENT_METHODS: &[&str] = &[
+    "ping",
+    "tools/list",
+    "resources/list",
+    "resources/read",
+    "prompts/list",
+    "prompts/get",
+];
+
 /// A wrapper service that implements Tower's Service trait for MCP transport
 #[derive(Clone)]
 pub struct McpService<T: TransportHandle> {
@@ -44,9 +60,207 @@ impl<T> McpService<T>
 where
     T: TransportHandle,
 {
-    pub fn with_timeout(transport: T, timeout: std::time::Duration) -> Timeout<McpService<T>> {
+    pub fn with_timeout(transport: T, timeout: Duration) -> RequestTimeout<McpService<T>> {
         ServiceBuilder::new()
-            .timeout(timeout)
+            .layer(RequestTimeoutLayer::new(timeout))
             .service(McpService::new(transport))
     }
 }
+
+/// Layer that applies a per-request timeout, see [`RequestTimeout`]
+#[derive(Debug, Clone, Copy)]
+pub struct RequestTimeoutLayer {
+    timeout: Duration,
+    max_retries: usize,
+}
+
+impl RequestTimeoutLayer {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            max_retries: 0,
+        }
+    }
+
+    /// Send idempotent requests that time out again, up to `max_retries` more times
+    pub fn with_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+}
+
+impl Default for RequestTimeoutLayer {
+    fn default() -> Self {
+        Self::new(DEFAULT_REQUEST_TIMEOUT)
+    }
+}
+
+impl<S> Layer<S> for RequestTimeoutLayer {
+    type Service = RequestTimeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestTimeout {
+            inner,
+            timeout: self.timeout,
+            max_retries: self.max_retries,
+        }
+    }
+}
+
+/// Fails requests that get no response in time with [`ClientError::Timeout`]
+///
+/// Requests that only read from the server, such as listing tools or reading a resource, can be
+/// retried a bounded number of times after a timeout. Tool calls and notifications are never
+/// sent twice.
+#[derive(Debug, Clone)]
+pub struct RequestTimeout<S> {
+    inner: S,
+    timeout: Duration,
+    max_retries: usize,
+}
+
+impl<S> RequestTimeout<S> {
+    /// Send idempotent requests that time out again, up to `max_retries` more times
+    pub fn with_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+}
+
+fn is_idempotent(message: &JsonRpcMessage) -> bool {
+    matches!(message, JsonRpcMessage::Request(request) if IDEMPOTENT_METHODS.contains(&request.method.as_str()))
+}
+
+impl<S> Service<JsonRpcMessage> for RequestTimeout<S>
+where
+    S: Service<JsonRpcMessage, Response = JsonRpcMessage> + Clone + Send + 'static,
+    S::Error: Into<ClientError> + Send,
+    S::Future: Send,
+{
+    type Response = JsonRpcMessage;
+    type Error = ClientError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: JsonRpcMessage) -> Self::Future {
+        // Use the service that was polled ready for the first attempt, and clones for retries
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let timeout = self.timeout;
+        let retries = if is_idempotent(&request) {
+            self.max_retries
+        } else {
+            0
+        };
+
+        Box::pin(async move {
+            let mut attempt = 0;
+            loop {
+                let response = tokio::time::timeout(timeout, async {
+                    inner.ready().await?.call(request.clone()).await
+                })
+                .await;
+                match response {
+                    Ok(result) => return result.map_err(Into::into),
+                    Err(_) if attempt < retries => {
+                        attempt += 1;
+                        warn!(
+                            "Request timed out after {:?}, retrying ({}/{})",
+                            timeout, attempt, retries
+                        );
+                    }
+                    Err(_) => return Err(ClientError::Timeout(timeout)),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use mcp_core::protocol::{JsonRpcRequest, JsonRpcResponse};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::time::Instant;
+
+    // Mock transport that never answers, counting the messages sent through it
+    #[derive(Clone, Default)]
+    struct SilentTransport {
+        sent: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl TransportHandle for SilentTransport {
+        async fn send(&self, _message: JsonRpcMessage) -> Result<JsonRpcMessage, Error> {
+            self.sent.fetch_add(1, Ordering::SeqCst);
+            futures::future::pending().await
+        }
+    }
+
+    fn request(method: &str) -> JsonRpcMessage {
+        JsonRpcMessage::Request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(1),
+            method: method.to_string(),
+            params: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_request_times_out() {
+        let transport = SilentTransport::default();
+        let timeout = Duration::from_millis(100);
+        let mut service = McpService::with_timeout(transport.clone(), timeout);
+
+        let start = Instant::now();
+        let result = service.call(request("tools/call")).await;
+        let elapsed = start.elapsed();
+
+        assert!(matches!(result, Err(ClientError::Timeout(t)) if t == timeout));
+        assert!(elapsed >= timeout);
+        assert!(elapsed < Duration::from_secs(2));
+        assert_eq!(transport.sent.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_only_idempotent_requests_are_retried() {
+        let transport = SilentTransport::default();
+        let mut service =
+            McpService::with_timeout(transport.clone(), Duration::from_millis(20)).with_retries(2);
+
+        let result = service.call(request("tools/list")).await;
+        assert!(matches!(result, Err(ClientError::Timeout(_))));
+        assert_eq!(transport.sent.load(Ordering::SeqCst), 3);
+
+        // A tool call may have side effects, so it is only sent once
+        let result = service.call(request("tools/call")).await;
+        assert!(matches!(result, Err(ClientError::Timeout(_))));
+        assert_eq!(transport.sent.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_response_within_timeout_is_returned() {
+        #[derive(Clone)]
+        struct EchoTransport;
+
+        #[async_trait]
+        impl TransportHandle for EchoTransport {
+            async fn send(&self, _message: JsonRpcMessage) -> Result<JsonRpcMessage, Error> {
+                Ok(JsonRpcMessage::Response(JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    id: Some(1),
+                    result: Some(serde_json::json!({})),
+                    error: None,
+                }))
+            }
+        }
+
+        let mut service = McpService::with_timeout(EchoTransport, Duration::from_secs(1));
+        let response = service.call(request("tools/list")).await.unwrap();
+        assert!(matches!(response, JsonRpcMessage::Response(_)));
+    }
+}
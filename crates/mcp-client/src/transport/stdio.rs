@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
 
 use async_trait::async_trait;
@@ -9,6 +10,62 @@ use tokio::sync::{mpsc, Mutex};
 
 use super::{send_message, Error, PendingRequests, Transport, TransportHandle, TransportMessage};
 
+/// Whether a `StdioTransport` restarts its child process when it exits unexpectedly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartPolicy {
+    /// The transport closes when the process exits, failing any later requests
+    #[default]
+    Never,
+    /// Respawn the process up to `max_restarts` times, waiting `initial_backoff` before the first
+    /// restart and doubling the wait for each one after, up to `max_backoff`
+    ///
+    /// The initialize handshake sent through the transport is replayed to every new process, so
+    /// the client can keep using it. Requests in flight when the process exits still fail.
+    OnExit {
+        max_restarts: usize,
+        initial_backoff: Duration,
+        max_backoff: Duration,
+    },
+}
+
+impl RestartPolicy {
+    /// Restart up to 5 times, starting with a 500ms wait that doubles up to 30s
+    pub fn on_exit() -> Self {
+        Self::OnExit {
+            max_restarts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+
+    /// How long to wait before the restart after `restarts` earlier ones, or None to give up
+    fn backoff(&self, restarts: usize) -> Option<Duration> {
+        match *self {
+            Self::Never => None,
+            Self::OnExit {
+                max_restarts,
+                initial_backoff,
+                max_backoff,
+            } => (restarts < max_restarts).then(|| {
+                initial_backoff
+                    .saturating_mul(2u32.saturating_pow(restarts as u32))
+                    .min(max_backoff)
+            }),
+        }
+    }
+}
+
+/// Whether a message is part of the initialize handshake, to replay it after a restart
+fn is_handshake(message: &JsonRpcMessage) -> bool {
+    match message {
+        JsonRpcMessage::Request(request) => request.method == "initialize",
+        JsonRpcMessage::Notification(notification) => {
+            notification.method == "notifications/initialized"
+        }
+        _ => false,
+    }
+}
+
 /// A `StdioTransport` uses a child process's stdin/stdout as a communication channel.
 ///
 /// It uses channels for message passing and handles responses asynchronously through a background task.
@@ -20,17 +77,63 @@ pub struct StdioActor {
     stdin: ChildStdin,
     stdout: ChildStdout,
     stderr: ChildStderr,
+    transport: StdioTransport,
+    handshake: Vec<String>,
 }
 
 impl StdioActor {
     pub async fn run(mut self) {
+        let mut restarts = 0;
+        loop {
+            self.run_process().await;
+
+            // Then always try to read stderr before cleaning up
+            let mut stderr_buffer = Vec::new();
+            let err_msg = match self.stderr.read_to_end(&mut stderr_buffer).await {
+                Ok(bytes) if bytes > 0 => String::from_utf8_lossy(&stderr_buffer).to_string(),
+                _ => "Process ended unexpectedly".to_string(),
+            };
+            tracing::info!("Process stderr: {}", err_msg);
+
+            // Requests sent to the old process will never be answered
+            self.pending_requests.clear().await;
+
+            // Restart unless the handle was dropped or the policy gives up
+            let backoff = match self.transport.restart_policy.backoff(restarts) {
+                Some(backoff) if !self.receiver.is_closed() => backoff,
+                _ => {
+                    let _ = self
+                        .error_sender
+                        .send(Error::StdioProcessError(err_msg))
+                        .await;
+                    break;
+                }
+            };
+            restarts += 1;
+            tracing::warn!(
+                "Process exited, restarting it in {:?} (restart {})",
+                backoff,
+                restarts
+            );
+            tokio::time::sleep(backoff).await;
+
+            match self.restart().await {
+                Ok(()) => tracing::info!("Process restarted"),
+                Err(e) => tracing::error!(error = ?e, "Failed to restart process"),
+            }
+        }
+    }
+
+    /// Pass messages to and from the process until it exits or the handle is dropped
+    async fn run_process(&mut self) {
         use tokio::pin;
 
-        let incoming = Self::handle_incoming_messages(self.stdout, self.pending_requests.clone());
+        let incoming = Self::handle_incoming_messages(&mut self.stdout, &self.pending_requests);
         let outgoing = Self::handle_outgoing_messages(
-            self.receiver,
-            self.stdin,
-            self.pending_requests.clone(),
+            &mut self.receiver,
+            &mut self.stdin,
+            &self.pending_requests,
+            &mut self.handshake,
         );
 
         // take ownership of futures for tokio::select
@@ -50,28 +153,28 @@ impl StdioActor {
                 tracing::debug!("Process exited with status: {:?}", status);
             }
         }
+    }
 
-        // Then always try to read stderr before cleaning up
-        let mut stderr_buffer = Vec::new();
-        if let Ok(bytes) = self.stderr.read_to_end(&mut stderr_buffer).await {
-            let err_msg = if bytes > 0 {
-                String::from_utf8_lossy(&stderr_buffer).to_string()
-            } else {
-                "Process ended unexpectedly".to_string()
-            };
-
-            tracing::info!("Process stderr: {}", err_msg);
-            let _ = self
-                .error_sender
-                .send(Error::StdioProcessError(err_msg))
-                .await;
+    /// Spawn a new process and replay the initialize handshake to it
+    async fn restart(&mut self) -> Result<(), Error> {
+        let (process, stdin, stdout, stderr) = self.transport.spawn_process().await?;
+        self._process = process;
+        self.stdin = stdin;
+        self.stdout = stdout;
+        self.stderr = stderr;
+
+        // The response to the replayed initialize request has no pending request and is dropped
+        for line in &self.handshake {
+            self.stdin.write_all(line.as_bytes()).await?;
         }
-
-        // Clean up regardless of which path we took
-        self.pending_requests.clear().await;
+        self.stdin.flush().await?;
+        Ok(())
     }
 
-    async fn handle_incoming_messages(stdout: ChildStdout, pending_requests: Arc<PendingRequests>) {
+    async fn handle_incoming_messages(
+        stdout: &mut ChildStdout,
+        pending_requests: &PendingRequests,
+    ) {
         let mut reader = BufReader::new(stdout);
         let mut line = String::new();
         loop {
@@ -104,9 +207,10 @@ impl StdioActor {
     }
 
     async fn handle_outgoing_messages(
-        mut receiver: mpsc::Receiver<TransportMessage>,
-        mut stdin: ChildStdin,
-        pending_requests: Arc<PendingRequests>,
+        receiver: &mut mpsc::Receiver<TransportMessage>,
+        stdin: &mut ChildStdin,
+        pending_requests: &PendingRequests,
+        handshake: &mut Vec<String>,
     ) {
         while let Some(mut transport_msg) = receiver.recv().await {
             let message_str = match serde_json::to_string(&transport_msg.message) {
@@ -121,6 +225,11 @@ impl StdioActor {
 
             tracing::debug!(message = ?transport_msg.message, "Sending outgoing message");
 
+            let line = format!("{}\n", message_str);
+            if is_handshake(&transport_msg.message) {
+                handshake.push(line.clone());
+            }
+
             if let Some(response_tx) = transport_msg.response_tx.take() {
                 if let JsonRpcMessage::Request(request) = &transport_msg.message {
                     if let Some(id) = &request.id {
@@ -129,10 +238,7 @@ impl StdioActor {
                 }
             }
 
-            if let Err(e) = stdin
-                .write_all(format!("{}\n", message_str).as_bytes())
-                .await
-            {
+            if let Err(e) = stdin.write_all(line.as_bytes()).await {
                 tracing::error!(error = ?e, "Error writing message to child process");
                 pending_requests.clear().await;
                 break;
@@ -176,10 +282,12 @@ impl StdioTransportHandle {
     }
 }
 
+#[derive(Clone)]
 pub struct StdioTransport {
     command: String,
     args: Vec<String>,
     env: HashMap<String, String>,
+    restart_policy: RestartPolicy,
 }
 
 impl StdioTransport {
@@ -192,9 +300,16 @@ impl StdioTransport {
             command: command.into(),
             args,
             env,
+            restart_policy: RestartPolicy::default(),
         }
     }
 
+    /// Set whether the process is restarted when it exits, see [`RestartPolicy`]
+    pub fn with_restart_policy(mut self, restart_policy: RestartPolicy) -> Self {
+        self.restart_policy = restart_policy;
+        self
+    }
+
     async fn spawn_process(&self) -> Result<(Child, ChildStdin, ChildStdout, ChildStderr), Error> {
         let mut process = Command::new(&self.command)
             .envs(&self.env)
@@ -244,6 +359,8 @@ impl Transport for StdioTransport {
             stdin,
             stdout,
             stderr,
+            transport: self.clone(),
+            handshake: Vec::new(),
         };
 
         tokio::spawn(actor.run());
@@ -259,3 +376,112 @@ impl Transport for StdioTransport {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCapabilities, ClientInfo, McpClient, McpClientTrait};
+    use crate::service::McpService;
+
+    // A minimal MCP server that exits on its first tools/list call if `marker` does not exist yet,
+    // and logs every method it is sent
+    const FLAKY_SERVER: &str = r#"
+        marker="$1"; log="$2"
+        while read -r line; do
+            method=$(echo "$line" | sed -n 's/.*"method":"\([^"]*\)".*/\1/p')
+            id=$(echo "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo "$method" >> "$log"
+            [ -z "$id" ] && continue
+            case "$method" in
+                initialize)
+                    echo '{"jsonrpc":"2.0","id":'"$id"',"result":{"protocolVersion":"1.0.0","capabilities":{"tools":{}},"serverInfo":{"name":"flaky","version":"1.0.0"}}}' ;;
+                tools/list)
+                    [ -f "$marker" ] || { touch "$marker"; exit 1; }
+                    echo '{"jsonrpc":"2.0","id":'"$id"',"result":{"tools":[]}}' ;;
+            esac
+        done
+    "#;
+
+    #[test]
+    fn test_restart_backoff_is_capped() {
+        let policy = RestartPolicy::OnExit {
+            max_restarts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+        };
+        let backoffs: Vec<_> = (0..6).map(|restarts| policy.backoff(restarts)).collect();
+        assert_eq!(
+            backoffs,
+            vec![
+                Some(Duration::from_millis(100)),
+                Some(Duration::from_millis(200)),
+                Some(Duration::from_millis(400)),
+                Some(Duration::from_millis(500)),
+                Some(Duration::from_millis(500)),
+                None,
+            ]
+        );
+        assert_eq!(RestartPolicy::Never.backoff(0), None);
+    }
+
+    #[tokio::test]
+    async fn test_client_recovers_after_process_exits() {
+        let dir = std::env::temp_dir().join(format!("mcp-client-flaky-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("crashed");
+        let log = dir.join("methods.log");
+        let _ = std::fs::remove_file(&marker);
+        let _ = std::fs::remove_file(&log);
+
+        let transport = StdioTransport::new(
+            "sh",
+            vec![
+                "-c".to_string(),
+                FLAKY_SERVER.to_string(),
+                "flaky".to_string(),
+                marker.to_string_lossy().to_string(),
+                log.to_string_lossy().to_string(),
+            ],
+            HashMap::new(),
+        )
+        .with_restart_policy(RestartPolicy::OnExit {
+            max_restarts: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(100),
+        });
+        let handle = transport.start().await.unwrap();
+        let mut client = McpClient::new(McpService::with_timeout(handle, Duration::from_secs(10)));
+        client
+            .initialize(
+                ClientInfo {
+                    name: "test".to_string(),
+                    version: "1.0.0".to_string(),
+                },
+                ClientCapabilities::default(),
+            )
+            .await
+            .unwrap();
+
+        // The server exits while handling this call, so it fails
+        assert!(client.list_tools(None).await.is_err());
+
+        // The restarted server is initialized again without any help from the client
+        let tools = client.list_tools(None).await.unwrap();
+        assert!(tools.tools.is_empty());
+
+        let methods = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(
+            methods.lines().collect::<Vec<_>>(),
+            vec![
+                "initialize",
+                "notifications/initialized",
+                "tools/list",
+                "initialize",
+                "notifications/initialized",
+                "tools/list",
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -15,6 +15,9 @@ pub struct Annotations {
     pub priority: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timestamp: Option<DateTime<Utc>>,
+    /// Whether a resource is pinned into the context, so it is never dropped to make room
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<bool>,
 }
 
 impl Annotations {
@@ -29,6 +32,7 @@ impl Annotations {
             priority: Some(priority),
             timestamp: Some(timestamp),
             audience: None,
+            pinned: None,
         }
     }
 }
@@ -141,6 +145,7 @@ impl Content {
                 audience: Some(audience),
                 priority: None,
                 timestamp: None,
+                pinned: None,
             },
         });
         self
@@ -167,6 +172,7 @@ impl Content {
                 audience: None,
                 priority: Some(priority),
                 timestamp: None,
+                pinned: None,
             },
         });
         self
@@ -133,6 +133,20 @@ impl Resource {
         }
     }
 
+    /// Pin the resource into the context, so it is never dropped to make room or when idle
+    pub fn with_pinned(mut self, pinned: bool) -> Self {
+        self.annotations.as_mut().unwrap().pinned = Some(pinned);
+        self
+    }
+
+    /// Check if the resource is pinned into the context
+    pub fn is_pinned(&self) -> bool {
+        self.annotations
+            .as_ref()
+            .and_then(|a| a.pinned)
+            .unwrap_or(false)
+    }
+
     /// Returns the priority of the resource, if set
     pub fn priority(&self) -> Option<f32> {
         self.annotations.as_ref().and_then(|a| a.priority)
@@ -252,6 +266,19 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_with_pinned() -> Result<()> {
+        let resource = Resource::with_uri("str:///cwd", "cwd", 0.0, None)?;
+        assert!(!resource.is_pinned());
+        assert!(!serde_json::to_string(&resource)?.contains("pinned"));
+
+        let resource = resource.with_pinned(true);
+        assert!(resource.is_pinned());
+        let json = serde_json::to_string(&resource)?;
+        assert!(serde_json::from_str::<Resource>(&json)?.is_pinned());
+        Ok(())
+    }
+
     #[test]
     fn test_invalid_uri() {
         let result = Resource::new("not-a-uri", None, None);
@@ -0,0 +1,107 @@
+/// How far, as a fraction of the kept bytes, a cut may move to land on a line or word break
+const BREAK_WINDOW_DIVISOR: usize = 4;
+
+/// The longest prefix of `s` that is at most `max_bytes` long, never splitting a character
+///
+/// When a line break, or failing that a space, is close to the cut, the prefix ends just
+/// before it instead, so the text is not cut in the middle of a line or word.
+pub fn truncate_str(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let mut window_start = end - end / BREAK_WINDOW_DIVISOR;
+    while !s.is_char_boundary(window_start) {
+        window_start += 1;
+    }
+    let window = &s[window_start..end];
+    match window
+        .rfind('\n')
+        .or_else(|| window.rfind(char::is_whitespace))
+    {
+        Some(i) if window_start + i > 0 => &s[..window_start + i],
+        _ => &s[..end],
+    }
+}
+
+/// The longest suffix of `s` that is at most `max_bytes` long, never splitting a character
+///
+/// Like [`truncate_str`], the suffix starts just after a nearby line break or space.
+pub fn truncate_str_start(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut start = s.len() - max_bytes;
+    while !s.is_char_boundary(start) {
+        start += 1;
+    }
+
+    let mut window_end = start + (s.len() - start) / BREAK_WINDOW_DIVISOR;
+    while !s.is_char_boundary(window_end) {
+        window_end -= 1;
+    }
+    let window = &s[start..window_end];
+    let break_at = window
+        .find('\n')
+        .or_else(|| window.find(char::is_whitespace));
+    match break_at {
+        Some(i) => {
+            // Skip the break itself, which may be more than one byte
+            let after = start + i + window[i..].chars().next().map_or(0, char::len_utf8);
+            if after < s.len() {
+                &s[after..]
+            } else {
+                &s[start..]
+            }
+        }
+        None => &s[start..],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_str_never_splits_characters() {
+        assert_eq!(truncate_str("short", 10), "short");
+
+        // Each char is 3 bytes, so 10 bytes falls inside the fourth
+        let text = "€".repeat(10);
+        assert_eq!(truncate_str(&text, 10), "€€€");
+        assert_eq!(truncate_str_start(&text, 10), "€€€");
+
+        // 4 byte emoji cut in their middle at both ends
+        let text = "😀".repeat(5);
+        assert_eq!(truncate_str(&text, 7), "😀");
+        assert_eq!(truncate_str_start(&text, 7), "😀");
+        assert_eq!(truncate_str(&text, 2), "");
+        assert_eq!(truncate_str_start(&text, 2), "");
+
+        // Mixed widths, where every cut has to move to a different distance
+        let text = "aé€😀".repeat(3);
+        for max_bytes in 0..=text.len() {
+            assert!(truncate_str(&text, max_bytes).len() <= max_bytes);
+            assert!(truncate_str_start(&text, max_bytes).len() <= max_bytes);
+        }
+    }
+
+    #[test]
+    fn test_truncate_str_prefers_line_then_word_breaks() {
+        let text = "first line\nsecond line here";
+        assert_eq!(truncate_str(text, 13), "first line");
+        assert_eq!(truncate_str_start(text, 19), "second line here");
+
+        let text = "grüße aus köln und münchen";
+        assert_eq!(truncate_str(text, 20), "grüße aus köln");
+        assert_eq!(truncate_str_start(text, 14), "und münchen");
+
+        // A break far from the cut is ignored
+        let text = format!("a {}", "b".repeat(40));
+        assert_eq!(truncate_str(&text, 30), &text[..30]);
+    }
+}
@@ -100,7 +100,12 @@ pub trait Router: Send + Sync + 'static {
     fn list_prompts(&self) -> Option<Vec<Prompt>> {
         None
     }
-    fn get_prompt(&self, _prompt_name: &str) -> Option<PromptFuture> {
+    /// The text of the prompt called `prompt_name`, with its `arguments` filled in
+    fn get_prompt(
+        &self,
+        _prompt_name: &str,
+        _arguments: &serde_json::Map<String, Value>,
+    ) -> Option<PromptFuture> {
         None
     }
 
@@ -320,13 +325,6 @@ pub trait Router: Send + Sync + 'static {
                 }
             }
 
-            // Now get the prompt content
-            let description = self
-                .get_prompt(prompt_name)
-                .ok_or_else(|| RouterError::PromptNotFound("Prompt not found".into()))?
-                .await
-                .map_err(|e| RouterError::Internal(e.to_string()))?;
-
             // Validate prompt arguments for potential security issues from user text input
             // Checks:
             // - Prompt must be less than 10000 total characters
@@ -360,23 +358,24 @@ pub trait Router: Send + Sync + 'static {
                 }
             }
 
+            // Now get the prompt content, rendered with the validated arguments
+            let description_filled = self
+                .get_prompt(prompt_name, arguments)
+                .ok_or_else(|| RouterError::PromptNotFound("Prompt not found".into()))?
+                .await
+                .map_err(|e| match e {
+                    PromptError::InvalidParameters(msg) => RouterError::InvalidParams(msg),
+                    PromptError::NotFound(msg) => RouterError::PromptNotFound(msg),
+                    PromptError::InternalError(msg) => RouterError::Internal(msg),
+                })?;
+
             // Validate the prompt description length
-            if description.len() > 10000 {
+            if description_filled.len() > 10000 {
                 return Err(RouterError::Internal(
                     "Prompt description exceeds maximum allowed length".into(),
                 ));
             }
 
-            // Create a mutable copy of the description to fill in arguments
-            let mut description_filled = description.clone();
-
-            // Replace each argument placeholder with its value from the arguments object
-            for (key, value) in arguments {
-                let placeholder = format!("{{{}}}", key);
-                description_filled =
-                    description_filled.replace(&placeholder, value.as_str().unwrap_or_default());
-            }
-
             let messages = vec![PromptMessage::new_text(
                 PromptMessageRole::User,
                 description_filled.to_string(),